@@ -0,0 +1,171 @@
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table};
+
+use crate::{
+    config::{DotmanConfig, Link},
+    error::{DotmanError, PathError},
+    utils::{self, Absolute, ExpandTilde},
+};
+
+/// Moves an existing file or directory into the dotfiles repository and links
+/// it back into place. `source` defaults to the file name, without a leading
+/// dot, inside the repository.
+pub fn adopt(
+    config: &DotmanConfig,
+    target: &std::path::Path,
+    source: Option<&str>,
+) -> Result<Link, DotmanError> {
+    let target = target.expand_tilde_path()?.absolute()?;
+    let metadata =
+        std::fs::symlink_metadata(&target).map_err(|_| PathError::NotFound(target.clone()))?;
+    if metadata.is_symlink() {
+        return Err(PathError::AlreadySymlink(target).into());
+    }
+
+    let source = match source {
+        Some(source) => source.to_string(),
+        None => default_source(config, &target)?,
+    };
+    let source_path = config.resolve_source(&source)?;
+    if source_path.exists() {
+        return Err(PathError::AlreadyExists(source_path).into());
+    }
+
+    if let Some(parent) = source_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    move_path(&target, &source_path)?;
+    utils::symlink(&source_path, &target, false)?;
+
+    Ok(Link {
+        source,
+        target: home_relative(&target),
+        if_cond: None,
+        if_not_cond: None,
+        profiles: vec![],
+        ..Default::default()
+    })
+}
+
+/// Appends a `[[links]]` entry to a TOML configuration file, keeping the rest
+/// of the document untouched.
+pub fn add_link(input: &str, link: &Link) -> Result<String, toml_edit::TomlError> {
+    let mut doc: DocumentMut = input.parse()?;
+
+    let mut table = Table::new();
+    table["source"] = toml_edit::value(&link.source);
+    table["target"] = toml_edit::value(&link.target);
+
+    match doc.get_mut("links") {
+        Some(Item::ArrayOfTables(links)) => links.push(table),
+        _ => {
+            let mut links = ArrayOfTables::new();
+            links.push(table);
+            doc.insert("links", Item::ArrayOfTables(links));
+        }
+    }
+    Ok(doc.to_string())
+}
+
+fn default_source(config: &DotmanConfig, target: &std::path::Path) -> Result<String, DotmanError> {
+    let name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().trim_start_matches('.').to_string())
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| PathError::NoFileName(target.to_path_buf()))?;
+    let path = config.repo_dir()?.join(name);
+
+    let config_dir = std::path::Path::new(&config.config_path)
+        .parent()
+        .unwrap_or(std::path::Path::new(""))
+        .absolute()?;
+    Ok(match path.strip_prefix(&config_dir) {
+        Ok(relative) => relative.to_string_lossy().to_string(),
+        Err(_) => path.to_string_lossy().to_string(),
+    })
+}
+
+/// Renames `from` to `to`, copying across filesystems when needed.
+fn move_path(from: &std::path::Path, to: &std::path::Path) -> std::io::Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    utils::copy_recursive(from, to)?;
+    if from.is_dir() {
+        std::fs::remove_dir_all(from)
+    } else {
+        std::fs::remove_file(from)
+    }
+}
+
+/// Writes paths inside the home directory as `~/...`.
+fn home_relative(path: &std::path::Path) -> String {
+    match dirs::home_dir().and_then(|home| path.strip_prefix(home).ok().map(|p| p.to_path_buf())) {
+        Some(relative) => format!("~/{}", relative.display()),
+        None => path.display().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_adopt_moves_file_and_links_it_back() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path().join("dotfiles");
+        std::fs::create_dir_all(&repo).unwrap();
+        let target = temp_dir.path().join(".zshrc");
+        std::fs::write(&target, "export EDITOR=vim").unwrap();
+        let config = DotmanConfig {
+            config_path: repo.join("dotman.toml").to_string_lossy().to_string(),
+            ..Default::default()
+        };
+
+        let link = adopt(&config, &target, None).unwrap();
+
+        assert_eq!(link.source, "zshrc");
+        assert!(target.is_symlink());
+        assert_eq!(std::fs::read_link(&target).unwrap(), repo.join("zshrc"));
+        assert_eq!(
+            std::fs::read_to_string(repo.join("zshrc")).unwrap(),
+            "export EDITOR=vim"
+        );
+    }
+
+    #[test]
+    fn test_adopt_rejects_symlinks() {
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join(".zshrc");
+        utils::symlink(temp_dir.path().join("elsewhere"), target.clone(), false).unwrap();
+        let config = DotmanConfig {
+            config_path: temp_dir
+                .path()
+                .join("dotman.toml")
+                .to_string_lossy()
+                .to_string(),
+            ..Default::default()
+        };
+
+        assert!(adopt(&config, &target, None).is_err());
+    }
+
+    #[test]
+    fn test_add_link_appends_entry() {
+        let input = "version = 2\n\n# Shell\n[[links]]\nsource = \"a\"\ntarget = \"~/.a\"\n";
+        let link = Link {
+            source: "b".to_string(),
+            target: "~/.b".to_string(),
+            if_cond: None,
+            if_not_cond: None,
+            profiles: vec![],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            add_link(input, &link).unwrap(),
+            "version = 2\n\n# Shell\n[[links]]\nsource = \"a\"\ntarget = \"~/.a\"\n\n[[links]]\nsource = \"b\"\ntarget = \"~/.b\"\n"
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! Installing from async applications, with the `async` feature.
+
+use colored::Colorize;
+
+use crate::{Dotman, config::Action, error::DotmanError, options::InstallOptions};
+
+impl Dotman {
+    /// Like [`Dotman::install_with`], but without blocking the async runtime.
+    /// Everything but the actions is installed on a blocking thread, then up
+    /// to `options.parallelism` actions run at once. Failing actions are
+    /// reported together once the others have finished.
+    pub async fn install_async(
+        self: std::sync::Arc<Self>,
+        options: InstallOptions,
+    ) -> Result<(), DotmanError> {
+        self.check_preflight(&options)?;
+        let _prompts = self.allow_prompts(&options);
+        let options = std::sync::Arc::new(options);
+        let (dotman, step_options) = (self.clone(), options.clone());
+        let prepared = tokio::task::spawn_blocking(move || dotman.install_steps(&step_options))
+            .await
+            .map_err(|err| DotmanError::IoError(std::io::Error::other(err)))?;
+        if options.fail_fast || matches!(prepared, Err(DotmanError::Cancelled)) {
+            prepared?;
+            return self.clone().run_actions_async(options).await;
+        }
+        let ran = self.clone().run_actions_async(options).await;
+        if matches!(ran, Err(DotmanError::Cancelled)) {
+            return ran;
+        }
+        DotmanError::collect([prepared, ran].into_iter().filter_map(Result::err))
+    }
+
+    /// Runs the actions `options` selects and remembers which command each
+    /// one ran. With `options.parallelism` above 1 up to that many run at
+    /// once, otherwise one after another in the order they are declared.
+    /// With `options.fail_fast` no action starts after one fails.
+    async fn run_actions_async(
+        self: std::sync::Arc<Self>,
+        options: std::sync::Arc<InstallOptions>,
+    ) -> Result<(), DotmanError> {
+        let mut runnable = Vec::new();
+        for action in self.config.get_effective_actions() {
+            if !self.should_run(action, &options)? {
+                continue;
+            }
+            if options.dry_run {
+                println!("{} {}", "Would run:".cyan().bold(), action.name());
+                continue;
+            }
+            runnable.push((action.clone(), self.elevation(action)?));
+        }
+
+        let mut errors = Vec::new();
+        if options.parallelism <= 1 {
+            for (action, elevate) in runnable {
+                if options.is_cancelled() {
+                    break;
+                }
+                if let Err(err) = self.clone().run_action_async(action, elevate).await {
+                    errors.push(err);
+                    if options.fail_fast {
+                        break;
+                    }
+                }
+            }
+        } else {
+            let slots = std::sync::Arc::new(tokio::sync::Semaphore::new(options.parallelism));
+            let mut running = tokio::task::JoinSet::new();
+            for (action, elevate) in runnable {
+                let (dotman, slots, options) = (self.clone(), slots.clone(), options.clone());
+                running.spawn(async move {
+                    // The slots are closed once an action fails with
+                    // `fail_fast`, so the ones still waiting never start.
+                    let Ok(_slot) = slots.acquire().await else {
+                        return Ok(());
+                    };
+                    if options.is_cancelled() {
+                        return Err(DotmanError::Cancelled);
+                    }
+                    dotman.run_action_async(action, elevate).await
+                });
+            }
+            while let Some(finished) = running.join_next().await {
+                let result =
+                    finished.map_err(|err| DotmanError::IoError(std::io::Error::other(err)))?;
+                if let Err(err) = result {
+                    if matches!(err, DotmanError::Cancelled) {
+                        continue;
+                    }
+                    if options.fail_fast {
+                        slots.close();
+                    }
+                    errors.push(err);
+                }
+            }
+        }
+
+        if !options.dry_run {
+            self.state.lock().unwrap().save()?;
+        }
+        if options.is_cancelled() {
+            return Err(DotmanError::Cancelled);
+        }
+        if options.fail_fast && !errors.is_empty() {
+            return Err(errors.remove(0));
+        }
+        DotmanError::collect(errors)
+    }
+
+    /// Announces and runs one action once it may start, and remembers the
+    /// command it ran if it succeeded.
+    async fn run_action_async(
+        self: std::sync::Arc<Self>,
+        action: Action,
+        elevate: Option<&'static str>,
+    ) -> Result<(), DotmanError> {
+        self.announce(&action);
+        let name = action.name().to_string();
+        let hash = self.action_hash(&action);
+        let result = match action {
+            Action::ShellCommand { run, .. } => {
+                let output = run.execute_async(elevate).await;
+                self.finish_action(&name, output)
+            }
+            action => {
+                let dotman = self.clone();
+                tokio::task::spawn_blocking(move || dotman.perform(&action))
+                    .await
+                    .map_err(|err| DotmanError::IoError(std::io::Error::other(err)))
+                    .flatten()
+            }
+        };
+        if result.is_ok() {
+            self.state.lock().unwrap().record_action(&name, hash);
+        }
+        result.map_err(|err| err.in_action(&name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Link, fs::Filesystem};
+
+    #[test]
+    fn test_install_async() {
+        let fs = std::sync::Arc::new(crate::fs::MemoryFilesystem::new());
+        fs.write(std::path::Path::new("/dotfiles/zshrc"), b"# zsh", false)
+            .unwrap();
+        let config = crate::DotmanConfig::builder()
+            .link(Link::new("/dotfiles/zshrc", "/home/me/.zshrc"))
+            .action(Action::shell("Greet", "true"))
+            .action(Action::shell("Fail", "exit 3"))
+            .dir(crate::config::Directory::new("/home/me/.local/bin"))
+            .build();
+        let dotman = std::sync::Arc::new(Dotman::new(config).with_filesystem(fs.clone()));
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let result = runtime.block_on(dotman.clone().install_async(InstallOptions {
+            parallelism: 2,
+            ..Default::default()
+        }));
+
+        assert!(fs.exists(std::path::Path::new("/home/me/.zshrc")));
+        assert_eq!(
+            fs.kind(std::path::Path::new("/home/me/.local/bin")),
+            Some(crate::fs::EntryKind::Dir)
+        );
+        let state = dotman.state.lock().unwrap();
+        assert!(state.action_hash("Greet").is_some());
+        assert!(state.action_hash("Fail").is_none());
+        drop(state);
+        assert!(matches!(
+            result,
+            Err(DotmanError::CommandError { command, .. }) if command == "Fail"
+        ));
+    }
+
+    #[test]
+    fn test_install_async_runs_in_order_and_fails_fast() {
+        let config = crate::DotmanConfig::builder()
+            .action(Action::shell("First", "true"))
+            .action(Action::shell("Fail", "exit 3"))
+            .action(Action::shell("Never", "true"))
+            .build();
+        let started = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = started.clone();
+        let dotman = std::sync::Arc::new(
+            Dotman::new(config)
+                .with_filesystem(std::sync::Arc::new(crate::fs::MemoryFilesystem::new()))
+                .with_events(move |event| {
+                    if let crate::events::Event::ActionStarted { name } = event {
+                        recorded.lock().unwrap().push(name.clone());
+                    }
+                }),
+        );
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let result = runtime.block_on(dotman.install_async(InstallOptions {
+            parallelism: 1,
+            fail_fast: true,
+            ..Default::default()
+        }));
+
+        assert!(matches!(
+            result,
+            Err(DotmanError::CommandError { command, .. }) if command == "Fail"
+        ));
+        assert_eq!(*started.lock().unwrap(), vec!["First", "Fail"]);
+    }
+
+    #[test]
+    fn test_install_async_stops_when_cancelled() {
+        for parallelism in [1, 2] {
+            let config = crate::DotmanConfig::builder()
+                .action(Action::shell("Fail", "exit 3"))
+                .action(Action::shell("Cancel", "true"))
+                .action(Action::shell("Later", "true"))
+                .action(Action::shell("Never", "true"))
+                .build();
+            let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+            let started = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+            let (recorded, cancelling) = (started.clone(), cancel.clone());
+            let dotman = std::sync::Arc::new(
+                Dotman::new(config)
+                    .with_filesystem(std::sync::Arc::new(crate::fs::MemoryFilesystem::new()))
+                    .with_events(move |event| {
+                        if let crate::events::Event::ActionStarted { name } = event {
+                            recorded.lock().unwrap().push(name.clone());
+                            if name == "Cancel" {
+                                cancelling.store(true, std::sync::atomic::Ordering::Relaxed);
+                            }
+                        }
+                    }),
+            );
+
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            let result = runtime.block_on(dotman.install_async(InstallOptions {
+                parallelism,
+                fail_fast: false,
+                cancel: Some(cancel),
+                ..Default::default()
+            }));
+
+            assert!(matches!(result, Err(DotmanError::Cancelled)));
+            assert!(!started.lock().unwrap().contains(&"Never".to_string()));
+        }
+    }
+}
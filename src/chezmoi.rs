@@ -0,0 +1,386 @@
+use crate::{
+    config::{Link, LinkMode},
+    error::DotmanError,
+};
+
+/// Name prefixes whose behaviour dotman cannot reproduce.
+const UNSUPPORTED_PREFIXES: &[(&str, &str)] = &[
+    ("run_", "scripts are not translated"),
+    ("create_", "create-only files are not supported"),
+    ("modify_", "modify scripts are not supported"),
+    ("remove_", "removal entries are not supported"),
+    ("symlink_", "symlink entries are not supported"),
+    ("encrypted_", "encrypted files are not supported"),
+];
+
+/// Name prefixes that only carry attributes dotman does not enforce.
+const ATTRIBUTE_PREFIXES: &[&str] = &[
+    "private_",
+    "readonly_",
+    "executable_",
+    "empty_",
+    "exact_",
+    "external_",
+    "once_",
+    "onchange_",
+];
+
+/// A source entry that could not be translated, with the reason.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Untranslated {
+    pub path: std::path::PathBuf,
+    pub reason: String,
+}
+
+/// A chezmoi template rewritten for a template link, to be written next to
+/// the original.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranslatedTemplate {
+    pub path: std::path::PathBuf,
+    pub contents: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ChezmoiImport {
+    pub links: Vec<Link>,
+    pub untranslated: Vec<Untranslated>,
+    /// Templates whose syntax had to be rewritten, relative to the source
+    /// directory like the links' sources.
+    pub templates: Vec<TranslatedTemplate>,
+    /// Template data the translated templates use, which has to be
+    /// declared in `[variables]`.
+    pub variables: std::collections::BTreeSet<String>,
+}
+
+/// Returns chezmoi's default source directory.
+pub fn default_source_dir() -> Option<std::path::PathBuf> {
+    dirs::home_dir().map(|home| home.join(".local/share/chezmoi"))
+}
+
+/// Translates a chezmoi source directory into links. Sources are relative to
+/// `source_dir`; targets are relative to `~`. Templates become template
+/// links, rewritten when they use chezmoi's syntax. Entries that rely on
+/// chezmoi features dotman lacks (template functions, scripts, encryption,
+/// ...) are reported instead of being linked.
+pub fn import(
+    source_dir: &std::path::Path,
+    home: &std::path::Path,
+) -> Result<ChezmoiImport, DotmanError> {
+    let root = match std::fs::read_to_string(source_dir.join(".chezmoiroot")) {
+        Ok(root) => source_dir.join(root.trim()),
+        Err(_) => source_dir.to_path_buf(),
+    };
+
+    let mut result = ChezmoiImport::default();
+    import_dir(
+        source_dir,
+        &root,
+        std::path::Path::new(""),
+        home,
+        &mut result,
+    )?;
+    Ok(result)
+}
+
+fn import_dir(
+    source_dir: &std::path::Path,
+    dir: &std::path::Path,
+    relative_target: &std::path::Path,
+    home: &std::path::Path,
+    result: &mut ChezmoiImport,
+) -> Result<(), DotmanError> {
+    let mut entries: Vec<std::fs::DirEntry> =
+        std::fs::read_dir(dir)?.filter_map(Result::ok).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let relative_source = path.strip_prefix(source_dir).unwrap_or(&path).to_path_buf();
+
+        if name.starts_with(".chezmoi") {
+            result.untranslated.push(Untranslated {
+                path: relative_source,
+                reason: "chezmoi configuration is not translated".to_string(),
+            });
+            continue;
+        }
+        if name.starts_with('.') {
+            continue;
+        }
+
+        let target_name = match decode_name(&name) {
+            Ok(target_name) => target_name,
+            Err(reason) => {
+                result.untranslated.push(Untranslated {
+                    path: relative_source,
+                    reason: reason.to_string(),
+                });
+                continue;
+            }
+        };
+
+        let relative_target = relative_target.join(target_name);
+        let target = home.join(&relative_target);
+        let is_dir = path.is_dir();
+        if is_dir && ((target.is_dir() && !target.is_symlink()) || !is_plain(&path)) {
+            import_dir(source_dir, &path, &relative_target, home, result)?;
+            continue;
+        }
+
+        let mut source = relative_source.clone();
+        let mut mode = LinkMode::Symlink;
+        if !is_dir && name.ends_with(".tmpl") {
+            let template = std::fs::read_to_string(&path)?;
+            match translate_template(&template) {
+                Ok((translated, variables)) => {
+                    if translated != template {
+                        source = relative_source.with_extension("jinja");
+                        result.templates.push(TranslatedTemplate {
+                            path: source.clone(),
+                            contents: translated,
+                        });
+                    }
+                    result.variables.extend(variables);
+                    mode = LinkMode::Template;
+                }
+                Err(reason) => {
+                    result.untranslated.push(Untranslated {
+                        path: relative_source,
+                        reason,
+                    });
+                    continue;
+                }
+            }
+        }
+
+        result.links.push(Link {
+            source: source.to_string_lossy().to_string(),
+            target: format!("~/{}", relative_target.display()),
+            mode,
+            if_cond: None,
+            if_not_cond: None,
+            profiles: vec![],
+            ..Default::default()
+        });
+    }
+    Ok(())
+}
+
+/// Rewrites a chezmoi (Go) template for dotman's templates, with the
+/// template data it reads. Only references to data, like `{{ .email }}`,
+/// and comments can be translated; anything else, such as functions,
+/// conditionals and chezmoi's own `.chezmoi` data, is named in the error.
+fn translate_template(
+    template: &str,
+) -> Result<(String, std::collections::BTreeSet<String>), String> {
+    let mut translated = String::new();
+    let mut variables = std::collections::BTreeSet::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let text = &rest[..start];
+        if text.contains("{%") || text.contains("{#") {
+            return Err("the template text contains dotman template syntax".to_string());
+        }
+        translated.push_str(text);
+        let end = rest[start..]
+            .find("}}")
+            .map(|end| start + end)
+            .ok_or("the template has an unterminated action")?;
+        let action = &rest[start + 2..end];
+        let (trim_left, action) = match action.strip_prefix("- ") {
+            Some(action) => ("-", action),
+            None => ("", action),
+        };
+        let (trim_right, action) = match action.strip_suffix(" -") {
+            Some(action) => ("-", action),
+            None => ("", action),
+        };
+        let body = action.trim();
+
+        if let Some(comment) = body
+            .strip_prefix("/*")
+            .and_then(|body| body.strip_suffix("*/"))
+        {
+            translated.push_str(&format!("{{#{trim_left}{comment}{trim_right}#}}"));
+        } else if let Some(path) = body.strip_prefix('.').filter(|path| {
+            !path.starts_with("chezmoi")
+                && path.split('.').all(|part| {
+                    part.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                        && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                })
+        }) {
+            variables.insert(path.split('.').next().unwrap_or(path).to_string());
+            translated.push_str(&format!("{{{{{trim_left} {path} {trim_right}}}}}"));
+        } else {
+            return Err(format!("`{{{{ {body} }}}}` cannot be translated"));
+        }
+        rest = &rest[end + 2..];
+    }
+    if rest.contains("{%") || rest.contains("{#") {
+        return Err("the template text contains dotman template syntax".to_string());
+    }
+    translated.push_str(rest);
+    Ok((translated, variables))
+}
+
+/// Decodes a chezmoi source name into the target name, or explains why the
+/// entry cannot be translated.
+fn decode_name(name: &str) -> Result<String, &'static str> {
+    let mut rest = name.strip_suffix(".tmpl").unwrap_or(name);
+    loop {
+        if let Some((_, reason)) = UNSUPPORTED_PREFIXES
+            .iter()
+            .find(|(prefix, _)| rest.starts_with(prefix))
+        {
+            return Err(reason);
+        }
+        if let Some(stripped) = rest.strip_prefix("literal_") {
+            return Ok(stripped.to_string());
+        }
+        match ATTRIBUTE_PREFIXES
+            .iter()
+            .find_map(|prefix| rest.strip_prefix(prefix))
+        {
+            Some(stripped) => rest = stripped,
+            None => break,
+        }
+    }
+
+    let rest = rest.strip_suffix(".literal").unwrap_or(rest);
+    Ok(match rest.strip_prefix("dot_") {
+        Some(stripped) => format!(".{stripped}"),
+        None => rest.to_string(),
+    })
+}
+
+/// Returns true if every entry below `dir` keeps its name when decoded, so the
+/// directory can be linked as a whole.
+fn is_plain(dir: &std::path::Path) -> bool {
+    std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .all(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            decode_name(&name).is_ok_and(|decoded| decoded == name)
+                && (!entry.path().is_dir() || is_plain(&entry.path()))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_decode_name() {
+        assert_eq!(decode_name("dot_zshrc"), Ok(".zshrc".to_string()));
+        assert_eq!(
+            decode_name("private_executable_dot_ssh"),
+            Ok(".ssh".to_string())
+        );
+        assert_eq!(decode_name("literal_dot_x"), Ok("dot_x".to_string()));
+        assert_eq!(decode_name("init.lua"), Ok("init.lua".to_string()));
+        assert_eq!(
+            decode_name("dot_gitconfig.tmpl"),
+            Ok(".gitconfig".to_string())
+        );
+        assert!(decode_name("run_once_install.sh").is_err());
+        assert!(decode_name("encrypted_private_dot_netrc.age").is_err());
+    }
+
+    #[test]
+    fn test_import_chezmoi_source() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("chezmoi");
+        let home = temp_dir.path().join("home");
+        fs::create_dir_all(source.join("dot_config/nvim")).unwrap();
+        fs::write(source.join("dot_config/nvim/init.lua"), "").unwrap();
+        fs::create_dir_all(source.join("dot_config/private_fish")).unwrap();
+        fs::write(source.join("dot_config/private_fish/dot_hidden"), "").unwrap();
+        fs::write(source.join("dot_zshrc"), "").unwrap();
+        fs::write(
+            source.join("dot_gitconfig.tmpl"),
+            "[user]\n  email = {{ .email }}\n",
+        )
+        .unwrap();
+        fs::write(source.join("dot_vimrc.tmpl"), "set nocompatible\n").unwrap();
+        fs::write(
+            source.join("dot_bashrc.tmpl"),
+            "{{ if eq .chezmoi.os \"darwin\" }}x{{ end }}",
+        )
+        .unwrap();
+        fs::write(source.join(".chezmoiignore"), "").unwrap();
+        fs::create_dir_all(home.join(".config")).unwrap();
+
+        let result = import(&source, &home).unwrap();
+        let pairs: Vec<(&str, &str)> = result
+            .links
+            .iter()
+            .map(|link| (link.source.as_str(), link.target.as_str()))
+            .collect();
+
+        assert_eq!(
+            pairs,
+            vec![
+                ("dot_config/nvim", "~/.config/nvim"),
+                (
+                    "dot_config/private_fish/dot_hidden",
+                    "~/.config/fish/.hidden"
+                ),
+                ("dot_gitconfig.jinja", "~/.gitconfig"),
+                ("dot_vimrc.tmpl", "~/.vimrc"),
+                ("dot_zshrc", "~/.zshrc"),
+            ]
+        );
+        assert_eq!(result.links[2].mode, LinkMode::Template);
+        assert_eq!(result.links[3].mode, LinkMode::Template);
+        assert_eq!(
+            result.templates,
+            vec![TranslatedTemplate {
+                path: "dot_gitconfig.jinja".into(),
+                contents: "[user]\n  email = {{ email }}\n".to_string(),
+            }]
+        );
+        assert_eq!(result.variables.iter().collect::<Vec<_>>(), vec!["email"]);
+        let untranslated: Vec<&str> = result
+            .untranslated
+            .iter()
+            .map(|entry| entry.path.to_str().unwrap())
+            .collect();
+        assert_eq!(untranslated, vec![".chezmoiignore", "dot_bashrc.tmpl"]);
+    }
+
+    #[test]
+    fn test_translate_template() {
+        let (translated, variables) =
+            translate_template("{{/* work */}}\n{{- .git.email -}}\n{{ .name }}").unwrap();
+        assert_eq!(translated, "{# work #}\n{{- git.email -}}\n{{ name }}");
+        assert_eq!(
+            variables.into_iter().collect::<Vec<_>>(),
+            vec!["git", "name"]
+        );
+
+        assert!(translate_template("{{ .chezmoi.hostname }}").is_err());
+        assert!(translate_template("{{ env \"HOME\" }}").is_err());
+        assert!(translate_template("{{ .name ").is_err());
+    }
+
+    #[test]
+    fn test_import_honours_chezmoiroot() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("repo");
+        let home = temp_dir.path().join("home");
+        fs::create_dir_all(source.join("home")).unwrap();
+        fs::write(source.join(".chezmoiroot"), "home\n").unwrap();
+        fs::write(source.join("home/dot_vimrc"), "").unwrap();
+
+        let result = import(&source, &home).unwrap();
+
+        assert_eq!(result.links.len(), 1);
+        assert_eq!(result.links[0].source, "home/dot_vimrc");
+        assert_eq!(result.links[0].target, "~/.vimrc");
+    }
+}
@@ -0,0 +1,279 @@
+//! Garbage-collecting backups, stale state and caches for `dotman clean`.
+
+use colored::Colorize;
+
+use crate::{Dotman, error::DotmanError};
+
+/// What [`Dotman::clean`] removes.
+#[derive(Debug, Clone)]
+pub struct CleanOptions {
+    /// Directory holding the backups, one subdirectory per run named after
+    /// the time it started.
+    pub backups_dir: Option<std::path::PathBuf>,
+    /// Directory holding the files dotman wrote and then wrote again, one
+    /// subdirectory per run like the backups.
+    pub replaced_dir: Option<std::path::PathBuf>,
+    /// Number of most recent runs whose backups and replaced files are
+    /// kept.
+    pub keep: usize,
+    /// Also remove those older than this, even among the most recent.
+    pub max_age: Option<std::time::Duration>,
+    /// Cache directories that are emptied, such as downloads.
+    pub caches: Vec<std::path::PathBuf>,
+    /// Report what would be removed without removing it.
+    pub dry_run: bool,
+}
+
+impl Default for CleanOptions {
+    fn default() -> Self {
+        CleanOptions {
+            backups_dir: None,
+            replaced_dir: None,
+            keep: 5,
+            max_age: None,
+            caches: vec![],
+            dry_run: false,
+        }
+    }
+}
+
+/// What [`Dotman::clean`] removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CleanReport {
+    /// Runs whose backups were removed.
+    pub backups: usize,
+    /// Runs whose replaced files were removed.
+    pub replaced: usize,
+    /// State entries dropped because their target is gone.
+    pub state_entries: usize,
+    /// Bytes freed on disk.
+    pub reclaimed: u64,
+}
+
+/// `bytes` for people, like `1.5 MiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// The total size of the files at or below `path`, without following
+/// symlinks.
+fn size_of(path: &std::path::Path) -> u64 {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| size_of(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// The backups of each run in `dir` with when the run started, newest
+/// first. Runs are named after their start in seconds since the epoch.
+fn backup_runs(dir: &std::path::Path) -> Result<Vec<(std::path::PathBuf, u64)>, DotmanError> {
+    let mut runs = Vec::new();
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(runs),
+        Err(err) => return Err(err.into()),
+    };
+    for entry in entries {
+        let entry = entry?;
+        if let Some(started) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        {
+            runs.push((entry.path(), started));
+        }
+    }
+    runs.sort_by_key(|(_, started)| std::cmp::Reverse(*started));
+    Ok(runs)
+}
+
+/// Removes the runs in `dir` that `options` does not keep. Returns how many
+/// were removed and how many bytes they took up.
+fn clean_runs(
+    dir: &std::path::Path,
+    options: &CleanOptions,
+    verb: &colored::ColoredString,
+) -> Result<(usize, u64), DotmanError> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (mut removed, mut reclaimed) = (0, 0);
+    for (index, (run, started)) in backup_runs(dir)?.into_iter().enumerate() {
+        let expired = options
+            .max_age
+            .is_some_and(|max_age| now.saturating_sub(started) > max_age.as_secs());
+        if index < options.keep && !expired {
+            continue;
+        }
+        reclaimed += size_of(&run);
+        removed += 1;
+        if !options.dry_run {
+            std::fs::remove_dir_all(&run)?;
+        }
+        println!("{verb} {}", run.display());
+    }
+    Ok((removed, reclaimed))
+}
+
+impl Dotman {
+    /// Removes the backups and replaced files of all but the `keep` most
+    /// recent runs, and of those older than `max_age`; forgets targets in the
+    /// state that no longer exist; and empties the checksum cache and the
+    /// other caches. Runs whose backups or replaced files are removed can no
+    /// longer be fully undone.
+    pub fn clean(&self, options: &CleanOptions) -> Result<CleanReport, DotmanError> {
+        let mut report = CleanReport::default();
+        let verb = if options.dry_run {
+            "Would remove:".cyan().bold()
+        } else {
+            "Removed:".green().bold()
+        };
+
+        if let Some(dir) = &options.backups_dir {
+            let (removed, reclaimed) = clean_runs(dir, options, &verb)?;
+            report.backups = removed;
+            report.reclaimed += reclaimed;
+        }
+        if let Some(dir) = &options.replaced_dir {
+            let (removed, reclaimed) = clean_runs(dir, options, &verb)?;
+            report.replaced = removed;
+            report.reclaimed += reclaimed;
+        }
+
+        let gone: Vec<std::path::PathBuf> = self
+            .state
+            .lock()
+            .unwrap()
+            .targets
+            .keys()
+            .filter(|target| self.fs.kind(target).is_none())
+            .cloned()
+            .collect();
+        for target in &gone {
+            println!("{verb} {} from the state", target.display());
+        }
+        report.state_entries = gone.len();
+        if !options.dry_run && !gone.is_empty() {
+            let mut state = self.state.lock().unwrap();
+            for target in &gone {
+                state.forget(target);
+            }
+            state.save()?;
+        }
+
+        let mut checksums = self.checksums.lock().unwrap();
+        let mut caches: Vec<std::path::PathBuf> =
+            checksums.path().map(Into::into).into_iter().collect();
+        caches.extend(options.caches.iter().cloned());
+        for cache in caches {
+            let size = size_of(&cache);
+            if size == 0 && !cache.exists() {
+                continue;
+            }
+            report.reclaimed += size;
+            println!("{verb} {}", cache.display());
+            if options.dry_run {
+                continue;
+            }
+            if Some(cache.as_path()) == checksums.path() {
+                checksums.clear()?;
+            } else {
+                std::fs::remove_dir_all(&cache)?;
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{State, TargetState};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_clean() {
+        let temp_dir = TempDir::new().unwrap();
+        let backups = temp_dir.path().join("backups");
+        let replaced = temp_dir.path().join("replaced");
+        for run in ["100", "200", "300"] {
+            std::fs::create_dir_all(backups.join(run).join("home")).unwrap();
+            std::fs::write(backups.join(run).join("home/.zshrc"), "# zsh").unwrap();
+        }
+        for run in ["200", "300"] {
+            std::fs::create_dir_all(replaced.join(run).join("home")).unwrap();
+            std::fs::write(replaced.join(run).join("home/.gitconfig"), "[user]").unwrap();
+        }
+        let downloads = temp_dir.path().join("downloads");
+        std::fs::create_dir_all(&downloads).unwrap();
+        std::fs::write(downloads.join("abc"), "font").unwrap();
+
+        let existing = temp_dir.path().join(".vimrc");
+        std::fs::write(&existing, "").unwrap();
+        let mut state = State::load(&temp_dir.path().join("state.json")).unwrap();
+        for target in [existing.clone(), temp_dir.path().join(".gone")] {
+            state.record(
+                &target,
+                TargetState {
+                    source: "/dotfiles/file".into(),
+                    checksum: None,
+                },
+            );
+        }
+        let dotman = Dotman::new(crate::DotmanConfig::builder().build()).with_state(state);
+        let options = CleanOptions {
+            backups_dir: Some(backups.clone()),
+            replaced_dir: Some(replaced.clone()),
+            keep: 1,
+            caches: vec![downloads.clone()],
+            ..Default::default()
+        };
+
+        let expected = CleanReport {
+            backups: 2,
+            replaced: 1,
+            state_entries: 1,
+            reclaimed: 20,
+        };
+        let dry_run = CleanOptions {
+            dry_run: true,
+            ..options.clone()
+        };
+        assert_eq!(dotman.clean(&dry_run).unwrap(), expected);
+        assert!(backups.join("100").exists());
+
+        assert_eq!(dotman.clean(&options).unwrap(), expected);
+        assert!(backups.join("300").exists() && !backups.join("200").exists());
+        assert!(replaced.join("300").exists() && !replaced.join("200").exists());
+        assert!(!downloads.exists());
+        let state = State::load(&temp_dir.path().join("state.json")).unwrap();
+        assert_eq!(state.targets.keys().collect::<Vec<_>>(), vec![&existing]);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(14), "14 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
@@ -1,13 +1,49 @@
 use clap::Parser;
 use colored::Colorize;
 
-use dotman::{Dotman, DotmanConfig};
+use crate::exit_code::ExitCode;
+use dotman::{
+    Dotman, DotmanConfig, adopt, chezmoi,
+    clean::{self, CleanOptions},
+    config::{self, ConfigFormat, LinkMode},
+    context::Context,
+    download,
+    error::DotmanError,
+    events::{Event, SkipReason},
+    format, git,
+    hash::ChecksumCache,
+    journal::{Change, Journal, Recorder},
+    lockfile::Lockfile,
+    migrate,
+    notification::Notification,
+    options::InstallOptions,
+    remote,
+    run_lock::RunLock,
+    schedule, secrets,
+    state::State,
+    stow,
+    utils::{self, Absolute, ExpandTilde},
+    validate::{self, Diagnostic, Severity},
+    watch::{self, Watcher},
+};
 
 #[derive(Parser, Debug)]
 pub struct Cli {
-    /// Path to the configuration file
-    #[clap(short, long, default_value = "dotman.toml")]
-    pub config: std::path::PathBuf,
+    /// Path or https URL of the configuration file (TOML, YAML or JSON) [default: dotman.toml, dotman.yaml, dotman.yml or dotman.json]
+    #[clap(short, long)]
+    pub config: Option<std::path::PathBuf>,
+    /// Evaluate conditions as if running on this operating system
+    #[clap(long, global = true, value_name = "OS")]
+    pub assume_os: Option<config::OperatingSystem>,
+    /// Evaluate conditions as if running on a machine with this hostname
+    #[clap(long, global = true, value_name = "HOSTNAME")]
+    pub assume_hostname: Option<String>,
+    /// Wait for another running dotman to finish instead of failing
+    #[clap(long, global = true, default_value = "false")]
+    pub wait: bool,
+    /// Show a desktop notification when a command that changes the machine finishes
+    #[clap(long, global = true, default_value = "false")]
+    pub notify: bool,
     #[clap(subcommand)]
     pub command: Command,
 }
@@ -25,9 +61,121 @@ pub enum Command {
         /// Profile to use (applies global + profile-specific configuration)
         #[clap(short, long)]
         profile: Option<String>,
+        /// Update the dotfiles repository before linking
+        #[clap(long, default_value = "false")]
+        pull: bool,
+        /// Replace files even if they were edited since dotman wrote them
+        #[clap(short, long, default_value = "false")]
+        force: bool,
+        /// Print what would be linked and run without changing anything
+        #[clap(long, default_value = "false")]
+        dry_run: bool,
+        /// Only install links with this source, target or target file name, or actions with this name
+        #[clap(long, value_name = "NAME")]
+        only: Vec<String>,
+        /// Only install links with this tag
+        #[clap(long)]
+        tag: Vec<String>,
+        /// Install the remaining links when one fails
+        #[clap(long, default_value = "false")]
+        keep_going: bool,
+        /// Number of links to install at once
+        #[clap(short, long, default_value = "1")]
+        jobs: usize,
+        /// Refuse to install if anything differs from dotman.lock
+        #[clap(long, default_value = "false")]
+        locked: bool,
+    },
+    /// Apply only what changed since the last run
+    Update {
+        /// Replace files even if they were edited since dotman wrote them
+        #[clap(short, long, default_value = "false")]
+        force: bool,
+        /// Profile to use (applies global + profile-specific configuration)
+        #[clap(short, long)]
+        profile: Option<String>,
+    },
+    /// Apply changes to the configuration and sources as they are saved
+    Watch {
+        /// Profile to use (applies global + profile-specific configuration)
+        #[clap(short, long)]
+        profile: Option<String>,
+    },
+    /// Run `dotman sync` periodically with a systemd user timer or launchd agent
+    Schedule {
+        /// How often to sync, e.g. 30m, 6h or 1d
+        #[clap(long, default_value = "6h")]
+        every: String,
+        /// Print the generated files instead of installing them
+        #[clap(long, default_value = "false")]
+        print: bool,
+    },
+    /// Clone or fast-forward the dotfiles repository
+    Pull,
+    /// Pull the dotfiles repository, link what changed and report drift
+    Sync {
+        /// Replace targets that have drifted from their source
+        #[clap(short, long, default_value = "false")]
+        overwrite: bool,
+        /// Replace files even if they were edited since dotman wrote them
+        #[clap(short, long, default_value = "false")]
+        force: bool,
+        /// Profile to use (applies global + profile-specific configuration)
+        #[clap(short, long)]
+        profile: Option<String>,
+    },
+    /// Move an existing file into the dotfiles repository and link it back
+    Adopt {
+        /// The file or directory to adopt, e.g. ~/.zshrc
+        target: std::path::PathBuf,
+        /// Where to put it, relative to the configuration file [default: its name without the leading dot]
+        #[clap(short, long)]
+        source: Option<String>,
+    },
+    /// Clone a dotfiles repository and install its configuration
+    Bootstrap {
+        /// URL of the dotfiles repository
+        url: String,
+        /// Where to clone the repository
+        #[clap(short, long, default_value = "~/dotfiles")]
+        dest: std::path::PathBuf,
+        /// Branch to check out
+        #[clap(short, long)]
+        branch: Option<String>,
+        /// Override existing links if they already exist
+        #[clap(short, long, default_value = "false")]
+        overwrite: bool,
+        /// Profile to use (applies global + profile-specific configuration)
+        #[clap(short, long)]
+        profile: Option<String>,
     },
     /// Validate the configuration file
-    Validate,
+    Validate {
+        /// Profile to validate (checks that it is used by the configuration)
+        #[clap(short, long)]
+        profile: Option<String>,
+        /// Treat warnings as errors
+        #[clap(long, default_value = "false")]
+        strict: bool,
+    },
+    /// Report dead and unused entries in the configuration file
+    Lint {
+        /// Treat warnings as errors
+        #[clap(long, default_value = "false")]
+        strict: bool,
+    },
+    /// Rewrite the configuration file in its canonical format
+    Fmt {
+        /// Only check whether the file is formatted, without rewriting it
+        #[clap(long, default_value = "false")]
+        check: bool,
+    },
+    /// Upgrade the configuration file to the current format version
+    Migrate {
+        /// Print the migrated configuration instead of writing it
+        #[clap(long, default_value = "false")]
+        dry_run: bool,
+    },
     /// Remove all links created by Dotman
     Remove {
         /// Profile to use (removes global + profile-specific configuration)
@@ -39,61 +187,1160 @@ pub enum Command {
         /// Profile to use (shows status for global + profile-specific configuration)
         #[clap(short, long)]
         profile: Option<String>,
+        /// Repair the links status flags first, asking before replacing
+        /// modified files. Actions are not run
+        #[clap(long, default_value = "false")]
+        fix: bool,
+        /// Remove targets dotman installed for links that are no longer
+        /// configured, unless they changed since
+        #[clap(long, default_value = "false")]
+        prune_orphans: bool,
+    },
+    /// Remove broken symlinks that point into the dotfiles repository
+    Prune {
+        /// Directories to search [default: the home directory]
+        dirs: Vec<std::path::PathBuf>,
+        /// Print the symlinks that would be removed without removing them
+        #[clap(long, default_value = "false")]
+        dry_run: bool,
+    },
+    /// Re-point symlinks that point somewhere other than their source
+    Repair {
+        /// Profile to use (repairs global + profile-specific configuration)
+        #[clap(short, long)]
+        profile: Option<String>,
+        /// Print the symlinks that would be re-pointed without changing them
+        #[clap(long, default_value = "false")]
+        dry_run: bool,
+    },
+    /// Remove old backups, state of targets that are gone and the caches
+    Clean {
+        /// Number of most recent runs whose backups are kept
+        #[clap(long, default_value = "5")]
+        keep: usize,
+        /// Also remove backups older than this, e.g. 30d
+        #[clap(long, value_name = "AGE")]
+        older_than: Option<String>,
+        /// Print what would be removed without removing it
+        #[clap(long, default_value = "false")]
+        dry_run: bool,
+    },
+    /// Check that installed targets still match their sources
+    Verify {
+        /// Profile to use (verifies global + profile-specific configuration)
+        #[clap(short, long)]
+        profile: Option<String>,
+    },
+    /// Record the resolved sources and repository commits in dotman.lock
+    Lock {
+        /// Profile to use (locks global + profile-specific configuration)
+        #[clap(short, long)]
+        profile: Option<String>,
+    },
+    /// Write what Homebrew has installed to the Brewfile in [[packages]]
+    DumpBrewfile {
+        /// Profile to use (its packages are searched for the Brewfile)
+        #[clap(short, long)]
+        profile: Option<String>,
+    },
+    /// Revert the most recent run that changed something
+    Undo,
+    /// List past runs from the journal
+    History {
+        #[clap(subcommand)]
+        command: Option<HistoryCommand>,
+        /// Number of most recent runs to list
+        #[clap(short = 'n', long, default_value = "20")]
+        limit: usize,
     },
+    /// Print the resolved configuration
+    Show {
+        /// Output format
+        #[clap(short, long, value_enum, default_value = "toml")]
+        format: OutputFormat,
+        /// Profile to resolve (only its links and actions are shown)
+        #[clap(short, long)]
+        profile: Option<String>,
+    },
+    /// Export the configuration to another dotfiles layout
+    Export {
+        #[clap(subcommand)]
+        format: ExportFormat,
+    },
+    /// Generate a configuration file from another dotfiles layout
+    Import {
+        #[clap(subcommand)]
+        format: ImportFormat,
+    },
+    /// Encrypt, decrypt and edit secret files
+    Secret {
+        #[clap(subcommand)]
+        command: SecretCommand,
+    },
+    /// Print the JSON Schema for the configuration file
+    Schema,
+}
+
+impl Command {
+    /// Whether the command changes targets or the state, and so must not run
+    /// alongside another such command.
+    fn changes_machine(&self) -> bool {
+        match self {
+            Command::Install { dry_run, .. }
+            | Command::Prune { dry_run, .. }
+            | Command::Clean { dry_run, .. }
+            | Command::Repair { dry_run, .. } => !dry_run,
+            Command::Status {
+                fix, prune_orphans, ..
+            } => *fix || *prune_orphans,
+            Command::Sync { .. }
+            | Command::Update { .. }
+            | Command::Adopt { .. }
+            | Command::Bootstrap { .. }
+            | Command::Remove { .. }
+            | Command::Undo => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub enum SecretCommand {
+    /// Encrypt a file (or a value for [variables]) with the secrets backend
+    Encrypt {
+        /// The file to encrypt
+        #[clap(required_unless_present = "value")]
+        file: Option<std::path::PathBuf>,
+        /// Encrypt this value and print it as an `enc:` string instead
+        #[clap(long, conflicts_with = "file")]
+        value: Option<String>,
+        /// Where to write the encrypted file [default: <file>.age or <file>.gpg]
+        #[clap(short, long)]
+        out: Option<std::path::PathBuf>,
+    },
+    /// Decrypt a file to stdout, or to --out
+    Decrypt {
+        /// The encrypted file
+        file: std::path::PathBuf,
+        /// Where to write the plaintext
+        #[clap(short, long)]
+        out: Option<std::path::PathBuf>,
+    },
+    /// Decrypt a file, open it in $EDITOR and re-encrypt it
+    Edit {
+        /// The encrypted file
+        file: std::path::PathBuf,
+    },
+}
+
+#[derive(Parser, Debug)]
+pub enum HistoryCommand {
+    /// Show everything a run changed
+    Show {
+        /// The run's number, as listed by `dotman history`
+        id: usize,
+    },
+}
+
+#[derive(Parser, Debug)]
+pub enum ImportFormat {
+    /// Infer links from a GNU stow directory
+    Stow {
+        /// The stow directory containing one directory per package
+        dir: std::path::PathBuf,
+        /// Directory the packages are stowed into
+        #[clap(short, long, default_value = "~")]
+        target: std::path::PathBuf,
+        /// Where to write the generated configuration [default: <dir>/dotman.toml]
+        #[clap(short, long)]
+        out: Option<std::path::PathBuf>,
+        /// Overwrite the output file if it exists
+        #[clap(short, long, default_value = "false")]
+        force: bool,
+    },
+    /// Translate a chezmoi source directory into links, rewriting its
+    /// templates for template links
+    Chezmoi {
+        /// The chezmoi source directory [default: ~/.local/share/chezmoi]
+        dir: Option<std::path::PathBuf>,
+        /// Where to write the generated configuration [default: <dir>/dotman.toml]
+        #[clap(short, long)]
+        out: Option<std::path::PathBuf>,
+        /// Overwrite the output file if it exists
+        #[clap(short, long, default_value = "false")]
+        force: bool,
+    },
+}
+
+#[derive(Parser, Debug)]
+pub enum ExportFormat {
+    /// Copy sources into GNU stow packages, one per link
+    Stow {
+        /// Directory to write the stow packages to
+        #[clap(short, long, default_value = "stow")]
+        out: std::path::PathBuf,
+        /// Profile to export (global + profile-specific links)
+        #[clap(short, long)]
+        profile: Option<String>,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum OutputFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl From<OutputFormat> for ConfigFormat {
+    fn from(format: OutputFormat) -> Self {
+        match format {
+            OutputFormat::Toml => ConfigFormat::Toml,
+            OutputFormat::Yaml => ConfigFormat::Yaml,
+            OutputFormat::Json => ConfigFormat::Json,
+        }
+    }
 }
 
 impl Cli {
     /// Runs the command specified in the CLI arguments.
     pub fn run(self) -> anyhow::Result<()> {
-        // Load and validate the config once
-        let config = DotmanConfig::try_from(self.config.as_path()).map_err(|err| {
-            eprintln!("{} {}", "Error:".red().bold(), err);
-            err
-        })?;
+        if !(self.notify && self.command.changes_machine()) {
+            return self.execute();
+        }
+        let started = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        let result = self.execute();
+        Self::notify_finished(started, result.as_ref().err());
+        result
+    }
+
+    fn execute(self) -> anyhow::Result<()> {
+        let config_path = self.config.unwrap_or_else(config::default_config_path);
+        let context = Self::context(self.assume_os.clone(), self.assume_hostname.as_deref());
+        let _lock = if self.command.changes_machine() {
+            Some(RunLock::acquire_default(self.wait).map_err(|err| {
+                eprintln!("{} {}", "Error:".red().bold(), err);
+                err
+            })?)
+        } else {
+            None
+        };
 
         match self.command {
             Command::Install {
                 overwrite,
                 ask,
                 profile,
+                pull,
+                force,
+                dry_run,
+                only,
+                tag,
+                keep_going,
+                jobs,
+                locked,
+            } => {
+                if pull {
+                    Self::handle_pull(&Self::load_config(&config_path)?)?;
+                }
+                let dotman_config = Self::load_config(&config_path)?.with_profile(profile);
+                if locked {
+                    Self::check_locked(&dotman_config)?;
+                }
+                let dotman = Self::dotman(dotman_config, context)?;
+                let options = InstallOptions {
+                    overwrite,
+                    force,
+                    ask,
+                    dry_run,
+                    names: only,
+                    tags: tag,
+                    fail_fast: !keep_going,
+                    parallelism: jobs.max(1),
+                    cancel: Some(Self::cancel_on_interrupt()),
+                };
+                Self::handle_install(dotman, &options)
+            }
+            Command::Update { force, profile } => {
+                let dotman_config = Self::load_config(&config_path)?.with_profile(profile);
+                let dotman = Self::dotman(dotman_config, context)?;
+                let options = InstallOptions {
+                    force,
+                    cancel: Some(Self::cancel_on_interrupt()),
+                    ..Default::default()
+                };
+                Self::handle_update(dotman, &options)
+            }
+            Command::Watch { profile } => {
+                Self::handle_watch(&config_path, profile.as_deref(), || {
+                    Self::context(self.assume_os.clone(), self.assume_hostname.as_deref())
+                })
+            }
+            Command::Schedule { every, print } => {
+                Self::handle_schedule(&config_path, &every, print, &context.os)
+            }
+            Command::Pull => Self::handle_pull(&Self::load_config(&config_path)?).map(|_| ()),
+            Command::Sync {
+                overwrite,
+                force,
+                profile,
+            } => {
+                let changed = Self::handle_pull(&Self::load_config(&config_path)?)?;
+                let dotman_config = Self::load_config(&config_path)?.with_profile(profile);
+                let dotman = Self::dotman(dotman_config, context)?;
+                let options = InstallOptions {
+                    overwrite,
+                    force,
+                    cancel: Some(Self::cancel_on_interrupt()),
+                    ..Default::default()
+                };
+                Self::handle_sync(dotman, &options, changed)
+            }
+            Command::Adopt { target, source } => {
+                Self::handle_adopt(&config_path, &target, source.as_deref())
+            }
+            Command::Bootstrap {
+                url,
+                dest,
+                branch,
+                overwrite,
+                profile,
             } => {
-                let dotman_config = config
-                    .with_overwrite(overwrite)
-                    .with_ask(ask)
-                    .with_profile(profile);
-                let dotman = Dotman::new(dotman_config);
-                Self::handle_install(dotman)
-            }
-            Command::Validate => Self::handle_validate(),
+                Self::handle_bootstrap(&url, &dest, branch.as_deref(), overwrite, profile, context)
+            }
+            Command::Validate { profile, strict } => {
+                let dotman_config = Self::load_config(&config_path)?.with_profile(profile);
+                Self::handle_validate(dotman_config, strict)
+            }
+            Command::Lint { strict } => Self::handle_lint(Self::load_config(&config_path)?, strict),
+            Command::Fmt { check } => Self::handle_fmt(&config_path, check),
+            Command::Migrate { dry_run } => Self::handle_migrate(&config_path, dry_run),
             Command::Remove { profile } => {
-                let dotman_config = config.with_profile(profile);
-                let dotman = Dotman::new(dotman_config);
+                let dotman_config = Self::load_config(&config_path)?.with_profile(profile);
+                let dotman = Self::dotman(dotman_config, context)?;
                 Self::handle_remove(dotman)
             }
-            Command::Status { profile } => {
-                let dotman_config = config.with_profile(profile);
-                let dotman = Dotman::new(dotman_config);
+            Command::Status {
+                profile,
+                fix,
+                prune_orphans,
+            } => {
+                let dotman_config = Self::load_config(&config_path)?.with_profile(profile);
+                let dotman = Self::dotman(dotman_config, context)?;
+                if fix || prune_orphans {
+                    let options = InstallOptions {
+                        cancel: Some(Self::cancel_on_interrupt()),
+                        ..Default::default()
+                    };
+                    return Self::handle_fix(dotman, &options, fix, prune_orphans);
+                }
                 Self::handle_status(dotman)
             }
+            Command::Prune { dirs, dry_run } => {
+                let dotman = Self::dotman(Self::load_config(&config_path)?, context)?;
+                Self::handle_prune(dotman, dirs, dry_run)
+            }
+            Command::Repair { profile, dry_run } => {
+                let dotman_config = Self::load_config(&config_path)?.with_profile(profile);
+                let dotman = Self::dotman(dotman_config, context)?;
+                Self::handle_repair(dotman, dry_run)
+            }
+            Command::Clean {
+                keep,
+                older_than,
+                dry_run,
+            } => {
+                let max_age = older_than
+                    .as_deref()
+                    .map(schedule::parse_interval)
+                    .transpose()
+                    .map_err(|err| anyhow::anyhow!(err))?;
+                let options = CleanOptions {
+                    backups_dir: dotman::state::state_dir().map(|dir| dir.join("backups")),
+                    replaced_dir: dotman::state::state_dir().map(|dir| dir.join("replaced")),
+                    keep,
+                    max_age,
+                    caches: vec![download::cache_dir()?],
+                    dry_run,
+                };
+                let dotman = Self::dotman(Self::load_config(&config_path)?, context)?;
+                Self::handle_clean(dotman, &options)
+            }
+            Command::Verify { profile } => {
+                let dotman_config = Self::load_config(&config_path)?.with_profile(profile);
+                let dotman = Self::dotman(dotman_config, context)?;
+                Self::handle_verify(dotman)
+            }
+            Command::Show { format, profile } => {
+                let dotman_config = Self::load_config(&config_path)?.with_profile(profile);
+                Self::handle_show(dotman_config, format)
+            }
+            Command::Export {
+                format: ExportFormat::Stow { out, profile },
+            } => {
+                let dotman_config = Self::load_config(&config_path)?.with_profile(profile);
+                Self::handle_export_stow(dotman_config, &out)
+            }
+            Command::Import {
+                format:
+                    ImportFormat::Stow {
+                        dir,
+                        target,
+                        out,
+                        force,
+                    },
+            } => Self::handle_import_stow(&dir, &target, out, force),
+            Command::Import {
+                format: ImportFormat::Chezmoi { dir, out, force },
+            } => Self::handle_import_chezmoi(dir, out, force),
+            Command::Secret { command } => {
+                Self::handle_secret(&Self::load_config(&config_path)?, command)
+            }
+            Command::Schema => Self::handle_schema(),
+            Command::History { command, limit } => Self::handle_history(command, limit),
+            Command::Lock { profile } => {
+                Self::handle_lock(&Self::load_config(&config_path)?.with_profile(profile))
+            }
+            Command::DumpBrewfile { profile } => {
+                let dotman_config = Self::load_config(&config_path)?.with_profile(profile);
+                let dotman = Self::dotman(dotman_config, context)?;
+                Self::handle_dump_brewfile(dotman)
+            }
+            Command::Undo => {
+                let dotman = Self::dotman(Self::load_config(&config_path)?, context)?;
+                Self::handle_undo(dotman)
+            }
+        }
+    }
+
+    /// Detects the machine conditions are evaluated against, overridden by
+    /// `--assume-os` and `--assume-hostname`.
+    fn context(os: Option<config::OperatingSystem>, hostname: Option<&str>) -> Context {
+        let mut context = Context::detect();
+        if let Some(os) = os {
+            context = context.with_os(os);
+        }
+        if let Some(hostname) = hostname {
+            context = context.with_hostname(hostname);
+        }
+        context
+    }
+
+    /// Loads and validates the config, printing any error. URLs are fetched
+    /// into the cache first.
+    fn load_config(path: &std::path::Path) -> anyhow::Result<DotmanConfig> {
+        let fetched;
+        let path = if remote::is_url(path) {
+            fetched = remote::fetch_config(&path.to_string_lossy()).map_err(|err| {
+                eprintln!("{} {}", "Error:".red().bold(), err);
+                err
+            })?;
+            if let Some(reason) = &fetched.stale {
+                eprintln!(
+                    "{} {}; using cached copy {}",
+                    "Warning:".yellow().bold(),
+                    reason,
+                    fetched.path.display()
+                );
+            }
+            fetched.path.as_path()
+        } else {
+            path
+        };
+
+        DotmanConfig::try_from(path).map_err(|err| {
+            eprintln!("{} {}", "Error:".red().bold(), err);
+            err.context(ExitCode::Config)
+        })
+    }
+
+    fn handle_prune(
+        dotman: Dotman,
+        dirs: Vec<std::path::PathBuf>,
+        dry_run: bool,
+    ) -> anyhow::Result<()> {
+        let dirs = if dirs.is_empty() {
+            vec![dirs::home_dir().ok_or(dotman::error::PathError::HomeDirNotFound)?]
+        } else {
+            dirs.iter()
+                .map(|dir| dir.expand_tilde_path())
+                .collect::<Result<_, _>>()?
+        };
+        let pruned = Self::journaled(dotman, !dry_run, |dotman| dotman.prune(&dirs, dry_run))
+            .map_err(|err| {
+                eprintln!("{} {}", "Error:".red().bold(), err);
+                err
+            })?;
+        match pruned {
+            0 => println!("{}", "No broken symlinks found.".green()),
+            pruned if dry_run => println!("{} broken symlinks would be removed.", pruned),
+            pruned => println!("{}", format!("Removed {pruned} broken symlinks.").green()),
+        }
+        Ok(())
+    }
+
+    fn handle_repair(dotman: Dotman, dry_run: bool) -> anyhow::Result<()> {
+        let repaired =
+            Self::journaled(dotman, !dry_run, |dotman| dotman.repair(dry_run)).map_err(|err| {
+                eprintln!("{} {}", "Error:".red().bold(), err);
+                err
+            })?;
+        match repaired {
+            0 => println!("{}", "Every symlink points to its source.".green()),
+            repaired if dry_run => println!("{repaired} symlinks would be re-pointed."),
+            repaired => println!("{}", format!("Re-pointed {repaired} symlinks.").green()),
         }
+        Ok(())
     }
 
-    fn handle_install(dotman: Dotman) -> anyhow::Result<()> {
-        if let Err(e) = dotman.install() {
+    fn handle_clean(dotman: Dotman, options: &CleanOptions) -> anyhow::Result<()> {
+        let report = dotman.clean(options).map_err(|err| {
+            eprintln!("{} {}", "Error:".red().bold(), err);
+            err
+        })?;
+        let summary = format!(
+            "{} backups, {} replaced, {} state entries, {}",
+            report.backups,
+            report.replaced,
+            report.state_entries,
+            clean::format_size(report.reclaimed)
+        );
+        if options.dry_run {
+            println!("{} {}", "Would reclaim:".cyan().bold(), summary);
+        } else {
+            println!("{} {}", "Reclaimed:".green().bold(), summary);
+        }
+        Ok(())
+    }
+
+    fn handle_verify(dotman: Dotman) -> anyhow::Result<()> {
+        let mismatches = dotman.verify().map_err(|err| {
+            eprintln!("{} {}", "Error:".red().bold(), err);
+            err
+        })?;
+
+        for (target, mismatch) in &mismatches {
+            eprintln!(
+                "{} {} {}",
+                "Mismatch:".red().bold(),
+                target.display(),
+                mismatch
+            );
+        }
+        if !mismatches.is_empty() {
+            eprintln!(
+                "{}",
+                format!("{} targets do not match their sources.", mismatches.len()).red()
+            );
+            return Err(anyhow::anyhow!("Verification failed").context(ExitCode::Drift));
+        }
+
+        println!("{}", "All installed targets match their sources.".green());
+        Ok(())
+    }
+
+    /// Runs `run`, then appends what it changed to the journal if `record`
+    /// is set.
+    fn journaled<T>(
+        dotman: Dotman,
+        record: bool,
+        run: impl FnOnce(&Dotman) -> Result<T, DotmanError>,
+    ) -> Result<T, DotmanError> {
+        if !record {
+            return run(&dotman);
+        }
+        Self::recorded(dotman, Recorder::start(Self::command_line()), run)
+    }
+
+    /// Runs `run` while `recorder` collects what it changed, then appends
+    /// that to the journal. Failing to write the journal only warns.
+    fn recorded<T>(
+        dotman: Dotman,
+        recorder: Recorder,
+        run: impl FnOnce(&Dotman) -> Result<T, DotmanError>,
+    ) -> Result<T, DotmanError> {
+        let dotman = dotman.with_events(recorder.handler());
+        let result = run(&dotman);
+
+        let entry = recorder.finish(result.as_ref().err());
+        if let Err(err) = Journal::load_default().and_then(|journal| journal.append(&entry)) {
+            eprintln!(
+                "{} Could not write the journal: {}",
+                "Warning:".yellow().bold(),
+                err
+            );
+        }
+        result
+    }
+
+    /// Notifies about the run journaled since `started`, or about `err` if
+    /// the command failed before it got that far. Failing to notify only
+    /// warns.
+    fn notify_finished(started: u64, err: Option<&anyhow::Error>) {
+        let run = Journal::load_default()
+            .and_then(|journal| journal.runs())
+            .ok()
+            .and_then(|runs| runs.into_iter().rev().find(|run| run.started >= started));
+        let notification = match (run, err) {
+            (Some(run), _) => Notification::for_run(&run),
+            (None, Some(err)) => Notification {
+                title: format!("{} failed", Self::command_line()),
+                body: err.to_string(),
+            },
+            (None, None) => return,
+        };
+        if let Err(err) = notification.send(&utils::get_current_os()) {
+            eprintln!(
+                "{} Could not show a notification: {}",
+                "Warning:".yellow().bold(),
+                err
+            );
+        }
+    }
+
+    /// The command line dotman was run with, for the journal.
+    fn command_line() -> String {
+        std::iter::once("dotman".to_string())
+            .chain(std::env::args().skip(1))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Returns a flag that the first Ctrl-C sets, so the install stops after
+    /// the current link and still saves its state. A second Ctrl-C exits
+    /// immediately.
+    fn cancel_on_interrupt() -> std::sync::Arc<std::sync::atomic::AtomicBool> {
+        let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let flag = cancel.clone();
+        let _ = ctrlc::set_handler(move || {
+            if flag.swap(true, std::sync::atomic::Ordering::Relaxed) {
+                std::process::exit(130);
+            }
+            eprintln!(
+                "{} Stopping after the current step, press Ctrl-C again to exit now.",
+                "Interrupted:".yellow().bold()
+            );
+        });
+        cancel
+    }
+
+    /// Creates a `Dotman` that remembers installed targets in the state file
+    /// and backs up replaced targets in the state directory.
+    fn dotman(config: DotmanConfig, context: Context) -> anyhow::Result<Dotman> {
+        let state = State::load_default().map_err(|err| {
+            eprintln!("{} {}", "Error:".red().bold(), err);
+            err
+        })?;
+        let checksums = ChecksumCache::load_default().map_err(|err| {
+            eprintln!("{} {}", "Error:".red().bold(), err);
+            err
+        })?;
+        let mut dotman = Dotman::new(config)
+            .with_context(context)
+            .with_state(state)
+            .with_checksum_cache(checksums);
+        if let Some(dir) = dotman::state::state_dir() {
+            let started = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs());
+            dotman = dotman
+                .with_backup_dir(dir.join("backups").join(started.to_string()))
+                .with_replaced_dir(dir.join("replaced").join(started.to_string()));
+        }
+        Ok(dotman)
+    }
+
+    /// Fails with [`ExitCode::Conflict`] if targets were in the way, or
+    /// [`ExitCode::AllSkipped`] if the conditions ruled out every link.
+    fn handle_install(dotman: Dotman, options: &InstallOptions) -> anyhow::Result<()> {
+        let skips = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let installed = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let dotman = dotman.with_events({
+            let (skips, installed) = (skips.clone(), installed.clone());
+            move |event| match event {
+                Event::LinkSkipped { reason, .. } => skips.lock().unwrap().push(*reason),
+                Event::LinkCreated { .. } => {
+                    installed.store(true, std::sync::atomic::Ordering::Relaxed)
+                }
+                _ => {}
+            }
+        });
+        if let Err(e) = Self::journaled(dotman, !options.dry_run, |dotman| {
+            dotman.install_with(options)
+        }) {
             eprintln!("{} {}", "Error:".red().bold(), e);
             return Err(e.into());
         }
-        println!("{}", "Installation completed successfully.".green());
+
+        let skips = skips.lock().unwrap();
+        if skips.iter().any(|reason| {
+            matches!(
+                reason,
+                SkipReason::TargetExists | SkipReason::TargetModified
+            )
+        }) {
+            eprintln!(
+                "{}",
+                "Some targets were in the way and not installed.".yellow()
+            );
+            return Err(anyhow::anyhow!("Targets are in the way").context(ExitCode::Conflict));
+        }
+        if !installed.load(std::sync::atomic::Ordering::Relaxed)
+            && !skips.is_empty()
+            && skips
+                .iter()
+                .all(|reason| *reason == SkipReason::ConditionNotMet)
+        {
+            eprintln!("{}", "No link applies to this machine.".yellow());
+            return Err(anyhow::anyhow!("Every link was skipped").context(ExitCode::AllSkipped));
+        }
+        if !options.dry_run {
+            println!("{}", "Installation completed successfully.".green());
+        }
+        Ok(())
+    }
+
+    fn handle_update(dotman: Dotman, options: &InstallOptions) -> anyhow::Result<()> {
+        match Self::journaled(dotman, true, |dotman| dotman.update(options)) {
+            Ok(0) => println!("{}", "Everything is up to date.".green()),
+            Ok(_) => println!("{}", "Update completed successfully.".green()),
+            Err(e) => {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                return Err(e.into());
+            }
+        }
         Ok(())
     }
 
-    fn handle_validate() -> anyhow::Result<()> {
+    /// Runs `dotman update` whenever the configuration or a source changes,
+    /// until interrupted. Each update waits for other runs to finish first.
+    fn handle_watch(
+        config_path: &std::path::Path,
+        profile: Option<&str>,
+        context: impl Fn() -> Context,
+    ) -> anyhow::Result<()> {
+        loop {
+            let config = Self::load_config(config_path)
+                .map(|config| config.with_profile(profile.map(str::to_string)));
+            let paths = match &config {
+                Ok(config) => watch::watched_paths(config),
+                Err(_) => vec![config_path.absolute()?],
+            };
+            let watcher = Watcher::new(paths).map_err(|err| {
+                eprintln!("{} {}", "Error:".red().bold(), err);
+                err
+            })?;
+
+            if let Ok(config) = config {
+                let _lock = RunLock::acquire_default(true)?;
+                let dotman = Self::dotman(config, context())?;
+                // Failures are printed; keep watching so they can be fixed.
+                let _ = Self::handle_update(dotman, &InstallOptions::default());
+            }
+
+            println!("{}", "Watching for changes...".blue());
+            for path in watcher.wait(std::time::Duration::from_millis(200))? {
+                println!("{} {}", "Changed:".blue().bold(), path.display());
+            }
+        }
+    }
+
+    fn handle_schedule(
+        config_path: &std::path::Path,
+        every: &str,
+        print: bool,
+        os: &config::OperatingSystem,
+    ) -> anyhow::Result<()> {
+        let report = |err: DotmanError| {
+            eprintln!("{} {}", "Error:".red().bold(), err);
+            err
+        };
+        let every = schedule::parse_interval(every).map_err(|err| {
+            eprintln!("{} {}", "Error:".red().bold(), err);
+            anyhow::anyhow!(err)
+        })?;
+        let exe = std::env::current_exe()?;
+        let config = if remote::is_url(config_path) {
+            config_path.to_path_buf()
+        } else {
+            config_path.absolute()?
+        };
+        let (files, activations) =
+            schedule::unit_files(os, &exe, &config, every).map_err(report)?;
+
+        if print {
+            for file in &files {
+                println!(
+                    "{} {}",
+                    "#".dimmed(),
+                    file.path.display().to_string().dimmed()
+                );
+                println!("{}", file.contents);
+            }
+            return Ok(());
+        }
+
+        for file in &files {
+            if let Some(parent) = file.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&file.path, &file.contents)?;
+            println!("{} {}", "Wrote:".green().bold(), file.path.display());
+        }
+        for activation in &activations {
+            let status = std::process::Command::new(&activation.args[0])
+                .args(&activation.args[1..])
+                .status();
+            if !activation.may_fail && !status.as_ref().is_ok_and(|status| status.success()) {
+                eprintln!(
+                    "{} `{}` failed.",
+                    "Error:".red().bold(),
+                    activation.args.join(" ")
+                );
+                return Err(anyhow::anyhow!("Failed to activate the schedule"));
+            }
+        }
+        println!(
+            "{} dotman sync will run every {}.",
+            "Scheduled:".green().bold(),
+            Self::format_duration(every)
+        );
+        Ok(())
+    }
+
+    fn format_duration(duration: std::time::Duration) -> String {
+        let seconds = duration.as_secs();
+        match seconds {
+            s if s % 86400 == 0 => format!("{}d", s / 86400),
+            s if s % 3600 == 0 => format!("{}h", s / 3600),
+            s if s % 60 == 0 => format!("{}m", s / 60),
+            s => format!("{s}s"),
+        }
+    }
+
+    /// Pulls the main repository and every repository in `[repos]`. Returns
+    /// whether anything changed.
+    fn handle_pull(config: &DotmanConfig) -> anyhow::Result<bool> {
+        let mut repos = Vec::new();
+        if config.repo.is_some() || config.repos.is_empty() {
+            let dir = config.repo_dir()?;
+            repos.push((dir, config.repo.clone().unwrap_or_default()));
+        }
+        for (name, repo) in &config.repos {
+            if let Some(dir) = config.named_repo_dir(name)? {
+                repos.push((dir, repo.clone()));
+            }
+        }
+
+        let mut changed = false;
+        let mut dirty = false;
+        for (dir, repo) in repos {
+            let outcome =
+                git::pull(&dir, repo.url.as_deref(), repo.branch.as_deref()).map_err(|err| {
+                    eprintln!("{} {}", "Error:".red().bold(), err);
+                    err
+                })?;
+
+            match outcome {
+                git::PullOutcome::Cloned => {
+                    changed = true;
+                    println!("{} {}", "Cloned:".green().bold(), dir.display());
+                }
+                git::PullOutcome::UpToDate => {
+                    println!(
+                        "{} {} is up to date.",
+                        "Pulled:".green().bold(),
+                        dir.display()
+                    );
+                }
+                git::PullOutcome::Updated { from, to } => {
+                    changed = true;
+                    println!(
+                        "{} {} {}..{}",
+                        "Pulled:".green().bold(),
+                        dir.display(),
+                        from,
+                        to
+                    );
+                }
+                git::PullOutcome::Dirty(files) => {
+                    dirty = true;
+                    eprintln!(
+                        "{} {} has uncommitted changes, not pulling:",
+                        "Error:".red().bold(),
+                        dir.display()
+                    );
+                    for file in files {
+                        eprintln!("  {}", file);
+                    }
+                }
+            }
+        }
+
+        if dirty {
+            return Err(anyhow::anyhow!("Repository has uncommitted changes"));
+        }
+        Ok(changed)
+    }
+
+    /// Actions only run when the pull brought in changes.
+    fn handle_sync(
+        dotman: Dotman,
+        options: &InstallOptions,
+        run_actions: bool,
+    ) -> anyhow::Result<()> {
+        match Self::journaled(dotman, true, |dotman| dotman.sync(options, run_actions)) {
+            Ok(0) => Ok(()),
+            Ok(_) => Err(anyhow::anyhow!("Targets have drifted").context(ExitCode::Drift)),
+            Err(e) => {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                Err(e.into())
+            }
+        }
+    }
+
+    fn handle_adopt(
+        config_path: &std::path::Path,
+        target: &std::path::Path,
+        source: Option<&str>,
+    ) -> anyhow::Result<()> {
+        Self::require_toml(config_path, "adopt")?;
+        let config = Self::load_config(config_path)?;
+
+        let link = adopt::adopt(&config, target, source).map_err(|err| {
+            eprintln!("{} {}", "Error:".red().bold(), err);
+            err
+        })?;
+        let input = std::fs::read_to_string(config_path)?;
+        std::fs::write(config_path, adopt::add_link(&input, &link)?)?;
+
+        let source_path = config.resolve_source(&link.source)?;
+        println!(
+            "{} {} -> {}",
+            "Adopted:".green().bold(),
+            link.target,
+            source_path.display()
+        );
+
+        if config.repo.as_ref().is_some_and(|repo| repo.auto_commit) {
+            let repo_dir = config.repo_dir()?;
+            let config_file = config_path.absolute()?;
+            let paths = [source_path.as_path(), config_file.as_path()];
+            let message = format!("Adopt {}", link.target);
+            git::commit(&repo_dir, &paths, &message).map_err(|err| {
+                eprintln!("{} {}", "Error:".red().bold(), err);
+                err
+            })?;
+            println!("{} {}", "Committed:".green().bold(), message);
+        }
+        Ok(())
+    }
+
+    fn handle_bootstrap(
+        url: &str,
+        dest: &std::path::Path,
+        branch: Option<&str>,
+        overwrite: bool,
+        profile: Option<String>,
+        context: Context,
+    ) -> anyhow::Result<()> {
+        let dest = dest.expand_tilde_path()?;
+
+        if git::is_repo(&dest) {
+            println!(
+                "{} {} is already a git repository, skipping clone.",
+                "Skipped:".yellow().bold(),
+                dest.display()
+            );
+        } else if dest
+            .read_dir()
+            .is_ok_and(|mut entries| entries.next().is_some())
+        {
+            eprintln!(
+                "{} {} exists and is not empty.",
+                "Error:".red().bold(),
+                dest.display()
+            );
+            return Err(anyhow::anyhow!("Destination is not empty"));
+        } else {
+            git::clone(url, &dest, branch).map_err(|err| {
+                eprintln!("{} {}", "Error:".red().bold(), err);
+                err
+            })?;
+            println!("{} {} -> {}", "Cloned:".green().bold(), url, dest.display());
+        }
+
+        let Some(config_path) = config::find_config_in(&dest) else {
+            eprintln!(
+                "{} No dotman configuration found in {}.",
+                "Error:".red().bold(),
+                dest.display()
+            );
+            return Err(anyhow::anyhow!("Configuration file not found"));
+        };
+
+        let dotman_config = Self::load_config(&config_path)?.with_profile(profile);
+        let options = InstallOptions {
+            overwrite,
+            cancel: Some(Self::cancel_on_interrupt()),
+            ..Default::default()
+        };
+        Self::handle_install(Self::dotman(dotman_config, context)?, &options)
+    }
+
+    fn handle_validate(config: DotmanConfig, strict: bool) -> anyhow::Result<()> {
+        let diagnostics = if strict {
+            validate::validate_strict(&config)
+        } else {
+            validate::validate(&config)
+        };
+
+        Self::print_diagnostics(&diagnostics);
+
+        if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+            eprintln!("{}", "Configuration file is invalid.".red());
+            return Err(anyhow::anyhow!("Configuration file is invalid").context(ExitCode::Config));
+        }
+
         println!("{}", "Configuration file is valid.".green());
         Ok(())
     }
 
+    fn handle_lint(config: DotmanConfig, strict: bool) -> anyhow::Result<()> {
+        let mut diagnostics = validate::lint(&config);
+        if strict {
+            validate::deny_warnings(&mut diagnostics);
+        }
+
+        Self::print_diagnostics(&diagnostics);
+
+        if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+            return Err(anyhow::anyhow!("Lint failed").context(ExitCode::Config));
+        }
+
+        if diagnostics.is_empty() {
+            println!("{}", "No lint findings.".green());
+        }
+        Ok(())
+    }
+
+    fn handle_fmt(path: &std::path::Path, check: bool) -> anyhow::Result<()> {
+        Self::require_toml(path, "fmt")?;
+        let input = std::fs::read_to_string(path).map_err(|err| {
+            eprintln!(
+                "{} Failed to read configuration file '{}': {}",
+                "Error:".red().bold(),
+                path.display(),
+                err
+            );
+            err
+        })?;
+        let formatted = format::format_config(&input).map_err(|err| {
+            eprintln!("{} {}", "Error:".red().bold(), err);
+            err
+        })?;
+
+        if formatted == input {
+            println!("{} is already formatted.", path.display());
+            return Ok(());
+        }
+
+        if check {
+            eprintln!(
+                "{} {} is not formatted. Run `dotman fmt` to fix it.",
+                "Error:".red().bold(),
+                path.display()
+            );
+            return Err(anyhow::anyhow!("Configuration file is not formatted"));
+        }
+
+        std::fs::write(path, formatted)?;
+        println!("{} {}", "Formatted:".green().bold(), path.display());
+        Ok(())
+    }
+
+    fn handle_migrate(path: &std::path::Path, dry_run: bool) -> anyhow::Result<()> {
+        Self::require_toml(path, "migrate")?;
+        let migrated = std::fs::read_to_string(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|input| migrate::migrate(&input))
+            .map_err(|err| {
+                eprintln!("{} {}", "Error:".red().bold(), err);
+                err
+            })?;
+
+        if migrated.from == migrate::CURRENT_VERSION {
+            println!(
+                "{} is already at version {}.",
+                path.display(),
+                migrate::CURRENT_VERSION
+            );
+            return Ok(());
+        }
+
+        for description in &migrated.applied {
+            println!("{} {}", "Migrate:".blue().bold(), description);
+        }
+
+        if dry_run {
+            print!("{}", migrated.output);
+            return Ok(());
+        }
+
+        let backup = path.with_extension("toml.bak");
+        std::fs::copy(path, &backup)?;
+        std::fs::write(path, &migrated.output)?;
+        println!(
+            "{} {} from version {} to {} (backup at {})",
+            "Migrated:".green().bold(),
+            path.display(),
+            migrated.from,
+            migrate::CURRENT_VERSION,
+            backup.display()
+        );
+        Ok(())
+    }
+
+    fn require_toml(path: &std::path::Path, command: &str) -> anyhow::Result<()> {
+        if remote::is_url(path) {
+            eprintln!(
+                "{} `dotman {}` only supports local configuration files.",
+                "Error:".red().bold(),
+                command
+            );
+            return Err(anyhow::anyhow!("Unsupported remote configuration"));
+        }
+        if ConfigFormat::from_path(path) != ConfigFormat::Toml {
+            eprintln!(
+                "{} `dotman {}` only supports TOML configuration files.",
+                "Error:".red().bold(),
+                command
+            );
+            return Err(anyhow::anyhow!("Unsupported configuration format"));
+        }
+        Ok(())
+    }
+
+    fn print_diagnostics(diagnostics: &[Diagnostic]) {
+        for diagnostic in diagnostics {
+            let label = match diagnostic.severity {
+                Severity::Error => "Error:".red().bold(),
+                Severity::Warning => "Warning:".yellow().bold(),
+            };
+            eprintln!(
+                "{} {} {}",
+                label,
+                diagnostic.message,
+                format!("[{}]", diagnostic.lint.as_str()).dimmed()
+            );
+        }
+    }
+
     fn handle_remove(dotman: Dotman) -> anyhow::Result<()> {
-        if let Err(e) = dotman.remove() {
+        if let Err(e) = Self::journaled(dotman, true, Dotman::remove) {
             eprintln!("{} {}", "Error:".red().bold(), e);
             return Err(e.into());
         }
@@ -101,11 +1348,453 @@ impl Cli {
         Ok(())
     }
 
+    /// Fails with [`ExitCode::Drift`] if links are missing or have drifted,
+    /// so scripts can check whether the machine matches the configuration.
     fn handle_status(dotman: Dotman) -> anyhow::Result<()> {
-        if let Err(e) = dotman.status() {
+        match dotman.status() {
+            Ok(0) => Ok(()),
+            Ok(_) => {
+                Err(anyhow::anyhow!("Links are missing or have drifted").context(ExitCode::Drift))
+            }
+            Err(e) => {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Repairs the drifted links if `fix` is set and removes the orphaned
+    /// targets if `prune_orphans` is, then shows the status they are left in.
+    /// Like `dotman status`, fails with [`ExitCode::Drift`] if drift remains.
+    fn handle_fix(
+        dotman: Dotman,
+        options: &InstallOptions,
+        fix: bool,
+        prune_orphans: bool,
+    ) -> anyhow::Result<()> {
+        let result = Self::journaled(dotman, true, |dotman| {
+            let mut fixed = 0;
+            if fix {
+                fixed += dotman.fix(options)?;
+            }
+            if prune_orphans {
+                fixed += dotman.prune_orphans(options)?;
+            }
+            println!();
+            Ok((fixed, dotman.status()?))
+        });
+        let (fixed, unconverged) = result.map_err(|e| {
+            eprintln!("{} {}", "Error:".red().bold(), e);
+            e
+        })?;
+        match fixed {
+            0 => println!("\n{}", "Nothing needed fixing.".green()),
+            fixed => println!("\n{} {} targets.", "Fixed:".green().bold(), fixed),
+        }
+        if unconverged > 0 {
+            return Err(
+                anyhow::anyhow!("Links are missing or have drifted").context(ExitCode::Drift)
+            );
+        }
+        Ok(())
+    }
+
+    fn handle_show(config: DotmanConfig, format: OutputFormat) -> anyhow::Result<()> {
+        print!("{}", config.resolved().to_string_as(format.into())?);
+        Ok(())
+    }
+
+    fn handle_export_stow(config: DotmanConfig, out: &std::path::Path) -> anyhow::Result<()> {
+        let report = stow::export(&config, out).map_err(|err| {
+            eprintln!("{} {}", "Error:".red().bold(), err);
+            err
+        })?;
+
+        for link in &report.exported {
+            println!(
+                "{} {} -> {}",
+                "Exported:".green().bold(),
+                link.source.display(),
+                link.destination.display()
+            );
+            if link.dropped_condition {
+                println!(
+                    "{} conditions on {} cannot be expressed in stow and were dropped.",
+                    "Warning:".yellow().bold(),
+                    link.source.display()
+                );
+            }
+        }
+        for link in &report.skipped {
+            println!(
+                "{} {}: {}",
+                "Skipped:".yellow().bold(),
+                link.source,
+                link.reason
+            );
+        }
+
+        let mut packages: Vec<&str> = report.exported.iter().map(|l| l.package.as_str()).collect();
+        packages.dedup();
+        if !packages.is_empty() {
+            println!();
+            println!("Link the packages with:");
+            println!("  stow -d {} -t ~ {}", out.display(), packages.join(" "));
+        }
+        Ok(())
+    }
+
+    fn handle_import_stow(
+        dir: &std::path::Path,
+        target: &std::path::Path,
+        out: Option<std::path::PathBuf>,
+        force: bool,
+    ) -> anyhow::Result<()> {
+        let dir = dir.expand_tilde_path()?;
+        let target = target.expand_tilde_path()?;
+        let links = stow::import(&dir, &target).map_err(|err| {
+            eprintln!("{} {}", "Error:".red().bold(), err);
+            err
+        })?;
+
+        let config = DotmanConfig::builder().links(links).build();
+        Self::write_generated_config(
+            &config,
+            &out.unwrap_or_else(|| dir.join("dotman.toml")),
+            force,
+        )
+    }
+
+    fn handle_import_chezmoi(
+        dir: Option<std::path::PathBuf>,
+        out: Option<std::path::PathBuf>,
+        force: bool,
+    ) -> anyhow::Result<()> {
+        let dir = match dir {
+            Some(dir) => dir.expand_tilde_path()?,
+            None => chezmoi::default_source_dir()
+                .ok_or_else(|| anyhow::anyhow!("Home directory not found"))?,
+        };
+        let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Home directory not found"))?;
+        let imported = chezmoi::import(&dir, &home).map_err(|err| {
+            eprintln!("{} {}", "Error:".red().bold(), err);
+            err
+        })?;
+
+        for entry in &imported.untranslated {
+            println!(
+                "{} {}: {}",
+                "Skipped:".yellow().bold(),
+                entry.path.display(),
+                entry.reason
+            );
+        }
+        for template in &imported.templates {
+            let path = dir.join(&template.path);
+            if path.exists() && !force {
+                eprintln!(
+                    "{} {} already exists. Use --force to overwrite it.",
+                    "Error:".red().bold(),
+                    path.display()
+                );
+                return Err(anyhow::anyhow!("Translated template already exists"));
+            }
+            std::fs::write(&path, &template.contents)?;
+            println!("{} {}", "Translated:".green().bold(), path.display());
+        }
+        if !imported.variables.is_empty() {
+            let variables: Vec<&str> = imported.variables.iter().map(String::as_str).collect();
+            println!(
+                "{} the templates use {}, which [variables] has to declare.",
+                "Warning:".yellow().bold(),
+                variables.join(", ")
+            );
+        }
+
+        let config = DotmanConfig::builder().links(imported.links).build();
+        Self::write_generated_config(
+            &config,
+            &out.unwrap_or_else(|| dir.join("dotman.toml")),
+            force,
+        )
+    }
+
+    /// Writes a generated configuration in canonical TOML form.
+    fn write_generated_config(
+        config: &DotmanConfig,
+        out: &std::path::Path,
+        force: bool,
+    ) -> anyhow::Result<()> {
+        if out.exists() && !force {
+            eprintln!(
+                "{} {} already exists. Use --force to overwrite it.",
+                "Error:".red().bold(),
+                out.display()
+            );
+            return Err(anyhow::anyhow!("Output file already exists"));
+        }
+
+        let toml = format::format_config(&config.to_string_as(ConfigFormat::Toml)?)?;
+        std::fs::write(out, toml)?;
+        println!(
+            "{} {} with {} links.",
+            "Generated:".green().bold(),
+            out.display(),
+            config.links.len()
+        );
+        Ok(())
+    }
+
+    fn handle_lock(config: &DotmanConfig) -> anyhow::Result<()> {
+        let path = Lockfile::path_for(config);
+        Lockfile::resolve(config)
+            .and_then(|lockfile| lockfile.save(&path))
+            .map_err(|err| {
+                eprintln!("{} {}", "Error:".red().bold(), err);
+                err
+            })?;
+        println!("{} {}", "Locked:".green().bold(), path.display());
+        Ok(())
+    }
+
+    fn handle_dump_brewfile(dotman: Dotman) -> anyhow::Result<()> {
+        let path = dotman.dump_brewfile().map_err(|err| {
+            eprintln!("{} {}", "Error:".red().bold(), err);
+            err
+        })?;
+        println!("{} {}", "Dumped:".green().bold(), path.display());
+        Ok(())
+    }
+
+    /// Fails with [`ExitCode::Drift`] unless `config` resolves to exactly
+    /// what its lockfile records.
+    fn check_locked(config: &DotmanConfig) -> anyhow::Result<()> {
+        let path = Lockfile::path_for(config);
+        let drift = Lockfile::load(&path)
+            .and_then(|locked| Ok(locked.drift(&Lockfile::resolve(config)?)))
+            .map_err(|err| {
+                eprintln!("{} {}: {}", "Error:".red().bold(), path.display(), err);
+                err
+            })?;
+        if drift.is_empty() {
+            return Ok(());
+        }
+        for difference in &drift {
+            eprintln!("{} {}", "Drift:".red().bold(), difference);
+        }
+        eprintln!(
+            "{}",
+            "Not installing: the configuration no longer matches dotman.lock. Run `dotman lock` to update it.".red()
+        );
+        Err(anyhow::anyhow!("Lockfile is out of date").context(ExitCode::Drift))
+    }
+
+    fn handle_undo(dotman: Dotman) -> anyhow::Result<()> {
+        let runs = Journal::load_default()
+            .and_then(|journal| journal.runs())
+            .map_err(|err| {
+                eprintln!("{} {}", "Error:".red().bold(), err);
+                err
+            })?;
+        let Some(id) = Journal::last_undoable(&runs) else {
+            println!("Nothing to undo.");
+            return Ok(());
+        };
+        let run = &runs[id - 1];
+        println!(
+            "{} #{} {} ({})",
+            "Undoing:".blue().bold(),
+            id,
+            run.command,
+            Self::format_time(run.started)
+        );
+
+        let recorder = Recorder::start(Self::command_line()).undoing(id);
+        if let Err(e) = Self::recorded(dotman, recorder, |dotman| dotman.undo(run)) {
             eprintln!("{} {}", "Error:".red().bold(), e);
             return Err(e.into());
         }
+        println!("{}", "Undo completed successfully.".green());
+        Ok(())
+    }
+
+    fn handle_history(command: Option<HistoryCommand>, limit: usize) -> anyhow::Result<()> {
+        let runs = Journal::load_default()
+            .and_then(|journal| journal.runs())
+            .map_err(|err| {
+                eprintln!("{} {}", "Error:".red().bold(), err);
+                err
+            })?;
+
+        let Some(HistoryCommand::Show { id }) = command else {
+            if runs.is_empty() {
+                println!("No runs recorded yet.");
+            }
+            for (index, run) in runs
+                .iter()
+                .enumerate()
+                .skip(runs.len().saturating_sub(limit))
+            {
+                let failures = if run.failures.is_empty() {
+                    String::new()
+                } else {
+                    format!(", {}", format!("{} failed", run.failures.len()).red())
+                };
+                println!(
+                    "{} {} {} ({} changed{})",
+                    format!("#{}", index + 1).blue().bold(),
+                    Self::format_time(run.started),
+                    run.command,
+                    run.changes.len(),
+                    failures
+                );
+            }
+            return Ok(());
+        };
+
+        let Some(run) = id.checked_sub(1).and_then(|index| runs.get(index)) else {
+            eprintln!("{} No run #{} in the journal.", "Error:".red().bold(), id);
+            return Err(anyhow::anyhow!("Run not found"));
+        };
+        println!("{} {}", "Command:".blue().bold(), run.command);
+        println!(
+            "{} {} to {}",
+            "Ran:".blue().bold(),
+            Self::format_time(run.started),
+            Self::format_time(run.finished)
+        );
+        for change in &run.changes {
+            match change {
+                Change::Link {
+                    source,
+                    target,
+                    mode,
+                } => {
+                    let label = match mode {
+                        LinkMode::Symlink | LinkMode::Junction => "Linked:",
+                        LinkMode::Secret => "Decrypted:",
+                        LinkMode::Template => "Rendered:",
+                        LinkMode::Copy => "Copied:",
+                        LinkMode::Concat => "Assembled:",
+                    };
+                    println!(
+                        "{} {} -> {}",
+                        label.green().bold(),
+                        source.display(),
+                        target.display()
+                    );
+                }
+                Change::Unlink { target } => {
+                    println!("{} {}", "Removed:".green().bold(), target.display());
+                }
+                Change::Redecrypt { target, .. } => {
+                    println!("{} {}", "Rewritten:".green().bold(), target.display());
+                }
+                Change::Backup { target, backup, .. } => {
+                    println!(
+                        "{} {} -> {}",
+                        "Backed up:".green().bold(),
+                        target.display(),
+                        backup.display()
+                    );
+                }
+                Change::Action {
+                    name,
+                    success: true,
+                } => println!("{} {}", "Action:".green().bold(), name),
+                Change::Action {
+                    name,
+                    success: false,
+                } => println!("{} {}", "Action failed:".red().bold(), name),
+            }
+        }
+        for failure in &run.failures {
+            println!("{} {}", "Error:".red().bold(), failure);
+        }
+        Ok(())
+    }
+
+    /// Formats seconds since the Unix epoch in local time.
+    fn format_time(seconds: u64) -> String {
+        chrono::DateTime::from_timestamp(seconds as i64, 0)
+            .map(|time| {
+                time.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            })
+            .unwrap_or_else(|| seconds.to_string())
+    }
+
+    fn handle_secret(config: &DotmanConfig, command: SecretCommand) -> anyhow::Result<()> {
+        let report = |err: DotmanError| {
+            eprintln!("{} {}", "Error:".red().bold(), err);
+            err
+        };
+
+        match command {
+            SecretCommand::Encrypt {
+                value: Some(value), ..
+            } => {
+                println!(
+                    "{}",
+                    secrets::encrypt_value(config, &value).map_err(report)?
+                );
+            }
+            SecretCommand::Encrypt { file, out, .. } => {
+                let file = file.expect("clap requires file without --value");
+                let backend = config.secrets.clone().unwrap_or_default().backend;
+                let out = out.unwrap_or_else(|| {
+                    let mut name = file.clone().into_os_string();
+                    name.push(format!(".{}", backend.extension()));
+                    name.into()
+                });
+                let ciphertext =
+                    secrets::encrypt(config, &std::fs::read(&file)?).map_err(report)?;
+                std::fs::write(&out, ciphertext)?;
+                println!(
+                    "{} {} -> {}",
+                    "Encrypted:".green().bold(),
+                    file.display(),
+                    out.display()
+                );
+            }
+            SecretCommand::Decrypt { file, out } => {
+                let plaintext = secrets::decrypt(config, &file).map_err(report)?;
+                match out {
+                    Some(out) => {
+                        secrets::write_plaintext(&out, &plaintext)?;
+                        println!(
+                            "{} {} -> {}",
+                            "Decrypted:".green().bold(),
+                            file.display(),
+                            out.display()
+                        );
+                    }
+                    None => {
+                        use std::io::Write;
+                        std::io::stdout().write_all(&plaintext)?;
+                    }
+                }
+            }
+            SecretCommand::Edit { file } => {
+                let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+                if secrets::edit(config, &file, &editor).map_err(report)? {
+                    println!("{} {}", "Encrypted:".green().bold(), file.display());
+                } else {
+                    println!(
+                        "{} {} is unchanged.",
+                        "Skipped:".yellow().bold(),
+                        file.display()
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_schema() -> anyhow::Result<()> {
+        let schema = serde_json::to_string_pretty(&DotmanConfig::json_schema())?;
+        println!("{}", schema);
         Ok(())
     }
 }
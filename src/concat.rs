@@ -0,0 +1,120 @@
+//! Assembling `mode = "concat"` links from fragments.
+
+use crate::{
+    Dotman,
+    config::Link,
+    error::{DotmanError, PathError},
+};
+
+impl Dotman {
+    /// The fragments of `link` in `source`, in order: those it lists whose
+    /// conditions are met, or else every file in `source` by name. Hidden
+    /// files are left out of the latter.
+    fn fragments(
+        &self,
+        link: &Link,
+        source: &std::path::Path,
+    ) -> Result<Vec<std::path::PathBuf>, DotmanError> {
+        if !link.fragments.is_empty() {
+            return Ok(link
+                .fragments
+                .iter()
+                .filter(|fragment| fragment.is_met(&self.context))
+                .map(|fragment| source.join(fragment.source()))
+                .collect());
+        }
+        let mut fragments = Vec::new();
+        for entry in std::fs::read_dir(source)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') || !entry.path().is_file() {
+                continue;
+            }
+            fragments.push(entry.path());
+        }
+        fragments.sort();
+        Ok(fragments)
+    }
+
+    /// What the target of a `concat` link should contain: a header saying
+    /// it is generated, then each fragment in turn, ending in a newline.
+    pub(crate) fn concatenate(
+        &self,
+        link: &Link,
+        source: &std::path::Path,
+    ) -> Result<Vec<u8>, DotmanError> {
+        let mut contents = format!(
+            "# Generated by dotman from {}. Do not edit: changes are overwritten.\n",
+            source.display()
+        )
+        .into_bytes();
+        for fragment in self.fragments(link, source)? {
+            let fragment = std::fs::read(&fragment).map_err(|source| PathError::Unreadable {
+                path: fragment.clone(),
+                source,
+            })?;
+            contents.extend_from_slice(&fragment);
+            if !fragment.is_empty() && !fragment.ends_with(b"\n") {
+                contents.push(b'\n');
+            }
+        }
+        Ok(contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Condition, Fragment, Link, LinkMode, OneOrMany};
+    use crate::{Dotman, DotmanConfig, LinkStatus};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_concat_link() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("ssh");
+        std::fs::create_dir(&source).unwrap();
+        std::fs::write(source.join("10-work"), "Host work\n").unwrap();
+        std::fs::write(source.join("20-home"), "Host home").unwrap();
+        std::fs::write(source.join(".swp"), "ignored").unwrap();
+        let target = temp_dir.path().join("config");
+
+        let link = Link::new(source.to_str().unwrap(), target.to_str().unwrap())
+            .with_mode(LinkMode::Concat);
+        let config = DotmanConfig::builder().link(link.clone()).build();
+        let dotman = Dotman::new(config);
+        dotman.install().unwrap();
+
+        let header = format!(
+            "# Generated by dotman from {}. Do not edit: changes are overwritten.\n",
+            source.display()
+        );
+        assert_eq!(
+            std::fs::read_to_string(&target).unwrap(),
+            format!("{header}Host work\nHost home\n")
+        );
+        assert_eq!(dotman.link_statuses().unwrap()[0].status, LinkStatus::Ok);
+
+        std::fs::write(source.join("10-work"), "Host office\n").unwrap();
+        assert_eq!(
+            dotman.link_statuses().unwrap()[0].status,
+            LinkStatus::Modified
+        );
+
+        let work_only = |source: &str| Fragment::Conditional {
+            source: source.to_string(),
+            if_cond: Some(Box::new(Condition {
+                exists: Some(OneOrMany::One("/nonexistent/dotman-work".to_string())),
+                ..Default::default()
+            })),
+            if_not_cond: None,
+        };
+        let link = Link {
+            fragments: vec![Fragment::Path("20-home".to_string()), work_only("10-work")],
+            ..link
+        };
+        let contents = dotman.concatenate(&link, &source).unwrap();
+        assert_eq!(
+            String::from_utf8(contents).unwrap(),
+            format!("{header}Host home\n")
+        );
+    }
+}
@@ -1,6 +1,17 @@
-use anyhow::Context;
+use anyhow::Context as _;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    context::Context,
+    error::{ConfigFileParseError, DotmanError, PathError},
+    migrate::CURRENT_VERSION,
+    packages::PackageManager,
+    secrets,
+    utils::{Absolute, ExpandTilde},
+    validate::{Lint, LintLevel},
+};
+
 const fn default_false() -> bool {
     false
 }
@@ -9,15 +20,112 @@ fn base_config_path() -> String {
     "dotman.toml".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum OperatingSystem {
     Linux,
     MacOS,
     Windows,
+    FreeBSD,
+    OpenBSD,
+    NetBSD,
+    DragonFly,
+    /// Any other system, by the name Rust reports for it (e.g. `illumos`).
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl std::str::FromStr for OperatingSystem {
+    type Err = std::convert::Infallible;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Ok(match name.to_lowercase().as_str() {
+            "linux" => OperatingSystem::Linux,
+            "macos" => OperatingSystem::MacOS,
+            "windows" => OperatingSystem::Windows,
+            "freebsd" => OperatingSystem::FreeBSD,
+            "openbsd" => OperatingSystem::OpenBSD,
+            "netbsd" => OperatingSystem::NetBSD,
+            "dragonfly" => OperatingSystem::DragonFly,
+            other => OperatingSystem::Other(other.to_string()),
+        })
+    }
+}
+
+/// Names Rust reports in `std::env::consts::OS` for systems without a
+/// variant of their own.
+const OTHER_OS_NAMES: &[&str] = &[
+    "aix",
+    "android",
+    "cygwin",
+    "emscripten",
+    "espidf",
+    "fuchsia",
+    "haiku",
+    "hermit",
+    "horizon",
+    "hurd",
+    "illumos",
+    "ios",
+    "l4re",
+    "nto",
+    "nuttx",
+    "psp",
+    "redox",
+    "rtems",
+    "solaris",
+    "solid_asp3",
+    "teeos",
+    "trusty",
+    "tvos",
+    "uefi",
+    "visionos",
+    "vita",
+    "vxworks",
+    "wasi",
+    "watchos",
+    "xous",
+    "zkvm",
+];
+
+impl OperatingSystem {
+    /// Whether Rust reports this system on some target. Any other name, like
+    /// `darwin` or `MacOS`, never matches.
+    pub fn is_known(&self) -> bool {
+        match self {
+            OperatingSystem::Other(name) => OTHER_OS_NAMES.contains(&name.as_str()),
+            _ => true,
+        }
+    }
+
+    /// The known name closest to an unknown one, like `macos` for `darwin`.
+    pub fn suggestion(&self) -> Option<&'static str> {
+        let OperatingSystem::Other(name) = self else {
+            return None;
+        };
+        let name = name.to_lowercase();
+        if name == "darwin" || name == "osx" {
+            return Some("macos");
+        }
+        [
+            "linux",
+            "macos",
+            "windows",
+            "freebsd",
+            "openbsd",
+            "netbsd",
+            "dragonfly",
+        ]
+        .iter()
+        .chain(OTHER_OS_NAMES)
+        .map(|known| (strsim::jaro_winkler(&name, known), *known))
+        .filter(|(score, _)| *score >= 0.8)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, known)| known)
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum Shell {
     #[default]
@@ -25,6 +133,8 @@ pub enum Shell {
     Bash,
     Zsh,
     Fish,
+    /// Nushell.
+    Nu,
 }
 
 impl Shell {
@@ -34,11 +144,12 @@ impl Shell {
             Shell::Bash => "bash",
             Shell::Zsh => "zsh",
             Shell::Fish => "fish",
+            Shell::Nu => "nu",
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(untagged)]
 pub enum RunCommand {
     Simple(String),
@@ -48,27 +159,127 @@ pub enum RunCommand {
     },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(untagged)]
 pub enum Hostname {
     Single(String),
     Multiple(Vec<String>),
 }
 
+/// A single value or a list of values.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        let values = match self {
+            OneOrMany::One(value) => std::slice::from_ref(value),
+            OneOrMany::Many(values) => values.as_slice(),
+        };
+        values.iter().map(String::as_str)
+    }
+}
+
 impl RunCommand {
-    pub fn execute(&self) -> Result<std::process::Output, std::io::Error> {
+    /// Returns the command line that will be executed.
+    pub fn command(&self) -> &str {
+        match self {
+            RunCommand::Simple(command) | RunCommand::Complex { command, .. } => command,
+        }
+    }
+
+    /// Returns the shell the command runs in.
+    pub fn shell(&self) -> &'static str {
         match self {
-            RunCommand::Simple(cmd) => std::process::Command::new("sh").arg("-c").arg(cmd).output(),
-            RunCommand::Complex { command, shell } => {
-                let shell = shell.as_ref().unwrap_or(&Shell::Sh).as_str();
-                std::process::Command::new(shell)
-                    .arg("-c")
-                    .arg(command)
-                    .output()
+            RunCommand::Simple(_) => Shell::Sh.as_str(),
+            RunCommand::Complex { shell, .. } => shell.as_ref().unwrap_or(&Shell::Sh).as_str(),
+        }
+    }
+
+    /// Returns the program that runs the command: the interpreter on a
+    /// script's `#!` line, or the shell.
+    pub fn interpreter(&self) -> &str {
+        match self.script_shebang() {
+            Some((interpreter, _)) => interpreter,
+            None => self.shell(),
+        }
+    }
+
+    /// The `#!` line of a command spanning several lines.
+    fn script_shebang(&self) -> Option<(&str, Option<&str>)> {
+        let script = self.command();
+        script.trim().contains('\n').then(|| shebang(script))?
+    }
+
+    pub fn execute(&self) -> Result<std::process::Output, std::io::Error> {
+        self.execute_as(None)
+    }
+
+    /// Runs the command, as root through `elevate` (`sudo` or `doas`) if
+    /// given.
+    pub fn execute_as(
+        &self,
+        elevate: Option<&str>,
+    ) -> Result<std::process::Output, std::io::Error> {
+        let (command, _script) = self.to_command()?;
+        match elevate {
+            Some(program) => crate::privilege::elevate(&command, program).output(),
+            None => {
+                let mut command = command;
+                command.output()
             }
         }
     }
 
+    /// Runs the command without blocking the async runtime, as root through
+    /// `elevate` if given.
+    #[cfg(feature = "async")]
+    pub async fn execute_async(
+        &self,
+        elevate: Option<&str>,
+    ) -> Result<std::process::Output, std::io::Error> {
+        let (command, _script) = self.to_command()?;
+        let command = match elevate {
+            Some(program) => crate::privilege::elevate(&command, program),
+            None => command,
+        };
+        tokio::process::Command::from(command).output().await
+    }
+
+    /// Builds the process to run. A command spanning several lines is a
+    /// script: it is written to a temporary file, which is returned to be
+    /// kept until the process has finished, and run by the interpreter on
+    /// its `#!` line, or by the shell.
+    fn to_command(
+        &self,
+    ) -> Result<(std::process::Command, Option<tempfile::TempPath>), std::io::Error> {
+        let script = self.command();
+        if !script.trim().contains('\n') {
+            let mut command = std::process::Command::new(self.shell());
+            command.arg("-c").arg(script);
+            return Ok((command, None));
+        }
+
+        let mut file = tempfile::Builder::new().prefix("dotman-").tempfile()?;
+        std::io::Write::write_all(&mut file, script.as_bytes())?;
+        let path = file.into_temp_path();
+
+        let mut command = match self.script_shebang() {
+            Some((interpreter, argument)) => {
+                let mut command = std::process::Command::new(interpreter);
+                command.args(argument);
+                command
+            }
+            None => std::process::Command::new(self.shell()),
+        };
+        command.arg(&path);
+        Ok((command, Some(path)))
+    }
+
     pub fn is_successful(&self) -> bool {
         match self.execute() {
             Ok(output) => output.status.success(),
@@ -77,16 +288,78 @@ impl RunCommand {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+/// The interpreter on a script's `#!` line and its optional argument, such
+/// as `/usr/bin/env` and `python3`.
+fn shebang(script: &str) -> Option<(&str, Option<&str>)> {
+    let line = script
+        .trim_start()
+        .lines()
+        .next()?
+        .strip_prefix("#!")?
+        .trim();
+    match line.split_once(char::is_whitespace) {
+        Some((interpreter, argument)) => Some((interpreter, Some(argument.trim()))),
+        None if line.is_empty() => None,
+        None => Some((line, None)),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct Condition {
-    #[serde(default)]
+    /// Operating systems the entry applies to. Empty means any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub os: Vec<OperatingSystem>,
-    #[serde(default)]
+    /// Hostname, or list of hostnames, the entry applies to. Supports globs
+    /// like `work-*`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub hostname: Option<Hostname>,
-    #[serde(default)]
+    /// Command that must exit successfully.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub run: Option<RunCommand>,
-    #[serde(default)]
+    /// Paths that must all exist.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub file_exists: Vec<String>,
+    /// Path, or list of paths, that must all exist.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exists: Option<OneOrMany>,
+    /// Path, or list of paths, that must not exist.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_exists: Option<OneOrMany>,
+    /// Program, or list of programs, that must be found in `PATH`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_command: Option<OneOrMany>,
+    /// Linux distributions the entry applies to, matched against `ID` and
+    /// `ID_LIKE` in `/etc/os-release`. Empty means any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub distro: Vec<String>,
+    /// CPU architectures (e.g. `x86_64`, `aarch64`) the entry applies to.
+    /// Empty means any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arch: Vec<String>,
+    /// Desktop environments (e.g. `gnome`, `kde`, `sway`) or display servers
+    /// (`wayland`, `x11`) the entry applies to. Empty means any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub desktop: Vec<String>,
+    /// User dotman must run as.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// Whether dotman must (`true`) or must not (`false`) run as root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<bool>,
+    /// Question the user must answer yes to at install time. Not met when
+    /// dotman is not running in a terminal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    /// Conditions that must all be met.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub all: Vec<Condition>,
+    /// Conditions of which at least one must be met. Empty means no
+    /// requirement.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub any: Vec<Condition>,
+    /// Condition that must not be met.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not: Option<Box<Condition>>,
 }
 
 fn expand_tilde(path: &str) -> String {
@@ -98,123 +371,1463 @@ fn expand_tilde(path: &str) -> String {
     path.to_string()
 }
 
+/// Matches a hostname against a pattern, which may be a glob such as
+/// `work-*` or `laptop?`.
+fn hostname_matches(pattern: &str, hostname: &str) -> bool {
+    match globset::Glob::new(pattern) {
+        Ok(glob) => glob.compile_matcher().is_match(hostname),
+        Err(_) => pattern == hostname,
+    }
+}
+
+fn path_exists(path: &str) -> bool {
+    std::path::Path::new(&expand_tilde(path)).exists()
+}
+
 impl Condition {
-    pub fn is_met(&self, os: &OperatingSystem, hostname: Option<&str>) -> bool {
-        let os_matches = self.os.is_empty() || self.os.contains(os);
-        let hostname_matches = self.hostname.as_ref().is_none_or(|h| match hostname {
-            None => false,
-            Some(hostname) => match h {
-                Hostname::Single(h) => h == hostname,
-                Hostname::Multiple(hosts) => hosts.iter().any(|h| h == hostname),
-            },
-        });
-        let command_succeeds = self.run.as_ref().is_none_or(|cmd| cmd.is_successful());
+    /// Returns true if the condition has no requirements and thus always matches.
+    pub fn is_empty(&self) -> bool {
+        self.os.is_empty()
+            && self.hostname.is_none()
+            && self.run.is_none()
+            && self.file_exists.is_empty()
+            && self.exists.is_none()
+            && self.not_exists.is_none()
+            && self.has_command.is_none()
+            && self.distro.is_empty()
+            && self.arch.is_empty()
+            && self.desktop.is_empty()
+            && self.user.is_none()
+            && self.root.is_none()
+            && self.prompt.is_none()
+            && self.all.is_empty()
+            && self.any.is_empty()
+            && self.not.is_none()
+    }
+
+    pub fn is_met(&self, context: &Context) -> bool {
+        let os_matches = self.os.is_empty() || self.os.contains(&context.os);
+        let hostname_matches = self
+            .hostname
+            .as_ref()
+            .is_none_or(|h| match &context.hostname {
+                None => false,
+                Some(hostname) => match h {
+                    Hostname::Single(h) => hostname_matches(h, hostname),
+                    Hostname::Multiple(hosts) => {
+                        hosts.iter().any(|h| hostname_matches(h, hostname))
+                    }
+                },
+            });
+        let command_succeeds = self
+            .run
+            .as_ref()
+            .is_none_or(|cmd| context.run_succeeds(cmd));
         let files_exist = self
             .file_exists
             .iter()
-            .all(|path| std::path::Path::new(&expand_tilde(path)).exists());
+            .map(String::as_str)
+            .chain(self.exists.iter().flat_map(OneOrMany::iter))
+            .all(path_exists);
+        let files_missing = self
+            .not_exists
+            .iter()
+            .flat_map(OneOrMany::iter)
+            .all(|path| !path_exists(path));
+        let commands_found = self
+            .has_command
+            .iter()
+            .flat_map(OneOrMany::iter)
+            .all(|command| which::which(command).is_ok());
+        let distro_matches = self.distro.is_empty() || {
+            let ids = crate::utils::get_distro_ids();
+            self.distro
+                .iter()
+                .any(|distro| ids.contains(&distro.to_lowercase()))
+        };
+
+        let arch_matches = self.arch.is_empty() || self.arch.contains(&context.arch);
+        let desktop_matches = self.desktop.is_empty()
+            || self
+                .desktop
+                .iter()
+                .any(|desktop| context.desktops.contains(&desktop.to_lowercase()));
+        let user_matches = self
+            .user
+            .as_ref()
+            .is_none_or(|user| context.user.as_ref() == Some(user));
+        let root_matches = self.root.is_none_or(|root| root == context.root);
+
+        os_matches
+            && hostname_matches
+            && arch_matches
+            && desktop_matches
+            && user_matches
+            && root_matches
+            && command_succeeds
+            && files_exist
+            && files_missing
+            && commands_found
+            && distro_matches
+            && self.all.iter().all(|cond| cond.is_met(context))
+            && (self.any.is_empty() || self.any.iter().any(|cond| cond.is_met(context)))
+            && self.not.as_ref().is_none_or(|cond| !cond.is_met(context))
+            // Asked last, so the user is not bothered when nothing else matches.
+            && self
+                .prompt
+                .as_ref()
+                .is_none_or(|question| context.confirm(question))
+    }
+}
+
+/// How a link's source is put in place.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkMode {
+    /// Symlink the target to the source.
+    #[default]
+    Symlink,
+    /// Decrypt the source with the `[secrets]` backend and write the plaintext
+    /// to the target.
+    Secret,
+    /// Render the source as a template with `[variables]` and write the
+    /// result to the target.
+    Template,
+    /// Copy the source to the target. Symlinks are copied like this where
+    /// they cannot be created, such as on Windows without Developer Mode.
+    Copy,
+    /// Link a directory with an NTFS junction, which needs no special rights
+    /// on Windows. Elsewhere it is a symlink.
+    Junction,
+    /// Concatenate the files in the source directory, or its `fragments`,
+    /// into the target below a generated header.
+    Concat,
+}
 
-        os_matches && hostname_matches && command_succeeds && files_exist
+impl LinkMode {
+    /// Whether the target links to the source, rather than being a file
+    /// dotman writes.
+    pub fn is_link(&self) -> bool {
+        matches!(self, LinkMode::Symlink | LinkMode::Junction)
+    }
+
+    fn is_default(&self) -> bool {
+        *self == LinkMode::default()
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct Link {
+    /// Where the symlink is created.
     pub target: String,
+    /// File or directory the symlink points to.
     pub source: String,
-    #[serde(rename = "if")]
+    /// Only link when this condition is met.
+    #[serde(rename = "if", skip_serializing_if = "Option::is_none")]
     pub if_cond: Option<Condition>,
-    #[serde(rename = "if-not")]
+    /// Only link when this condition is not met.
+    #[serde(rename = "if-not", skip_serializing_if = "Option::is_none")]
     pub if_not_cond: Option<Condition>,
-    #[serde(default)]
+    /// Profiles the link belongs to. Empty means all profiles.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub profiles: Vec<String>,
+    /// Labels for picking links with `dotman install --tag`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// How the source is put in place.
+    #[serde(default, skip_serializing_if = "LinkMode::is_default")]
+    pub mode: LinkMode,
+    /// Create the target as root, through sudo or doas, for targets in
+    /// system directories such as `/etc`.
+    #[serde(default, rename = "become", skip_serializing_if = "std::ops::Not::not")]
+    pub elevate: bool,
+    /// Octal permissions the target must have, like `"600"`. For symlinks,
+    /// those of the file they point to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chmod: Option<String>,
+    /// User that must own the target, by name or ID.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Group that must own the target, by name or ID.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Files in the source directory a `concat` link is assembled from, in
+    /// order. Defaults to every file in it, sorted by name.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fragments: Vec<Fragment>,
+}
+
+/// A file a `concat` link is assembled from, relative to the link's source
+/// directory. Written as a plain path, or as a table with conditions.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Fragment {
+    Path(String),
+    Conditional {
+        source: String,
+        /// Only include when this condition is met.
+        #[serde(rename = "if", skip_serializing_if = "Option::is_none")]
+        if_cond: Option<Box<Condition>>,
+        /// Only include when this condition is not met.
+        #[serde(rename = "if-not", skip_serializing_if = "Option::is_none")]
+        if_not_cond: Option<Box<Condition>>,
+    },
+}
+
+impl Fragment {
+    pub fn source(&self) -> &str {
+        match self {
+            Fragment::Path(source) | Fragment::Conditional { source, .. } => source,
+        }
+    }
+
+    pub fn is_met(&self, context: &Context) -> bool {
+        match self {
+            Fragment::Path(_) => true,
+            Fragment::Conditional {
+                if_cond,
+                if_not_cond,
+                ..
+            } => {
+                if_cond.as_ref().is_none_or(|cond| cond.is_met(context))
+                    && if_not_cond
+                        .as_ref()
+                        .is_none_or(|cond| !cond.is_met(context))
+            }
+        }
+    }
 }
 
 pub fn condition_is_met(
     if_cond: &Option<Condition>,
     if_not_cond: &Option<Condition>,
-    os: &OperatingSystem,
-    hostname: Option<&str>,
+    context: &Context,
 ) -> bool {
-    let if_condition_passes = if_cond
-        .as_ref()
-        .is_none_or(|cond| cond.is_met(os, hostname));
+    let if_condition_passes = if_cond.as_ref().is_none_or(|cond| cond.is_met(context));
     let if_not_condition_passes = if_not_cond
         .as_ref()
-        .is_none_or(|cond| !cond.is_met(os, hostname));
+        .is_none_or(|cond| !cond.is_met(context));
 
     if_condition_passes && if_not_condition_passes
 }
 
 impl Link {
-    pub fn is_met(&self, os: &OperatingSystem, hostname: Option<&str>) -> bool {
-        condition_is_met(&self.if_cond, &self.if_not_cond, os, hostname)
+    /// Creates a symlink from `target` to `source` without conditions.
+    pub fn new(source: impl Into<String>, target: impl Into<String>) -> Self {
+        Link {
+            source: source.into(),
+            target: target.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_mode(mut self, mode: LinkMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Only links when `condition` is met.
+    pub fn with_if(mut self, condition: Condition) -> Self {
+        self.if_cond = Some(condition);
+        self
+    }
+
+    pub fn is_met(&self, context: &Context) -> bool {
+        condition_is_met(&self.if_cond, &self.if_not_cond, context)
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum Action {
     #[serde(rename = "shell-command")]
     ShellCommand {
+        /// Name shown when the action runs.
         name: String,
+        /// Command to execute.
         run: RunCommand,
-        #[serde(rename = "if")]
+        /// Command that reverts `run`, used by `dotman undo`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        undo: Option<RunCommand>,
+        #[serde(rename = "if", skip_serializing_if = "Option::is_none")]
+        if_cond: Option<Condition>,
+        #[serde(rename = "if-not", skip_serializing_if = "Option::is_none")]
+        if_not_cond: Option<Condition>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        profiles: Vec<String>,
+        /// Run the command as root, through sudo or doas.
+        #[serde(default, rename = "become", skip_serializing_if = "std::ops::Not::not")]
+        elevate: bool,
+    },
+    /// Downloads a file, such as a font, theme or single-file tool, and
+    /// checks it against its SHA-256 digest.
+    #[serde(rename = "download")]
+    Download {
+        /// Name shown when the action runs.
+        name: String,
+        /// HTTPS URL of the file.
+        url: String,
+        /// Where the file is saved.
+        dest: String,
+        /// Hex-encoded SHA-256 digest the file must have. The download is
+        /// skipped when `dest` already has it.
+        sha256: String,
+        /// Octal permissions of the saved file, like `"755"`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mode: Option<String>,
+        #[serde(rename = "if", skip_serializing_if = "Option::is_none")]
+        if_cond: Option<Condition>,
+        #[serde(rename = "if-not", skip_serializing_if = "Option::is_none")]
+        if_not_cond: Option<Condition>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        profiles: Vec<String>,
+    },
+    /// Unpacks a `.tar.gz` or `.zip` archive, such as a theme, plugin bundle
+    /// or prebuilt toolchain, into a directory.
+    #[serde(rename = "extract")]
+    Extract {
+        /// Name shown when the action runs.
+        name: String,
+        /// Path or HTTPS URL of the archive.
+        source: String,
+        /// Directory the archive is unpacked into.
+        dest: String,
+        /// Hex-encoded SHA-256 digest the archive must have.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sha256: Option<String>,
+        /// Number of leading path components removed from each entry, like
+        /// `tar --strip-components`.
+        #[serde(default, skip_serializing_if = "is_zero")]
+        strip_components: usize,
+        #[serde(rename = "if", skip_serializing_if = "Option::is_none")]
+        if_cond: Option<Condition>,
+        #[serde(rename = "if-not", skip_serializing_if = "Option::is_none")]
+        if_not_cond: Option<Condition>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        profiles: Vec<String>,
+    },
+    /// Clones a git repository, such as a plugin manager, and fast-forwards
+    /// it on later runs.
+    #[serde(rename = "git")]
+    Git {
+        /// Name shown when the action runs.
+        name: String,
+        /// URL of the repository.
+        url: String,
+        /// Where the repository is cloned.
+        dest: String,
+        /// Branch to follow instead of the remote's default branch.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        branch: Option<String>,
+        /// Commit or tag to check out instead of following a branch.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rev: Option<String>,
+        /// Number of commits to fetch, for a shallow clone.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        depth: Option<u32>,
+        #[serde(rename = "if", skip_serializing_if = "Option::is_none")]
+        if_cond: Option<Condition>,
+        #[serde(rename = "if-not", skip_serializing_if = "Option::is_none")]
+        if_not_cond: Option<Condition>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        profiles: Vec<String>,
+    },
+    /// Makes sure a line, or a block between marker comments, is in a file
+    /// dotman does not otherwise manage, such as a system-provided
+    /// `~/.bashrc`.
+    #[serde(rename = "line-in-file")]
+    LineInFile {
+        /// Name shown when the action runs. It also identifies the block.
+        name: String,
+        /// File to change. It is created if missing.
+        path: String,
+        /// Line that must be in the file.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        line: Option<String>,
+        /// Lines kept between `BEGIN` and `END` marker comments, replaced
+        /// whenever they change.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        block: Option<String>,
+        /// Comment prefix of the markers. Defaults to `#`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        comment: Option<String>,
+        #[serde(rename = "if", skip_serializing_if = "Option::is_none")]
+        if_cond: Option<Condition>,
+        #[serde(rename = "if-not", skip_serializing_if = "Option::is_none")]
+        if_not_cond: Option<Condition>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        profiles: Vec<String>,
+    },
+    /// Enable and start a systemd user unit, linking its unit file into
+    /// `~/.config/systemd/user` first if it comes from the dotfiles.
+    #[serde(rename = "systemd-unit")]
+    SystemdUnit {
+        /// Name shown when the action runs.
+        name: String,
+        /// Unit to enable, like `syncthing.service`.
+        unit: String,
+        /// Unit file to link into `~/.config/systemd/user`. Units installed
+        /// by packages need none.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        source: Option<String>,
+        #[serde(rename = "if", skip_serializing_if = "Option::is_none")]
+        if_cond: Option<Condition>,
+        #[serde(rename = "if-not", skip_serializing_if = "Option::is_none")]
+        if_not_cond: Option<Condition>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        profiles: Vec<String>,
+    },
+    /// Set a value in the Windows registry.
+    #[serde(rename = "registry")]
+    Registry {
+        /// Name shown when the action runs.
+        name: String,
+        /// Root key, like `HKCU` or `HKEY_LOCAL_MACHINE`.
+        hive: String,
+        /// Path of the key under the hive, like `Console`.
+        key: String,
+        /// Name of the value. Defaults to the key's default value.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        value: Option<String>,
+        /// Type of the value. Defaults to `dword` for numbers and `sz` for
+        /// strings.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        value_type: Option<RegistryType>,
+        data: RegistryData,
+        #[serde(rename = "if", skip_serializing_if = "Option::is_none")]
+        if_cond: Option<Condition>,
+        #[serde(rename = "if-not", skip_serializing_if = "Option::is_none")]
+        if_not_cond: Option<Condition>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        profiles: Vec<String>,
+    },
+    /// Deep-merge a snippet into a JSON, YAML or TOML file an application
+    /// owns, such as VS Code's `settings.json`, leaving its other keys alone.
+    #[serde(rename = "merge")]
+    Merge {
+        /// Name shown when the action runs.
+        name: String,
+        /// Snippet with the keys to set, in the format of the target.
+        source: String,
+        /// File to merge into. Its format is told by its extension, and it
+        /// is created if missing.
+        target: String,
+        #[serde(rename = "if", skip_serializing_if = "Option::is_none")]
         if_cond: Option<Condition>,
-        #[serde(rename = "if-not")]
+        #[serde(rename = "if-not", skip_serializing_if = "Option::is_none")]
         if_not_cond: Option<Condition>,
-        #[serde(default)]
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
         profiles: Vec<String>,
     },
 }
 
+/// Type of a Windows registry value.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistryType {
+    /// A string, `REG_SZ`.
+    Sz,
+    /// A string with `%VARIABLES%` expanded when read, `REG_EXPAND_SZ`.
+    ExpandSz,
+    /// A 32-bit number, `REG_DWORD`.
+    Dword,
+    /// A 64-bit number, `REG_QWORD`.
+    Qword,
+}
+
+/// Data of a Windows registry value.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum RegistryData {
+    Number(u64),
+    String(String),
+}
+
+impl std::fmt::Display for RegistryData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryData::Number(number) => write!(f, "{number}"),
+            RegistryData::String(string) => write!(f, "{string}"),
+        }
+    }
+}
+
+fn is_zero(value: &usize) -> bool {
+    *value == 0
+}
+
 impl Action {
-    pub fn is_met(&self, os: &OperatingSystem, hostname: Option<&str>) -> bool {
+    /// Creates a shell command action without conditions.
+    pub fn shell(name: impl Into<String>, run: impl Into<String>) -> Self {
+        Action::ShellCommand {
+            name: name.into(),
+            run: RunCommand::Simple(run.into()),
+            undo: None,
+            if_cond: None,
+            if_not_cond: None,
+            profiles: vec![],
+            elevate: false,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Action::ShellCommand { name, .. }
+            | Action::Download { name, .. }
+            | Action::Extract { name, .. }
+            | Action::Git { name, .. }
+            | Action::LineInFile { name, .. }
+            | Action::Merge { name, .. }
+            | Action::SystemdUnit { name, .. }
+            | Action::Registry { name, .. } => name,
+        }
+    }
+
+    pub fn profiles(&self) -> &[String] {
+        match self {
+            Action::ShellCommand { profiles, .. }
+            | Action::Download { profiles, .. }
+            | Action::Extract { profiles, .. }
+            | Action::Git { profiles, .. }
+            | Action::LineInFile { profiles, .. }
+            | Action::Merge { profiles, .. }
+            | Action::SystemdUnit { profiles, .. }
+            | Action::Registry { profiles, .. } => profiles,
+        }
+    }
+
+    /// The command a shell command action runs.
+    pub fn run(&self) -> Option<&RunCommand> {
+        match self {
+            Action::ShellCommand { run, .. } => Some(run),
+            _ => None,
+        }
+    }
+
+    pub fn undo(&self) -> Option<&RunCommand> {
+        match self {
+            Action::ShellCommand { undo, .. } => undo.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Whether the action runs as root.
+    pub fn elevate(&self) -> bool {
+        match self {
+            Action::ShellCommand { elevate, .. } => *elevate,
+            _ => false,
+        }
+    }
+
+    /// Returns the `if` and `if-not` conditions of the action.
+    pub fn conditions(&self) -> (&Option<Condition>, &Option<Condition>) {
         match self {
             Action::ShellCommand {
                 if_cond,
                 if_not_cond,
                 ..
-            } => condition_is_met(if_cond, if_not_cond, os, hostname),
+            }
+            | Action::Download {
+                if_cond,
+                if_not_cond,
+                ..
+            }
+            | Action::Extract {
+                if_cond,
+                if_not_cond,
+                ..
+            }
+            | Action::Git {
+                if_cond,
+                if_not_cond,
+                ..
+            }
+            | Action::LineInFile {
+                if_cond,
+                if_not_cond,
+                ..
+            }
+            | Action::Merge {
+                if_cond,
+                if_not_cond,
+                ..
+            }
+            | Action::SystemdUnit {
+                if_cond,
+                if_not_cond,
+                ..
+            }
+            | Action::Registry {
+                if_cond,
+                if_not_cond,
+                ..
+            } => (if_cond, if_not_cond),
+        }
+    }
+
+    pub fn is_met(&self, context: &Context) -> bool {
+        let (if_cond, if_not_cond) = self.conditions();
+        condition_is_met(if_cond, if_not_cond, context)
+    }
+}
+
+/// A package to install, named for each package manager it is available
+/// from. It is installed with whichever of them the machine has.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Package {
+    /// Name of the Homebrew formula or cask.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brew: Option<String>,
+    /// Name of the Debian or Ubuntu package.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub apt: Option<String>,
+    /// Name of the Arch Linux package.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pacman: Option<String>,
+    /// Name of the package in the Arch User Repository, installed with the
+    /// AUR helper.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aur: Option<String>,
+    /// Flatpak application ID, like `org.mozilla.firefox`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flatpak: Option<String>,
+    /// Remote the Flatpak is installed from. Defaults to `flathub`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flatpak_remote: Option<String>,
+    /// Name of the snap.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snap: Option<String>,
+    /// Install the snap with classic confinement.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub snap_classic: bool,
+    /// Name of the crate to `cargo install`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cargo: Option<String>,
+    /// Version of the crate. It is reinstalled when another version is
+    /// installed. Defaults to any version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cargo_version: Option<String>,
+    /// Build the crate with the versions in its `Cargo.lock`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cargo_locked: bool,
+    /// Name of the Python package to `pipx install`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipx: Option<String>,
+    /// Name of the npm package to install globally.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub npm: Option<String>,
+    /// Homebrew Brewfile, relative to the configuration file, installed
+    /// with `brew bundle`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brewfile: Option<String>,
+    /// Only install when this condition is met.
+    #[serde(rename = "if", skip_serializing_if = "Option::is_none")]
+    pub if_cond: Option<Condition>,
+    /// Only install when this condition is not met.
+    #[serde(rename = "if-not", skip_serializing_if = "Option::is_none")]
+    pub if_not_cond: Option<Condition>,
+    /// Profiles the package belongs to. Empty means all profiles.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<String>,
+}
+
+impl Package {
+    /// The package's name for `manager`, if it is available from it.
+    pub fn name_for(&self, manager: PackageManager) -> Option<&str> {
+        match manager {
+            PackageManager::Brew => self.brew.as_deref(),
+            PackageManager::Apt => self.apt.as_deref(),
+            PackageManager::Pacman => self.pacman.as_deref(),
+            PackageManager::Aur(_) => self.aur.as_deref(),
+            PackageManager::Flatpak => self.flatpak.as_deref(),
+            PackageManager::Snap => self.snap.as_deref(),
+            PackageManager::Cargo => self.cargo.as_deref(),
+            PackageManager::Pipx => self.pipx.as_deref(),
+            PackageManager::Npm => self.npm.as_deref(),
+        }
+    }
+
+    /// Every name the package is declared with, and the key it is declared
+    /// under, such as `apt`.
+    pub fn names(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        [
+            ("brew", &self.brew),
+            ("apt", &self.apt),
+            ("pacman", &self.pacman),
+            ("aur", &self.aur),
+            ("flatpak", &self.flatpak),
+            ("snap", &self.snap),
+            ("cargo", &self.cargo),
+            ("pipx", &self.pipx),
+            ("npm", &self.npm),
+        ]
+        .into_iter()
+        .filter_map(|(key, name)| Some((key, name.as_deref()?)))
+    }
+
+    pub fn is_met(&self, context: &Context) -> bool {
+        condition_is_met(&self.if_cond, &self.if_not_cond, context)
+    }
+}
+
+/// Assembles `~/.ssh/config` from a fragment per host, for ssh versions
+/// whose `Include` cannot be relied on.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Ssh {
+    /// Directory with the host fragments.
+    pub source: String,
+    /// File to assemble. Defaults to `~/.ssh/config`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// Fragments to include, in order. Defaults to every file in `source`,
+    /// sorted by name.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<Fragment>,
+}
+
+impl Ssh {
+    /// The `concat` link that assembles the file, readable only by its
+    /// owner as ssh requires.
+    pub fn link(&self) -> Link {
+        let target = self.target.as_deref().unwrap_or("~/.ssh/config");
+        Link {
+            mode: LinkMode::Concat,
+            fragments: self.hosts.clone(),
+            chmod: Some("600".to_string()),
+            ..Link::new(&self.source, target)
+        }
+    }
+}
+
+/// Repository-managed git configuration included from `~/.gitconfig`, which
+/// keeps the machine's own settings.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GitConfig {
+    /// File the includes are written to. Defaults to `~/.gitconfig`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Files to include, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<GitInclude>,
+}
+
+impl GitConfig {
+    pub fn file(&self) -> &str {
+        self.file.as_deref().unwrap_or("~/.gitconfig")
+    }
+}
+
+/// A file included from the git configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GitInclude {
+    /// Path of the file, resolved like a link source.
+    pub path: String,
+    /// Only include it in repositories under this directory, like `~/work/`,
+    /// through `[includeIf "gitdir:..."]`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gitdir: Option<String>,
+}
+
+/// A directory that must exist, created before links are installed.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Directory {
+    /// Path of the directory, like `~/.local/bin`.
+    pub path: String,
+    /// Octal permissions the directory must have, like `"700"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    /// Only create when this condition is met.
+    #[serde(rename = "if", skip_serializing_if = "Option::is_none")]
+    pub if_cond: Option<Condition>,
+    /// Only create when this condition is not met.
+    #[serde(rename = "if-not", skip_serializing_if = "Option::is_none")]
+    pub if_not_cond: Option<Condition>,
+    /// Profiles the directory belongs to. Empty means all profiles.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<String>,
+}
+
+impl Directory {
+    pub fn new(path: impl Into<String>) -> Self {
+        Directory {
+            path: path.into(),
+            mode: None,
+            if_cond: None,
+            if_not_cond: None,
+            profiles: vec![],
+        }
+    }
+
+    pub fn is_met(&self, context: &Context) -> bool {
+        condition_is_met(&self.if_cond, &self.if_not_cond, context)
+    }
+}
+
+/// A macOS preference, set with `defaults write` after links are installed.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Preference {
+    /// Domain the key is in, like `com.apple.dock` or `NSGlobalDomain`.
+    pub domain: String,
+    pub key: String,
+    /// Type the value is written as. Defaults to the type of `value`.
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<PreferenceType>,
+    pub value: PreferenceValue,
+    /// Only set when this condition is met.
+    #[serde(rename = "if", skip_serializing_if = "Option::is_none")]
+    pub if_cond: Option<Condition>,
+    /// Only set when this condition is not met.
+    #[serde(rename = "if-not", skip_serializing_if = "Option::is_none")]
+    pub if_not_cond: Option<Condition>,
+    /// Profiles the preference belongs to. Empty means all profiles.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<String>,
+}
+
+impl Preference {
+    pub fn new(
+        domain: impl Into<String>,
+        key: impl Into<String>,
+        value: impl Into<PreferenceValue>,
+    ) -> Self {
+        Preference {
+            domain: domain.into(),
+            key: key.into(),
+            kind: None,
+            value: value.into(),
+            if_cond: None,
+            if_not_cond: None,
+            profiles: vec![],
+        }
+    }
+
+    pub fn is_met(&self, context: &Context) -> bool {
+        condition_is_met(&self.if_cond, &self.if_not_cond, context)
+    }
+}
+
+/// Type of a macOS preference, as passed to `defaults write`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PreferenceType {
+    String,
+    Int,
+    Float,
+    Bool,
+}
+
+/// Value of a macOS preference.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(untagged)]
+pub enum PreferenceValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl From<bool> for PreferenceValue {
+    fn from(value: bool) -> Self {
+        PreferenceValue::Bool(value)
+    }
+}
+
+impl From<i64> for PreferenceValue {
+    fn from(value: i64) -> Self {
+        PreferenceValue::Int(value)
+    }
+}
+
+impl From<f64> for PreferenceValue {
+    fn from(value: f64) -> Self {
+        PreferenceValue::Float(value)
+    }
+}
+
+impl From<&str> for PreferenceValue {
+    fn from(value: &str) -> Self {
+        PreferenceValue::String(value.to_string())
+    }
+}
+
+impl std::fmt::Display for PreferenceValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PreferenceValue::Bool(value) => write!(f, "{value}"),
+            PreferenceValue::Int(value) => write!(f, "{value}"),
+            PreferenceValue::Float(value) => write!(f, "{value}"),
+            PreferenceValue::String(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+/// A GNOME setting, set with `gsettings set` after links are installed.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GSetting {
+    /// Schema the key belongs to, like `org.gnome.desktop.interface`.
+    pub schema: String,
+    /// Path of a relocatable schema, such as that of a custom keybinding.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub key: String,
+    pub value: SettingValue,
+    /// Only set when this condition is met.
+    #[serde(rename = "if", skip_serializing_if = "Option::is_none")]
+    pub if_cond: Option<Condition>,
+    /// Only set when this condition is not met.
+    #[serde(rename = "if-not", skip_serializing_if = "Option::is_none")]
+    pub if_not_cond: Option<Condition>,
+    /// Profiles the setting belongs to. Empty means all profiles.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<String>,
+}
+
+impl GSetting {
+    pub fn new(
+        schema: impl Into<String>,
+        key: impl Into<String>,
+        value: impl Into<SettingValue>,
+    ) -> Self {
+        GSetting {
+            schema: schema.into(),
+            path: None,
+            key: key.into(),
+            value: value.into(),
+            if_cond: None,
+            if_not_cond: None,
+            profiles: vec![],
+        }
+    }
+
+    pub fn is_met(&self, context: &Context) -> bool {
+        condition_is_met(&self.if_cond, &self.if_not_cond, context)
+    }
+}
+
+/// Value of a GNOME setting. Lists become GVariant arrays, like the
+/// `['<Super>q']` of a keybinding.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(untagged)]
+pub enum SettingValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    List(Vec<SettingValue>),
+}
+
+impl From<bool> for SettingValue {
+    fn from(value: bool) -> Self {
+        SettingValue::Bool(value)
+    }
+}
+
+impl From<i64> for SettingValue {
+    fn from(value: i64) -> Self {
+        SettingValue::Int(value)
+    }
+}
+
+impl From<f64> for SettingValue {
+    fn from(value: f64) -> Self {
+        SettingValue::Float(value)
+    }
+}
+
+impl From<&str> for SettingValue {
+    fn from(value: &str) -> Self {
+        SettingValue::String(value.to_string())
+    }
+}
+
+impl<T: Into<SettingValue>> From<Vec<T>> for SettingValue {
+    fn from(values: Vec<T>) -> Self {
+        SettingValue::List(values.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Program that builds and installs packages from the Arch User Repository.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AurHelper {
+    Paru,
+    Yay,
+}
+
+impl AurHelper {
+    pub fn program(&self) -> &'static str {
+        match self {
+            AurHelper::Paru => "paru",
+            AurHelper::Yay => "yay",
+        }
+    }
+}
+
+/// Configuration files dotman looks for when none is given, in order.
+pub const DEFAULT_CONFIG_FILES: &[&str] =
+    &["dotman.toml", "dotman.yaml", "dotman.yml", "dotman.json"];
+
+/// Returns the first default configuration file that exists in the current
+/// directory, or `dotman.toml` if none do.
+pub fn default_config_path() -> std::path::PathBuf {
+    find_config_in(std::path::Path::new(""))
+        .unwrap_or_else(|| std::path::PathBuf::from(DEFAULT_CONFIG_FILES[0]))
+}
+
+/// Returns the first default configuration file that exists in `dir`.
+pub fn find_config_in(dir: &std::path::Path) -> Option<std::path::PathBuf> {
+    DEFAULT_CONFIG_FILES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.exists())
+}
+
+/// File format of a configuration file, detected from its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Detects the format from the file extension, defaulting to TOML.
+    pub fn from_path(path: &std::path::Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            Some("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Toml,
+        }
+    }
+}
+
+/// A key in the configuration file that dotman does not recognise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownKey {
+    /// Dotted path to the key, e.g. `links.0.targett`.
+    pub path: String,
+    /// The closest known key name, if one is similar enough.
+    pub suggestion: Option<String>,
+}
+
+impl UnknownKey {
+    fn new(path: String, known_keys: &[String]) -> Self {
+        let key = path.rsplit('.').next().unwrap_or(&path);
+        let suggestion = known_keys
+            .iter()
+            .map(|known| (strsim::jaro_winkler(key, known), known))
+            .filter(|(score, _)| *score >= 0.8)
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, known)| known.clone());
+
+        UnknownKey { path, suggestion }
+    }
+}
+
+impl std::fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown key '{}'", self.path)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (did you mean '{}'?)", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects every property name declared anywhere in the config schema.
+fn known_keys() -> Vec<String> {
+    fn collect(value: &serde_json::Value, keys: &mut Vec<String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                if let Some(serde_json::Value::Object(properties)) = map.get("properties") {
+                    keys.extend(properties.keys().cloned());
+                }
+                map.values().for_each(|v| collect(v, keys));
+            }
+            serde_json::Value::Array(items) => items.iter().for_each(|v| collect(v, keys)),
+            _ => {}
+        }
+    }
+
+    let mut keys = Vec::new();
+    if let Ok(schema) = serde_json::to_value(DotmanConfig::json_schema()) {
+        collect(&schema, &mut keys);
+    }
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// The git repository holding the dotfiles.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub struct Repo {
+    /// Remote to clone from when the repository does not exist yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Branch to track. Defaults to the branch that is checked out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Location of the repository, relative to the configuration file.
+    /// Defaults to the directory containing the configuration file, or to
+    /// dotman's data directory for entries in `[repos]`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Commit files moved into the repository by `dotman adopt`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub auto_commit: bool,
+}
+
+/// Tool used to encrypt and decrypt secrets.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretBackend {
+    #[default]
+    Age,
+    Gpg,
+}
+
+impl SecretBackend {
+    fn is_default(&self) -> bool {
+        *self == SecretBackend::default()
+    }
+
+    /// File extension given to files encrypted with this backend.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SecretBackend::Age => "age",
+            SecretBackend::Gpg => "gpg",
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Hash function used to detect drift in generated targets.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Blake3,
+    Xxhash,
+}
+
+/// How dotman checks targets for changes.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub struct Integrity {
+    /// `sha256` (default), or the faster `blake3` or `xxhash`.
+    #[serde(default)]
+    pub algorithm: HashAlgorithm,
+    /// Skip files matched by `.gitignore` or `.dotmanignore` when hashing
+    /// directories.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub respect_ignore: bool,
+    /// Include file permissions in directory hashes.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub include_permissions: bool,
+    /// Hash where symlinks inside directories point instead of following
+    /// them.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub symlink_targets: bool,
+}
+
+/// Where dotman keeps logs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub struct Logging {
+    /// Directory that the output of every action run is saved to, one
+    /// timestamped file each.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actions_dir: Option<String>,
+}
+
+/// How links with `mode = "secret"` are decrypted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub struct Secrets {
+    /// Tool used for encryption: `age` (default) or `gpg`.
+    #[serde(default, skip_serializing_if = "SecretBackend::is_default")]
+    pub backend: SecretBackend,
+    /// The age identity file used to decrypt secrets.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+    /// Who secrets are encrypted for: an age public key, or a gpg key id or
+    /// email. Required for gpg; age defaults to the identity's own key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipient: Option<String>,
+}
+
+/// A value computed from the configuration the first time it is needed.
+/// Clones start empty, so a clone that is edited afterwards never sees a
+/// value computed from the original.
+#[derive(Debug)]
+pub(crate) struct Cache<T>(std::sync::OnceLock<T>);
+
+impl<T> Cache<T> {
+    fn get_or_init(&self, init: impl FnOnce() -> T) -> &T {
+        self.0.get_or_init(init)
+    }
+}
+
+impl<T> Default for Cache<T> {
+    fn default() -> Self {
+        Cache(std::sync::OnceLock::new())
+    }
+}
+
+impl<T> Clone for Cache<T> {
+    fn clone(&self) -> Self {
+        Cache::default()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct DotmanConfig {
-    #[serde(default = "base_config_path")]
+    #[serde(skip, default = "base_config_path")]
     pub config_path: String,
-    #[serde(default)]
+    /// Version of the configuration format. Run `dotman migrate` to upgrade.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    /// Files and directories to symlink.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub links: Vec<Link>,
-    #[serde(default)]
+    /// Commands to run after linking.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub actions: Vec<Action>,
-    #[serde(default = "default_false")]
+    /// Packages to install before linking.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub packages: Vec<Package>,
+    /// Directories to create before linking.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dirs: Vec<Directory>,
+    /// Assembles `~/.ssh/config` from per-host fragments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh: Option<Ssh>,
+    /// Git configuration files included from `~/.gitconfig`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gitconfig: Option<GitConfig>,
+    /// Environment variables exported from the files dotman generates for
+    /// each shell.
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub env: std::collections::BTreeMap<String, String>,
+    /// Directories prepended to `PATH` by the same files, the first taking
+    /// priority.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub path: Vec<String>,
+    /// Shell aliases, written in the syntax of each shell and loaded along
+    /// with the environment.
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub aliases: std::collections::BTreeMap<String, String>,
+    /// macOS preferences to set after linking.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub defaults: Vec<Preference>,
+    /// GNOME settings to set after linking.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gsettings: Vec<GSetting>,
+    /// Replace existing targets when linking.
+    #[serde(default = "default_false", skip_serializing_if = "std::ops::Not::not")]
     pub overwrite: bool,
-    #[serde(skip)]
-    pub ask: bool,
+    /// AUR helper that installs `aur` packages. Defaults to `paru` or `yay`,
+    /// whichever is installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aur_helper: Option<AurHelper>,
+    /// Profiles available to links and actions, checked by `dotman lint`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<String>,
+    /// Per-lint severity overrides used by `dotman validate` and `dotman lint`.
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub lints: std::collections::BTreeMap<Lint, LintLevel>,
+    /// The git repository holding the dotfiles, used by `dotman pull`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo: Option<Repo>,
+    /// Additional repositories, referenced from link sources as `name:path`.
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub repos: std::collections::BTreeMap<String, Repo>,
+    /// Settings for decrypting secret links.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secrets: Option<Secrets>,
+    /// Settings for drift detection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<Integrity>,
+    /// Where action output is logged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logging: Option<Logging>,
+    /// Values available to template links. Values starting with `enc:` are
+    /// decrypted with the `[secrets]` backend.
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub variables: std::collections::BTreeMap<String, String>,
     #[serde(skip)]
     pub selected_profile: Option<String>,
+    #[serde(skip)]
+    pub unknown_keys: Vec<UnknownKey>,
+    /// Cache for the link [`DotmanConfig::all_links`] generates for `[ssh]`.
+    #[serde(skip)]
+    pub(crate) ssh_link: Cache<Option<Link>>,
+    /// Cache for [`DotmanConfig::resolved_variables`].
+    #[serde(skip)]
+    pub(crate) decrypted_variables:
+        Cache<Result<std::collections::BTreeMap<String, String>, String>>,
 }
 
-impl DotmanConfig {
-    pub fn with_overwrite(mut self, overwrite: bool) -> Self {
-        self.overwrite = overwrite;
+/// Builds a [`DotmanConfig`] in code, see [`DotmanConfig::builder`].
+#[derive(Debug, Default)]
+pub struct DotmanConfigBuilder {
+    config: DotmanConfig,
+}
+
+impl DotmanConfigBuilder {
+    /// Path relative sources are resolved against. Defaults to
+    /// `dotman.toml` in the current directory.
+    pub fn config_path(mut self, path: impl Into<String>) -> Self {
+        self.config.config_path = path.into();
+        self
+    }
+
+    pub fn link(mut self, link: Link) -> Self {
+        self.config.links.push(link);
+        self
+    }
+
+    pub fn links(mut self, links: impl IntoIterator<Item = Link>) -> Self {
+        self.config.links.extend(links);
+        self
+    }
+
+    pub fn action(mut self, action: Action) -> Self {
+        self.config.actions.push(action);
+        self
+    }
+
+    pub fn actions(mut self, actions: impl IntoIterator<Item = Action>) -> Self {
+        self.config.actions.extend(actions);
+        self
+    }
+
+    pub fn dir(mut self, dir: Directory) -> Self {
+        self.config.dirs.push(dir);
+        self
+    }
+
+    pub fn preference(mut self, preference: Preference) -> Self {
+        self.config.defaults.push(preference);
+        self
+    }
+
+    pub fn gsetting(mut self, setting: GSetting) -> Self {
+        self.config.gsettings.push(setting);
+        self
+    }
+
+    pub fn variable(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config.variables.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn env(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config.env.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn path(mut self, dir: impl Into<String>) -> Self {
+        self.config.path.push(dir.into());
+        self
+    }
+
+    pub fn alias(mut self, name: impl Into<String>, command: impl Into<String>) -> Self {
+        self.config.aliases.insert(name.into(), command.into());
+        self
+    }
+
+    pub fn secrets(mut self, secrets: Secrets) -> Self {
+        self.config.secrets = Some(secrets);
         self
     }
 
-    pub fn with_ask(mut self, ask: bool) -> Self {
-        self.ask = ask;
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.config.overwrite = overwrite;
         self
     }
 
+    /// Selects the profile whose links and actions apply.
+    pub fn profile(mut self, profile: impl Into<String>) -> Self {
+        self.config.selected_profile = Some(profile.into());
+        self
+    }
+
+    pub fn build(self) -> DotmanConfig {
+        DotmanConfig {
+            version: Some(CURRENT_VERSION),
+            ..self.config
+        }
+    }
+}
+
+impl DotmanConfig {
+    /// Starts building a configuration in code:
+    ///
+    /// ```
+    /// use dotman::config::{DotmanConfig, Link};
+    ///
+    /// let config = DotmanConfig::builder()
+    ///     .link(Link::new("zshrc", "~/.zshrc"))
+    ///     .overwrite(true)
+    ///     .build();
+    /// assert_eq!(config.links.len(), 1);
+    /// ```
+    pub fn builder() -> DotmanConfigBuilder {
+        DotmanConfigBuilder::default()
+    }
+
+    /// Returns the JSON Schema describing the configuration file format.
+    pub fn json_schema() -> schemars::Schema {
+        schemars::schema_for!(DotmanConfig)
+    }
+
+    /// Returns the `[integrity]` settings.
+    pub fn integrity(&self) -> Integrity {
+        self.integrity.unwrap_or_default()
+    }
+
     pub fn with_profile(mut self, profile: Option<String>) -> Self {
         self.selected_profile = profile;
         self
     }
 
+    /// Returns a copy of the configuration containing only the links,
+    /// actions, packages, directories, preferences and settings that apply
+    /// to the selected profile.
+    pub fn resolved(&self) -> DotmanConfig {
+        DotmanConfig {
+            links: self.get_effective_links().into_iter().cloned().collect(),
+            ssh: None,
+            actions: self.get_effective_actions().into_iter().cloned().collect(),
+            packages: self.get_effective_packages().into_iter().cloned().collect(),
+            dirs: self.get_effective_dirs().into_iter().cloned().collect(),
+            defaults: self.get_effective_defaults().into_iter().cloned().collect(),
+            gsettings: self
+                .get_effective_gsettings()
+                .into_iter()
+                .cloned()
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Serializes the configuration in the given format.
+    pub fn to_string_as(&self, format: ConfigFormat) -> anyhow::Result<String> {
+        Ok(match format {
+            ConfigFormat::Toml => toml::to_string_pretty(self)?,
+            ConfigFormat::Yaml => serde_yaml::to_string(self)?,
+            ConfigFormat::Json => serde_json::to_string_pretty(self)? + "\n",
+        })
+    }
+
+    /// The links in `[[links]]`, followed by the one `[ssh]` generates.
+    pub fn all_links(&self) -> impl Iterator<Item = &Link> {
+        let ssh_link = self
+            .ssh_link
+            .get_or_init(|| self.ssh.as_ref().map(Ssh::link));
+        self.links.iter().chain(ssh_link)
+    }
+
     pub fn get_effective_links(&self) -> Vec<&Link> {
-        self.links
-            .iter()
+        self.all_links()
             .filter(|link| self.profile_matches(&link.profiles))
             .collect()
     }
@@ -222,12 +1835,105 @@ impl DotmanConfig {
     pub fn get_effective_actions(&self) -> Vec<&Action> {
         self.actions
             .iter()
-            .filter(|action| match action {
-                Action::ShellCommand { profiles, .. } => self.profile_matches(profiles),
-            })
+            .filter(|action| self.profile_matches(action.profiles()))
+            .collect()
+    }
+
+    pub fn get_effective_packages(&self) -> Vec<&Package> {
+        self.packages
+            .iter()
+            .filter(|package| self.profile_matches(&package.profiles))
+            .collect()
+    }
+
+    pub fn get_effective_dirs(&self) -> Vec<&Directory> {
+        self.dirs
+            .iter()
+            .filter(|dir| self.profile_matches(&dir.profiles))
+            .collect()
+    }
+
+    pub fn get_effective_defaults(&self) -> Vec<&Preference> {
+        self.defaults
+            .iter()
+            .filter(|preference| self.profile_matches(&preference.profiles))
+            .collect()
+    }
+
+    pub fn get_effective_gsettings(&self) -> Vec<&GSetting> {
+        self.gsettings
+            .iter()
+            .filter(|setting| self.profile_matches(&setting.profiles))
             .collect()
     }
 
+    /// Resolves a link source by expanding `~` and treating relative paths as
+    /// relative to the directory containing the configuration file. Sources
+    /// written as `name:path` are relative to the repository `name` in
+    /// `[repos]`.
+    pub fn resolve_source(&self, source: &str) -> Result<std::path::PathBuf, PathError> {
+        if let Some((name, path)) = source.split_once(':')
+            && let Some(dir) = self.named_repo_dir(name)?
+        {
+            return Ok(dir.join(path));
+        }
+        self.resolve_path(source)
+    }
+
+    /// Returns the location of a repository declared in `[repos]`.
+    pub fn named_repo_dir(&self, name: &str) -> Result<Option<std::path::PathBuf>, PathError> {
+        let Some(repo) = self.repos.get(name) else {
+            return Ok(None);
+        };
+        match &repo.path {
+            Some(path) => self.resolve_path(path).map(Some),
+            None => dirs::data_dir()
+                .map(|dir| Some(dir.join("dotman").join("repos").join(name)))
+                .ok_or(PathError::DataDirNotFound),
+        }
+    }
+
+    /// Expands `~` in `path` and makes it relative to the directory
+    /// containing the configuration file.
+    pub(crate) fn resolve_path(&self, path: &str) -> Result<std::path::PathBuf, PathError> {
+        let path = path.expand_tilde_path()?;
+        let path = match std::path::Path::new(&self.config_path).parent() {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path,
+        };
+        path.absolute()
+    }
+
+    /// Returns `[variables]` with `enc:` values decrypted. Decryption runs
+    /// once, the first time the variables are needed.
+    pub fn resolved_variables(
+        &self,
+    ) -> Result<&std::collections::BTreeMap<String, String>, DotmanError> {
+        self.decrypted_variables
+            .get_or_init(|| {
+                self.variables
+                    .iter()
+                    .map(|(name, value)| {
+                        secrets::decrypt_value(self, value)
+                            .map(|value| (name.clone(), value))
+                            .map_err(|err| format!("variable '{name}': {err}"))
+                    })
+                    .collect()
+            })
+            .as_ref()
+            .map_err(|err| DotmanError::SecretError(err.clone()))
+    }
+
+    /// Returns the location of the dotfiles repository.
+    pub fn repo_dir(&self) -> Result<std::path::PathBuf, PathError> {
+        let path = self
+            .repo
+            .as_ref()
+            .and_then(|repo| repo.path.as_deref())
+            .unwrap_or(".");
+        self.resolve_path(path)
+    }
+
     fn profile_matches(&self, profiles: &[String]) -> bool {
         profiles.is_empty()
             || self
@@ -251,8 +1957,44 @@ impl TryFrom<&std::path::Path> for DotmanConfig {
         let file_str = std::fs::read_to_string(path)
             .with_context(|| format!("Failed to read configuration file '{}'", path.display()))?;
 
-        let config: DotmanConfig = toml::from_str(&file_str)
-            .with_context(|| format!("Failed to parse configuration file '{}'", path.display()))?;
+        let mut ignored = Vec::new();
+        let mut on_ignored = |key: serde_ignored::Path| ignored.push(key.to_string());
+        let mut config: DotmanConfig = match ConfigFormat::from_path(path) {
+            ConfigFormat::Toml => {
+                let deserializer = toml::Deserializer::new(&file_str);
+                serde_ignored::deserialize(deserializer, &mut on_ignored)
+                    .map_err(|err| ConfigFileParseError::from_toml(path, &file_str, &err))?
+            }
+            ConfigFormat::Yaml => {
+                let deserializer = serde_yaml::Deserializer::from_str(&file_str);
+                serde_ignored::deserialize(deserializer, &mut on_ignored)
+                    .map_err(|err| ConfigFileParseError::from_yaml(path, &file_str, &err))?
+            }
+            ConfigFormat::Json => {
+                let mut deserializer = serde_json::Deserializer::from_str(&file_str);
+                serde_ignored::deserialize(&mut deserializer, &mut on_ignored)
+                    .map_err(|err| ConfigFileParseError::from_json(path, &file_str, &err))?
+            }
+        };
+
+        config.config_path = path.to_string_lossy().to_string();
+
+        if let Some(version) = config.version
+            && version > CURRENT_VERSION
+        {
+            return Err(anyhow::anyhow!(
+                "Configuration file '{}' uses version {}, but this dotman only supports up to version {}",
+                path.display(),
+                version,
+                CURRENT_VERSION
+            ));
+        }
+
+        let known_keys = known_keys();
+        config.unknown_keys = ignored
+            .into_iter()
+            .map(|key| UnknownKey::new(key, &known_keys))
+            .collect();
 
         Ok(config)
     }
@@ -298,6 +2040,214 @@ mod test {
         assert!(!config.overwrite);
     }
 
+    #[test]
+    fn test_parse_config_reports_unknown_keys() {
+        let config_file = PathBuf::from("tests/config/fail_to_parse.toml");
+        let config = DotmanConfig::try_from(config_file.as_path()).unwrap();
+
+        assert_eq!(
+            config.unknown_keys,
+            vec![UnknownKey {
+                path: "link".to_string(),
+                suggestion: Some("links".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_config_unknown_nested_key() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_file = temp_dir.path().join("dotman.toml");
+        std::fs::write(
+            &config_file,
+            "[[links]]\nsource = \"a\"\ntarget = \"b\"\ntargett = \"c\"\n",
+        )
+        .unwrap();
+
+        let config = DotmanConfig::try_from(config_file.as_path()).unwrap();
+
+        assert_eq!(config.unknown_keys.len(), 1);
+        assert_eq!(config.unknown_keys[0].path, "links.0.targett");
+        assert_eq!(config.unknown_keys[0].suggestion.as_deref(), Some("target"));
+    }
+
+    #[test]
+    fn test_parse_yaml_config() {
+        let config_file = PathBuf::from("tests/config/working.yaml");
+        let config = DotmanConfig::try_from(config_file.as_path()).unwrap();
+
+        assert_eq!(config.links.len(), 2);
+        assert_eq!(config.links[1].if_cond.as_ref().unwrap().os.len(), 1);
+        assert_eq!(config.actions.len(), 1);
+        assert!(config.unknown_keys.is_empty());
+    }
+
+    #[test]
+    fn test_parse_yaml_error_reports_location() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_file = temp_dir.path().join("dotman.yml");
+        std::fs::write(&config_file, "links:\n  - source: a\n    target: [1]\n").unwrap();
+
+        let err = DotmanConfig::try_from(config_file.as_path()).unwrap_err();
+        let err = err.downcast_ref::<ConfigFileParseError>().unwrap();
+
+        assert_eq!((err.line, err.column), (3, 13));
+    }
+
+    #[test]
+    fn test_parse_json_config() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_file = temp_dir.path().join("dotman.json");
+        std::fs::write(
+            &config_file,
+            r#"{ "links": [{ "source": "a", "target": "b", "if": { "os": ["linux"] } }] }"#,
+        )
+        .unwrap();
+
+        let config = DotmanConfig::try_from(config_file.as_path()).unwrap();
+
+        assert_eq!(config.links.len(), 1);
+        assert_eq!(config.links[0].if_cond.as_ref().unwrap().os.len(), 1);
+    }
+
+    #[test]
+    fn test_config_round_trips_through_every_format() {
+        let config_file = PathBuf::from("tests/config/working.toml");
+        let config = DotmanConfig::try_from(config_file.as_path()).unwrap();
+        let temp_dir = tempfile::TempDir::new().unwrap();
+
+        for (format, name) in [
+            (ConfigFormat::Toml, "dotman.toml"),
+            (ConfigFormat::Yaml, "dotman.yaml"),
+            (ConfigFormat::Json, "dotman.json"),
+        ] {
+            let path = temp_dir.path().join(name);
+            std::fs::write(&path, config.to_string_as(format).unwrap()).unwrap();
+
+            let parsed = DotmanConfig::try_from(path.as_path()).unwrap();
+            assert_eq!(parsed.links.len(), 2, "{name}");
+            assert_eq!(parsed.actions.len(), 1, "{name}");
+            assert!(parsed.unknown_keys.is_empty(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_resolved_applies_profile() {
+        let work = Link {
+            source: "a".to_string(),
+            target: "b".to_string(),
+            if_cond: None,
+            if_not_cond: None,
+            profiles: vec!["work".to_string()],
+            ..Default::default()
+        };
+        let config = DotmanConfig {
+            links: vec![work.clone()],
+            ..Default::default()
+        };
+        assert!(config.resolved().links.is_empty());
+
+        let config = config.with_profile(Some("work".to_string()));
+        assert_eq!(config.resolved().links.len(), 1);
+    }
+
+    #[test]
+    fn test_ssh_generates_concat_link() {
+        let config: DotmanConfig = toml::from_str(
+            "[ssh]\nsource = \"ssh/hosts\"\nhosts = [\"github\", { source = \"work\", if = { os = [\"linux\"] } }]",
+        )
+        .unwrap();
+
+        let links = config.get_effective_links();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].target, "~/.ssh/config");
+        assert_eq!(links[0].mode, LinkMode::Concat);
+        assert_eq!(links[0].chmod.as_deref(), Some("600"));
+        assert_eq!(links[0].fragments[1].source(), "work");
+
+        let resolved = config.resolved();
+        assert!(resolved.ssh.is_none());
+        assert_eq!(resolved.get_effective_links().len(), 1);
+    }
+
+    #[test]
+    fn test_ssh_link_follows_clone() {
+        let config: DotmanConfig = toml::from_str("[ssh]\nsource = \"ssh/hosts\"").unwrap();
+        assert_eq!(config.all_links().count(), 1);
+
+        let mut cloned = config.clone();
+        cloned.ssh.as_mut().unwrap().target = Some("~/.ssh/generated".to_string());
+        assert_eq!(
+            cloned.all_links().next().unwrap().target,
+            "~/.ssh/generated"
+        );
+    }
+
+    #[test]
+    fn test_resolve_source_in_named_repo() {
+        let config = DotmanConfig {
+            config_path: "/home/me/dotfiles/dotman.toml".to_string(),
+            repos: [(
+                "work".to_string(),
+                Repo {
+                    path: Some("/home/me/work".to_string()),
+                    ..Default::default()
+                },
+            )]
+            .into(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            config.resolve_source("work:zsh/zshrc").unwrap(),
+            PathBuf::from("/home/me/work/zsh/zshrc")
+        );
+        assert_eq!(
+            config.resolve_source("zshrc").unwrap(),
+            PathBuf::from("/home/me/dotfiles/zshrc")
+        );
+        assert_eq!(
+            config.resolve_source("other:zshrc").unwrap(),
+            PathBuf::from("/home/me/dotfiles/other:zshrc")
+        );
+    }
+
+    #[test]
+    fn test_parse_error_reports_location() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_file = temp_dir.path().join("dotman.toml");
+        std::fs::write(&config_file, "[[links]]\nsource = \"a\"\ntarget = 12\n").unwrap();
+
+        let err = DotmanConfig::try_from(config_file.as_path()).unwrap_err();
+        let err = err.downcast_ref::<ConfigFileParseError>().unwrap();
+
+        assert_eq!((err.line, err.column), (3, 10));
+        let rendered = err.to_string();
+        assert!(rendered.contains("3 | target = 12"));
+        assert!(rendered.contains("  |          ^^"));
+    }
+
+    #[test]
+    fn test_parse_config_without_unknown_keys() {
+        let config_file = PathBuf::from("tests/config/working.toml");
+        let config = DotmanConfig::try_from(config_file.as_path()).unwrap();
+        assert!(config.unknown_keys.is_empty());
+    }
+
+    #[test]
+    fn test_json_schema_describes_config() {
+        let schema = serde_json::to_value(DotmanConfig::json_schema()).unwrap();
+        let properties = schema["properties"].as_object().unwrap();
+
+        assert!(properties.contains_key("links"));
+        assert!(properties.contains_key("actions"));
+        assert!(!properties.contains_key("config_path"));
+
+        let link = &schema["$defs"]["Link"]["properties"];
+        assert!(link.get("if").is_some());
+        assert!(link.get("if-not").is_some());
+    }
+
     #[test]
     fn test_run_command_simple() {
         let cmd = RunCommand::Simple("echo test".to_string());
@@ -317,6 +2267,51 @@ mod test {
         assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "test");
     }
 
+    #[test]
+    fn test_run_command_script() {
+        let cmd = RunCommand::Simple("greeting=hello\necho \"$greeting\"\n".to_string());
+        let output = cmd.execute().unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "hello");
+
+        let cmd = RunCommand::Simple("#!/bin/sh -e\nfalse\necho unreachable\n".to_string());
+        let output = cmd.execute().unwrap();
+        assert!(!output.status.success());
+        assert!(output.stdout.is_empty());
+    }
+
+    #[test]
+    fn test_shebang() {
+        assert_eq!(
+            shebang("#!/usr/bin/env python3\nprint()"),
+            Some(("/usr/bin/env", Some("python3")))
+        );
+        assert_eq!(shebang("#!/bin/bash\necho"), Some(("/bin/bash", None)));
+        assert_eq!(shebang("echo\necho"), None);
+    }
+
+    #[test]
+    fn test_run_command_nushell() {
+        let action: Action = toml::from_str(
+            "type = \"shell-command\"\nname = \"Env\"\nrun = { command = \"$env.PATH\", shell = \"nu\" }",
+        )
+        .unwrap();
+        assert_eq!(action.run().unwrap().shell(), "nu");
+    }
+
+    #[test]
+    fn test_become() {
+        let action: Action = toml::from_str(
+            "type = \"shell-command\"\nname = \"Sshd\"\nrun = \"true\"\nbecome = true",
+        )
+        .unwrap();
+        assert!(action.elevate());
+
+        let link: Link = toml::from_str("source = \"hosts\"\ntarget = \"/etc/hosts\"").unwrap();
+        assert!(!link.elevate);
+        assert!(!toml::to_string(&link).unwrap().contains("become"));
+    }
+
     #[test]
     fn test_run_command_complex_default_shell() {
         let cmd = RunCommand::Complex {
@@ -348,8 +2343,8 @@ mod test {
             run: None,
             ..Default::default()
         };
-        assert!(condition.is_met(&OperatingSystem::Linux, Some("test")));
-        assert!(!condition.is_met(&OperatingSystem::MacOS, Some("test")));
+        assert!(condition.is_met(&Context::new(OperatingSystem::Linux, Some("test"))));
+        assert!(!condition.is_met(&Context::new(OperatingSystem::MacOS, Some("test"))));
     }
 
     #[test]
@@ -360,10 +2355,10 @@ mod test {
             run: None,
             ..Default::default()
         };
-        assert!(condition.is_met(&OperatingSystem::Linux, Some("test-host")));
-        assert!(!condition.is_met(&OperatingSystem::Linux, Some("other-host")));
+        assert!(condition.is_met(&Context::new(OperatingSystem::Linux, Some("test-host"))));
+        assert!(!condition.is_met(&Context::new(OperatingSystem::Linux, Some("other-host"))));
         // Should not match when hostname is None
-        assert!(!condition.is_met(&OperatingSystem::Linux, None));
+        assert!(!condition.is_met(&Context::new(OperatingSystem::Linux, None)));
     }
 
     #[test]
@@ -379,22 +2374,22 @@ mod test {
             ..Default::default()
         };
         // Should match any of the hostnames in the list
-        assert!(condition.is_met(&OperatingSystem::Linux, Some("host1")));
-        assert!(condition.is_met(&OperatingSystem::Linux, Some("host2")));
-        assert!(condition.is_met(&OperatingSystem::Linux, Some("host3")));
+        assert!(condition.is_met(&Context::new(OperatingSystem::Linux, Some("host1"))));
+        assert!(condition.is_met(&Context::new(OperatingSystem::Linux, Some("host2"))));
+        assert!(condition.is_met(&Context::new(OperatingSystem::Linux, Some("host3"))));
         // Should not match hostnames not in the list
-        assert!(!condition.is_met(&OperatingSystem::Linux, Some("other-host")));
+        assert!(!condition.is_met(&Context::new(OperatingSystem::Linux, Some("other-host"))));
         // Should not match when hostname is None
-        assert!(!condition.is_met(&OperatingSystem::Linux, None));
+        assert!(!condition.is_met(&Context::new(OperatingSystem::Linux, None)));
     }
 
     #[test]
     fn test_condition_empty_matches_all() {
         let condition = Condition::default();
-        assert!(condition.is_met(&OperatingSystem::Linux, Some("any")));
-        assert!(condition.is_met(&OperatingSystem::MacOS, Some("any")));
+        assert!(condition.is_met(&Context::new(OperatingSystem::Linux, Some("any"))));
+        assert!(condition.is_met(&Context::new(OperatingSystem::MacOS, Some("any"))));
         // Should also match when hostname is None
-        assert!(condition.is_met(&OperatingSystem::Linux, None));
+        assert!(condition.is_met(&Context::new(OperatingSystem::Linux, None)));
     }
 
     #[test]
@@ -405,7 +2400,7 @@ mod test {
             run: Some(RunCommand::Simple("true".to_string())),
             ..Default::default()
         };
-        assert!(condition.is_met(&OperatingSystem::Linux, Some("test")));
+        assert!(condition.is_met(&Context::new(OperatingSystem::Linux, Some("test"))));
     }
 
     #[test]
@@ -416,7 +2411,7 @@ mod test {
             run: Some(RunCommand::Simple("false".to_string())),
             ..Default::default()
         };
-        assert!(!condition.is_met(&OperatingSystem::Linux, Some("test")));
+        assert!(!condition.is_met(&Context::new(OperatingSystem::Linux, Some("test"))));
     }
 
     #[test]
@@ -427,10 +2422,10 @@ mod test {
             run: Some(RunCommand::Simple("true".to_string())),
             ..Default::default()
         };
-        assert!(condition.is_met(&OperatingSystem::Linux, Some("test-host")));
-        assert!(!condition.is_met(&OperatingSystem::MacOS, Some("test-host")));
-        assert!(!condition.is_met(&OperatingSystem::Linux, Some("other-host")));
-        assert!(!condition.is_met(&OperatingSystem::Linux, None));
+        assert!(condition.is_met(&Context::new(OperatingSystem::Linux, Some("test-host"))));
+        assert!(!condition.is_met(&Context::new(OperatingSystem::MacOS, Some("test-host"))));
+        assert!(!condition.is_met(&Context::new(OperatingSystem::Linux, Some("other-host"))));
+        assert!(!condition.is_met(&Context::new(OperatingSystem::Linux, None)));
     }
 
     #[test]
@@ -443,7 +2438,7 @@ mod test {
             file_exists: vec![temp_path.clone()],
             ..Default::default()
         };
-        assert!(condition.is_met(&OperatingSystem::Linux, Some("test")));
+        assert!(condition.is_met(&Context::new(OperatingSystem::Linux, Some("test"))));
 
         // File doesn't exist - should not match
         let non_existent = "/tmp/non_existent_file_12345.txt".to_string();
@@ -451,7 +2446,147 @@ mod test {
             file_exists: vec![non_existent],
             ..Default::default()
         };
-        assert!(!condition.is_met(&OperatingSystem::Linux, Some("test")));
+        assert!(!condition.is_met(&Context::new(OperatingSystem::Linux, Some("test"))));
+    }
+
+    #[test]
+    fn test_condition_exists_and_not_exists() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let present = temp_dir.path().to_string_lossy().to_string();
+        let missing = temp_dir
+            .path()
+            .join("missing")
+            .to_string_lossy()
+            .to_string();
+
+        let condition: Condition =
+            toml::from_str(&format!("exists = '{present}'\nnot_exists = ['{missing}']")).unwrap();
+        assert!(condition.is_met(&Context::new(OperatingSystem::Linux, None)));
+
+        let condition = Condition {
+            not_exists: Some(OneOrMany::One(present)),
+            ..Default::default()
+        };
+        assert!(!condition.is_met(&Context::new(OperatingSystem::Linux, None)));
+
+        let condition = Condition {
+            exists: Some(OneOrMany::Many(vec![missing])),
+            ..Default::default()
+        };
+        assert!(!condition.is_met(&Context::new(OperatingSystem::Linux, None)));
+    }
+
+    #[test]
+    fn test_condition_hostname_globs() {
+        let condition = Condition {
+            hostname: Some(Hostname::Multiple(vec![
+                "work-*".to_string(),
+                "laptop?".to_string(),
+            ])),
+            ..Default::default()
+        };
+
+        assert!(condition.is_met(&Context::new(OperatingSystem::Linux, Some("work-desktop"))));
+        assert!(condition.is_met(&Context::new(OperatingSystem::Linux, Some("laptop2"))));
+        assert!(!condition.is_met(&Context::new(OperatingSystem::Linux, Some("laptop"))));
+        assert!(!condition.is_met(&Context::new(OperatingSystem::Linux, Some("home-work-pc"))));
+    }
+
+    #[test]
+    fn test_condition_all_any_not() {
+        let condition: Condition = toml::from_str(
+            r#"
+            any = [{ os = ["macos"] }, { hostname = "work-laptop" }]
+            not = { all = [{ os = ["linux"] }, { hostname = "work-laptop" }] }
+            "#,
+        )
+        .unwrap();
+
+        assert!(condition.is_met(&Context::new(OperatingSystem::MacOS, None)));
+        assert!(condition.is_met(&Context::new(OperatingSystem::Windows, Some("work-laptop"))));
+        assert!(!condition.is_met(&Context::new(OperatingSystem::Linux, Some("work-laptop"))));
+        assert!(!condition.is_met(&Context::new(OperatingSystem::Linux, Some("home"))));
+    }
+
+    #[test]
+    fn test_condition_arch() {
+        let mut context = Context::new(OperatingSystem::MacOS, None);
+        context.arch = "aarch64".to_string();
+        let condition = Condition {
+            arch: vec!["aarch64".to_string()],
+            ..Default::default()
+        };
+
+        assert!(condition.is_met(&context));
+        context.arch = "x86_64".to_string();
+        assert!(!condition.is_met(&context));
+    }
+
+    #[test]
+    fn test_condition_desktop() {
+        let mut context = Context::new(OperatingSystem::Linux, None);
+        context.desktops = vec!["sway".to_string(), "wayland".to_string()];
+
+        let condition: Condition = toml::from_str(r#"desktop = ["GNOME", "sway"]"#).unwrap();
+        assert!(condition.is_met(&context));
+
+        let condition: Condition = toml::from_str(r#"desktop = ["x11"]"#).unwrap();
+        assert!(!condition.is_met(&context));
+    }
+
+    #[test]
+    fn test_condition_user_and_root() {
+        let mut context = Context::new(OperatingSystem::Linux, None);
+        context.user = Some("omfj".to_string());
+        let condition = Condition {
+            user: Some("omfj".to_string()),
+            root: Some(false),
+            ..Default::default()
+        };
+        assert!(condition.is_met(&context));
+
+        context.root = true;
+        assert!(!condition.is_met(&context));
+
+        context.user = Some("root".to_string());
+        let condition = Condition {
+            root: Some(true),
+            ..Default::default()
+        };
+        assert!(condition.is_met(&context));
+    }
+
+    #[test]
+    fn test_operating_system_names() {
+        let condition: Condition = toml::from_str(r#"os = ["freebsd", "illumos"]"#).unwrap();
+
+        assert_eq!(
+            condition.os,
+            [
+                OperatingSystem::FreeBSD,
+                OperatingSystem::Other("illumos".to_string())
+            ]
+        );
+        assert!(condition.is_met(&Context::new(
+            OperatingSystem::Other("illumos".to_string()),
+            None
+        )));
+        assert!(!condition.is_met(&Context::new(OperatingSystem::OpenBSD, None)));
+    }
+
+    #[test]
+    fn test_condition_has_command() {
+        let condition = Condition {
+            has_command: Some(OneOrMany::Many(vec!["sh".to_string()])),
+            ..Default::default()
+        };
+        assert!(condition.is_met(&Context::new(OperatingSystem::Linux, None)));
+
+        let condition = Condition {
+            has_command: Some(OneOrMany::One("dotman-missing-command".to_string())),
+            ..Default::default()
+        };
+        assert!(!condition.is_met(&Context::new(OperatingSystem::Linux, None)));
     }
 
     #[test]
@@ -468,14 +2603,14 @@ mod test {
             file_exists: vec![tilde_path],
             ..Default::default()
         };
-        assert!(condition.is_met(&OperatingSystem::Linux, Some("test")));
+        assert!(condition.is_met(&Context::new(OperatingSystem::Linux, Some("test"))));
 
         // Non-existent file with tilde - should not match
         let condition = Condition {
             file_exists: vec!["~/non_existent_file_12345.txt".to_string()],
             ..Default::default()
         };
-        assert!(!condition.is_met(&OperatingSystem::Linux, Some("test")));
+        assert!(!condition.is_met(&Context::new(OperatingSystem::Linux, Some("test"))));
     }
 
     #[test]
@@ -483,6 +2618,7 @@ mod test {
         let action = Action::ShellCommand {
             name: "test".to_string(),
             run: RunCommand::Simple("echo test".to_string()),
+            undo: None,
             if_cond: Some(Condition {
                 os: vec![OperatingSystem::Linux],
                 hostname: None,
@@ -491,9 +2627,10 @@ mod test {
             }),
             if_not_cond: None,
             profiles: vec![],
+            elevate: false,
         };
-        assert!(action.is_met(&OperatingSystem::Linux, Some("test")));
-        assert!(!action.is_met(&OperatingSystem::MacOS, Some("test")));
+        assert!(action.is_met(&Context::new(OperatingSystem::Linux, Some("test"))));
+        assert!(!action.is_met(&Context::new(OperatingSystem::MacOS, Some("test"))));
     }
 
     #[test]
@@ -501,6 +2638,7 @@ mod test {
         let action = Action::ShellCommand {
             name: "test".to_string(),
             run: RunCommand::Simple("echo test".to_string()),
+            undo: None,
             if_cond: None,
             if_not_cond: Some(Condition {
                 os: vec![OperatingSystem::MacOS],
@@ -509,9 +2647,10 @@ mod test {
                 ..Default::default()
             }),
             profiles: vec![],
+            elevate: false,
         };
-        assert!(action.is_met(&OperatingSystem::Linux, Some("test")));
-        assert!(!action.is_met(&OperatingSystem::MacOS, Some("test")));
+        assert!(action.is_met(&Context::new(OperatingSystem::Linux, Some("test"))));
+        assert!(!action.is_met(&Context::new(OperatingSystem::MacOS, Some("test"))));
     }
 
     #[test]
@@ -519,6 +2658,7 @@ mod test {
         let action_met = Action::ShellCommand {
             name: "Test action".to_string(),
             run: RunCommand::Simple("echo test".to_string()),
+            undo: None,
             if_cond: Some(Condition {
                 os: vec![],
                 hostname: None,
@@ -527,13 +2667,15 @@ mod test {
             }),
             if_not_cond: None,
             profiles: vec![],
+            elevate: false,
         };
 
-        assert!(action_met.is_met(&OperatingSystem::Linux, Some("test")));
+        assert!(action_met.is_met(&Context::new(OperatingSystem::Linux, Some("test"))));
 
         let action_not_met = Action::ShellCommand {
             name: "Test action".to_string(),
             run: RunCommand::Simple("echo test".to_string()),
+            undo: None,
             if_cond: Some(Condition {
                 os: vec![],
                 hostname: None,
@@ -542,8 +2684,9 @@ mod test {
             }),
             if_not_cond: None,
             profiles: vec![],
+            elevate: false,
         };
 
-        assert!(!action_not_met.is_met(&OperatingSystem::Linux, Some("test")));
+        assert!(!action_not_met.is_met(&Context::new(OperatingSystem::Linux, Some("test"))));
     }
 }
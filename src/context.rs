@@ -0,0 +1,177 @@
+use crate::{
+    config::{OperatingSystem, RunCommand},
+    utils,
+};
+
+/// What conditions are evaluated against during one dotman invocation.
+#[derive(Debug)]
+pub struct Context {
+    pub os: OperatingSystem,
+    pub hostname: Option<String>,
+    /// CPU architecture, as Rust names it (`x86_64`, `aarch64`, ...).
+    pub arch: String,
+    /// Environment variables at the start of the run.
+    pub env: std::collections::BTreeMap<String, String>,
+    /// Name of the user dotman runs as.
+    pub user: Option<String>,
+    /// Whether dotman runs with root privileges.
+    pub root: bool,
+    /// Running desktop environments and display server, lowercased.
+    pub desktops: Vec<String>,
+    /// Outcome of each condition `run` command, so a command shared by many
+    /// links only runs once.
+    run_results: std::sync::Mutex<std::collections::HashMap<(String, String), bool>>,
+    /// Whether `prompt` conditions may ask the user. Only installs do, and
+    /// only from a terminal.
+    prompts: std::sync::atomic::AtomicBool,
+    /// Answers to `prompt` conditions, so each question is asked once.
+    answers: std::sync::Mutex<std::collections::HashMap<String, bool>>,
+}
+
+impl Context {
+    pub fn new(os: OperatingSystem, hostname: Option<&str>) -> Self {
+        Context {
+            os,
+            hostname: hostname.map(str::to_string),
+            arch: std::env::consts::ARCH.to_string(),
+            env: Default::default(),
+            user: None,
+            root: false,
+            desktops: vec![],
+            run_results: Default::default(),
+            prompts: Default::default(),
+            answers: Default::default(),
+        }
+    }
+
+    /// Detects the current machine: operating system, hostname, environment
+    /// and the user and desktop derived from it.
+    pub fn detect() -> Self {
+        let env: std::collections::BTreeMap<String, String> = std::env::vars().collect();
+        let var = |name: &str| env.get(name).cloned();
+        let mut context =
+            Context::new(utils::get_current_os(), utils::get_hostname(var).as_deref());
+        context.user = utils::get_username(var);
+        context.desktops = utils::get_desktops(var);
+        context.root = utils::is_root();
+        context.env = env;
+        context
+    }
+
+    /// Evaluates conditions as if running on `os`.
+    pub fn with_os(mut self, os: OperatingSystem) -> Self {
+        self.os = os;
+        self
+    }
+
+    /// Evaluates conditions as if running on a machine called `hostname`.
+    pub fn with_hostname(mut self, hostname: &str) -> Self {
+        self.hostname = Some(hostname.to_string());
+        self
+    }
+
+    /// Runs a condition command, reusing the result of an identical command
+    /// from earlier in this invocation.
+    pub fn run_succeeds(&self, command: &RunCommand) -> bool {
+        let key = (command.shell().to_string(), command.command().to_string());
+        if let Some(succeeded) = self.run_results.lock().unwrap().get(&key) {
+            return *succeeded;
+        }
+
+        let succeeded = command.is_successful();
+        self.run_results.lock().unwrap().insert(key, succeeded);
+        succeeded
+    }
+
+    /// Lets `prompt` conditions ask the user, or not, until the returned
+    /// guard is dropped.
+    pub fn allow_prompts(&self, allow: bool) -> PromptGuard<'_> {
+        let previous = self
+            .prompts
+            .swap(allow, std::sync::atomic::Ordering::Relaxed);
+        PromptGuard {
+            context: self,
+            previous,
+        }
+    }
+
+    /// Asks a yes/no question once per invocation. Without prompts allowed,
+    /// the answer is no.
+    pub fn confirm(&self, question: &str) -> bool {
+        use std::io::Write;
+
+        let mut answers = self.answers.lock().unwrap();
+        if let Some(answer) = answers.get(question) {
+            return *answer;
+        }
+        if !self.prompts.load(std::sync::atomic::Ordering::Relaxed) {
+            return false;
+        }
+
+        print!("{question} [y/N] ");
+        let _ = std::io::stdout().flush();
+        let mut input = String::new();
+        let answer = std::io::stdin().read_line(&mut input).is_ok()
+            && matches!(input.trim().to_lowercase().as_str(), "y" | "yes");
+        answers.insert(question.to_string(), answer);
+        answer
+    }
+}
+
+/// Puts back whether prompts were allowed when dropped.
+#[must_use]
+#[derive(Debug)]
+pub struct PromptGuard<'a> {
+    context: &'a Context,
+    previous: bool,
+}
+
+impl Drop for PromptGuard<'_> {
+    fn drop(&mut self) {
+        self.context
+            .prompts
+            .store(self.previous, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_run_results_are_cached() {
+        let temp_dir = TempDir::new().unwrap();
+        let counter = temp_dir.path().join("count");
+        let command = RunCommand::Simple(format!("echo run >> '{}'", counter.display()));
+        let context = Context::new(OperatingSystem::Linux, None);
+
+        assert!(context.run_succeeds(&command));
+        assert!(context.run_succeeds(&command));
+
+        assert_eq!(std::fs::read_to_string(&counter).unwrap(), "run\n");
+    }
+
+    #[test]
+    fn test_confirm_without_prompts_is_no() {
+        let context = Context::new(OperatingSystem::Linux, None);
+
+        assert!(!context.confirm("Install gaming configs?"));
+    }
+
+    #[test]
+    fn test_prompts_are_allowed_while_guarded() {
+        let context = Context::new(OperatingSystem::Linux, None);
+        let prompts = || context.prompts.load(std::sync::atomic::Ordering::Relaxed);
+
+        {
+            let _outer = context.allow_prompts(true);
+            {
+                let _inner = context.allow_prompts(false);
+                assert!(!prompts());
+            }
+            assert!(prompts());
+        }
+        assert!(!prompts());
+    }
+}
@@ -0,0 +1,246 @@
+//! Setting the macOS preferences declared in `[[defaults]]`.
+
+use colored::Colorize;
+
+use crate::{
+    Dotman,
+    config::{Preference, PreferenceType, PreferenceValue},
+    error::DotmanError,
+    options::InstallOptions,
+};
+
+/// State of a preference on this machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreferenceStatus {
+    ConditionNotMet,
+    /// The `defaults` command is not available, as on anything but macOS.
+    Unavailable,
+    Unset,
+    /// The preference has another value, as `defaults read` prints it.
+    Differs(String),
+    Ok,
+}
+
+impl PreferenceStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PreferenceStatus::ConditionNotMet => "[CONDITION NOT MET]",
+            PreferenceStatus::Unavailable => "[DEFAULTS NOT AVAILABLE]",
+            PreferenceStatus::Unset => "[UNSET]",
+            PreferenceStatus::Differs(_) => "[DIFFERS]",
+            PreferenceStatus::Ok => "[OK]",
+        }
+    }
+}
+
+/// The type `preference` is written as: the declared one, or that of its
+/// value.
+pub fn kind(preference: &Preference) -> PreferenceType {
+    preference.kind.unwrap_or(match preference.value {
+        PreferenceValue::Bool(_) => PreferenceType::Bool,
+        PreferenceValue::Int(_) => PreferenceType::Int,
+        PreferenceValue::Float(_) => PreferenceType::Float,
+        PreferenceValue::String(_) => PreferenceType::String,
+    })
+}
+
+/// Arguments of the `defaults write` that sets `preference`.
+pub fn write_args(preference: &Preference) -> Vec<String> {
+    let flag = match kind(preference) {
+        PreferenceType::String => "-string",
+        PreferenceType::Int => "-int",
+        PreferenceType::Float => "-float",
+        PreferenceType::Bool => "-bool",
+    };
+    vec![
+        "write".to_string(),
+        preference.domain.clone(),
+        preference.key.clone(),
+        flag.to_string(),
+        preference.value.to_string(),
+    ]
+}
+
+/// Whether `actual`, as `defaults read` prints it, is the value of
+/// `preference`. Booleans are printed as `1` or `0`, and numbers are
+/// compared by value.
+pub fn matches(preference: &Preference, actual: &str) -> bool {
+    let expected = preference.value.to_string();
+    match kind(preference) {
+        PreferenceType::String => actual == expected,
+        PreferenceType::Int => actual.parse::<i64>().ok() == expected.parse().ok(),
+        PreferenceType::Float => actual
+            .parse::<f64>()
+            .is_ok_and(|actual| expected.parse() == Ok(actual)),
+        PreferenceType::Bool => {
+            let truthy = ["1", "true", "yes"].contains(&expected.to_lowercase().as_str());
+            actual == if truthy { "1" } else { "0" }
+        }
+    }
+}
+
+/// Reads the current value of `preference`, or `None` if it is unset.
+fn read(preference: &Preference) -> Option<String> {
+    let output = std::process::Command::new("defaults")
+        .args(["read", &preference.domain, &preference.key])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+impl Dotman {
+    /// Checks whether `preference` has its declared value.
+    pub fn preference_status(&self, preference: &Preference) -> PreferenceStatus {
+        if !preference.is_met(&self.context) {
+            return PreferenceStatus::ConditionNotMet;
+        }
+        if which::which("defaults").is_err() {
+            return PreferenceStatus::Unavailable;
+        }
+        match read(preference) {
+            None => PreferenceStatus::Unset,
+            Some(actual) if matches(preference, &actual) => PreferenceStatus::Ok,
+            Some(actual) => PreferenceStatus::Differs(actual),
+        }
+    }
+
+    /// Sets the preferences `options` selects that do not have their value
+    /// yet. Unless `options.fail_fast` is set, the rest are still set when
+    /// one fails.
+    pub fn apply_defaults(&self, options: &InstallOptions) -> Result<(), DotmanError> {
+        let preferences: Vec<&Preference> = self
+            .config
+            .get_effective_defaults()
+            .into_iter()
+            .filter(|preference| options.selects_preference(preference))
+            .collect();
+        if preferences.is_empty() {
+            return Ok(());
+        }
+        if which::which("defaults").is_err() {
+            println!(
+                "{} defaults is not available, skipping macOS preferences.",
+                "Warning:".yellow().bold()
+            );
+            return Ok(());
+        }
+        let mut errors = Vec::new();
+        for preference in preferences {
+            if options.is_cancelled() {
+                return Err(DotmanError::Cancelled);
+            }
+            match self.apply_preference(preference, options) {
+                Err(err) if options.fail_fast => return Err(err),
+                result => errors.extend(result.err()),
+            }
+        }
+        DotmanError::collect(errors)
+    }
+
+    fn apply_preference(
+        &self,
+        preference: &Preference,
+        options: &InstallOptions,
+    ) -> Result<(), DotmanError> {
+        let args = write_args(preference);
+        let command = format!("defaults {}", args.join(" "));
+        match self.preference_status(preference) {
+            PreferenceStatus::Ok | PreferenceStatus::Unavailable => {}
+            PreferenceStatus::ConditionNotMet => {
+                println!(
+                    "{} {} {} failed condition check, skipping.",
+                    "Ignored:".yellow().bold(),
+                    preference.domain,
+                    preference.key
+                );
+            }
+            _ if options.dry_run => {
+                println!("{} {}", "Would run:".cyan().bold(), command);
+            }
+            _ => {
+                let output = std::process::Command::new("defaults")
+                    .args(&args)
+                    .output()
+                    .map_err(|err| DotmanError::CommandError {
+                        command: command.clone(),
+                        message: err.to_string(),
+                    })?;
+                if !output.status.success() {
+                    return Err(DotmanError::CommandError {
+                        command,
+                        message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                    });
+                }
+                println!(
+                    "{} {} {} to {}",
+                    "Set:".green().bold(),
+                    preference.domain,
+                    preference.key,
+                    preference.value
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints the status of every preference for `dotman status`.
+    pub(crate) fn print_defaults_status(&self) {
+        for preference in self.config.get_effective_defaults() {
+            let status = self.preference_status(preference);
+            match &status {
+                PreferenceStatus::Ok => print!("{}", status.label().green().bold()),
+                PreferenceStatus::Differs(actual) => {
+                    print!("{} (is {})", status.label().yellow().bold(), actual)
+                }
+                PreferenceStatus::ConditionNotMet
+                | PreferenceStatus::Unavailable
+                | PreferenceStatus::Unset => print!("{}", status.label().yellow().bold()),
+            }
+            println!(
+                " {} {} = {}",
+                preference.domain, preference.key, preference.value
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_args() {
+        let autohide = Preference::new("com.apple.dock", "autohide", true);
+        assert_eq!(
+            write_args(&autohide),
+            ["write", "com.apple.dock", "autohide", "-bool", "true"]
+        );
+
+        let delay = Preference {
+            kind: Some(PreferenceType::Float),
+            ..Preference::new("com.apple.dock", "autohide-delay", 0_i64)
+        };
+        assert_eq!(write_args(&delay)[3..], ["-float", "0"]);
+    }
+
+    #[test]
+    fn test_matches() {
+        let autohide = Preference::new("com.apple.dock", "autohide", true);
+        assert!(matches(&autohide, "1"));
+        assert!(!matches(&autohide, "0"));
+
+        let delay = Preference::new("com.apple.dock", "autohide-delay", 0.5);
+        assert!(matches(&delay, "0.5"));
+        assert!(!matches(&delay, "0"));
+
+        let size = Preference::new("com.apple.dock", "tilesize", 48_i64);
+        assert!(matches(&size, "48"));
+
+        let style = Preference::new("NSGlobalDomain", "AppleInterfaceStyle", "Dark");
+        assert!(matches(&style, "Dark"));
+        assert!(!matches(&style, "Light"));
+    }
+}
@@ -0,0 +1,216 @@
+//! Creating the directories declared in `[[dirs]]`.
+
+use colored::Colorize;
+
+use crate::{
+    Dotman,
+    config::Directory,
+    error::{DotmanError, PathError},
+    fs::EntryKind,
+    options::InstallOptions,
+    utils::{self, Absolute, ExpandTilde},
+};
+
+/// State of a directory on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DirStatus {
+    ConditionNotMet,
+    Missing,
+    /// Something other than a directory is at the path.
+    NotDirectory,
+    /// The directory has other permissions than declared.
+    WrongMode(u32),
+    Ok,
+}
+
+impl DirStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DirStatus::ConditionNotMet => "[CONDITION NOT MET]",
+            DirStatus::Missing => "[MISSING]",
+            DirStatus::NotDirectory => "[NOT A DIRECTORY]",
+            DirStatus::WrongMode(_) => "[WRONG MODE]",
+            DirStatus::Ok => "[OK]",
+        }
+    }
+}
+
+impl Dotman {
+    fn dir_path(&self, dir: &Directory) -> Result<std::path::PathBuf, DotmanError> {
+        Ok(dir.path.expand_tilde_path()?.absolute()?)
+    }
+
+    /// Checks whether `dir` exists with the declared permissions.
+    pub fn dir_status(&self, dir: &Directory) -> Result<DirStatus, DotmanError> {
+        if !dir.is_met(&self.context) {
+            return Ok(DirStatus::ConditionNotMet);
+        }
+        let path = self.dir_path(dir)?;
+        if !self.fs.exists(&path) {
+            return Ok(DirStatus::Missing);
+        }
+        if self.fs.kind(&path) == Some(EntryKind::File) {
+            return Ok(DirStatus::NotDirectory);
+        }
+        let expected = dir.mode.as_deref().map(utils::parse_mode).transpose()?;
+        Ok(match (expected, self.fs.mode(&path)) {
+            (Some(expected), Some(actual)) if expected != actual => DirStatus::WrongMode(actual),
+            _ => DirStatus::Ok,
+        })
+    }
+
+    /// Creates the directories `options` selects that are missing and
+    /// corrects the permissions of those that differ. Unless
+    /// `options.fail_fast` is set, the rest are still created when one
+    /// fails.
+    pub fn create_dirs(&self, options: &InstallOptions) -> Result<(), DotmanError> {
+        let mut errors = Vec::new();
+        for dir in self.config.get_effective_dirs() {
+            if options.is_cancelled() {
+                return Err(DotmanError::Cancelled);
+            }
+            if !options.selects_dir(dir) {
+                continue;
+            }
+            match self.create_dir(dir, options) {
+                Err(err) if options.fail_fast => return Err(err),
+                result => errors.extend(result.err()),
+            }
+        }
+        DotmanError::collect(errors)
+    }
+
+    fn create_dir(&self, dir: &Directory, options: &InstallOptions) -> Result<(), DotmanError> {
+        let path = self.dir_path(dir)?;
+        let mode = dir.mode.as_deref().map(utils::parse_mode).transpose()?;
+        match self.dir_status(dir)? {
+            DirStatus::ConditionNotMet => {
+                println!(
+                    "{} {} failed condition check, skipping.",
+                    "Ignored:".yellow().bold(),
+                    dir.path
+                );
+            }
+            DirStatus::Ok => {}
+            DirStatus::NotDirectory => {
+                return Err(PathError::NotADirectory(path).into());
+            }
+            DirStatus::Missing if options.dry_run => {
+                println!("{} {}", "Would create:".cyan().bold(), path.display());
+            }
+            DirStatus::Missing => {
+                self.fs.create_dir_all(&path)?;
+                if let Some(mode) = mode {
+                    self.fs.set_mode(&path, mode)?;
+                }
+                println!("{} {}", "Created:".green().bold(), path.display());
+            }
+            DirStatus::WrongMode(actual) if options.dry_run => {
+                println!(
+                    "{} mode of {} from {:o} to {}",
+                    "Would update:".cyan().bold(),
+                    path.display(),
+                    actual,
+                    dir.mode.as_deref().unwrap_or_default()
+                );
+            }
+            DirStatus::WrongMode(actual) => {
+                if let Some(mode) = mode {
+                    self.fs.set_mode(&path, mode)?;
+                }
+                println!(
+                    "{} mode of {} from {:o} to {}",
+                    "Updated:".green().bold(),
+                    path.display(),
+                    actual,
+                    dir.mode.as_deref().unwrap_or_default()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that a missing `dir` can be created in the closest directory
+    /// that exists.
+    pub(crate) fn check_dir(&self, dir: &Directory) -> Result<(), String> {
+        let path = self.dir_path(dir).map_err(|err| err.to_string())?;
+        let Some(parent) = path
+            .ancestors()
+            .find(|ancestor| self.fs.kind(ancestor).is_some())
+        else {
+            return Ok(());
+        };
+        if parent == path {
+            return Ok(());
+        }
+        if self.fs.kind(parent) == Some(EntryKind::File) {
+            return Err(format!("{} is not a directory", parent.display()));
+        }
+        if !self.fs.is_writable(parent) {
+            return Err(format!("{} is not writable", parent.display()));
+        }
+        Ok(())
+    }
+
+    /// Prints the status of every directory for `dotman status`.
+    pub(crate) fn print_dir_status(&self) -> Result<(), DotmanError> {
+        for dir in self.config.get_effective_dirs() {
+            let status = self.dir_status(dir)?;
+            match status {
+                DirStatus::Ok => print!("{}", status.label().green().bold()),
+                DirStatus::NotDirectory => print!("{}", status.label().red().bold()),
+                DirStatus::WrongMode(actual) => {
+                    print!("{} (is {:o})", status.label().yellow().bold(), actual)
+                }
+                DirStatus::ConditionNotMet | DirStatus::Missing => {
+                    print!("{}", status.label().yellow().bold())
+                }
+            }
+            println!(" {}", dir.path);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{Filesystem, MemoryFilesystem};
+
+    #[test]
+    fn test_create_dirs() {
+        let fs = std::sync::Arc::new(MemoryFilesystem::new());
+        fs.create_dir_all(std::path::Path::new("/home/me/.ssh"))
+            .unwrap();
+        fs.write(std::path::Path::new("/home/me/.cache"), b"", false)
+            .unwrap();
+
+        let private = |path: &str| Directory {
+            mode: Some("700".to_string()),
+            ..Directory::new(path)
+        };
+        let config = crate::DotmanConfig::builder()
+            .dir(Directory::new("/home/me/.local/bin"))
+            .dir(private("/home/me/.ssh"))
+            .dir(private("/home/me/.gnupg"))
+            .build();
+        let dotman = Dotman::new(config).with_filesystem(fs.clone());
+        let ssh = &dotman.config.dirs[1];
+        assert_eq!(dotman.dir_status(ssh).unwrap(), DirStatus::WrongMode(0o755));
+
+        dotman.create_dirs(&InstallOptions::default()).unwrap();
+
+        assert_eq!(
+            fs.kind(std::path::Path::new("/home/me/.local/bin")),
+            Some(EntryKind::Dir)
+        );
+        assert_eq!(
+            fs.mode(std::path::Path::new("/home/me/.gnupg")),
+            Some(0o700)
+        );
+        assert_eq!(dotman.dir_status(ssh).unwrap(), DirStatus::Ok);
+
+        let cache = Directory::new("/home/me/.cache");
+        assert_eq!(dotman.dir_status(&cache).unwrap(), DirStatus::NotDirectory);
+    }
+}
@@ -0,0 +1,254 @@
+//! Downloading files for `type = "download"` actions.
+
+use colored::Colorize;
+
+use crate::{
+    Dotman,
+    error::{DotmanError, PathError},
+    hash, utils,
+};
+
+/// Whether the file at `path` exists and has the SHA-256 digest `sha256`.
+pub fn has_digest(path: &std::path::Path, sha256: &str) -> bool {
+    std::fs::read(path).is_ok_and(|contents| hash::sha256(&contents).eq_ignore_ascii_case(sha256))
+}
+
+/// Whether `sha256` is a hex-encoded SHA-256 digest: 64 hex digits.
+pub fn is_sha256(sha256: &str) -> bool {
+    sha256.len() == 64 && sha256.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+fn check_sha256(url: &str, sha256: &str) -> Result<(), DotmanError> {
+    if is_sha256(sha256) {
+        return Ok(());
+    }
+    Err(DotmanError::InvalidConfig(format!(
+        "'{sha256}' given for {url} is not a SHA-256 digest of 64 hex digits"
+    )))
+}
+
+/// The directory verified downloads are cached in, by digest.
+pub fn cache_dir() -> Result<std::path::PathBuf, DotmanError> {
+    dirs::cache_dir()
+        .map(|dir| dir.join("dotman").join("downloads"))
+        .ok_or_else(|| PathError::CacheDirNotFound.into())
+}
+
+/// Checks that the host of `url` can be connected to, so a download does not
+/// fail only once the install is under way.
+pub fn check_reachable(url: &str) -> Result<(), String> {
+    let host = url
+        .strip_prefix("https://")
+        .ok_or("only https URLs are supported")?
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or_default();
+    let address = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{host}:443")
+    };
+    let addresses = std::net::ToSocketAddrs::to_socket_addrs(&address)
+        .map_err(|err| format!("cannot resolve {host}: {err}"))?;
+    let timeout = std::time::Duration::from_secs(5);
+    let mut last_error = format!("cannot resolve {host}");
+    for address in addresses {
+        match std::net::TcpStream::connect_timeout(&address, timeout) {
+            Ok(_) => return Ok(()),
+            Err(err) => last_error = format!("cannot reach {host}: {err}"),
+        }
+    }
+    Err(last_error)
+}
+
+/// Returns the contents of `url`, which must have the SHA-256 digest
+/// `sha256`. A file downloaded before is taken from the cache in
+/// `cache_dir` instead.
+pub(crate) fn fetch_verified(
+    url: &str,
+    sha256: &str,
+    cache_dir: &std::path::Path,
+) -> Result<Vec<u8>, DotmanError> {
+    check_sha256(url, sha256)?;
+    let cached = cache_dir.join(sha256.to_ascii_lowercase());
+    if has_digest(&cached, sha256) {
+        return Ok(std::fs::read(cached)?);
+    }
+
+    let contents = fetch(url)?;
+    verify(url, &contents, sha256)?;
+    std::fs::create_dir_all(cache_dir)?;
+    std::fs::write(cached, &contents)?;
+    Ok(contents)
+}
+
+pub(crate) fn fetch(url: &str) -> Result<Vec<u8>, DotmanError> {
+    let error = |message: String| DotmanError::FetchError {
+        url: url.to_string(),
+        message,
+    };
+    if !url.starts_with("https://") {
+        return Err(error("only https URLs are supported".to_string()));
+    }
+    ureq::get(url)
+        .call()
+        .map_err(|err| error(err.to_string()))?
+        .body_mut()
+        .with_config()
+        .limit(u64::MAX)
+        .read_to_vec()
+        .map_err(|err| error(err.to_string()))
+}
+
+pub(crate) fn verify(url: &str, contents: &[u8], sha256: &str) -> Result<(), DotmanError> {
+    check_sha256(url, sha256)?;
+    let actual = hash::sha256(contents);
+    if actual.eq_ignore_ascii_case(sha256) {
+        Ok(())
+    } else {
+        Err(DotmanError::FetchError {
+            url: url.to_string(),
+            message: format!("expected SHA-256 {sha256}, got {actual}"),
+        })
+    }
+}
+
+/// Writes `contents` to `dest` through a temporary file in the same
+/// directory, so `dest` is never left half written.
+fn save(dest: &std::path::Path, contents: &[u8], mode: Option<u32>) -> Result<(), DotmanError> {
+    use std::io::Write;
+
+    let dir = dest
+        .parent()
+        .ok_or_else(|| PathError::NoParent(dest.to_path_buf()))?;
+    std::fs::create_dir_all(dir)?;
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(contents)?;
+    if let Some(mode) = mode {
+        utils::set_mode(file.path(), mode)?;
+    }
+    file.persist(dest).map_err(|err| err.error)?;
+    Ok(())
+}
+
+impl Dotman {
+    /// Whether the destination of a download already has its digest.
+    pub(crate) fn is_downloaded(&self, dest: &str, sha256: &str) -> bool {
+        self.config
+            .resolve_path(dest)
+            .is_ok_and(|dest| has_digest(&dest, sha256))
+    }
+
+    /// Downloads `url` to `dest`, checking it against `sha256` before
+    /// anything is written.
+    pub(crate) fn download(
+        &self,
+        url: &str,
+        dest: &str,
+        sha256: &str,
+        mode: Option<&str>,
+    ) -> Result<(), DotmanError> {
+        let mode = mode.map(utils::parse_mode).transpose()?;
+        let dest = self.config.resolve_path(dest)?;
+        let contents = fetch_verified(url, sha256, &cache_dir()?)?;
+        save(&dest, &contents, mode)?;
+        println!(
+            "{} {} -> {}",
+            "Downloaded:".green().bold(),
+            url,
+            dest.display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Action, DotmanConfig};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_has_digest() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("font.ttf");
+        let sha256 = hash::sha256(b"glyphs");
+
+        assert!(!has_digest(&path, &sha256));
+        save(&path, b"glyphs", Some(0o600)).unwrap();
+        assert!(has_digest(&path, &sha256));
+        assert!(has_digest(&path, &sha256.to_uppercase()));
+        assert!(!has_digest(&path, &hash::sha256(b"other")));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_download_skipped_when_up_to_date() {
+        let temp_dir = TempDir::new().unwrap();
+        let dest = temp_dir.path().join("font.ttf");
+        std::fs::write(&dest, "glyphs").unwrap();
+        let dest = dest.to_string_lossy().to_string();
+
+        let download = |sha256: String| Action::Download {
+            name: "Font".to_string(),
+            url: "https://example.invalid/font.ttf".to_string(),
+            dest: dest.clone(),
+            sha256,
+            mode: None,
+            if_cond: None,
+            if_not_cond: None,
+            profiles: vec![],
+        };
+        let dotman = Dotman::new(DotmanConfig::default());
+
+        assert!(dotman.is_downloaded(&dest, &hash::sha256(b"glyphs")));
+        assert!(dotman.is_done(&download(hash::sha256(b"glyphs"))));
+        assert!(!dotman.is_downloaded(&dest, &hash::sha256(b"new glyphs")));
+        assert!(!dotman.is_done(&download(hash::sha256(b"new glyphs"))));
+        assert!(!dotman.is_downloaded(
+            &temp_dir.path().join("missing.ttf").to_string_lossy(),
+            &hash::sha256(b"glyphs")
+        ));
+    }
+
+    #[test]
+    fn test_fetch_verified() {
+        let temp_dir = TempDir::new().unwrap();
+        let sha256 = hash::sha256(b"#!/bin/sh\n");
+        std::fs::write(temp_dir.path().join(&sha256), b"#!/bin/sh\n").unwrap();
+
+        let contents = fetch_verified("https://example.invalid/tool", &sha256, temp_dir.path());
+        assert_eq!(contents.unwrap(), b"#!/bin/sh\n");
+
+        let err = verify("https://example.invalid/tool", b"tampered", &sha256).unwrap_err();
+        assert!(err.to_string().contains("expected SHA-256"));
+        assert!(
+            fetch_verified(
+                "http://example.invalid/tool",
+                &hash::sha256(b""),
+                temp_dir.path()
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_digests_must_be_sha256() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("tool"), b"#!/bin/sh\n").unwrap();
+
+        assert!(is_sha256(&hash::sha256(b"")));
+        assert!(is_sha256(&hash::sha256(b"").to_uppercase()));
+        assert!(!is_sha256("abc123"));
+        assert!(!is_sha256(&"g".repeat(64)));
+
+        let err = fetch_verified("https://example.invalid/tool", "../tool", temp_dir.path());
+        assert!(matches!(err, Err(DotmanError::InvalidConfig(_))));
+    }
+}
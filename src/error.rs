@@ -6,12 +6,235 @@ pub enum DotmanError {
     IoError(#[from] std::io::Error),
     #[error("Command '{command}' failed: {message}")]
     CommandError { command: String, message: String },
-    #[error("Path error: {0}")]
-    PathError(String),
+    #[error(transparent)]
+    Path(#[from] PathError),
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+    #[error("State error: {0}")]
+    State(String),
+    #[error("Lockfile error: {0}")]
+    Lockfile(String),
+    #[error("Repository error: {0}")]
+    Repository(String),
+    #[error("Cannot merge into {}: {message}", target.display())]
+    Merge {
+        target: std::path::PathBuf,
+        message: String,
+    },
+    #[error("Cannot restore {} from {}: the target exists", target.display(), backup.display())]
+    RestoreConflict {
+        target: std::path::PathBuf,
+        backup: std::path::PathBuf,
+    },
+    #[error("Not supported: {0}")]
+    Unsupported(String),
+    #[error("Failed to fetch '{url}': {message}")]
+    FetchError { url: String, message: String },
+    #[error("Template error: {0}")]
+    TemplateError(String),
+    #[error("Secret error: {0}")]
+    SecretError(String),
+    #[error("{} failures:{}", .0.len(), .0.iter().map(|err| format!("\n  - {err}")).collect::<String>())]
+    Multiple(Vec<DotmanError>),
+    #[error("Nothing was changed, fix these first:{}", .0.iter().map(|problem| format!("\n  - {problem}")).collect::<String>())]
+    Preflight(Vec<crate::preflight::Problem>),
+    #[error("Cancelled")]
+    Cancelled,
+    #[error("Another dotman run is active (lock held on {})", .0.display())]
+    Locked(std::path::PathBuf),
+    #[error("Link '{link_source}' -> '{target}': {error}")]
+    Link {
+        link_source: String,
+        target: String,
+        #[source]
+        error: Box<DotmanError>,
+    },
+    #[error("Action '{name}': {error}")]
+    Action {
+        name: String,
+        #[source]
+        error: Box<DotmanError>,
+    },
 }
 
-impl From<String> for DotmanError {
-    fn from(err_msg: String) -> Self {
-        DotmanError::PathError(err_msg)
+impl DotmanError {
+    /// Combines the failures of a run that kept going: `Ok` if there were
+    /// none, the error itself if there was one.
+    pub fn collect(errors: impl IntoIterator<Item = DotmanError>) -> Result<(), DotmanError> {
+        let mut errors: Vec<DotmanError> = errors
+            .into_iter()
+            .flat_map(|err| match err {
+                DotmanError::Multiple(errors) => errors,
+                err => vec![err],
+            })
+            .collect();
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            _ => Err(DotmanError::Multiple(errors)),
+        }
+    }
+
+    /// The individual failures, in the order they happened.
+    pub fn errors(&self) -> Vec<&DotmanError> {
+        match self {
+            DotmanError::Multiple(errors) => errors.iter().collect(),
+            err => vec![err],
+        }
+    }
+
+    /// Names the link, as written in the configuration, that failed.
+    pub fn in_link(self, link: &crate::config::Link) -> Self {
+        match self {
+            DotmanError::Cancelled | DotmanError::Link { .. } => self,
+            error => DotmanError::Link {
+                link_source: link.source.clone(),
+                target: link.target.clone(),
+                error: Box::new(error),
+            },
+        }
+    }
+
+    /// Names the action that failed.
+    pub fn in_action(self, name: &str) -> Self {
+        match self {
+            DotmanError::Cancelled | DotmanError::Action { .. } => self,
+            // Already names the action.
+            DotmanError::CommandError { .. } => self,
+            error => DotmanError::Action {
+                name: name.to_string(),
+                error: Box::new(error),
+            },
+        }
+    }
+}
+
+/// A path in the configuration could not be resolved.
+#[derive(Debug, thiserror::Error)]
+pub enum PathError {
+    #[error("Home directory not found")]
+    HomeDirNotFound,
+    #[error("Data directory not found")]
+    DataDirNotFound,
+    #[error("State directory not found")]
+    StateDirNotFound,
+    #[error("Cache directory not found")]
+    CacheDirNotFound,
+    #[error("Config directory not found")]
+    ConfigDirNotFound,
+    #[error("{} does not exist", .0.display())]
+    NotFound(std::path::PathBuf),
+    #[error("{} already exists", .0.display())]
+    AlreadyExists(std::path::PathBuf),
+    #[error("{} is already a symlink", .0.display())]
+    AlreadySymlink(std::path::PathBuf),
+    #[error("{} exists and is not a directory", .0.display())]
+    NotADirectory(std::path::PathBuf),
+    #[error("{} has no file name", .0.display())]
+    NoFileName(std::path::PathBuf),
+    #[error("{} has no parent", .0.display())]
+    NoParent(std::path::PathBuf),
+    #[error("Cannot read {}: {source}", path.display())]
+    Unreadable {
+        path: std::path::PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Current directory is unavailable: {0}")]
+    CurrentDirUnavailable(#[source] std::io::Error),
+    #[error("Path is not valid UTF-8: {}", .0.display())]
+    InvalidUtf8(std::path::PathBuf),
+    #[error("Invalid file mode '{0}', expected octal permissions like 644")]
+    InvalidMode(String),
+}
+
+/// The configuration file could not be parsed.
+///
+/// Displays the offending line with the problematic span underlined.
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to parse configuration file '{}': {message}\n{snippet}", path.display())]
+pub struct ConfigFileParseError {
+    pub path: std::path::PathBuf,
+    pub message: String,
+    /// 1-based line of the error.
+    pub line: usize,
+    /// 1-based column of the error.
+    pub column: usize,
+    snippet: String,
+}
+
+impl ConfigFileParseError {
+    /// Builds the error from a message and the byte range it refers to.
+    pub fn new(
+        path: &std::path::Path,
+        contents: &str,
+        message: impl Into<String>,
+        span: std::ops::Range<usize>,
+    ) -> Self {
+        let start = span.start.min(contents.len());
+        let line_start = contents[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = contents[start..]
+            .find('\n')
+            .map_or(contents.len(), |i| start + i);
+        let line = contents[..start].matches('\n').count() + 1;
+        let column = contents[line_start..start].chars().count() + 1;
+
+        let source_line = contents[line_start..line_end].trim_end_matches('\r');
+        let width = contents[start..span.end.clamp(start, line_end)]
+            .chars()
+            .count()
+            .max(1);
+        let gutter = line.to_string();
+        let pad = " ".repeat(gutter.len());
+        let snippet = format!(
+            "{pad}--> {path}:{line}:{column}\n\
+             {pad} |\n\
+             {gutter} | {source_line}\n\
+             {pad} | {indent}{underline}\n\
+             {pad} = hint: run `dotman schema` to see the expected format",
+            path = path.display(),
+            indent = " ".repeat(column - 1),
+            underline = "^".repeat(width),
+        );
+
+        ConfigFileParseError {
+            path: path.to_path_buf(),
+            message: message.into(),
+            line,
+            column,
+            snippet,
+        }
+    }
+
+    pub fn from_toml(path: &std::path::Path, contents: &str, err: &toml::de::Error) -> Self {
+        Self::new(path, contents, err.message(), err.span().unwrap_or(0..0))
+    }
+
+    pub fn from_yaml(path: &std::path::Path, contents: &str, err: &serde_yaml::Error) -> Self {
+        let start = err.location().map_or(0, |location| location.index());
+        // serde_yaml appends the location to its message; it is shown separately.
+        let message = err.to_string();
+        let message = message
+            .split(" at line ")
+            .next()
+            .unwrap_or(&message)
+            .to_string();
+        Self::new(path, contents, message, start..start)
+    }
+
+    pub fn from_json(path: &std::path::Path, contents: &str, err: &serde_json::Error) -> Self {
+        let line_start: usize = contents
+            .split_inclusive('\n')
+            .take(err.line().saturating_sub(1))
+            .map(str::len)
+            .sum();
+        let start = (line_start + err.column().saturating_sub(1)).min(contents.len());
+        let message = err.to_string();
+        let message = message
+            .split(" at line ")
+            .next()
+            .unwrap_or(&message)
+            .to_string();
+        Self::new(path, contents, message, start..start)
     }
 }
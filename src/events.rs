@@ -0,0 +1,77 @@
+use crate::{config::LinkMode, state::TargetState};
+
+/// Something that happened during an install, for embedding applications
+/// that show their own progress. Register a callback with
+/// [`crate::Dotman::with_events`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A target was linked, decrypted or rendered.
+    LinkCreated {
+        source: std::path::PathBuf,
+        target: std::path::PathBuf,
+        mode: LinkMode,
+    },
+    /// An existing target was moved aside before being replaced.
+    TargetBackedUp {
+        target: std::path::PathBuf,
+        backup: std::path::PathBuf,
+        /// What dotman had recorded for the target, if it wrote it.
+        previous: Option<TargetState>,
+    },
+    /// A secret dotman had decrypted was decrypted again. Its old plaintext
+    /// is not kept.
+    SecretRewritten {
+        target: std::path::PathBuf,
+        previous: TargetState,
+    },
+    /// A target was removed by `remove`, `undo`, `prune` or pruning
+    /// orphans.
+    LinkRemoved {
+        target: std::path::PathBuf,
+    },
+    LinkSkipped {
+        target: std::path::PathBuf,
+        reason: SkipReason,
+    },
+    ActionStarted {
+        name: String,
+    },
+    ActionFinished {
+        name: String,
+        success: bool,
+    },
+}
+
+/// Callback registered with [`crate::Dotman::with_events`].
+pub type EventHandler = Box<dyn Fn(&Event) + Send + Sync>;
+
+/// Why a link was not installed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    ConditionNotMet,
+    SourceMissing,
+    /// The target is already a symlink to the source.
+    AlreadyLinked,
+    /// The target exists and overwriting was not asked for.
+    TargetExists,
+    /// The target was modified since dotman wrote it.
+    TargetModified,
+    /// The user answered no when asked.
+    Declined,
+    /// Nothing is changed in a dry run.
+    DryRun,
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SkipReason::ConditionNotMet => "condition not met",
+            SkipReason::SourceMissing => "source missing",
+            SkipReason::AlreadyLinked => "already linked",
+            SkipReason::TargetExists => "target exists",
+            SkipReason::TargetModified => "target modified",
+            SkipReason::Declined => "declined",
+            SkipReason::DryRun => "dry run",
+        })
+    }
+}
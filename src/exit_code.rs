@@ -0,0 +1,64 @@
+use dotman::error::{ConfigFileParseError, DotmanError};
+
+/// What the process exits with, so scripts can tell failures apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[repr(i32)]
+pub enum ExitCode {
+    #[error("failed")]
+    Failure = 1,
+    #[error("invalid configuration")]
+    Config = 3,
+    #[error("targets are in the way")]
+    Conflict = 4,
+    #[error("an action failed")]
+    ActionFailed = 5,
+    #[error("targets have drifted")]
+    Drift = 6,
+    #[error("every link was skipped by its conditions")]
+    AllSkipped = 7,
+}
+
+impl ExitCode {
+    /// The code for `err`: one attached as context, otherwise one derived
+    /// from the underlying error.
+    pub fn of(err: &anyhow::Error) -> Self {
+        if let Some(code) = err.downcast_ref::<ExitCode>() {
+            return *code;
+        }
+        for cause in err.chain() {
+            if cause.is::<ConfigFileParseError>() {
+                return ExitCode::Config;
+            }
+            if let Some(err) = cause.downcast_ref::<DotmanError>()
+                && err.errors().iter().any(|err| {
+                    matches!(
+                        err,
+                        DotmanError::CommandError { .. } | DotmanError::Action { .. }
+                    )
+                })
+            {
+                return ExitCode::ActionFailed;
+            }
+        }
+        ExitCode::Failure
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_code_of() {
+        let err = anyhow::Error::from(DotmanError::CommandError {
+            command: "brew".to_string(),
+            message: String::new(),
+        });
+        assert_eq!(ExitCode::of(&err), ExitCode::ActionFailed);
+        assert_eq!(ExitCode::of(&err.context(ExitCode::Drift)), ExitCode::Drift);
+        assert_eq!(
+            ExitCode::of(&anyhow::anyhow!("Repository has uncommitted changes")),
+            ExitCode::Failure
+        );
+    }
+}
@@ -0,0 +1,357 @@
+//! Unpacking archives for `type = "extract"` actions.
+
+use colored::Colorize;
+
+use crate::{Dotman, download, error::DotmanError, hash};
+
+/// Archive formats that can be extracted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    TarGz,
+    Zip,
+}
+
+impl ArchiveKind {
+    /// Detects the format from the file name at the end of `source`.
+    pub fn detect(source: &str) -> Option<ArchiveKind> {
+        let name = source.split(['?', '#']).next().unwrap_or(source);
+        let name = name.to_ascii_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveKind::TarGz)
+        } else if name.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns `path` without its first `strip` components, or `None` if
+/// nothing is left. Entries that would land outside the destination, through
+/// `..` or an absolute path, are rejected.
+fn strip_path(path: &std::path::Path, strip: usize) -> Result<Option<std::path::PathBuf>, String> {
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::Normal(part) => components.push(part),
+            std::path::Component::CurDir => {}
+            _ => return Err(format!("unsafe path in archive: {}", path.display())),
+        }
+    }
+    let stripped: std::path::PathBuf = components.into_iter().skip(strip).collect();
+    Ok((!stripped.as_os_str().is_empty()).then_some(stripped))
+}
+
+/// Whether a symlink to `link` at `path`, relative to the destination,
+/// stays inside it. Only relative links qualify, and their `..` may not
+/// climb above the destination.
+fn link_stays_inside(path: &std::path::Path, link: &std::path::Path) -> bool {
+    let mut depth = path.components().count().saturating_sub(1);
+    for component in link.components() {
+        match component {
+            std::path::Component::Normal(_) => depth += 1,
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir if depth > 0 => depth -= 1,
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Makes sure writing `path` cannot end up outside `dest`, which must be
+/// canonical: its nearest existing ancestor has to resolve inside `dest`,
+/// so an earlier symlink entry cannot redirect later ones. A symlink at
+/// `path` itself is removed so that writing does not follow it.
+fn ensure_inside(dest: &std::path::Path, path: &std::path::Path) -> Result<(), DotmanError> {
+    let escapes = || {
+        DotmanError::IoError(std::io::Error::other(format!(
+            "unsafe path in archive: {} leads outside {}",
+            path.display(),
+            dest.display()
+        )))
+    };
+    let existing = path
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| ancestor.symlink_metadata().is_ok())
+        .ok_or_else(escapes)?;
+    let resolved = existing.canonicalize().map_err(|_| escapes())?;
+    if !resolved.starts_with(dest) {
+        return Err(escapes());
+    }
+    if path
+        .symlink_metadata()
+        .is_ok_and(|metadata| metadata.is_symlink())
+    {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Unpacks `archive` into `dest`, dropping the first `strip` components of
+/// every entry. Links that point outside `dest`, and entries that would be
+/// written through such links, are rejected.
+pub fn unpack(
+    kind: ArchiveKind,
+    archive: &[u8],
+    dest: &std::path::Path,
+    strip: usize,
+) -> Result<(), DotmanError> {
+    let invalid = |message: String| DotmanError::IoError(std::io::Error::other(message));
+    std::fs::create_dir_all(dest)?;
+    let dest = &dest.canonicalize()?;
+    match kind {
+        ArchiveKind::TarGz => {
+            let decoder = flate2::read::GzDecoder::new(archive);
+            let mut archive = tar::Archive::new(decoder);
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                let Some(relative) = strip_path(&entry.path()?, strip).map_err(invalid)? else {
+                    continue;
+                };
+                let path = dest.join(&relative);
+                ensure_inside(dest, &path)?;
+                let entry_type = entry.header().entry_type();
+                if entry_type.is_symlink() || entry_type.is_hard_link() {
+                    let link = entry.link_name()?.unwrap_or_default().into_owned();
+                    let unsafe_link = || {
+                        invalid(format!(
+                            "unsafe link in archive: {} -> {}",
+                            relative.display(),
+                            link.display()
+                        ))
+                    };
+                    if entry_type.is_symlink() {
+                        if !link_stays_inside(&relative, &link) {
+                            return Err(unsafe_link());
+                        }
+                    } else {
+                        // Hard links name their original by its path in the
+                        // archive, which is stripped like any other.
+                        let original = strip_path(&link, strip)
+                            .ok()
+                            .flatten()
+                            .map(|original| dest.join(original))
+                            .and_then(|original| original.canonicalize().ok())
+                            .filter(|original| original.starts_with(dest))
+                            .ok_or_else(unsafe_link)?;
+                        if let Some(parent) = path.parent() {
+                            std::fs::create_dir_all(parent)?;
+                        }
+                        if path.symlink_metadata().is_ok() {
+                            std::fs::remove_file(&path)?;
+                        }
+                        std::fs::hard_link(original, &path)?;
+                        continue;
+                    }
+                }
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                entry.unpack(&path)?;
+            }
+        }
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(std::io::Cursor::new(archive))
+                .map_err(|err| invalid(err.to_string()))?;
+            for index in 0..archive.len() {
+                let mut file = archive
+                    .by_index(index)
+                    .map_err(|err| invalid(err.to_string()))?;
+                let name = std::path::PathBuf::from(file.name());
+                let Some(path) = strip_path(&name, strip).map_err(invalid)? else {
+                    continue;
+                };
+                let path = dest.join(path);
+                ensure_inside(dest, &path)?;
+                if file.is_dir() {
+                    std::fs::create_dir_all(&path)?;
+                    continue;
+                }
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::io::copy(&mut file, &mut std::fs::File::create(&path)?)?;
+                if let Some(mode) = file.unix_mode() {
+                    crate::utils::set_mode(&path, mode & 0o7777)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Dotman {
+    /// Reads the archive at `source`, downloading it if it is a URL, and
+    /// checks it against `sha256` when one is given.
+    fn read_archive(&self, source: &str, sha256: Option<&str>) -> Result<Vec<u8>, DotmanError> {
+        if crate::remote::is_url(std::path::Path::new(source)) {
+            return match sha256 {
+                Some(sha256) => download::fetch_verified(source, sha256, &download::cache_dir()?),
+                None => download::fetch(source),
+            };
+        }
+        let contents = std::fs::read(self.config.resolve_path(source)?)?;
+        if let Some(sha256) = sha256 {
+            download::verify(source, &contents, sha256)?;
+        }
+        Ok(contents)
+    }
+
+    /// Unpacks the archive at `source` into `dest`.
+    pub(crate) fn extract(
+        &self,
+        source: &str,
+        dest: &str,
+        sha256: Option<&str>,
+        strip_components: usize,
+    ) -> Result<(), DotmanError> {
+        let kind = ArchiveKind::detect(source).ok_or_else(|| {
+            DotmanError::InvalidConfig(format!(
+                "Cannot tell the format of {source}, expected .tar.gz, .tgz or .zip"
+            ))
+        })?;
+        let dest = self.config.resolve_path(dest)?;
+        let archive = self.read_archive(source, sha256)?;
+        unpack(kind, &archive, &dest, strip_components)?;
+        println!(
+            "{} {} -> {}",
+            "Extracted:".green().bold(),
+            source,
+            dest.display()
+        );
+        Ok(())
+    }
+
+    /// Digest of a local archive, so changing it extracts it again. Archives
+    /// behind a URL are identified by the URL alone.
+    pub(crate) fn archive_digest(&self, source: &str) -> Option<String> {
+        if crate::remote::is_url(std::path::Path::new(source)) {
+            return None;
+        }
+        let path = self.config.resolve_path(source).ok()?;
+        std::fs::read(path)
+            .ok()
+            .map(|contents| hash::sha256(&contents))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn tar_gz(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *contents).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (path, contents) in files {
+            writer
+                .start_file(*path, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            ArchiveKind::detect("https://example.com/theme.tar.gz?raw=1"),
+            Some(ArchiveKind::TarGz)
+        );
+        assert_eq!(ArchiveKind::detect("nvim.TGZ"), Some(ArchiveKind::TarGz));
+        assert_eq!(ArchiveKind::detect("plugins.zip"), Some(ArchiveKind::Zip));
+        assert_eq!(ArchiveKind::detect("tool.tar.xz"), None);
+    }
+
+    #[test]
+    fn test_unpack_strips_components() {
+        let files: &[(&str, &[u8])] = &[
+            ("nvim-linux64/bin/nvim", b"binary"),
+            ("nvim-linux64/share/man", b"manual"),
+            ("README", b"skipped"),
+        ];
+        for (kind, archive) in [
+            (ArchiveKind::TarGz, tar_gz(files)),
+            (ArchiveKind::Zip, zip(files)),
+        ] {
+            let temp_dir = TempDir::new().unwrap();
+            unpack(kind, &archive, temp_dir.path(), 1).unwrap();
+
+            assert_eq!(
+                std::fs::read(temp_dir.path().join("bin/nvim")).unwrap(),
+                b"binary"
+            );
+            assert!(temp_dir.path().join("share/man").exists());
+            assert!(!temp_dir.path().join("README").exists());
+        }
+    }
+
+    #[test]
+    fn test_unpack_rejects_writing_through_links() {
+        let temp_dir = TempDir::new().unwrap();
+        let outside = temp_dir.path().join("outside");
+        std::fs::create_dir_all(&outside).unwrap();
+        let dest = temp_dir.path().join("dest");
+
+        let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        builder.append_link(&mut header, "a", &outside).unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(3);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "a/authorized_keys", &b"key"[..])
+            .unwrap();
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+
+        assert!(unpack(ArchiveKind::TarGz, &archive, &dest, 0).is_err());
+        assert!(!outside.join("authorized_keys").exists());
+
+        // A link left by an earlier extraction is not written through either.
+        std::fs::remove_dir_all(&dest).unwrap();
+        std::fs::create_dir_all(&dest).unwrap();
+        crate::utils::symlink(&outside, &dest.join("a"), false).unwrap();
+        let archive = zip(&[("a/authorized_keys", b"key")]);
+        assert!(unpack(ArchiveKind::Zip, &archive, &dest, 0).is_err());
+        assert!(!outside.join("authorized_keys").exists());
+    }
+
+    #[test]
+    fn test_link_stays_inside() {
+        let path = std::path::Path::new("share/theme");
+        assert!(link_stays_inside(path, std::path::Path::new("../bin/tool")));
+        assert!(!link_stays_inside(path, std::path::Path::new("../../..")));
+        assert!(!link_stays_inside(
+            path,
+            std::path::Path::new("/etc/passwd")
+        ));
+    }
+
+    #[test]
+    fn test_unpack_rejects_escaping_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive = zip(&[("../evil", b"")]);
+
+        assert!(unpack(ArchiveKind::Zip, &archive, &temp_dir.path().join("dest"), 0).is_err());
+        assert!(!temp_dir.path().join("evil").exists());
+    }
+}
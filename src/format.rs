@@ -0,0 +1,261 @@
+use toml_edit::{DocumentMut, Item, Table};
+
+/// Key order for top-level values; unlisted keys keep their relative order
+/// after these.
+const ROOT_ORDER: &[&str] = &["version", "overwrite", "aur_helper", "profiles"];
+
+/// Key order within a `[[links]]` entry.
+const LINK_ORDER: &[&str] = &[
+    "source",
+    "target",
+    "mode",
+    "fragments",
+    "chmod",
+    "owner",
+    "group",
+    "profiles",
+    "tags",
+    "if",
+    "if-not",
+];
+
+/// Key order within an `[[actions]]` entry.
+const ACTION_ORDER: &[&str] = &[
+    "type",
+    "name",
+    "run",
+    "unit",
+    "url",
+    "source",
+    "dest",
+    "target",
+    "sha256",
+    "mode",
+    "strip_components",
+    "branch",
+    "rev",
+    "depth",
+    "path",
+    "hive",
+    "key",
+    "value",
+    "value_type",
+    "data",
+    "line",
+    "block",
+    "comment",
+    "profiles",
+    "if",
+    "if-not",
+];
+
+/// Key order within the `[repo]` table.
+const REPO_ORDER: &[&str] = &["url", "branch", "path", "auto_commit"];
+
+/// Order of top-level tables and arrays of tables.
+const TABLE_ORDER: &[&str] = &[
+    "repo",
+    "repos",
+    "secrets",
+    "integrity",
+    "logging",
+    "variables",
+    "lints",
+    "packages",
+    "dirs",
+    "ssh",
+    "gitconfig",
+    "env",
+    "aliases",
+    "links",
+    "defaults",
+    "gsettings",
+    "actions",
+];
+
+/// Rewrites a configuration file into its canonical layout: top-level values
+/// first, then tables, then `[[links]]` and `[[actions]]` in their declared
+/// order, each with a consistent key order and spacing. Comments are kept.
+pub fn format_config(input: &str) -> Result<String, toml_edit::TomlError> {
+    let mut doc: DocumentMut = input.parse()?;
+    let root = doc.as_table_mut();
+
+    sort_keys(root, ROOT_ORDER);
+    normalize_values(root);
+
+    let mut table_names: Vec<String> = root
+        .iter()
+        .filter(|(_, item)| item.is_table() || item.is_array_of_tables())
+        .map(|(key, _)| key.to_string())
+        .collect();
+    table_names.sort_by_key(|name| rank(name, TABLE_ORDER));
+
+    let mut position = 1;
+    let mut first_header = !root.iter().any(|(_, item)| item.is_value());
+    for name in table_names {
+        let order = match name.as_str() {
+            "links" => LINK_ORDER,
+            "actions" => ACTION_ORDER,
+            "repo" | "repos" => REPO_ORDER,
+            _ => &[],
+        };
+        let tables: Vec<&mut Table> = match root.get_mut(&name) {
+            // `[repos.name]` headers: lay out each named table.
+            Some(Item::Table(table)) if table.is_implicit() => table
+                .iter_mut()
+                .filter_map(|(_, item)| item.as_table_mut())
+                .collect(),
+            Some(Item::Table(table)) => vec![table],
+            Some(Item::ArrayOfTables(array)) => array.iter_mut().collect(),
+            _ => vec![],
+        };
+
+        for table in tables {
+            table.set_position(position);
+            position += 1;
+            sort_keys(table, order);
+            normalize_values(table);
+            separate_header(table, first_header);
+            first_header = false;
+        }
+    }
+
+    let mut output = doc.to_string();
+    output.truncate(output.trim_end().len());
+    output.push('\n');
+    Ok(output)
+}
+
+fn rank(key: &str, order: &[&str]) -> usize {
+    order
+        .iter()
+        .position(|known| *known == key)
+        .unwrap_or(order.len())
+}
+
+fn sort_keys(table: &mut Table, order: &[&str]) {
+    table.sort_values_by(|a, _, b, _| rank(a.get(), order).cmp(&rank(b.get(), order)));
+}
+
+/// Normalizes `key = value` spacing and drops blank lines between keys, while
+/// keeping any comments attached to them.
+fn normalize_values(table: &mut Table) {
+    for (mut key, item) in table.iter_mut() {
+        let Some(value) = item.as_value_mut() else {
+            continue;
+        };
+
+        let prefix = comments(key.leaf_decor().prefix());
+        key.leaf_decor_mut().set_prefix(prefix);
+        key.leaf_decor_mut().set_suffix(" ");
+
+        let suffix = value
+            .decor()
+            .suffix()
+            .and_then(|raw| raw.as_str())
+            .filter(|suffix| suffix.contains('#'))
+            .map(|suffix| format!(" {}", suffix.trim()))
+            .unwrap_or_default();
+        value.decor_mut().set_prefix(" ");
+        value.decor_mut().set_suffix(suffix);
+    }
+}
+
+/// Puts exactly one blank line before a table header, keeping comments.
+fn separate_header(table: &mut Table, first: bool) {
+    let comments = comments(table.decor().prefix());
+    let blank = if first { "" } else { "\n" };
+    table.decor_mut().set_prefix(format!("{blank}{comments}"));
+}
+
+/// Extracts the comment lines from a decor prefix, dropping blank lines and
+/// indentation.
+fn comments(raw: Option<&toml_edit::RawString>) -> String {
+    raw.and_then(|raw| raw.as_str())
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with('#'))
+        .map(|line| format!("{line}\n"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_reorders_keys_and_tables() {
+        let input = r#"overwrite = true
+[[actions]]
+name = "hello"
+type = "shell-command"
+run = "echo hi"
+
+
+[[links]]
+target   =   "~/.zshrc"
+
+source = "zshrc"
+"#;
+        let expected = r#"overwrite = true
+
+[[links]]
+source = "zshrc"
+target = "~/.zshrc"
+
+[[actions]]
+type = "shell-command"
+name = "hello"
+run = "echo hi"
+"#;
+        assert_eq!(format_config(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_keeps_comments() {
+        let input = r#"# Always symlink
+[[links]]
+  # where it goes
+  target = "~/.config/git"
+source = "git" # the repo copy
+"#;
+        let expected = r#"# Always symlink
+[[links]]
+source = "git" # the repo copy
+# where it goes
+target = "~/.config/git"
+"#;
+
+        assert_eq!(format_config(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_orders_named_repositories() {
+        let input = r#"[[links]]
+source = "work:zshrc"
+target = "~/.zshrc"
+
+[repos.work]
+path = "~/work"
+url = "git@example.com:work.git"
+"#;
+        let expected = r#"[repos.work]
+url = "git@example.com:work.git"
+path = "~/work"
+
+[[links]]
+source = "work:zshrc"
+target = "~/.zshrc"
+"#;
+
+        assert_eq!(format_config(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_is_idempotent() {
+        let input = std::fs::read_to_string("tests/config/working.toml").unwrap();
+        let once = format_config(&input).unwrap();
+        assert_eq!(format_config(&once).unwrap(), once);
+    }
+}
@@ -0,0 +1,537 @@
+/// What is at a path, without following symlinks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+/// The filesystem operations dotman performs on sources and targets.
+///
+/// Installs go through this trait, so they can run against something other
+/// than the real filesystem, such as [`MemoryFilesystem`] in tests.
+pub trait Filesystem: Send + Sync + std::fmt::Debug {
+    /// Returns what is at `path`, or `None` if nothing is.
+    fn kind(&self, path: &std::path::Path) -> Option<EntryKind>;
+
+    /// Whether `path` exists, following symlinks.
+    fn exists(&self, path: &std::path::Path) -> bool;
+
+    fn read_link(&self, path: &std::path::Path) -> std::io::Result<std::path::PathBuf>;
+
+    fn symlink(&self, source: &std::path::Path, target: &std::path::Path) -> std::io::Result<()>;
+
+    /// Links the directory `target` to `source` with a junction on Windows,
+    /// and a symlink elsewhere.
+    fn junction(&self, source: &std::path::Path, target: &std::path::Path) -> std::io::Result<()>;
+
+    /// Writes a file. `private` files are readable only by the owner.
+    fn write(&self, path: &std::path::Path, contents: &[u8], private: bool) -> std::io::Result<()>;
+
+    /// Copies a file or directory. Symlinks inside directories are recreated
+    /// rather than followed.
+    fn copy(&self, source: &std::path::Path, target: &std::path::Path) -> std::io::Result<()>;
+
+    fn create_dir_all(&self, path: &std::path::Path) -> std::io::Result<()>;
+
+    /// Removes a file, symlink or directory with its contents.
+    fn remove(&self, path: &std::path::Path) -> std::io::Result<()>;
+
+    /// Moves a file, symlink or directory, creating the parent directories
+    /// of `to`.
+    fn rename(&self, from: &std::path::Path, to: &std::path::Path) -> std::io::Result<()>;
+
+    /// Whether the current user may create entries in the directory `dir`.
+    fn is_writable(&self, dir: &std::path::Path) -> bool;
+
+    /// Returns the permission bits of `path`, following symlinks, or `None`
+    /// where the platform has none.
+    fn mode(&self, path: &std::path::Path) -> Option<u32>;
+
+    /// Sets the permission bits of `path`, following symlinks.
+    fn set_mode(&self, path: &std::path::Path, mode: u32) -> std::io::Result<()>;
+
+    /// Returns the user and group IDs owning `path`, following symlinks, or
+    /// `None` where the platform has none.
+    fn owner(&self, path: &std::path::Path) -> Option<(u32, u32)>;
+
+    /// Changes the user and/or group owning `path`, following symlinks.
+    fn set_owner(
+        &self,
+        path: &std::path::Path,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    ) -> std::io::Result<()>;
+}
+
+/// The machine's own filesystem.
+#[derive(Debug, Default)]
+pub struct RealFilesystem;
+
+impl Filesystem for RealFilesystem {
+    fn kind(&self, path: &std::path::Path) -> Option<EntryKind> {
+        let metadata = std::fs::symlink_metadata(path).ok()?;
+        Some(if metadata.is_symlink() {
+            EntryKind::Symlink
+        } else if metadata.is_dir() {
+            EntryKind::Dir
+        } else {
+            EntryKind::File
+        })
+    }
+
+    fn exists(&self, path: &std::path::Path) -> bool {
+        path.exists()
+    }
+
+    fn read_link(&self, path: &std::path::Path) -> std::io::Result<std::path::PathBuf> {
+        std::fs::read_link(path)
+    }
+
+    fn symlink(&self, source: &std::path::Path, target: &std::path::Path) -> std::io::Result<()> {
+        crate::utils::symlink(source, target, false)
+    }
+
+    fn junction(&self, source: &std::path::Path, target: &std::path::Path) -> std::io::Result<()> {
+        crate::utils::symlink(source, target, true)
+    }
+
+    fn write(&self, path: &std::path::Path, contents: &[u8], private: bool) -> std::io::Result<()> {
+        if private {
+            crate::secrets::write_plaintext(path, contents)
+        } else {
+            std::fs::write(path, contents)
+        }
+    }
+
+    fn copy(&self, source: &std::path::Path, target: &std::path::Path) -> std::io::Result<()> {
+        crate::utils::copy_recursive(source, target)
+    }
+
+    fn create_dir_all(&self, path: &std::path::Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(path)
+    }
+
+    fn remove(&self, path: &std::path::Path) -> std::io::Result<()> {
+        match self.kind(path) {
+            Some(EntryKind::Dir) => std::fs::remove_dir_all(path),
+            // Directory symlinks and junctions are removed like directories.
+            #[cfg(windows)]
+            Some(EntryKind::Symlink) if path.is_dir() => std::fs::remove_dir(path),
+            _ => std::fs::remove_file(path),
+        }
+    }
+
+    fn rename(&self, from: &std::path::Path, to: &std::path::Path) -> std::io::Result<()> {
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Renaming fails across filesystems, so fall back to copying.
+        if std::fs::rename(from, to).is_err() {
+            self.copy(from, to)?;
+            self.remove(from)?;
+        }
+        Ok(())
+    }
+
+    fn is_writable(&self, dir: &std::path::Path) -> bool {
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+
+            let Ok(path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+                return false;
+            };
+            // SAFETY: `path` is a valid NUL-terminated string for the call.
+            unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+        }
+        #[cfg(not(unix))]
+        {
+            std::fs::metadata(dir).is_ok_and(|metadata| !metadata.permissions().readonly())
+        }
+    }
+
+    fn mode(&self, path: &std::path::Path) -> Option<u32> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = std::fs::metadata(path).ok()?;
+            Some(metadata.permissions().mode() & 0o7777)
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = path;
+            None
+        }
+    }
+
+    fn set_mode(&self, path: &std::path::Path, mode: u32) -> std::io::Result<()> {
+        crate::utils::set_mode(path, mode)
+    }
+
+    fn owner(&self, path: &std::path::Path) -> Option<(u32, u32)> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let metadata = std::fs::metadata(path).ok()?;
+            Some((metadata.uid(), metadata.gid()))
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = path;
+            None
+        }
+    }
+
+    fn set_owner(
+        &self,
+        path: &std::path::Path,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    ) -> std::io::Result<()> {
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::chown(path, uid, gid)
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = (path, uid, gid);
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Entry {
+    File(Vec<u8>),
+    Dir,
+    Symlink(std::path::PathBuf),
+}
+
+impl<F: Filesystem + ?Sized> Filesystem for std::sync::Arc<F> {
+    fn kind(&self, path: &std::path::Path) -> Option<EntryKind> {
+        (**self).kind(path)
+    }
+
+    fn exists(&self, path: &std::path::Path) -> bool {
+        (**self).exists(path)
+    }
+
+    fn read_link(&self, path: &std::path::Path) -> std::io::Result<std::path::PathBuf> {
+        (**self).read_link(path)
+    }
+
+    fn symlink(&self, source: &std::path::Path, target: &std::path::Path) -> std::io::Result<()> {
+        (**self).symlink(source, target)
+    }
+
+    fn junction(&self, source: &std::path::Path, target: &std::path::Path) -> std::io::Result<()> {
+        (**self).junction(source, target)
+    }
+
+    fn write(&self, path: &std::path::Path, contents: &[u8], private: bool) -> std::io::Result<()> {
+        (**self).write(path, contents, private)
+    }
+
+    fn copy(&self, source: &std::path::Path, target: &std::path::Path) -> std::io::Result<()> {
+        (**self).copy(source, target)
+    }
+
+    fn create_dir_all(&self, path: &std::path::Path) -> std::io::Result<()> {
+        (**self).create_dir_all(path)
+    }
+
+    fn remove(&self, path: &std::path::Path) -> std::io::Result<()> {
+        (**self).remove(path)
+    }
+
+    fn rename(&self, from: &std::path::Path, to: &std::path::Path) -> std::io::Result<()> {
+        (**self).rename(from, to)
+    }
+
+    fn is_writable(&self, dir: &std::path::Path) -> bool {
+        (**self).is_writable(dir)
+    }
+
+    fn mode(&self, path: &std::path::Path) -> Option<u32> {
+        (**self).mode(path)
+    }
+
+    fn set_mode(&self, path: &std::path::Path, mode: u32) -> std::io::Result<()> {
+        (**self).set_mode(path, mode)
+    }
+
+    fn owner(&self, path: &std::path::Path) -> Option<(u32, u32)> {
+        (**self).owner(path)
+    }
+
+    fn set_owner(
+        &self,
+        path: &std::path::Path,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    ) -> std::io::Result<()> {
+        (**self).set_owner(path, uid, gid)
+    }
+}
+
+/// A filesystem kept in memory, for tests that should not touch the disk.
+/// Parent directories are created implicitly.
+#[derive(Debug, Default)]
+pub struct MemoryFilesystem {
+    entries: std::sync::Mutex<std::collections::BTreeMap<std::path::PathBuf, Entry>>,
+    read_only: std::sync::Mutex<std::collections::BTreeSet<std::path::PathBuf>>,
+    modes: std::sync::Mutex<std::collections::BTreeMap<std::path::PathBuf, u32>>,
+    owners: std::sync::Mutex<std::collections::BTreeMap<std::path::PathBuf, (u32, u32)>>,
+}
+
+impl MemoryFilesystem {
+    pub fn new() -> Self {
+        MemoryFilesystem::default()
+    }
+
+    /// Returns the contents of a file, following symlinks.
+    pub fn read(&self, path: &std::path::Path) -> std::io::Result<Vec<u8>> {
+        match self.resolve(path) {
+            Some(Entry::File(contents)) => Ok(contents),
+            Some(_) => Err(std::io::Error::other(format!(
+                "{} is not a file",
+                path.display()
+            ))),
+            None => Err(not_found(path)),
+        }
+    }
+
+    /// Makes `dir` refuse new entries, like a directory without write
+    /// permission.
+    pub fn set_read_only(&self, dir: &std::path::Path) {
+        self.read_only.lock().unwrap().insert(dir.to_path_buf());
+    }
+
+    /// Follows symlinks, giving up on cycles.
+    fn resolve(&self, path: &std::path::Path) -> Option<Entry> {
+        self.resolve_path(path).map(|(_, entry)| entry)
+    }
+
+    /// Follows symlinks and returns where they lead along with the entry
+    /// there.
+    fn resolve_path(&self, path: &std::path::Path) -> Option<(std::path::PathBuf, Entry)> {
+        let entries = self.entries.lock().unwrap();
+        let mut path = path.to_path_buf();
+        for _ in 0..40 {
+            match entries.get(&path)? {
+                Entry::Symlink(destination) => {
+                    path = match path.parent() {
+                        Some(parent) => parent.join(destination),
+                        None => destination.clone(),
+                    };
+                }
+                entry => return Some((path, entry.clone())),
+            }
+        }
+        None
+    }
+
+    fn insert(&self, path: &std::path::Path, entry: Entry) -> std::io::Result<()> {
+        if let Some(parent) = path.parent()
+            && !self.is_writable(parent)
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("{} is read-only", parent.display()),
+            ));
+        }
+        let mut entries = self.entries.lock().unwrap();
+        // Like the real thing, only files can be written over.
+        let conflict = !matches!(
+            (entries.get(path), &entry),
+            (None, _) | (Some(Entry::File(_)), Entry::File(_))
+        );
+        if conflict {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            ));
+        }
+        for ancestor in path.ancestors().skip(1) {
+            if ancestor.as_os_str().is_empty() {
+                break;
+            }
+            entries.entry(ancestor.to_path_buf()).or_insert(Entry::Dir);
+        }
+        entries.insert(path.to_path_buf(), entry);
+        Ok(())
+    }
+}
+
+fn not_found(path: &std::path::Path) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("{} does not exist", path.display()),
+    )
+}
+
+impl Filesystem for MemoryFilesystem {
+    fn kind(&self, path: &std::path::Path) -> Option<EntryKind> {
+        match self.entries.lock().unwrap().get(path)? {
+            Entry::File(_) => Some(EntryKind::File),
+            Entry::Dir => Some(EntryKind::Dir),
+            Entry::Symlink(_) => Some(EntryKind::Symlink),
+        }
+    }
+
+    fn exists(&self, path: &std::path::Path) -> bool {
+        self.resolve(path).is_some()
+    }
+
+    fn read_link(&self, path: &std::path::Path) -> std::io::Result<std::path::PathBuf> {
+        match self.entries.lock().unwrap().get(path) {
+            Some(Entry::Symlink(destination)) => Ok(destination.clone()),
+            Some(_) => Err(std::io::Error::other(format!(
+                "{} is not a symlink",
+                path.display()
+            ))),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn symlink(&self, source: &std::path::Path, target: &std::path::Path) -> std::io::Result<()> {
+        self.insert(target, Entry::Symlink(source.to_path_buf()))
+    }
+
+    fn junction(&self, source: &std::path::Path, target: &std::path::Path) -> std::io::Result<()> {
+        self.symlink(source, target)
+    }
+
+    fn write(
+        &self,
+        path: &std::path::Path,
+        contents: &[u8],
+        _private: bool,
+    ) -> std::io::Result<()> {
+        self.insert(path, Entry::File(contents.to_vec()))
+    }
+
+    fn copy(&self, source: &std::path::Path, target: &std::path::Path) -> std::io::Result<()> {
+        let copied: Vec<_> = self
+            .entries
+            .lock()
+            .unwrap()
+            .range(source.to_path_buf()..)
+            .take_while(|(path, _)| path.starts_with(source))
+            .map(|(path, entry)| {
+                let relative = path.strip_prefix(source).unwrap_or(path);
+                (target.join(relative), entry.clone())
+            })
+            .collect();
+        if copied.is_empty() {
+            return Err(not_found(source));
+        }
+        for (path, entry) in copied {
+            self.insert(&path, entry)?;
+        }
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &std::path::Path) -> std::io::Result<()> {
+        if self.kind(path) == Some(EntryKind::Dir) {
+            return Ok(());
+        }
+        self.insert(path, Entry::Dir)
+    }
+
+    fn remove(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.remove(path).is_none() {
+            return Err(not_found(path));
+        }
+        entries.retain(|entry, _| !entry.starts_with(path));
+        Ok(())
+    }
+
+    /// Like the real thing, replaces a file or symlink at `to`.
+    fn rename(&self, from: &std::path::Path, to: &std::path::Path) -> std::io::Result<()> {
+        let replaced = matches!(
+            self.kind(to),
+            Some(EntryKind::File) | Some(EntryKind::Symlink)
+        );
+        if replaced && self.kind(from).is_some_and(|kind| kind != EntryKind::Dir) {
+            self.remove(to)?;
+        }
+        self.copy(from, to)?;
+        self.remove(from)
+    }
+
+    fn is_writable(&self, dir: &std::path::Path) -> bool {
+        !self.read_only.lock().unwrap().contains(dir)
+    }
+
+    /// Entries start out with 0o755 for directories and 0o644 for files.
+    fn mode(&self, path: &std::path::Path) -> Option<u32> {
+        let (path, entry) = self.resolve_path(path)?;
+        let default = match entry {
+            Entry::Dir => 0o755,
+            _ => 0o644,
+        };
+        let modes = self.modes.lock().unwrap();
+        Some(modes.get(&path).copied().unwrap_or(default))
+    }
+
+    fn set_mode(&self, path: &std::path::Path, mode: u32) -> std::io::Result<()> {
+        let (path, _) = self.resolve_path(path).ok_or_else(|| not_found(path))?;
+        self.modes.lock().unwrap().insert(path, mode);
+        Ok(())
+    }
+
+    /// Entries start out owned by user and group 1000.
+    fn owner(&self, path: &std::path::Path) -> Option<(u32, u32)> {
+        let (path, _) = self.resolve_path(path)?;
+        let owners = self.owners.lock().unwrap();
+        Some(owners.get(&path).copied().unwrap_or((1000, 1000)))
+    }
+
+    fn set_owner(
+        &self,
+        path: &std::path::Path,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    ) -> std::io::Result<()> {
+        let (path, _) = self.resolve_path(path).ok_or_else(|| not_found(path))?;
+        let mut owners = self.owners.lock().unwrap();
+        let (current_uid, current_gid) = owners.get(&path).copied().unwrap_or((1000, 1000));
+        owners.insert(
+            path,
+            (uid.unwrap_or(current_uid), gid.unwrap_or(current_gid)),
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_filesystem() {
+        let fs = MemoryFilesystem::new();
+        let source = std::path::Path::new("/dotfiles/nvim/init.lua");
+        let target = std::path::Path::new("/home/me/.config/nvim");
+
+        fs.write(source, b"require('plugins')", false).unwrap();
+        fs.symlink(std::path::Path::new("/dotfiles/nvim"), target)
+            .unwrap();
+
+        assert_eq!(fs.kind(target), Some(EntryKind::Symlink));
+        assert_eq!(
+            fs.kind(std::path::Path::new("/home/me")),
+            Some(EntryKind::Dir)
+        );
+        assert!(fs.exists(target));
+        assert!(fs.symlink(source, target).is_err());
+
+        fs.remove(std::path::Path::new("/dotfiles")).unwrap();
+        assert!(!fs.exists(source));
+        assert!(!fs.exists(target));
+        assert_eq!(fs.kind(target), Some(EntryKind::Symlink));
+    }
+}
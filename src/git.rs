@@ -0,0 +1,440 @@
+use colored::Colorize;
+
+use crate::{Dotman, error::DotmanError};
+
+/// Runs `git` with `args` inside `dir` and returns its stdout without the
+/// trailing newline.
+fn git(dir: &std::path::Path, args: &[&str]) -> Result<String, DotmanError> {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(DotmanError::CommandError {
+            command: format!("git {}", args.join(" ")),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .to_string())
+}
+
+/// Fails if a branch or revision from the configuration would be read as an
+/// option by git.
+fn check_ref(kind: &str, name: Option<&str>) -> Result<(), DotmanError> {
+    match name {
+        Some(name) if name.starts_with('-') => Err(DotmanError::InvalidConfig(format!(
+            "Invalid {kind} '{name}': it must not start with '-'"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Returns true if `dir` is the root of a git working tree.
+pub fn is_repo(dir: &std::path::Path) -> bool {
+    dir.join(".git").exists()
+}
+
+/// Clones `url` into `dest`, optionally checking out `branch`.
+pub fn clone(url: &str, dest: &std::path::Path, branch: Option<&str>) -> Result<(), DotmanError> {
+    check_ref("branch", branch)?;
+    let parent = match dest.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => std::path::Path::new("."),
+    };
+    std::fs::create_dir_all(parent)?;
+
+    let dest = dest.to_string_lossy();
+    let mut args = vec!["clone"];
+    if let Some(branch) = branch {
+        args.extend(["--branch", branch]);
+    }
+    args.extend(["--", url, &dest]);
+    git(parent, &args).map(|_| ())
+}
+
+/// Returns the commit checked out in the repository at `dir`.
+pub fn head(dir: &std::path::Path) -> Result<String, DotmanError> {
+    git(dir, &["rev-parse", "HEAD"])
+}
+
+/// Returns the commit `branch`, or the default branch, of the repository at
+/// `url` points to, without cloning it.
+pub fn remote_head(url: &str, branch: Option<&str>) -> Result<String, DotmanError> {
+    check_ref("branch", branch)?;
+    let reference = branch.map_or_else(
+        || "HEAD".to_string(),
+        |branch| format!("refs/heads/{branch}"),
+    );
+    let output = git(
+        std::path::Path::new("."),
+        &["ls-remote", "--", url, &reference],
+    )?;
+    output
+        .split_whitespace()
+        .next()
+        .map(str::to_string)
+        .ok_or_else(|| {
+            DotmanError::Repository(format!("{url} has no branch {}", branch.unwrap_or("HEAD")))
+        })
+}
+
+/// Returns the paths with uncommitted changes in the working tree.
+pub fn dirty_files(dir: &std::path::Path) -> Result<Vec<String>, DotmanError> {
+    let status = git(dir, &["status", "--porcelain"])?;
+    Ok(status
+        .lines()
+        .map(|line| line.get(3..).unwrap_or(line).to_string())
+        .collect())
+}
+
+/// Commits `paths` in the repository at `dir` with `message`. Other staged
+/// changes are left out of the commit.
+pub fn commit(
+    dir: &std::path::Path,
+    paths: &[&std::path::Path],
+    message: &str,
+) -> Result<(), DotmanError> {
+    let paths: Vec<String> = paths
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+
+    git(dir, &[&["add", "--"][..], &paths].concat())?;
+    git(
+        dir,
+        &[&["commit", "--quiet", "-m", message, "--"][..], &paths].concat(),
+    )
+    .map(|_| ())
+}
+
+/// What `pull` did to the repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PullOutcome {
+    Cloned,
+    UpToDate,
+    Updated {
+        from: String,
+        to: String,
+    },
+    /// The working tree has uncommitted changes, so nothing was pulled.
+    Dirty(Vec<String>),
+}
+
+/// Brings the repository at `dir` up to date: clones it from `url` if it does
+/// not exist, otherwise fetches and fast-forwards `branch` (or the current
+/// branch's upstream). A dirty working tree is reported instead of updated.
+pub fn pull(
+    dir: &std::path::Path,
+    url: Option<&str>,
+    branch: Option<&str>,
+) -> Result<PullOutcome, DotmanError> {
+    check_ref("branch", branch)?;
+    if !is_repo(dir) {
+        let Some(url) = url else {
+            return Err(DotmanError::Repository(format!(
+                "{} is not a git repository and no repo url is configured",
+                dir.display()
+            )));
+        };
+        clone(url, dir, branch)?;
+        return Ok(PullOutcome::Cloned);
+    }
+
+    let dirty = dirty_files(dir)?;
+    if !dirty.is_empty() {
+        return Ok(PullOutcome::Dirty(dirty));
+    }
+
+    let upstream = match branch {
+        Some(branch) => {
+            if git(dir, &["rev-parse", "--abbrev-ref", "HEAD"])? != branch {
+                git(dir, &["checkout", "--quiet", branch])?;
+            }
+            git(dir, &["fetch", "--quiet", "origin", branch])?;
+            format!("origin/{branch}")
+        }
+        None => {
+            git(dir, &["fetch", "--quiet"])?;
+            "@{u}".to_string()
+        }
+    };
+
+    let from = git(dir, &["rev-parse", "--short", "HEAD"])?;
+    git(dir, &["merge", "--ff-only", "--quiet", &upstream])?;
+    let to = git(dir, &["rev-parse", "--short", "HEAD"])?;
+
+    Ok(if from == to {
+        PullOutcome::UpToDate
+    } else {
+        PullOutcome::Updated { from, to }
+    })
+}
+
+/// Brings a checkout managed by a `type = "git"` action up to date: clones
+/// `url` into `dir` if it is missing, otherwise fetches and fast-forwards
+/// it. With `rev`, that commit or tag is checked out instead of following a
+/// branch. `depth` makes the clone, and fetching a `rev` it lacks, shallow.
+pub fn checkout(
+    dir: &std::path::Path,
+    url: &str,
+    branch: Option<&str>,
+    rev: Option<&str>,
+    depth: Option<u32>,
+) -> Result<PullOutcome, DotmanError> {
+    check_ref("branch", branch)?;
+    check_ref("rev", rev)?;
+    let depth = depth.map(|depth| format!("--depth={depth}"));
+    if !is_repo(dir) {
+        let parent = dir.parent().unwrap_or(std::path::Path::new("."));
+        std::fs::create_dir_all(parent)?;
+        let dest = dir.to_string_lossy();
+        let mut args = vec!["clone", "--quiet"];
+        args.extend(depth.as_deref());
+        if let Some(branch) = branch {
+            args.extend(["--branch", branch]);
+        }
+        args.extend(["--", url, &dest]);
+        git(parent, &args)?;
+        if let Some(rev) = rev {
+            checkout_rev(dir, rev, depth.as_deref())?;
+        }
+        return Ok(PullOutcome::Cloned);
+    }
+
+    let dirty = dirty_files(dir)?;
+    if !dirty.is_empty() {
+        return Ok(PullOutcome::Dirty(dirty));
+    }
+
+    let from = git(dir, &["rev-parse", "--short", "HEAD"])?;
+    match rev {
+        Some(rev) => checkout_rev(dir, rev, depth.as_deref())?,
+        None => {
+            let mut args = vec!["fetch", "--quiet", "origin"];
+            args.extend(branch);
+            git(dir, &args)?;
+            let upstream = match branch {
+                Some(branch) => {
+                    if git(dir, &["rev-parse", "--abbrev-ref", "HEAD"])? != branch {
+                        git(dir, &["checkout", "--quiet", branch])?;
+                    }
+                    format!("origin/{branch}")
+                }
+                None => "@{u}".to_string(),
+            };
+            git(dir, &["merge", "--ff-only", "--quiet", &upstream])?;
+        }
+    }
+    let to = git(dir, &["rev-parse", "--short", "HEAD"])?;
+
+    Ok(if from == to {
+        PullOutcome::UpToDate
+    } else {
+        PullOutcome::Updated { from, to }
+    })
+}
+
+/// Checks out `rev` with a detached HEAD, fetching it first if the
+/// repository does not have it yet.
+fn checkout_rev(dir: &std::path::Path, rev: &str, depth: Option<&str>) -> Result<(), DotmanError> {
+    let commit = format!("{rev}^{{commit}}");
+    if git(dir, &["rev-parse", "--verify", "--quiet", &commit]).is_err() {
+        let mut args = vec!["fetch", "--quiet"];
+        args.extend(depth);
+        args.extend(["origin", rev]);
+        git(dir, &args)?;
+        return git(dir, &["checkout", "--quiet", "--detach", "FETCH_HEAD"]).map(|_| ());
+    }
+    git(dir, &["checkout", "--quiet", "--detach", rev]).map(|_| ())
+}
+
+impl Dotman {
+    /// Clones or fast-forwards the checkout of a `type = "git"` action. A
+    /// checkout with uncommitted changes is left alone and fails the action.
+    pub(crate) fn clone_or_update(
+        &self,
+        url: &str,
+        dest: &str,
+        branch: Option<&str>,
+        rev: Option<&str>,
+        depth: Option<u32>,
+    ) -> Result<(), DotmanError> {
+        let dir = self.config.resolve_path(dest)?;
+        match checkout(&dir, url, branch, rev, depth)? {
+            PullOutcome::Cloned => {
+                println!("{} {} -> {}", "Cloned:".green().bold(), url, dir.display());
+            }
+            PullOutcome::UpToDate => {
+                println!(
+                    "{} {} is up to date.",
+                    "Pulled:".green().bold(),
+                    dir.display()
+                );
+            }
+            PullOutcome::Updated { from, to } => {
+                println!(
+                    "{} {} {}..{}",
+                    "Pulled:".green().bold(),
+                    dir.display(),
+                    from,
+                    to
+                );
+            }
+            PullOutcome::Dirty(files) => {
+                return Err(DotmanError::Repository(format!(
+                    "{} has uncommitted changes, not pulling: {}",
+                    dir.display(),
+                    files.join(", ")
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn commit_file(repo: &std::path::Path, name: &str, contents: &str) {
+        std::fs::write(repo.join(name), contents).unwrap();
+        git(repo, &["add", "."]).unwrap();
+        git(
+            repo,
+            &[
+                "-c",
+                "user.name=test",
+                "-c",
+                "user.email=test@example.com",
+                "commit",
+                "--quiet",
+                "-m",
+                name,
+            ],
+        )
+        .unwrap();
+    }
+
+    fn init_origin(temp_dir: &TempDir) -> std::path::PathBuf {
+        let origin = temp_dir.path().join("origin");
+        std::fs::create_dir_all(&origin).unwrap();
+        git(&origin, &["init", "--quiet"]).unwrap();
+        commit_file(&origin, "dotman.toml", "version = 2\n");
+        origin
+    }
+
+    #[test]
+    fn test_clone_local_repository() {
+        let temp_dir = TempDir::new().unwrap();
+        let origin = init_origin(&temp_dir);
+
+        let dest = temp_dir.path().join("nested/dotfiles");
+        clone(origin.to_str().unwrap(), &dest, None).unwrap();
+
+        assert!(is_repo(&dest));
+        assert!(dest.join("dotman.toml").exists());
+    }
+
+    #[test]
+    fn test_pull_fast_forwards_and_reports_dirty_tree() {
+        let temp_dir = TempDir::new().unwrap();
+        let origin = init_origin(&temp_dir);
+        let dest = temp_dir.path().join("dotfiles");
+        let url = origin.to_str().unwrap();
+
+        assert_eq!(pull(&dest, Some(url), None).unwrap(), PullOutcome::Cloned);
+        assert_eq!(pull(&dest, Some(url), None).unwrap(), PullOutcome::UpToDate);
+
+        commit_file(&origin, "zshrc", "");
+        assert!(matches!(
+            pull(&dest, Some(url), None).unwrap(),
+            PullOutcome::Updated { .. }
+        ));
+        assert!(dest.join("zshrc").exists());
+
+        std::fs::write(dest.join("zshrc"), "changed").unwrap();
+        assert_eq!(
+            pull(&dest, Some(url), None).unwrap(),
+            PullOutcome::Dirty(vec!["zshrc".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_checkout_follows_branch_or_rev() {
+        let temp_dir = TempDir::new().unwrap();
+        let origin = init_origin(&temp_dir);
+        let first = head(&origin).unwrap();
+        let dest = temp_dir.path().join("plugins/zsh-autosuggestions");
+        let url = format!("file://{}", origin.display());
+
+        let outcome = checkout(&dest, &url, None, None, Some(1)).unwrap();
+        assert_eq!(outcome, PullOutcome::Cloned);
+        commit_file(&origin, "plugin.zsh", "");
+        assert!(matches!(
+            checkout(&dest, &url, None, None, Some(1)).unwrap(),
+            PullOutcome::Updated { .. }
+        ));
+        assert!(dest.join("plugin.zsh").exists());
+
+        checkout(&dest, &url, None, Some(&first), None).unwrap();
+        assert_eq!(head(&dest).unwrap(), first);
+        assert_eq!(
+            checkout(&dest, &url, None, Some(&first), None).unwrap(),
+            PullOutcome::UpToDate
+        );
+    }
+
+    #[test]
+    fn test_commit_only_given_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = init_origin(&temp_dir);
+        std::fs::write(repo.join("zshrc"), "").unwrap();
+        std::fs::write(repo.join("unrelated"), "").unwrap();
+
+        git(&repo, &["config", "user.name", "test"]).unwrap();
+        git(&repo, &["config", "user.email", "test@example.com"]).unwrap();
+        commit(&repo, &[&repo.join("zshrc")], "Adopt ~/.zshrc").unwrap();
+
+        assert_eq!(
+            git(&repo, &["log", "-1", "--format=%s"]).unwrap(),
+            "Adopt ~/.zshrc"
+        );
+        assert_eq!(dirty_files(&repo).unwrap(), vec!["unrelated".to_string()]);
+    }
+
+    #[test]
+    fn test_pull_without_repo_or_url_fails() {
+        let temp_dir = TempDir::new().unwrap();
+
+        assert!(pull(temp_dir.path(), None, None).is_err());
+    }
+
+    #[test]
+    fn test_options_are_not_read_from_the_configuration() {
+        let temp_dir = TempDir::new().unwrap();
+        let marker = temp_dir.path().join("marker");
+        let url = format!("--upload-pack=touch {}", marker.display());
+
+        assert!(clone(&url, &temp_dir.path().join("a"), None).is_err());
+        assert!(checkout(&temp_dir.path().join("b"), &url, None, None, None).is_err());
+        assert!(!marker.exists());
+
+        let origin = init_origin(&temp_dir);
+        let origin = origin.to_string_lossy();
+        let dest = temp_dir.path().join("c");
+        assert!(matches!(
+            checkout(&dest, &origin, Some("--orphan=x"), None, None),
+            Err(DotmanError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            checkout(&dest, &origin, None, Some("--help"), None),
+            Err(DotmanError::InvalidConfig(_))
+        ));
+    }
+}
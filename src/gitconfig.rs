@@ -0,0 +1,126 @@
+//! Including repository-managed git configuration from `~/.gitconfig`.
+
+use colored::Colorize;
+
+use crate::{
+    Dotman, config::GitConfig, error::DotmanError, line_in_file::Managed, options::InstallOptions,
+};
+
+/// Name of the block in the git configuration that holds the includes.
+const BLOCK: &str = "git includes";
+
+impl Dotman {
+    /// The `[include]` and `[includeIf]` sections of `gitconfig`, pointing at
+    /// the resolved paths of its files.
+    fn include_sections(&self, gitconfig: &GitConfig) -> Result<String, DotmanError> {
+        let mut sections = Vec::new();
+        for include in &gitconfig.includes {
+            let path = self.config.resolve_source(&include.path)?;
+            let path = path.to_string_lossy().replace('\\', "/");
+            let header = match &include.gitdir {
+                Some(gitdir) => format!("[includeIf \"gitdir:{gitdir}\"]"),
+                None => "[include]".to_string(),
+            };
+            sections.push(format!("{header}\n\tpath = {path}"));
+        }
+        Ok(sections.join("\n"))
+    }
+
+    /// Whether the git configuration already has the includes.
+    pub fn has_git_includes(&self, gitconfig: &GitConfig) -> Result<bool, DotmanError> {
+        let sections = self.include_sections(gitconfig)?;
+        let managed = Managed::Block {
+            name: BLOCK,
+            block: &sections,
+            comment: "#",
+        };
+        Ok(self.has_line(gitconfig.file(), managed))
+    }
+
+    /// Writes the includes into the git configuration, or updates them,
+    /// leaving the rest of the file alone.
+    pub fn include_gitconfig(&self, options: &InstallOptions) -> Result<(), DotmanError> {
+        let Some(gitconfig) = &self.config.gitconfig else {
+            return Ok(());
+        };
+        if !options.selects_gitconfig(gitconfig.file()) || self.has_git_includes(gitconfig)? {
+            return Ok(());
+        }
+        if options.dry_run {
+            println!(
+                "{} includes in {}",
+                "Would update:".cyan().bold(),
+                gitconfig.file()
+            );
+            return Ok(());
+        }
+        let sections = self.include_sections(gitconfig)?;
+        let managed = Managed::Block {
+            name: BLOCK,
+            block: &sections,
+            comment: "#",
+        };
+        self.ensure_line(gitconfig.file(), managed)
+    }
+
+    /// Prints whether the includes are in place for `dotman status`.
+    pub(crate) fn print_gitconfig_status(&self, gitconfig: &GitConfig) -> Result<(), DotmanError> {
+        if self.has_git_includes(gitconfig)? {
+            print!("{}", "[OK]".green().bold());
+        } else {
+            print!("{}", "[OUTDATED]".yellow().bold());
+        }
+        let paths: Vec<&str> = gitconfig
+            .includes
+            .iter()
+            .map(|include| include.path.as_str())
+            .collect();
+        println!(" {} includes {}", gitconfig.file(), paths.join(", "));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GitInclude;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_include_gitconfig() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join(".gitconfig");
+        std::fs::write(&file, "[user]\n\temail = me@laptop.local\n").unwrap();
+        let gitconfig = GitConfig {
+            file: Some(file.to_string_lossy().into_owned()),
+            includes: vec![
+                GitInclude {
+                    path: "/dotfiles/git/common".to_string(),
+                    gitdir: None,
+                },
+                GitInclude {
+                    path: "/dotfiles/git/work".to_string(),
+                    gitdir: Some("~/work/".to_string()),
+                },
+            ],
+        };
+        let mut config = crate::DotmanConfig::builder().build();
+        config.gitconfig = Some(gitconfig.clone());
+        let dotman = Dotman::new(config);
+
+        assert!(!dotman.has_git_includes(&gitconfig).unwrap());
+        dotman
+            .include_gitconfig(&InstallOptions::default())
+            .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "[user]\n\temail = me@laptop.local\n\
+             # BEGIN dotman: git includes\n\
+             [include]\n\tpath = /dotfiles/git/common\n\
+             [includeIf \"gitdir:~/work/\"]\n\tpath = /dotfiles/git/work\n\
+             # END dotman: git includes\n"
+        );
+        assert!(dotman.has_git_includes(&gitconfig).unwrap());
+    }
+}
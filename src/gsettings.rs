@@ -0,0 +1,276 @@
+//! Setting the GNOME settings declared in `[[gsettings]]`.
+
+use colored::Colorize;
+
+use crate::{
+    Dotman,
+    config::{GSetting, SettingValue},
+    error::DotmanError,
+    options::InstallOptions,
+};
+
+/// State of a setting on this machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SettingStatus {
+    ConditionNotMet,
+    /// The `gsettings` command is not available, as on desktops other than
+    /// GNOME.
+    Unavailable,
+    /// The schema or key is not installed.
+    UnknownKey,
+    /// The setting has another value, as `gsettings get` prints it.
+    Differs(String),
+    Ok,
+}
+
+impl SettingStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SettingStatus::ConditionNotMet => "[CONDITION NOT MET]",
+            SettingStatus::Unavailable => "[GSETTINGS NOT AVAILABLE]",
+            SettingStatus::UnknownKey => "[UNKNOWN KEY]",
+            SettingStatus::Differs(_) => "[DIFFERS]",
+            SettingStatus::Ok => "[OK]",
+        }
+    }
+}
+
+/// Writes `value` in the GVariant text format `gsettings` reads and prints.
+pub fn to_gvariant(value: &SettingValue) -> String {
+    match value {
+        SettingValue::Bool(value) => value.to_string(),
+        SettingValue::Int(value) => value.to_string(),
+        SettingValue::Float(value) => format!("{value:?}"),
+        SettingValue::String(value) => {
+            let escaped = value.replace('\\', "\\\\");
+            if value.contains('\'') && !value.contains('"') {
+                format!("\"{escaped}\"")
+            } else {
+                format!("'{}'", escaped.replace('\'', "\\'"))
+            }
+        }
+        SettingValue::List(values) => {
+            let values: Vec<String> = values.iter().map(to_gvariant).collect();
+            format!("[{}]", values.join(", "))
+        }
+    }
+}
+
+/// Drops the type annotation `gsettings get` puts before some values, like
+/// the `@as` of an empty list or the `uint32` of an unsigned number.
+fn strip_type(value: &str) -> &str {
+    const TYPES: &[&str] = &[
+        "byte ",
+        "int16 ",
+        "uint16 ",
+        "int32 ",
+        "uint32 ",
+        "int64 ",
+        "uint64 ",
+        "handle ",
+        "objectpath ",
+        "signature ",
+    ];
+    if let Some(annotated) = value.strip_prefix('@') {
+        return annotated.split_once(' ').map_or(value, |(_, value)| value);
+    }
+    TYPES
+        .iter()
+        .find_map(|prefix| value.strip_prefix(prefix))
+        .unwrap_or(value)
+}
+
+/// Whether `actual`, as `gsettings get` prints it, is `value`. Numbers are
+/// compared by value, so `1` matches a double printed as `1.0`.
+pub fn matches(value: &SettingValue, actual: &str) -> bool {
+    let actual = strip_type(actual);
+    let expected = to_gvariant(value);
+    actual == expected
+        || actual
+            .parse::<f64>()
+            .is_ok_and(|actual| expected.parse() == Ok(actual))
+}
+
+/// The schema argument of `setting`, with its path for relocatable schemas.
+fn schema(setting: &GSetting) -> String {
+    match &setting.path {
+        Some(path) => format!("{}:{}", setting.schema, path),
+        None => setting.schema.clone(),
+    }
+}
+
+/// Reads the current value of `setting`, or `None` if its schema or key is
+/// not installed.
+fn read(setting: &GSetting) -> Option<String> {
+    let output = std::process::Command::new("gsettings")
+        .args(["get", &schema(setting), &setting.key])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+impl Dotman {
+    /// Checks whether `setting` has its declared value.
+    pub fn gsetting_status(&self, setting: &GSetting) -> SettingStatus {
+        if !setting.is_met(&self.context) {
+            return SettingStatus::ConditionNotMet;
+        }
+        if which::which("gsettings").is_err() {
+            return SettingStatus::Unavailable;
+        }
+        match read(setting) {
+            None => SettingStatus::UnknownKey,
+            Some(actual) if matches(&setting.value, &actual) => SettingStatus::Ok,
+            Some(actual) => SettingStatus::Differs(actual),
+        }
+    }
+
+    /// Sets the GNOME settings `options` selects that do not have their
+    /// value yet. Unless `options.fail_fast` is set, the rest are still set
+    /// when one fails.
+    pub fn apply_gsettings(&self, options: &InstallOptions) -> Result<(), DotmanError> {
+        let settings: Vec<&GSetting> = self
+            .config
+            .get_effective_gsettings()
+            .into_iter()
+            .filter(|setting| options.selects_gsetting(setting))
+            .collect();
+        if settings.is_empty() {
+            return Ok(());
+        }
+        if which::which("gsettings").is_err() {
+            println!(
+                "{} gsettings is not available, skipping GNOME settings.",
+                "Warning:".yellow().bold()
+            );
+            return Ok(());
+        }
+        let mut errors = Vec::new();
+        for setting in settings {
+            if options.is_cancelled() {
+                return Err(DotmanError::Cancelled);
+            }
+            match self.apply_gsetting(setting, options) {
+                Err(err) if options.fail_fast => return Err(err),
+                result => errors.extend(result.err()),
+            }
+        }
+        DotmanError::collect(errors)
+    }
+
+    fn apply_gsetting(
+        &self,
+        setting: &GSetting,
+        options: &InstallOptions,
+    ) -> Result<(), DotmanError> {
+        let value = to_gvariant(&setting.value);
+        let command = format!(
+            "gsettings set {} {} {}",
+            schema(setting),
+            setting.key,
+            value
+        );
+        match self.gsetting_status(setting) {
+            SettingStatus::Ok | SettingStatus::Unavailable => {}
+            SettingStatus::ConditionNotMet => {
+                println!(
+                    "{} {} {} failed condition check, skipping.",
+                    "Ignored:".yellow().bold(),
+                    setting.schema,
+                    setting.key
+                );
+            }
+            SettingStatus::UnknownKey => {
+                return Err(DotmanError::CommandError {
+                    command,
+                    message: format!("No key {} in schema {}", setting.key, schema(setting)),
+                });
+            }
+            SettingStatus::Differs(_) if options.dry_run => {
+                println!("{} {}", "Would run:".cyan().bold(), command);
+            }
+            SettingStatus::Differs(_) => {
+                let output = std::process::Command::new("gsettings")
+                    .args(["set", &schema(setting), &setting.key, &value])
+                    .output()
+                    .map_err(|err| DotmanError::CommandError {
+                        command: command.clone(),
+                        message: err.to_string(),
+                    })?;
+                if !output.status.success() {
+                    return Err(DotmanError::CommandError {
+                        command,
+                        message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                    });
+                }
+                println!(
+                    "{} {} {} to {}",
+                    "Set:".green().bold(),
+                    setting.schema,
+                    setting.key,
+                    value
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints the status of every GNOME setting for `dotman status`.
+    pub(crate) fn print_gsettings_status(&self) {
+        for setting in self.config.get_effective_gsettings() {
+            let status = self.gsetting_status(setting);
+            match &status {
+                SettingStatus::Ok => print!("{}", status.label().green().bold()),
+                SettingStatus::UnknownKey => print!("{}", status.label().red().bold()),
+                SettingStatus::Differs(actual) => {
+                    print!("{} (is {})", status.label().yellow().bold(), actual)
+                }
+                SettingStatus::ConditionNotMet | SettingStatus::Unavailable => {
+                    print!("{}", status.label().yellow().bold())
+                }
+            }
+            println!(
+                " {} {} = {}",
+                setting.schema,
+                setting.key,
+                to_gvariant(&setting.value)
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_gvariant() {
+        assert_eq!(to_gvariant(&true.into()), "true");
+        assert_eq!(to_gvariant(&1.0.into()), "1.0");
+        assert_eq!(to_gvariant(&"Adwaita-dark".into()), "'Adwaita-dark'");
+        assert_eq!(to_gvariant(&"it's".into()), "\"it's\"");
+        assert_eq!(
+            to_gvariant(&vec!["<Super>q", "<Alt>F4"].into()),
+            "['<Super>q', '<Alt>F4']"
+        );
+    }
+
+    #[test]
+    fn test_strip_type() {
+        assert_eq!(strip_type("@as []"), "[]");
+        assert_eq!(strip_type("uint32 300"), "300");
+        assert_eq!(strip_type("'uint32 300'"), "'uint32 300'");
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches(&vec!["<Super>q"].into(), "['<Super>q']"));
+        assert!(matches(&SettingValue::List(vec![]), "@as []"));
+        assert!(matches(&300_i64.into(), "uint32 300"));
+        assert!(matches(&1_i64.into(), "1.0"));
+        assert!(!matches(&"prefer-dark".into(), "'default'"));
+    }
+}
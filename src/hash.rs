@@ -0,0 +1,427 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::{HashAlgorithm, Integrity},
+    error::{DotmanError, PathError},
+};
+
+/// Returns the hex-encoded SHA-256 digest of `data`.
+pub fn sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Returns the digest of `data`. SHA-256 digests are plain hex; others are
+/// prefixed with the algorithm name, so digests recorded with a different
+/// algorithm can still be checked.
+pub fn digest(algorithm: HashAlgorithm, data: &[u8]) -> String {
+    match algorithm {
+        HashAlgorithm::Sha256 => sha256(data),
+        HashAlgorithm::Blake3 => format!("blake3:{}", blake3::hash(data).to_hex()),
+        HashAlgorithm::Xxhash => format!("xxh3:{:032x}", xxhash_rust::xxh3::xxh3_128(data)),
+    }
+}
+
+/// Returns the algorithm that produced a digest returned by [`digest`].
+pub fn algorithm_of(digest: &str) -> HashAlgorithm {
+    match digest.split_once(':') {
+        Some(("blake3", _)) => HashAlgorithm::Blake3,
+        Some(("xxh3", _)) => HashAlgorithm::Xxhash,
+        _ => HashAlgorithm::Sha256,
+    }
+}
+
+/// Returns the digest of the file at `path`.
+pub fn digest_file(algorithm: HashAlgorithm, path: &std::path::Path) -> std::io::Result<String> {
+    std::fs::read(path).map(|data| digest(algorithm, &data))
+}
+
+/// Files modified this recently are not cached, since a write within the
+/// same mtime tick would go unnoticed.
+const RACY_WINDOW: std::time::Duration = std::time::Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheEntry {
+    size: u64,
+    /// Modification time in nanoseconds since the epoch.
+    modified: u128,
+    digest: String,
+}
+
+/// File digests keyed by size and modification time, kept in the state
+/// directory so unchanged files are not hashed again on every run.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChecksumCache {
+    #[serde(default)]
+    entries: std::collections::BTreeMap<std::path::PathBuf, CacheEntry>,
+    /// Where the cache is saved. An in-memory cache is never written.
+    #[serde(skip)]
+    path: Option<std::path::PathBuf>,
+    #[serde(skip)]
+    changed: bool,
+}
+
+impl ChecksumCache {
+    /// Loads the cache from the state directory.
+    pub fn load_default() -> Result<ChecksumCache, DotmanError> {
+        let dir = crate::state::state_dir().ok_or(PathError::StateDirNotFound)?;
+        Ok(ChecksumCache::load(&dir.join("checksums.json")))
+    }
+
+    /// Loads the cache at `path`. A missing or unreadable cache starts empty.
+    pub fn load(path: &std::path::Path) -> ChecksumCache {
+        let mut cache: ChecksumCache = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        cache.path = Some(path.to_path_buf());
+        cache
+    }
+
+    /// Writes the cache back if any digest was added or dropped.
+    pub fn save(&mut self) -> Result<(), DotmanError> {
+        let Some(path) = self.path.as_ref().filter(|_| self.changed) else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let contents = serde_json::to_string(self)
+            .map_err(|err| DotmanError::State(format!("Cannot write the checksum cache: {err}")))?;
+        std::fs::write(path, contents)?;
+        self.changed = false;
+        Ok(())
+    }
+
+    /// Where the cache is saved, unless it is in memory only.
+    pub fn path(&self) -> Option<&std::path::Path> {
+        self.path.as_deref()
+    }
+
+    /// Drops every digest and deletes the saved cache. Returns the size of
+    /// the file deleted.
+    pub fn clear(&mut self) -> Result<u64, DotmanError> {
+        self.entries.clear();
+        self.changed = false;
+        let Some(path) = &self.path else {
+            return Ok(0);
+        };
+        match std::fs::metadata(path) {
+            Ok(metadata) => {
+                std::fs::remove_file(path)?;
+                Ok(metadata.len())
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Returns the digest of a file, or of every file inside a directory.
+    pub fn checksum(
+        &mut self,
+        integrity: &Integrity,
+        path: &std::path::Path,
+    ) -> std::io::Result<String> {
+        if path.is_dir() {
+            self.folder_checksum(integrity, path)
+        } else {
+            self.file_checksum(integrity.algorithm, path)
+        }
+    }
+
+    /// Returns the digest of the file at `path`, reusing the cached one when
+    /// its size and modification time are unchanged.
+    pub fn file_checksum(
+        &mut self,
+        algorithm: HashAlgorithm,
+        path: &std::path::Path,
+    ) -> std::io::Result<String> {
+        let metadata = std::fs::metadata(path)?;
+        if let Some(digest) = self.cached(algorithm, path, &metadata) {
+            return Ok(digest);
+        }
+        let digest = digest_file(algorithm, path)?;
+        self.store(path, &metadata, &digest);
+        Ok(digest)
+    }
+
+    /// Returns a digest over the relative paths and contents of every file
+    /// below `dir`, in a stable order. Files missing from the cache are
+    /// hashed in parallel.
+    ///
+    /// With `include_permissions`, each file's mode bits are part of the
+    /// digest. With `symlink_targets`, symlinks are hashed by where they point
+    /// instead of being followed, so dangling links are covered too.
+    pub fn folder_checksum(
+        &mut self,
+        integrity: &Integrity,
+        dir: &std::path::Path,
+    ) -> std::io::Result<String> {
+        let algorithm = integrity.algorithm;
+        let mut entries = collect_files(dir, integrity)?;
+        entries.sort();
+
+        let digests = entries
+            .par_iter()
+            .map(|(path, is_symlink)| {
+                if *is_symlink {
+                    let target = std::fs::read_link(path)?;
+                    return Ok((format!("-> {}", target.display()), None, None));
+                }
+                let metadata = std::fs::metadata(path)?;
+                let mode = integrity.include_permissions.then(|| mode(&metadata));
+                match self.cached(algorithm, path, &metadata) {
+                    Some(digest) => Ok((digest, mode, None)),
+                    None => Ok((digest_file(algorithm, path)?, mode, Some(metadata))),
+                }
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        let mut listing = Vec::new();
+        for ((path, _), (entry_digest, mode, hashed)) in entries.iter().zip(digests) {
+            if let Some(metadata) = hashed {
+                self.store(path, &metadata, &entry_digest);
+            }
+            let relative = path.strip_prefix(dir).unwrap_or(path);
+            listing.extend_from_slice(relative.to_string_lossy().as_bytes());
+            listing.push(0);
+            if let Some(mode) = mode {
+                listing.extend_from_slice(format!("{mode:o}").as_bytes());
+                listing.push(0);
+            }
+            listing.extend_from_slice(entry_digest.as_bytes());
+            listing.push(b'\n');
+        }
+        Ok(digest(algorithm, &listing))
+    }
+
+    fn cached(
+        &self,
+        algorithm: HashAlgorithm,
+        path: &std::path::Path,
+        metadata: &std::fs::Metadata,
+    ) -> Option<String> {
+        let entry = self.entries.get(path)?;
+        (entry.size == metadata.len()
+            && Some(entry.modified) == modified_nanos(metadata)
+            && algorithm_of(&entry.digest) == algorithm)
+            .then(|| entry.digest.clone())
+    }
+
+    fn store(&mut self, path: &std::path::Path, metadata: &std::fs::Metadata, digest: &str) {
+        let racy = metadata
+            .modified()
+            .and_then(|modified| modified.elapsed().map_err(std::io::Error::other))
+            .map_or(true, |elapsed| elapsed < RACY_WINDOW);
+        match modified_nanos(metadata).filter(|_| !racy) {
+            Some(modified) => {
+                self.entries.insert(
+                    path.to_path_buf(),
+                    CacheEntry {
+                        size: metadata.len(),
+                        modified,
+                        digest: digest.to_string(),
+                    },
+                );
+                self.changed = true;
+            }
+            None => self.changed |= self.entries.remove(path).is_some(),
+        }
+    }
+}
+
+/// Returns the permission bits of a file. Outside Unix only the read-only
+/// flag is known.
+fn mode(metadata: &std::fs::Metadata) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o7777
+    }
+    #[cfg(not(unix))]
+    {
+        if metadata.permissions().readonly() {
+            0o444
+        } else {
+            0o644
+        }
+    }
+}
+
+fn modified_nanos(metadata: &std::fs::Metadata) -> Option<u128> {
+    let modified = metadata.modified().ok()?;
+    let since_epoch = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some(since_epoch.as_nanos())
+}
+
+/// Lists the files below `dir`, along with whether each is a symlink. Files
+/// matched by `.gitignore` or `.dotmanignore` are skipped with
+/// `respect_ignore`, and symlinks are only listed, not followed, with
+/// `symlink_targets`.
+fn collect_files(
+    dir: &std::path::Path,
+    integrity: &Integrity,
+) -> std::io::Result<Vec<(std::path::PathBuf, bool)>> {
+    let mut walker = ignore::WalkBuilder::new(dir);
+    walker
+        .standard_filters(false)
+        .follow_links(!integrity.symlink_targets)
+        .git_ignore(integrity.respect_ignore)
+        .require_git(false);
+    if integrity.respect_ignore {
+        walker.add_custom_ignore_filename(".dotmanignore");
+    }
+
+    let mut files = Vec::new();
+    for entry in walker.build() {
+        let entry = entry.map_err(std::io::Error::other)?;
+        match entry.file_type() {
+            Some(file_type) if file_type.is_file() => files.push((entry.into_path(), false)),
+            Some(file_type) if file_type.is_symlink() => files.push((entry.into_path(), true)),
+            _ => {}
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            sha256(b"hello"),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    #[test]
+    fn test_digest_algorithms() {
+        assert_eq!(digest(HashAlgorithm::Sha256, b"hello"), sha256(b"hello"));
+        for algorithm in [HashAlgorithm::Blake3, HashAlgorithm::Xxhash] {
+            let hello = digest(algorithm, b"hello");
+            assert_eq!(algorithm_of(&hello), algorithm);
+            assert_ne!(hello, digest(algorithm, b"world"));
+        }
+        assert_eq!(
+            digest(HashAlgorithm::Blake3, b"hello"),
+            "blake3:ea8f163db38682925e4491c5e58d4bb3506ef8c14eb78a86e908c5624a67200f"
+        );
+    }
+
+    #[test]
+    fn test_checksum_cache_skips_unchanged_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("init.lua");
+        let old = std::time::SystemTime::now() - std::time::Duration::from_secs(60);
+        let set_modified = |time| {
+            std::fs::File::options()
+                .write(true)
+                .open(&file)
+                .unwrap()
+                .set_modified(time)
+                .unwrap()
+        };
+
+        std::fs::write(&file, "hello").unwrap();
+        set_modified(old);
+        let mut cache = ChecksumCache::default();
+        assert_eq!(
+            cache.file_checksum(HashAlgorithm::Sha256, &file).unwrap(),
+            sha256(b"hello")
+        );
+
+        // Same size and mtime: the cached digest is trusted.
+        std::fs::write(&file, "world").unwrap();
+        set_modified(old);
+        assert_eq!(
+            cache.file_checksum(HashAlgorithm::Sha256, &file).unwrap(),
+            sha256(b"hello")
+        );
+
+        set_modified(old + std::time::Duration::from_secs(1));
+        assert_eq!(
+            cache.file_checksum(HashAlgorithm::Sha256, &file).unwrap(),
+            sha256(b"world")
+        );
+    }
+
+    #[test]
+    fn test_folder_checksum_covers_names_and_contents() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("nvim");
+        std::fs::create_dir_all(dir.join("lua")).unwrap();
+        std::fs::write(dir.join("init.lua"), "require('plugins')").unwrap();
+        std::fs::write(dir.join("lua/plugins.lua"), "return {}").unwrap();
+
+        let integrity = Integrity {
+            algorithm: HashAlgorithm::Blake3,
+            ..Default::default()
+        };
+        let mut cache = ChecksumCache::default();
+        let before = cache.checksum(&integrity, &dir).unwrap();
+        assert_eq!(cache.checksum(&integrity, &dir).unwrap(), before);
+
+        std::fs::rename(dir.join("lua/plugins.lua"), dir.join("lua/other.lua")).unwrap();
+        assert_ne!(cache.checksum(&integrity, &dir).unwrap(), before);
+    }
+
+    #[test]
+    fn test_folder_checksum_respects_ignore_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("nvim");
+        std::fs::create_dir_all(dir.join("plugin")).unwrap();
+        std::fs::write(dir.join("init.lua"), "require('plugins')").unwrap();
+        std::fs::write(dir.join(".gitignore"), "plugin/\n").unwrap();
+        std::fs::write(dir.join(".dotmanignore"), "*.log\n").unwrap();
+
+        let integrity = Integrity {
+            respect_ignore: true,
+            ..Default::default()
+        };
+        let mut cache = ChecksumCache::default();
+        let before = cache.checksum(&integrity, &dir).unwrap();
+        let unfiltered = cache.checksum(&Integrity::default(), &dir).unwrap();
+
+        std::fs::write(dir.join("plugin/packer_compiled.lua"), "-- generated").unwrap();
+        std::fs::write(dir.join("lsp.log"), "started").unwrap();
+        assert_eq!(cache.checksum(&integrity, &dir).unwrap(), before);
+        assert_ne!(
+            cache.checksum(&Integrity::default(), &dir).unwrap(),
+            unfiltered
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_folder_checksum_permissions_and_symlinks() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("bin");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("tool"), "#!/bin/sh").unwrap();
+        std::os::unix::fs::symlink("missing", dir.join("dangling")).unwrap();
+
+        let integrity = Integrity {
+            include_permissions: true,
+            symlink_targets: true,
+            ..Default::default()
+        };
+        let mut cache = ChecksumCache::default();
+        let before = cache.checksum(&integrity, &dir).unwrap();
+        // Following the dangling link fails.
+        assert!(cache.checksum(&Integrity::default(), &dir).is_err());
+
+        std::fs::set_permissions(dir.join("tool"), std::fs::Permissions::from_mode(0o755)).unwrap();
+        let executable = cache.checksum(&integrity, &dir).unwrap();
+        assert_ne!(executable, before);
+
+        std::fs::remove_file(dir.join("dangling")).unwrap();
+        std::os::unix::fs::symlink("elsewhere", dir.join("dangling")).unwrap();
+        assert_ne!(cache.checksum(&integrity, &dir).unwrap(), executable);
+    }
+}
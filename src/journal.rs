@@ -0,0 +1,254 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::LinkMode,
+    error::{DotmanError, PathError},
+    events::{Event, EventHandler},
+    state::TargetState,
+};
+
+/// What one run of dotman did, as appended to the journal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Run {
+    /// Seconds since the Unix epoch when the run started.
+    pub started: u64,
+    pub finished: u64,
+    /// The command line, such as `dotman install --tag shell`.
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<Change>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<String>,
+    /// For `dotman undo`, the number of the run it reverted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub undoes: Option<usize>,
+}
+
+/// Something a run changed on the machine.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Change {
+    Link {
+        source: std::path::PathBuf,
+        target: std::path::PathBuf,
+        mode: LinkMode,
+    },
+    Unlink {
+        target: std::path::PathBuf,
+    },
+    Backup {
+        target: std::path::PathBuf,
+        backup: std::path::PathBuf,
+        /// What dotman had recorded for the target, restored along with it.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        previous: Option<TargetState>,
+    },
+    /// A secret was written again; undoing it decrypts `previous` anew.
+    Redecrypt {
+        target: std::path::PathBuf,
+        previous: TargetState,
+    },
+    Action {
+        name: String,
+        success: bool,
+    },
+}
+
+/// Append-only log of runs, one JSON object per line, in the state
+/// directory.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    path: std::path::PathBuf,
+}
+
+impl Journal {
+    /// The journal in the state directory.
+    pub fn load_default() -> Result<Journal, DotmanError> {
+        let dir = crate::state::state_dir().ok_or(PathError::StateDirNotFound)?;
+        Ok(Journal::at(&dir.join("history.jsonl")))
+    }
+
+    pub fn at(path: &std::path::Path) -> Journal {
+        Journal {
+            path: path.to_path_buf(),
+        }
+    }
+
+    pub fn append(&self, run: &Run) -> Result<(), DotmanError> {
+        use std::io::Write;
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let line = serde_json::to_string(run)
+            .map_err(|err| DotmanError::State(format!("Cannot write the history: {err}")))?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{line}")?;
+        Ok(())
+    }
+
+    /// The number of the most recent run that changed something and has not
+    /// been undone, skipping undo runs themselves.
+    pub fn last_undoable(runs: &[Run]) -> Option<usize> {
+        let undone: std::collections::HashSet<usize> =
+            runs.iter().filter_map(|run| run.undoes).collect();
+        runs.iter()
+            .enumerate()
+            .rev()
+            .map(|(index, run)| (index + 1, run))
+            .find(|(id, run)| {
+                run.undoes.is_none() && !run.changes.is_empty() && !undone.contains(id)
+            })
+            .map(|(id, _)| id)
+    }
+
+    /// Every run, oldest first. A missing journal has no runs, and lines that
+    /// cannot be parsed, such as one cut short by a crash, are skipped.
+    pub fn runs(&self) -> Result<Vec<Run>, DotmanError> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(contents) => Ok(contents
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// Collects a [`Run`] from the events of a [`crate::Dotman`].
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    run: std::sync::Arc<std::sync::Mutex<Run>>,
+}
+
+impl Recorder {
+    pub fn start(command: impl Into<String>) -> Recorder {
+        Recorder {
+            run: std::sync::Arc::new(std::sync::Mutex::new(Run {
+                started: now(),
+                finished: 0,
+                command: command.into(),
+                changes: vec![],
+                failures: vec![],
+                undoes: None,
+            })),
+        }
+    }
+
+    /// Handler to pass to [`crate::Dotman::with_events`].
+    pub fn handler(&self) -> EventHandler {
+        let run = self.run.clone();
+        Box::new(move |event| {
+            let change = match event {
+                Event::LinkCreated {
+                    source,
+                    target,
+                    mode,
+                } => Change::Link {
+                    source: source.clone(),
+                    target: target.clone(),
+                    mode: *mode,
+                },
+                Event::LinkRemoved { target } => Change::Unlink {
+                    target: target.clone(),
+                },
+                Event::TargetBackedUp {
+                    target,
+                    backup,
+                    previous,
+                } => Change::Backup {
+                    target: target.clone(),
+                    backup: backup.clone(),
+                    previous: previous.clone(),
+                },
+                Event::SecretRewritten { target, previous } => Change::Redecrypt {
+                    target: target.clone(),
+                    previous: previous.clone(),
+                },
+                Event::ActionFinished { name, success } => Change::Action {
+                    name: name.clone(),
+                    success: *success,
+                },
+                Event::LinkSkipped { .. } | Event::ActionStarted { .. } => return,
+            };
+            run.lock().unwrap().changes.push(change);
+        })
+    }
+
+    /// Marks the run as reverting run number `id`.
+    pub fn undoing(self, id: usize) -> Recorder {
+        self.run.lock().unwrap().undoes = Some(id);
+        self
+    }
+
+    /// Completes the run, recording the failures in `error`.
+    pub fn finish(self, error: Option<&DotmanError>) -> Run {
+        let mut run = self.run.lock().unwrap().clone();
+        run.finished = now();
+        run.failures = error
+            .map(|err| err.errors().iter().map(ToString::to_string).collect())
+            .unwrap_or_default();
+        run
+    }
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_journal_records_runs() {
+        let temp_dir = TempDir::new().unwrap();
+        let journal = Journal::at(&temp_dir.path().join("nested/history.jsonl"));
+        assert!(journal.runs().unwrap().is_empty());
+
+        let recorder = Recorder::start("dotman install");
+        let handler = recorder.handler();
+        handler(&Event::LinkCreated {
+            source: "/dotfiles/zshrc".into(),
+            target: "/home/me/.zshrc".into(),
+            mode: LinkMode::Symlink,
+        });
+        handler(&Event::SecretRewritten {
+            target: "/home/me/.netrc".into(),
+            previous: TargetState {
+                source: "/dotfiles/netrc.age".into(),
+                checksum: Some("abc".to_string()),
+            },
+        });
+        handler(&Event::LinkSkipped {
+            target: "/home/me/.vimrc".into(),
+            reason: crate::events::SkipReason::SourceMissing,
+        });
+        let failure = DotmanError::CommandError {
+            command: "brew".to_string(),
+            message: "not found".to_string(),
+        };
+        let run = recorder.finish(Some(&failure));
+        journal.append(&run).unwrap();
+        journal.append(&run).unwrap();
+
+        let runs = journal.runs().unwrap();
+        assert_eq!(runs, [run.clone(), run]);
+        assert_eq!(runs[0].changes.len(), 2);
+        assert!(matches!(runs[0].changes[1], Change::Redecrypt { .. }));
+        assert_eq!(runs[0].failures, ["Command 'brew' failed: not found"]);
+        assert_eq!(Journal::last_undoable(&runs), Some(2));
+
+        journal
+            .append(&Recorder::start("dotman undo").undoing(2).finish(None))
+            .unwrap();
+        assert_eq!(Journal::last_undoable(&journal.runs().unwrap()), Some(1));
+    }
+}
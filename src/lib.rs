@@ -1,272 +1,1610 @@
 use colored::Colorize;
 
 use crate::{
-    config::{Action, condition_is_met},
+    config::{Action, Integrity, Link, LinkMode},
+    context::{Context, PromptGuard},
     error::DotmanError,
+    events::{Event, EventHandler, SkipReason},
+    fs::{EntryKind, Filesystem, RealFilesystem},
+    journal::{Change, Run},
+    options::InstallOptions,
+    state::{State, TargetState},
     utils::{Absolute, ExpandTilde},
 };
 
+pub mod adopt;
+#[cfg(feature = "async")]
+pub mod asynchronous;
+pub mod chezmoi;
+pub mod clean;
+pub mod concat;
 pub mod config;
+pub mod context;
+pub mod defaults;
+pub mod directories;
+pub mod download;
 pub mod error;
+pub mod events;
+pub mod extract;
+pub mod format;
+pub mod fs;
+pub mod git;
+pub mod gitconfig;
+pub mod gsettings;
+pub mod hash;
+pub mod journal;
+pub mod line_in_file;
+pub mod lockfile;
+pub mod merge;
+pub mod migrate;
+pub mod notification;
+pub mod options;
+pub mod orphans;
+pub mod packages;
+pub mod permissions;
+pub mod preflight;
+pub mod privilege;
+pub mod prune;
+pub mod registry;
+pub mod remote;
+pub mod repair;
+pub mod run_lock;
+pub mod schedule;
+pub mod secrets;
+pub mod shell_env;
+pub mod state;
+pub mod stow;
+pub mod systemd;
+pub mod template;
 pub mod utils;
+pub mod validate;
+pub mod watch;
 
 pub use crate::config::DotmanConfig;
 
+/// State of a link on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkStatus {
+    ConditionNotMet,
+    SourceMissing,
+    NotLinked,
+    Ok,
+    /// The target is a symlink to somewhere else.
+    WrongTarget(std::path::PathBuf),
+    SymlinkError,
+    /// The target exists but is not a symlink.
+    NotSymlink,
+    /// A copied, secret or template target differs from what the source
+    /// generates.
+    Modified,
+    /// A secret's source could not be decrypted.
+    DecryptFailed,
+    /// A template's source could not be rendered.
+    RenderFailed,
+    /// The target is in place, but its permissions or owner differ from
+    /// its `chmod`, `owner` or `group`.
+    WrongPermissions,
+}
+
+impl LinkStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LinkStatus::ConditionNotMet => "[CONDITION NOT MET]",
+            LinkStatus::SourceMissing => "[SOURCE MISSING]",
+            LinkStatus::NotLinked => "[NOT LINKED]",
+            LinkStatus::Ok => "[OK]",
+            LinkStatus::WrongTarget(_) => "[WRONG TARGET]",
+            LinkStatus::SymlinkError => "[SYMLINK ERROR]",
+            LinkStatus::NotSymlink => "[EXISTS BUT NOT SYMLINK]",
+            LinkStatus::Modified => "[MODIFIED]",
+            LinkStatus::DecryptFailed => "[DECRYPT FAILED]",
+            LinkStatus::RenderFailed => "[RENDER FAILED]",
+            LinkStatus::WrongPermissions => "[WRONG PERMISSIONS]",
+        }
+    }
+}
+
+/// Why an installed target no longer matches its source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    Missing,
+    NotSymlink,
+    /// The target is a symlink to somewhere else.
+    WrongTarget(std::path::PathBuf),
+    /// The target was edited after dotman wrote it.
+    Modified,
+    /// The source changed since the target was written.
+    Outdated,
+    /// The source could not be decrypted or rendered.
+    Unreadable(String),
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mismatch::Missing => write!(f, "is missing"),
+            Mismatch::NotSymlink => write!(f, "is not a symlink"),
+            Mismatch::WrongTarget(actual) => write!(f, "points to {}", actual.display()),
+            Mismatch::Modified => write!(f, "was modified since it was installed"),
+            Mismatch::Outdated => write!(f, "is older than its source"),
+            Mismatch::Unreadable(err) => write!(f, "cannot be compared: {err}"),
+        }
+    }
+}
+
+/// A link together with its resolved paths and state.
+#[derive(Debug)]
+pub struct LinkReport<'a> {
+    pub link: &'a Link,
+    pub source: std::path::PathBuf,
+    pub target: std::path::PathBuf,
+    pub status: LinkStatus,
+}
+
 pub struct Dotman {
     pub config: DotmanConfig,
+    /// Machine the conditions are evaluated against.
+    context: Context,
+    fs: Box<dyn Filesystem>,
+    /// Changes the targets of links with `become`. Unset when a custom
+    /// filesystem is used, which then handles those as well.
+    elevated_fs: Option<Box<dyn Filesystem>>,
+    state: std::sync::Mutex<State>,
+    checksums: std::sync::Mutex<hash::ChecksumCache>,
+    on_event: Vec<EventHandler>,
+    /// Where targets that are replaced are moved to, instead of deleting them.
+    backup_dir: Option<std::path::PathBuf>,
+    /// Where files dotman wrote are moved to when it writes them again.
+    replaced_dir: Option<std::path::PathBuf>,
 }
 
 impl Dotman {
     pub fn new(config: DotmanConfig) -> Self {
-        Dotman { config }
+        Dotman {
+            config,
+            context: Context::detect(),
+            fs: Box::new(RealFilesystem),
+            elevated_fs: Some(Box::new(privilege::ElevatedFilesystem)),
+            state: std::sync::Mutex::new(State::default()),
+            checksums: std::sync::Mutex::new(hash::ChecksumCache::default()),
+            on_event: Vec::new(),
+            backup_dir: None,
+            replaced_dir: None,
+        }
+    }
+
+    /// Uses `state` to remember what was installed. Without it, nothing is
+    /// remembered between runs.
+    pub fn with_state(mut self, state: State) -> Self {
+        self.state = std::sync::Mutex::new(state);
+        self
+    }
+
+    /// Evaluates conditions against `context` instead of the detected
+    /// machine.
+    pub fn with_context(mut self, context: Context) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Performs filesystem operations through `fs` instead of on the real
+    /// filesystem.
+    pub fn with_filesystem(mut self, fs: impl Filesystem + 'static) -> Self {
+        self.fs = Box::new(fs);
+        self.elevated_fs = None;
+        self
+    }
+
+    /// Uses `cache` to skip hashing targets that did not change since the
+    /// last run.
+    pub fn with_checksum_cache(mut self, cache: hash::ChecksumCache) -> Self {
+        self.checksums = std::sync::Mutex::new(cache);
+        self
+    }
+
+    /// Calls `on_event` for everything that happens during installs, such as
+    /// links being created or skipped and actions running. With parallel
+    /// installs it is called from several threads. Each call adds another
+    /// callback.
+    pub fn with_events(mut self, on_event: impl Fn(&Event) + Send + Sync + 'static) -> Self {
+        self.on_event.push(Box::new(on_event));
+        self
+    }
+
+    /// Moves targets that are in the way of an overwrite into `dir`, so
+    /// [`Dotman::undo`] can restore them. Without it, they are deleted.
+    pub fn with_backup_dir(mut self, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.backup_dir = Some(dir.into());
+        self
+    }
+
+    /// Moves templates, copies and assembled files dotman wrote into `dir`
+    /// before writing them again, so [`Dotman::undo`] can put them back.
+    /// Secrets are not kept, and are decrypted again instead. Without it,
+    /// they are deleted.
+    pub fn with_replaced_dir(mut self, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.replaced_dir = Some(dir.into());
+        self
+    }
+
+    fn emit(&self, event: Event) {
+        for on_event in &self.on_event {
+            on_event(&event);
+        }
+    }
+
+    fn skip(&self, target: &std::path::Path, reason: SkipReason) {
+        self.emit(Event::LinkSkipped {
+            target: target.to_path_buf(),
+            reason,
+        });
+    }
+
+    /// The filesystem that changes `link`'s target: through sudo or doas
+    /// for links with `become`, unless dotman already runs as root.
+    fn fs_for(&self, link: &Link) -> &dyn Filesystem {
+        match &self.elevated_fs {
+            Some(fs) if link.elevate && !self.context.root => fs.as_ref(),
+            _ => self.fs.as_ref(),
+        }
+    }
+
+    /// The program that runs `action` as root, if it has `become` and
+    /// dotman does not already run as root.
+    fn elevation(&self, action: &Action) -> Result<Option<&'static str>, DotmanError> {
+        if !action.elevate() || self.context.root {
+            return Ok(None);
+        }
+        privilege::program()
+            .map(Some)
+            .ok_or_else(|| DotmanError::CommandError {
+                command: action.name().to_string(),
+                message: "`become` needs sudo or doas, and neither is installed".to_string(),
+            })
+    }
+
+    fn checksum(&self, integrity: &Integrity, path: &std::path::Path) -> std::io::Result<String> {
+        self.checksums.lock().unwrap().checksum(integrity, path)
+    }
+
+    /// Whether `target` still has the checksum recorded when dotman wrote it,
+    /// hashed with whichever algorithm produced that checksum.
+    fn matches_recorded(&self, target: &std::path::Path, recorded: &str) -> bool {
+        let integrity = Integrity {
+            algorithm: hash::algorithm_of(recorded),
+            ..self.config.integrity()
+        };
+        self.checksum(&integrity, target)
+            .is_ok_and(|digest| digest == recorded)
+    }
+
+    /// Saves the checksum cache. It only speeds up later runs, so failing to
+    /// write it is not an error.
+    fn save_checksums(&self) {
+        let _ = self.checksums.lock().unwrap().save();
     }
 
     pub fn install(&self) -> Result<(), DotmanError> {
-        let os = utils::get_current_os();
-        let hostname = utils::get_hostname();
+        self.install_with(&InstallOptions::default())
+    }
 
-        for link in self.config.get_effective_links() {
-            let source = link.source.expand_tilde_path()?.absolute()?;
-            let target = link.target.expand_tilde_path()?.absolute()?;
+    /// Installs the packages, directories and links, writes the git
+    /// includes and shell environment, sets the macOS preferences and GNOME
+    /// settings and runs the actions that `options` selects.
+    pub fn install_with(&self, options: &InstallOptions) -> Result<(), DotmanError> {
+        self.check_preflight(options)?;
+        let _prompts = self.allow_prompts(options);
+        let prepared = self.install_steps(options);
+        if options.fail_fast || matches!(prepared, Err(DotmanError::Cancelled)) {
+            prepared?;
+            return self.run_actions(options);
+        }
+        let ran = self.run_actions(options);
+        if matches!(ran, Err(DotmanError::Cancelled)) {
+            return ran;
+        }
+        DotmanError::collect([prepared, ran].into_iter().filter_map(Result::err))
+    }
+
+    /// Everything an install does before running the actions, in order, for
+    /// both [`Dotman::install_with`] and the async install. Stops at the
+    /// first failure with `options.fail_fast`, and when cancelled.
+    pub(crate) fn install_steps(&self, options: &InstallOptions) -> Result<(), DotmanError> {
+        type Step = fn(&Dotman, &InstallOptions) -> Result<(), DotmanError>;
+        let steps: [Step; 7] = [
+            Dotman::install_packages,
+            Dotman::create_dirs,
+            Dotman::link,
+            Dotman::include_gitconfig,
+            Dotman::write_shell_env,
+            Dotman::apply_defaults,
+            Dotman::apply_gsettings,
+        ];
+        let mut errors = Vec::new();
+        for step in steps {
+            match step(self, options) {
+                Ok(()) => {}
+                Err(err) if options.fail_fast || matches!(err, DotmanError::Cancelled) => {
+                    return Err(err);
+                }
+                Err(err) => errors.push(err),
+            }
+        }
+        DotmanError::collect(errors)
+    }
+
+    /// Lets `prompt` conditions ask the user until the guard is dropped, if
+    /// the run changes anything and reads from a terminal.
+    pub(crate) fn allow_prompts(&self, options: &InstallOptions) -> PromptGuard<'_> {
+        self.context
+            .allow_prompts(!options.dry_run && std::io::IsTerminal::is_terminal(&std::io::stdin()))
+    }
+
+    /// Installs the links `options` selects and saves the state.
+    fn link(&self, options: &InstallOptions) -> Result<(), DotmanError> {
+        if !options.dry_run {
+            self.ensure_repos()?;
+        }
+
+        let links: Vec<&Link> = self
+            .config
+            .get_effective_links()
+            .into_iter()
+            .filter(|link| options.selects_link(link))
+            .collect();
+        let linked = self.install_links(&links, options);
+        if !options.dry_run {
+            self.state.lock().unwrap().save()?;
+        }
+        linked
+    }
+
+    /// Installs `links`, several at once if `options.parallelism` allows.
+    /// Unless `options.fail_fast` is set, the rest are still installed when a
+    /// link fails, and every failure is returned together.
+    fn install_links(&self, links: &[&Link], options: &InstallOptions) -> Result<(), DotmanError> {
+        let install = |link: &Link| {
+            if options.is_cancelled() {
+                return Err(DotmanError::Cancelled);
+            }
+            self.install_link(link, options)
+                .map_err(|err| err.in_link(link))
+        };
+
+        let results: Vec<Result<(), DotmanError>> = if options.parallelism > 1 && !options.ask {
+            use rayon::prelude::*;
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(options.parallelism)
+                .build()
+                .map_err(|err| DotmanError::IoError(std::io::Error::other(err)))?;
+            if options.fail_fast {
+                return pool.install(|| links.par_iter().try_for_each(|link| install(link)));
+            }
+            pool.install(|| links.par_iter().map(|link| install(link)).collect())
+        } else if options.fail_fast {
+            return links.iter().try_for_each(|link| install(link));
+        } else {
+            links.iter().map(|link| install(link)).collect()
+        };
+
+        if options.is_cancelled() {
+            return Err(DotmanError::Cancelled);
+        }
+        DotmanError::collect(results.into_iter().filter_map(Result::err))
+    }
+
+    /// Installs only what changed since the last run: links that are not
+    /// installed yet, files whose template or secret generates something new,
+    /// and actions whose command changed or that never ran. Returns how many
+    /// links and actions were out of date.
+    pub fn update(&self, options: &InstallOptions) -> Result<usize, DotmanError> {
+        let _prompts = self.allow_prompts(options);
+        let linked = self.relink(options)?;
+        let ran = self.run_actions_where(options, |action| {
+            self.state.lock().unwrap().action_hash(action.name())
+                != Some(self.action_hash(action).as_str())
+        })?;
+        Ok(linked + ran)
+    }
+
+    /// Installs the targets that are not linked yet or whose generated
+    /// contents changed, plus drifted ones when overwriting. Returns how many
+    /// it tried to install.
+    fn relink(&self, options: &InstallOptions) -> Result<usize, DotmanError> {
+        self.check_preflight(options)?;
+        let mut linked = 0;
+        for report in self.link_statuses()? {
+            let drifted = matches!(
+                report.status,
+                LinkStatus::WrongTarget(_) | LinkStatus::NotSymlink | LinkStatus::Modified
+            );
+            let outdated = matches!(report.status, LinkStatus::NotLinked | LinkStatus::Modified);
+            let overwrite = options.overwrite || self.config.overwrite;
+            if options.is_cancelled() {
+                break;
+            }
+            if options.selects_link(report.link) && (outdated || (drifted && overwrite)) {
+                linked += 1;
+                self.install_link(report.link, options)
+                    .map_err(|err| err.in_link(report.link))?;
+            } else if options.selects_link(report.link)
+                && report.status == LinkStatus::WrongPermissions
+            {
+                linked += 1;
+                if !options.dry_run {
+                    self.apply_permissions(report.link)
+                        .map_err(|err| err.in_link(report.link))?;
+                }
+            }
+        }
+        if !options.dry_run {
+            self.state.lock().unwrap().save()?;
+        }
+        if options.is_cancelled() {
+            return Err(DotmanError::Cancelled);
+        }
+        Ok(linked)
+    }
 
-            if !link.is_met(&os, hostname.as_deref()) {
+    /// Links only the targets that are not linked yet (or have drifted, when
+    /// overwriting), runs the actions if `run_actions` is set and prints a
+    /// drift summary. Pulling the repository is left to the caller. Returns
+    /// the number of targets still drifted.
+    pub fn sync(&self, options: &InstallOptions, run_actions: bool) -> Result<usize, DotmanError> {
+        let _prompts = self.allow_prompts(options);
+        self.relink(options)?;
+
+        if run_actions {
+            self.run_actions(options)?;
+        }
+
+        let reports = self.link_statuses()?;
+        let ok = reports
+            .iter()
+            .filter(|report| report.status == LinkStatus::Ok)
+            .count();
+        let drifted: Vec<&LinkReport> = reports
+            .iter()
+            .filter(|report| !matches!(report.status, LinkStatus::Ok | LinkStatus::ConditionNotMet))
+            .collect();
+
+        println!();
+        println!(
+            "{} {} linked, {} drifted.",
+            "Summary:".blue().bold(),
+            ok,
+            drifted.len()
+        );
+        for report in &drifted {
+            println!(
+                "  {} {}",
+                report.status.label().yellow().bold(),
+                report.target.display()
+            );
+        }
+        Ok(drifted.len())
+    }
+
+    /// Repairs what `dotman status` flags, without running actions: creates
+    /// missing links, re-points symlinks that point elsewhere and restores
+    /// permissions. Modified copies and files in place of a symlink are only
+    /// replaced once confirmed, and skipped without a terminal. Returns how
+    /// many targets were repaired.
+    pub fn fix(&self, options: &InstallOptions) -> Result<usize, DotmanError> {
+        self.check_preflight(options)?;
+        let _prompts = self.allow_prompts(options);
+        let replace = InstallOptions {
+            overwrite: true,
+            force: true,
+            ..options.clone()
+        };
+        let mut fixed = 0;
+        for report in self.link_statuses()? {
+            if options.is_cancelled() {
+                break;
+            }
+            if !options.selects_link(report.link) {
+                continue;
+            }
+            let result = match &report.status {
+                LinkStatus::NotLinked => self.install_link(report.link, options),
+                LinkStatus::WrongTarget(_)
+                    if !options.dry_run && self.mode_of(report.link).is_link() =>
+                {
+                    self.repoint(report.link, &report.source, &report.target)
+                }
+                LinkStatus::WrongTarget(_) => self.install_link(report.link, &replace),
+                LinkStatus::Modified | LinkStatus::NotSymlink => {
+                    let question = format!(
+                        "Replace {} with {}?",
+                        report.target.display(),
+                        report.source.display()
+                    );
+                    if !options.dry_run && !self.context.confirm(&question) {
+                        println!("{} {}", "Skipped:".yellow().bold(), report.target.display());
+                        continue;
+                    }
+                    self.install_link(report.link, &replace)
+                }
+                LinkStatus::WrongPermissions if options.dry_run => Ok(()),
+                LinkStatus::WrongPermissions => self.apply_permissions(report.link),
+                _ => continue,
+            };
+            result.map_err(|err| err.in_link(report.link))?;
+            fixed += 1;
+        }
+        if !options.dry_run {
+            self.state.lock().unwrap().save()?;
+        }
+        if options.is_cancelled() {
+            return Err(DotmanError::Cancelled);
+        }
+        Ok(fixed)
+    }
+
+    /// Clones every repository in `[repos]` that is not present yet.
+    pub fn ensure_repos(&self) -> Result<(), DotmanError> {
+        for (name, repo) in &self.config.repos {
+            let Some(dir) = self.config.named_repo_dir(name)? else {
+                continue;
+            };
+            if git::is_repo(&dir) {
+                continue;
+            }
+            let Some(url) = &repo.url else {
+                return Err(DotmanError::Repository(format!(
+                    "Repository '{}' is missing at {} and has no url",
+                    name,
+                    dir.display()
+                )));
+            };
+
+            git::clone(url, &dir, repo.branch.as_deref())?;
+            println!("{} {} -> {}", "Cloned:".green().bold(), url, dir.display());
+        }
+        Ok(())
+    }
+
+    /// Puts `link`'s target in place, then gives it the permissions and
+    /// owner it declares.
+    fn install_link(&self, link: &Link, options: &InstallOptions) -> Result<(), DotmanError> {
+        self.place_link(link, options)?;
+        if !options.dry_run && link.is_met(&self.context) {
+            self.apply_permissions(link)?;
+        }
+        Ok(())
+    }
+
+    fn place_link(&self, link: &Link, options: &InstallOptions) -> Result<(), DotmanError> {
+        let source = self.config.resolve_source(&link.source)?;
+        let target = link.target.expand_tilde_path()?.absolute()?;
+        let mode = self.mode_of(link);
+        let fs = self.fs_for(link);
+
+        if !link.is_met(&self.context) {
+            println!(
+                "{} {} failed condition check, skipping.",
+                "Ignored:".yellow().bold(),
+                source.display()
+            );
+            self.skip(&target, SkipReason::ConditionNotMet);
+            return Ok(());
+        }
+
+        if !fs.exists(&source) {
+            println!(
+                "{} {} was not found, and will not be linked. Skipping.",
+                "Ignored:".yellow().bold(),
+                source.display()
+            );
+            self.skip(&target, SkipReason::SourceMissing);
+            return Ok(());
+        }
+
+        // A dangling symlink is in the way too, though it does not exist.
+        if fs.kind(&target).is_some() {
+            let recorded = self
+                .state
+                .lock()
+                .unwrap()
+                .get(&target)
+                .and_then(|state| state.checksum.clone());
+            let written_by_dotman =
+                recorded.is_some() && fs.kind(&target) != Some(EntryKind::Symlink);
+
+            if let Some(recorded) = recorded.filter(|_| written_by_dotman)
+                && !self.matches_recorded(&target, &recorded)
+                && !options.force
+            {
                 println!(
-                    "{} {} failed condition check, skipping.",
-                    "Ignored:".yellow().bold(),
-                    source.display()
+                    "{} {} was modified since dotman wrote it, skipping. Use --force to replace it.",
+                    "Warning:".yellow().bold(),
+                    target.display()
                 );
-                continue;
+                println!("  Compare with: {}", diff_hint(mode, &source, &target));
+                self.skip(&target, SkipReason::TargetModified);
+                return Ok(());
             }
 
-            if !source.exists() {
+            if options.overwrite || self.config.overwrite || written_by_dotman {
+                if options.dry_run {
+                    println!(
+                        "{} {} -> {}",
+                        "Would replace:".cyan().bold(),
+                        source.display(),
+                        target.display()
+                    );
+                    self.skip(&target, SkipReason::DryRun);
+                    return Ok(());
+                }
+                let previous = self.state.lock().unwrap().get(&target).cloned();
+                if written_by_dotman
+                    && mode == LinkMode::Secret
+                    && let Some(previous) = &previous
+                {
+                    // Its plaintext is not kept; `undo` decrypts it again.
+                    self.emit(Event::SecretRewritten {
+                        target: target.clone(),
+                        previous: previous.clone(),
+                    });
+                }
+                if !written_by_dotman && let Some(backup_dir) = &self.backup_dir {
+                    self.back_up(fs, &target, backup_dir, None)?;
+                } else if written_by_dotman
+                    && mode != LinkMode::Secret
+                    && let Some(replaced_dir) = &self.replaced_dir
+                {
+                    // Kept so `undo` can put back what the previous run wrote.
+                    self.back_up(fs, &target, replaced_dir, previous)?;
+                } else if let Err(e) = fs.remove(&target) {
+                    println!(
+                        "{} Failed to remove existing target {}: {}",
+                        "Error:".red().bold(),
+                        target.display(),
+                        e
+                    );
+                    return Err(e.into());
+                }
+            } else {
                 println!(
-                    "{} {} was not found, and will not be linked. Skipping.",
-                    "Ignored:".yellow().bold(),
-                    source.display()
+                    "{} {} already exists, skipping. Use --overwrite to force linking.",
+                    "Warning:".yellow().bold(),
+                    target.display()
                 );
-                continue;
+                let linked = mode.is_link()
+                    && self
+                        .fs
+                        .read_link(&target)
+                        .is_ok_and(|actual| actual == source);
+                self.skip(
+                    &target,
+                    if linked {
+                        SkipReason::AlreadyLinked
+                    } else {
+                        SkipReason::TargetExists
+                    },
+                );
+                return Ok(());
             }
+        }
 
-            if target.exists() {
-                if self.config.overwrite {
-                    if target.is_dir() {
-                        if let Err(e) = std::fs::remove_dir_all(&target) {
-                            println!(
-                                "{} Failed to remove existing target directory {}: {}",
-                                "Error:".red().bold(),
-                                target.display(),
-                                e
-                            );
-                            return Err(DotmanError::IoError(e));
-                        }
-                    } else if let Err(e) = std::fs::remove_file(&target) {
-                        println!(
-                            "{} Failed to remove existing target {}: {}",
-                            "Error:".red().bold(),
-                            target.display(),
-                            e
-                        );
-                        return Err(e.into());
-                    }
+        if options.dry_run {
+            println!(
+                "{} {} -> {}",
+                "Would link:".cyan().bold(),
+                source.display(),
+                target.display()
+            );
+            self.skip(&target, SkipReason::DryRun);
+            return Ok(());
+        }
+
+        if options.ask {
+            use std::io::{self, Write};
+            print!("Link {} -> {}? [y/N] ", source.display(), target.display());
+            io::stdout().flush().map_err(DotmanError::IoError)?;
+            let mut input = String::new();
+            io::stdin()
+                .read_line(&mut input)
+                .map_err(DotmanError::IoError)?;
+            let input = input.trim().to_lowercase();
+            if input != "y" && input != "yes" {
+                println!("{} Skipping.", "Skipped:".yellow().bold());
+                self.skip(&target, SkipReason::Declined);
+                return Ok(());
+            }
+        }
+
+        let checksum = match mode {
+            LinkMode::Symlink | LinkMode::Junction => {
+                if mode == LinkMode::Junction {
+                    fs.junction(&source, &target)?;
                 } else {
+                    fs.symlink(&source, &target)?;
+                }
+                println!(
+                    "{} {} -> {}",
+                    "Linked:".green().bold(),
+                    source.display(),
+                    target.display()
+                );
+                None
+            }
+            LinkMode::Secret => {
+                let plaintext = secrets::decrypt(&self.config, &source)?;
+                fs.write(&target, &plaintext, true)?;
+                println!(
+                    "{} {} -> {}",
+                    "Decrypted:".green().bold(),
+                    source.display(),
+                    target.display()
+                );
+                Some(hash::digest(self.config.integrity().algorithm, &plaintext))
+            }
+            LinkMode::Template => {
+                let rendered = template::render(&self.config, &source)?;
+                fs.write(&target, rendered.as_bytes(), false)?;
+                println!(
+                    "{} {} -> {}",
+                    "Rendered:".green().bold(),
+                    source.display(),
+                    target.display()
+                );
+                Some(hash::digest(
+                    self.config.integrity().algorithm,
+                    rendered.as_bytes(),
+                ))
+            }
+            LinkMode::Concat => {
+                let contents = self.concatenate(link, &source)?;
+                fs.write(&target, &contents, false)?;
+                println!(
+                    "{} {} -> {}",
+                    "Assembled:".green().bold(),
+                    source.display(),
+                    target.display()
+                );
+                Some(hash::digest(self.config.integrity().algorithm, &contents))
+            }
+            LinkMode::Copy => {
+                if link.mode == LinkMode::Symlink {
                     println!(
-                        "{} {} already exists, skipping. Use --overwrite to force linking.",
+                        "{} Creating symlinks needs Developer Mode or administrator rights, copying {} instead.",
                         "Warning:".yellow().bold(),
-                        target.display()
+                        source.display()
                     );
-                    continue;
                 }
+                fs.copy(&source, &target)?;
+                println!(
+                    "{} {} -> {}",
+                    "Copied:".green().bold(),
+                    source.display(),
+                    target.display()
+                );
+                Some(self.checksum(&self.config.integrity(), &source)?)
             }
+        };
+
+        self.emit(Event::LinkCreated {
+            source: source.clone(),
+            target: target.clone(),
+            mode,
+        });
+        self.state
+            .lock()
+            .unwrap()
+            .record(&target, TargetState { source, checksum });
+        Ok(())
+    }
+
+    /// Runs the actions `options` selects. Unless `options.fail_fast` is
+    /// set, the rest still run when one fails.
+    fn run_actions(&self, options: &InstallOptions) -> Result<(), DotmanError> {
+        self.run_actions_where(options, |_| true).map(drop)
+    }
 
-            if self.config.ask {
-                use std::io::{self, Write};
-                print!("Link {} -> {}? [y/N] ", source.display(), target.display());
-                io::stdout().flush().map_err(DotmanError::IoError)?;
-                let mut input = String::new();
-                io::stdin()
-                    .read_line(&mut input)
-                    .map_err(DotmanError::IoError)?;
-                let input = input.trim().to_lowercase();
-                if input != "y" && input != "yes" {
-                    println!("{} Skipping.", "Skipped:".yellow().bold());
-                    continue;
+    /// Runs the selected actions that `filter` accepts and remembers which
+    /// command each one ran. Returns how many ran, or would run when
+    /// `options.dry_run` is set.
+    fn run_actions_where(
+        &self,
+        options: &InstallOptions,
+        mut filter: impl FnMut(&Action) -> bool,
+    ) -> Result<usize, DotmanError> {
+        let mut errors = Vec::new();
+        let mut ran = 0;
+        for action in self.config.get_effective_actions() {
+            if !filter(action) || !self.should_run(action, options)? {
+                continue;
+            }
+            ran += 1;
+            if options.dry_run {
+                println!("{} {}", "Would run:".cyan().bold(), action.name());
+                continue;
+            }
+            self.announce(action);
+            let result = match action {
+                Action::ShellCommand { name, run, .. } => self
+                    .elevation(action)
+                    .and_then(|elevate| self.finish_action(name, run.execute_as(elevate)))
+                    .map_err(|err| err.in_action(name)),
+                _ => self
+                    .perform(action)
+                    .map_err(|err| err.in_action(action.name())),
+            };
+            match result {
+                Ok(()) => self
+                    .state
+                    .lock()
+                    .unwrap()
+                    .record_action(action.name(), self.action_hash(action)),
+                Err(err) if options.fail_fast => {
+                    self.state.lock().unwrap().save()?;
+                    return Err(err);
                 }
+                Err(err) => errors.push(err),
             }
+        }
+        if !options.dry_run {
+            self.state.lock().unwrap().save()?;
+        }
+        DotmanError::collect(errors).map(|()| ran)
+    }
 
-            utils::symlink(source.clone(), target.clone())?;
+    /// Whether `action` is selected, its conditions are met and it has
+    /// something left to do. Skipped actions are reported.
+    fn should_run(&self, action: &Action, options: &InstallOptions) -> Result<bool, DotmanError> {
+        if options.is_cancelled() {
+            return Err(DotmanError::Cancelled);
+        }
+        if !options.selects_action(action) {
+            return Ok(false);
+        }
+        let name = action.name();
+        if !action.is_met(&self.context) {
+            println!(
+                "{} {} failed condition check, skipping.",
+                "Ignored:".yellow().bold(),
+                name
+            );
+            return Ok(false);
+        }
+        if self.is_done(action) {
+            println!(
+                "{} {} is up to date, skipping.",
+                "Ignored:".yellow().bold(),
+                name
+            );
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Announces that `action` starts running.
+    fn announce(&self, action: &Action) {
+        println!(
+            "{} Running action: {}",
+            "Action:".blue().bold(),
+            action.name()
+        );
+        self.emit(Event::ActionStarted {
+            name: action.name().to_string(),
+        });
+    }
 
+    /// Whether `action` has nothing left to do, like a download whose
+    /// destination already has the expected digest, an archive that was
+    /// extracted before and has not changed since, or a line or snippet
+    /// already in its file.
+    pub(crate) fn is_done(&self, action: &Action) -> bool {
+        match action {
+            Action::ShellCommand { .. } => false,
+            Action::Download { dest, sha256, .. } => self.is_downloaded(dest, sha256),
+            Action::Extract { dest, .. } => {
+                self.config
+                    .resolve_path(dest)
+                    .is_ok_and(|dest| dest.exists())
+                    && self.state.lock().unwrap().action_hash(action.name())
+                        == Some(self.action_hash(action).as_str())
+            }
+            Action::Git { .. } => false,
+            Action::LineInFile {
+                name,
+                path,
+                line,
+                block,
+                comment,
+                ..
+            } => line_in_file::Managed::new(
+                name,
+                line.as_deref(),
+                block.as_deref(),
+                comment.as_deref(),
+            )
+            .is_ok_and(|managed| self.has_line(path, managed)),
+            Action::Merge { source, target, .. } => self.is_merged(source, target),
+            Action::SystemdUnit { unit, source, .. } => {
+                self.is_unit_enabled(unit, source.as_deref())
+            }
+            Action::Registry { .. } => registry::RegistryValue::of(action)
+                .is_some_and(|value| self.has_registry_value(&value)),
+        }
+    }
+
+    /// Identifies what an action does, such as the command it runs or the
+    /// file it downloads, to tell when it changed.
+    fn action_hash(&self, action: &Action) -> String {
+        match action {
+            Action::ShellCommand { run, .. } => {
+                hash::sha256(format!("{} -c {}", run.shell(), run.command()).as_bytes())
+            }
+            Action::Download {
+                url,
+                dest,
+                sha256,
+                mode,
+                ..
+            } => hash::sha256(format!("{url} {dest} {sha256} {mode:?}").as_bytes()),
+            Action::Extract {
+                source,
+                dest,
+                sha256,
+                strip_components,
+                ..
+            } => {
+                let digest = sha256.clone().or_else(|| self.archive_digest(source));
+                hash::sha256(format!("{source} {dest} {digest:?} {strip_components}").as_bytes())
+            }
+            Action::Git {
+                url,
+                dest,
+                branch,
+                rev,
+                depth,
+                ..
+            } => hash::sha256(format!("{url} {dest} {branch:?} {rev:?} {depth:?}").as_bytes()),
+            Action::LineInFile {
+                path,
+                line,
+                block,
+                comment,
+                ..
+            } => hash::sha256(format!("{path} {line:?} {block:?} {comment:?}").as_bytes()),
+            Action::Merge { source, target, .. } => {
+                hash::sha256(format!("{source} {target}").as_bytes())
+            }
+            Action::SystemdUnit { unit, source, .. } => {
+                hash::sha256(format!("{unit} {source:?}").as_bytes())
+            }
+            Action::Registry {
+                hive,
+                key,
+                value,
+                value_type,
+                data,
+                ..
+            } => hash::sha256(format!("{hive} {key} {value:?} {value_type:?} {data}").as_bytes()),
+        }
+    }
+
+    /// Runs an action dotman carries out itself rather than through a shell,
+    /// and reports its outcome.
+    pub(crate) fn perform(&self, action: &Action) -> Result<(), DotmanError> {
+        let result = match action {
+            Action::ShellCommand { .. } => Ok(()),
+            Action::Download {
+                url,
+                dest,
+                sha256,
+                mode,
+                ..
+            } => self.download(url, dest, sha256, mode.as_deref()),
+            Action::Extract {
+                source,
+                dest,
+                sha256,
+                strip_components,
+                ..
+            } => self.extract(source, dest, sha256.as_deref(), *strip_components),
+            Action::Git {
+                url,
+                dest,
+                branch,
+                rev,
+                depth,
+                ..
+            } => self.clone_or_update(url, dest, branch.as_deref(), rev.as_deref(), *depth),
+            Action::LineInFile {
+                name,
+                path,
+                line,
+                block,
+                comment,
+                ..
+            } => line_in_file::Managed::new(
+                name,
+                line.as_deref(),
+                block.as_deref(),
+                comment.as_deref(),
+            )
+            .and_then(|managed| self.ensure_line(path, managed)),
+            Action::Merge { source, target, .. } => self.merge_into(source, target),
+            Action::SystemdUnit { unit, source, .. } => self.enable_unit(unit, source.as_deref()),
+            Action::Registry { .. } => match registry::RegistryValue::of(action) {
+                Some(value) => self.set_registry_value(&value),
+                None => Ok(()),
+            },
+        };
+        self.emit(Event::ActionFinished {
+            name: action.name().to_string(),
+            success: result.is_ok(),
+        });
+        result
+    }
+
+    /// Reports the outcome of the action called `name`.
+    fn finish_action(
+        &self,
+        name: &str,
+        output: std::io::Result<std::process::Output>,
+    ) -> Result<(), DotmanError> {
+        self.emit(Event::ActionFinished {
+            name: name.to_string(),
+            success: output.as_ref().is_ok_and(|output| output.status.success()),
+        });
+        let output = output?;
+        let log = self.log_action(name, &output);
+
+        if output.status.success() {
             println!(
-                "{} {} -> {}",
-                "Linked:".green().bold(),
-                source.display(),
-                target.display()
+                "{} {}",
+                "Success:".green().bold(),
+                String::from_utf8_lossy(&output.stdout)
             );
+            Ok(())
+        } else {
+            if let Some(log) = log {
+                println!("  Full output: {}", log.display());
+            }
+            Err(DotmanError::CommandError {
+                command: name.to_string(),
+                message: String::from_utf8_lossy(&output.stderr).to_string(),
+            })
         }
+    }
 
-        for action in self.config.get_effective_actions() {
-            match action {
-                Action::ShellCommand {
+    /// Saves the output of the action called `name` in the `[logging]`
+    /// directory, if one is set, and returns where. Failing to write the log
+    /// only warns.
+    fn log_action(&self, name: &str, output: &std::process::Output) -> Option<std::path::PathBuf> {
+        let dir = self.config.logging.as_ref()?.actions_dir.as_ref()?;
+        let file_name: String = name
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        let file_name = file_name.trim_matches('-');
+        let time = chrono::Local::now().format("%Y%m%d-%H%M%S");
+
+        let write = || -> Result<std::path::PathBuf, DotmanError> {
+            let dir = dir.expand_tilde_path()?;
+            std::fs::create_dir_all(&dir)?;
+            let path = dir.join(format!("{time}-{file_name}.log"));
+            let log = format!(
+                "{}\n{}\n--- stdout ---\n{}\n--- stderr ---\n{}",
+                name,
+                output.status,
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+            std::fs::write(&path, log)?;
+            Ok(path)
+        };
+        write()
+            .inspect_err(|err| {
+                println!(
+                    "{} Could not write the log of {}: {}",
+                    "Warning:".yellow().bold(),
                     name,
-                    run,
-                    if_cond,
-                    if_not_cond,
-                    ..
-                } => {
-                    if !condition_is_met(if_cond, if_not_cond, &os, hostname.as_deref()) {
-                        println!(
-                            "{} {} failed condition check, skipping.",
-                            "Ignored:".yellow().bold(),
-                            name
-                        );
-                        continue;
-                    }
+                    err
+                )
+            })
+            .ok()
+    }
+
+    /// Moves `target` to the same path under `dir`.
+    fn back_up(
+        &self,
+        fs: &dyn Filesystem,
+        target: &std::path::Path,
+        dir: &std::path::Path,
+        previous: Option<TargetState>,
+    ) -> Result<(), DotmanError> {
+        let relative: std::path::PathBuf = target
+            .components()
+            .filter(|component| matches!(component, std::path::Component::Normal(_)))
+            .collect();
+        let backup = dir.join(relative);
+        fs.rename(target, &backup)?;
+        println!(
+            "{} {} -> {}",
+            "Backed up:".blue().bold(),
+            target.display(),
+            backup.display()
+        );
+        self.emit(Event::TargetBackedUp {
+            target: target.to_path_buf(),
+            backup,
+            previous,
+        });
+        Ok(())
+    }
+
+    /// Reverts a run from the journal, newest change first: removes the
+    /// targets it installed, moves back what it backed up or wrote over and
+    /// runs the `undo` command of each action it ran. Targets it removed are
+    /// not restored.
+    pub fn undo(&self, run: &Run) -> Result<(), DotmanError> {
+        let mut errors = Vec::new();
+        for change in run.changes.iter().rev() {
+            let result = match change {
+                Change::Link { target, .. } => self.unlink(target),
+                Change::Backup {
+                    target,
+                    backup,
+                    previous,
+                } => self.restore(target, backup, previous.as_ref()),
+                Change::Redecrypt { target, previous } => self.redecrypt(target, previous),
+                Change::Action {
+                    name,
+                    success: true,
+                } => self.undo_action(name),
+                Change::Action { success: false, .. } => Ok(()),
+                Change::Unlink { target } => {
+                    println!(
+                        "{} {} was removed and is not restored.",
+                        "Ignored:".yellow().bold(),
+                        target.display()
+                    );
+                    Ok(())
+                }
+            };
+            errors.extend(result.err());
+        }
+        self.state.lock().unwrap().save()?;
+        DotmanError::collect(errors)
+    }
+
+    fn unlink(&self, target: &std::path::Path) -> Result<(), DotmanError> {
+        if self.fs.kind(target).is_none() {
+            println!(
+                "{} {} does not exist, skipping.",
+                "Ignored:".yellow().bold(),
+                target.display()
+            );
+            return Ok(());
+        }
+        self.fs.remove(target)?;
+        self.state.lock().unwrap().forget(target);
+        self.emit(Event::LinkRemoved {
+            target: target.to_path_buf(),
+        });
+        println!(
+            "{} {} removed.",
+            "Removed:".green().bold(),
+            target.display()
+        );
+        Ok(())
+    }
+
+    fn restore(
+        &self,
+        target: &std::path::Path,
+        backup: &std::path::Path,
+        previous: Option<&TargetState>,
+    ) -> Result<(), DotmanError> {
+        if self.fs.kind(target).is_some() {
+            return Err(DotmanError::RestoreConflict {
+                target: target.to_path_buf(),
+                backup: backup.to_path_buf(),
+            });
+        }
+        self.fs.rename(backup, target)?;
+        if let Some(previous) = previous {
+            self.state.lock().unwrap().record(target, previous.clone());
+        }
+        println!(
+            "{} {} -> {}",
+            "Restored:".green().bold(),
+            backup.display(),
+            target.display()
+        );
+        Ok(())
+    }
+
+    /// Decrypts the secret dotman had written at `target` before a run wrote
+    /// it again, from the source it was decrypted from then.
+    fn redecrypt(
+        &self,
+        target: &std::path::Path,
+        previous: &TargetState,
+    ) -> Result<(), DotmanError> {
+        if self.fs.kind(target).is_some() {
+            return Err(DotmanError::RestoreConflict {
+                target: target.to_path_buf(),
+                backup: previous.source.clone(),
+            });
+        }
+        let plaintext = secrets::decrypt(&self.config, &previous.source)?;
+        self.fs.write(target, &plaintext, true)?;
+        self.state.lock().unwrap().record(
+            target,
+            TargetState {
+                source: previous.source.clone(),
+                checksum: Some(hash::digest(self.config.integrity().algorithm, &plaintext)),
+            },
+        );
+        println!(
+            "{} {} -> {}",
+            "Decrypted:".green().bold(),
+            previous.source.display(),
+            target.display()
+        );
+        Ok(())
+    }
+
+    fn undo_action(&self, name: &str) -> Result<(), DotmanError> {
+        let Some((action, undo)) = self
+            .config
+            .actions
+            .iter()
+            .find(|action| action.name() == name)
+            .and_then(|action| Some((action, action.undo()?)))
+        else {
+            println!(
+                "{} Action {} has no undo command, skipping.",
+                "Ignored:".yellow().bold(),
+                name
+            );
+            return Ok(());
+        };
+        let name = format!("{name} (undo)");
+        println!("{} Running action: {}", "Action:".blue().bold(), name);
+        self.emit(Event::ActionStarted { name: name.clone() });
+        let elevate = self.elevation(action)?;
+        self.finish_action(&name, undo.execute_as(elevate))
+            .map_err(|err| err.in_action(&name))
+    }
+
+    pub fn remove(&self) -> Result<(), DotmanError> {
+        for link in self.config.get_effective_links() {
+            let target = link.target.expand_tilde_path()?.absolute()?;
+            let fs = self.fs_for(link);
+
+            if !fs.exists(&target) {
+                println!(
+                    "{} {} does not exist, skipping.",
+                    "Ignored:".yellow().bold(),
+                    target.display()
+                );
+                continue;
+            }
+
+            if let Err(e) = fs.remove(&target) {
+                eprintln!(
+                    "{} Failed to remove {}: {}",
+                    "Error:".red().bold(),
+                    target.display(),
+                    e
+                );
+                return Err(e.into());
+            }
+
+            self.state.lock().unwrap().forget(&target);
+            self.emit(Event::LinkRemoved {
+                target: target.clone(),
+            });
+            println!(
+                "{} {} removed.",
+                "Removed:".green().bold(),
+                target.display()
+            );
+        }
+
+        self.state.lock().unwrap().save()
+    }
+
+    /// Prints the status of everything configured. Returns how many links
+    /// are missing or have drifted, leaving out those whose conditions are
+    /// not met, plus how many targets are orphaned.
+    pub fn status(&self) -> Result<usize, DotmanError> {
+        println!("{}", "Dotman Status Report".blue().bold());
+        println!();
+
+        println!("{}", "Links:".blue().bold());
+        println!();
+
+        let mut unconverged = 0;
+        for report in self.link_statuses()? {
+            if !matches!(report.status, LinkStatus::Ok | LinkStatus::ConditionNotMet) {
+                unconverged += 1;
+            }
+            match &report.status {
+                LinkStatus::Ok => print!("{}", report.status.label().green().bold()),
+                LinkStatus::WrongTarget(actual_source) => print!(
+                    "{} (points to {})",
+                    report.status.label().red().bold(),
+                    actual_source.display()
+                ),
+                LinkStatus::SourceMissing
+                | LinkStatus::SymlinkError
+                | LinkStatus::DecryptFailed
+                | LinkStatus::RenderFailed => {
+                    print!("{}", report.status.label().red().bold())
+                }
+                LinkStatus::ConditionNotMet
+                | LinkStatus::NotLinked
+                | LinkStatus::NotSymlink
+                | LinkStatus::Modified
+                | LinkStatus::WrongPermissions => {
+                    print!("{}", report.status.label().yellow().bold())
+                }
+            }
+
+            print!(" ");
+            println!("{} -> {}", report.source.display(), report.target.display());
+        }
+
+        let orphans = self.orphans();
+        if !orphans.is_empty() {
+            unconverged += orphans.len();
+            println!();
+            println!("{}", "Orphaned:".blue().bold());
+            println!();
+            self.print_orphans(&orphans);
+        }
+
+        if !self.config.get_effective_dirs().is_empty() {
+            println!();
+            println!("{}", "Directories:".blue().bold());
+            println!();
+            self.print_dir_status()?;
+        }
+
+        if let Some(gitconfig) = &self.config.gitconfig {
+            println!();
+            println!("{}", "Git config:".blue().bold());
+            println!();
+            self.print_gitconfig_status(gitconfig)?;
+        }
+
+        if shell_env::exports_anything(&self.config) {
+            println!();
+            println!("{}", "Shell environment:".blue().bold());
+            println!();
+            self.print_shell_env_status()?;
+        }
+
+        if !self.config.get_effective_defaults().is_empty() {
+            println!();
+            println!("{}", "Defaults:".blue().bold());
+            println!();
+            self.print_defaults_status();
+        }
+
+        if !self.config.get_effective_gsettings().is_empty() {
+            println!();
+            println!("{}", "GNOME settings:".blue().bold());
+            println!();
+            self.print_gsettings_status();
+        }
 
-                    println!("{} Running action: {}", "Action:".blue().bold(), name);
+        if !self.config.get_effective_actions().is_empty() {
+            println!();
+            println!("{}", "Actions:".blue().bold());
+            println!();
 
-                    let output = run.execute()?;
+            for action in self.config.get_effective_actions() {
+                if !action.is_met(&self.context) {
+                    print!("{}", "[CONDITION NOT MET]".yellow().bold());
+                } else if self.is_done(action) {
+                    print!("{}", "[OK]".green().bold());
+                } else {
+                    print!("{}", "[READY TO RUN]".green().bold());
+                }
 
-                    if output.status.success() {
-                        println!(
-                            "{} {}",
-                            "Success:".green().bold(),
-                            String::from_utf8_lossy(&output.stdout)
-                        );
-                    } else {
-                        return Err(DotmanError::CommandError {
-                            command: name.clone(),
-                            message: String::from_utf8_lossy(&output.stderr).to_string(),
-                        });
-                    }
+                print!(" ");
+                match action {
+                    Action::SystemdUnit { unit, .. } => println!(
+                        "Action: {} ({})",
+                        action.name(),
+                        systemd::UnitState::of(unit)
+                    ),
+                    _ => println!("Action: {}", action.name()),
                 }
             }
         }
-        Ok(())
-    }
-
-    pub fn remove(&self) -> Result<(), DotmanError> {
-        for link in self.config.get_effective_links() {
-            let target = link.target.expand_tilde_path()?.absolute()?;
 
-            if !target.exists() {
-                println!(
-                    "{} {} does not exist, skipping.",
-                    "Ignored:".yellow().bold(),
-                    target.display()
-                );
-                continue;
-            }
+        if !self.config.get_effective_packages().is_empty() {
+            println!();
+            println!("{}", "Packages:".blue().bold());
+            println!();
+            self.print_package_status()?;
+        }
 
-            if target.is_dir() {
-                if let Err(e) = std::fs::remove_dir_all(&target) {
-                    eprintln!(
-                        "{} Failed to remove directory {}: {}",
-                        "Error:".red().bold(),
-                        target.display(),
-                        e
-                    );
-                    return Err(e.into());
-                }
-            } else if let Err(e) = std::fs::remove_file(&target) {
-                eprintln!(
-                    "{} Failed to remove file {}: {}",
-                    "Error:".red().bold(),
-                    target.display(),
-                    e
-                );
-                return Err(e.into());
-            }
+        Ok(unconverged)
+    }
 
-            println!(
-                "{} {} removed.",
-                "Removed:".green().bold(),
-                target.display()
-            );
+    /// How `link` is put in place on this machine. Where symlinks cannot be
+    /// created, they are copied instead.
+    fn mode_of(&self, link: &Link) -> LinkMode {
+        if link.mode == LinkMode::Symlink && !utils::can_create_symlinks() {
+            LinkMode::Copy
+        } else {
+            link.mode
         }
-
-        Ok(())
     }
 
-    pub fn status(&self) -> Result<(), DotmanError> {
-        let os = utils::get_current_os();
-        let hostname = utils::get_hostname();
+    /// The checksum the target of a copied, secret, template or concat link
+    /// should have: that of the source, or of what it decrypts, renders or
+    /// is assembled to.
+    fn expected_checksum(
+        &self,
+        link: &Link,
+        mode: LinkMode,
+        source: &std::path::Path,
+    ) -> Result<String, DotmanError> {
+        let integrity = self.config.integrity();
+        let contents = match mode {
+            LinkMode::Copy => return Ok(self.checksum(&integrity, source)?),
+            LinkMode::Secret => secrets::decrypt(&self.config, source)?,
+            LinkMode::Concat => self.concatenate(link, source)?,
+            _ => template::render(&self.config, source)?.into_bytes(),
+        };
+        Ok(hash::digest(integrity.algorithm, &contents))
+    }
 
-        println!("{}", "Dotman Status Report".blue().bold());
-        println!();
+    /// Compares the target of a copied, secret, template or concat link with
+    /// its source by hash, without writing the generated contents anywhere.
+    fn generated_status(
+        &self,
+        link: &Link,
+        mode: LinkMode,
+        source: &std::path::Path,
+        target: &std::path::Path,
+    ) -> LinkStatus {
+        let expected = match self.expected_checksum(link, mode, source) {
+            Ok(expected) => expected,
+            Err(_) if matches!(mode, LinkMode::Copy | LinkMode::Concat) => {
+                return LinkStatus::SourceMissing;
+            }
+            Err(_) if mode == LinkMode::Secret => return LinkStatus::DecryptFailed,
+            Err(_) => return LinkStatus::RenderFailed,
+        };
+        match self.checksum(&self.config.integrity(), target) {
+            Ok(digest) if digest == expected => LinkStatus::Ok,
+            _ => LinkStatus::Modified,
+        }
+    }
 
-        println!("{}", "Links:".blue().bold());
-        println!();
+    /// Checks that every installed target still matches its source: symlinks
+    /// point at their source, and written files have the checksum dotman
+    /// recorded, which in turn matches what the source generates now.
+    /// Links that were never installed are not checked.
+    pub fn verify(&self) -> Result<Vec<(std::path::PathBuf, Mismatch)>, DotmanError> {
+        let state = self.state.lock().unwrap().clone();
 
+        let mut mismatches = Vec::new();
         for link in self.config.get_effective_links() {
-            let source = link.source.expand_tilde_path()?.absolute()?;
-            let target = link.target.expand_tilde_path()?.absolute()?;
-
-            if !link.is_met(&os, hostname.as_deref()) {
-                print!("{}", "[CONDITION NOT MET]".yellow().bold());
+            if !link.is_met(&self.context) {
                 continue;
             }
-
-            if !source.exists() {
-                print!("{}", "[SOURCE MISSING]".red().bold());
-                continue;
-            }
-
-            if !target.exists() {
-                print!("{}", "[NOT LINKED]".yellow().bold());
+            let source = self.config.resolve_source(&link.source)?;
+            let target = link.target.expand_tilde_path()?.absolute()?;
+            let recorded = state.get(&target);
+            let kind = self.fs.kind(&target);
+            let exists = kind.is_some();
+            if recorded.is_none() && !exists {
                 continue;
             }
 
-            if target.is_symlink() {
-                match target.read_link() {
-                    Ok(actual_source) => {
-                        if actual_source == source {
-                            print!("{}", "[OK]".green().bold());
-                        } else {
-                            print!(
-                                "{} (points to {})",
-                                "[WRONG TARGET]".red().bold(),
-                                actual_source.display()
-                            );
-                        }
+            let mode = self.mode_of(link);
+            let mismatch = if !exists {
+                Some(Mismatch::Missing)
+            } else if mode.is_link() {
+                match self.fs.read_link(&target) {
+                    Ok(actual_source) if actual_source == source => None,
+                    Ok(actual_source) => Some(Mismatch::WrongTarget(actual_source)),
+                    Err(_) => Some(Mismatch::NotSymlink),
+                }
+            } else if kind == Some(EntryKind::Symlink) {
+                Some(Mismatch::WrongTarget(self.fs.read_link(&target)?))
+            } else {
+                let integrity = self.config.integrity();
+                let recorded = recorded.and_then(|state| state.checksum.as_ref());
+                match self.expected_checksum(link, mode, &source) {
+                    Err(err) => Some(Mismatch::Unreadable(err.to_string())),
+                    Ok(_)
+                        if recorded
+                            .is_some_and(|recorded| !self.matches_recorded(&target, recorded)) =>
+                    {
+                        Some(Mismatch::Modified)
                     }
-                    Err(_) => {
-                        print!("{}", "[SYMLINK ERROR]".red().bold());
+                    Ok(expected) if expected != self.checksum(&integrity, &target)? => {
+                        Some(Mismatch::Outdated)
                     }
+                    Ok(_) => None,
                 }
-            } else {
-                print!("{}", "[EXISTS BUT NOT SYMLINK]".yellow().bold());
-            }
+            };
 
-            print!(" ");
-            println!("{} -> {}", source.display(), target.display());
+            if let Some(mismatch) = mismatch {
+                mismatches.push((target, mismatch));
+            }
         }
+        self.save_checksums();
+        Ok(mismatches)
+    }
 
-        if !self.config.get_effective_actions().is_empty() {
-            println!();
-            println!("{}", "Actions:".blue().bold());
-            println!();
+    /// Reports the state of every effective link.
+    pub fn link_statuses(&self) -> Result<Vec<LinkReport<'_>>, DotmanError> {
+        let mut reports = Vec::new();
+        for link in self.config.get_effective_links() {
+            let source = self.config.resolve_source(&link.source)?;
+            let target = link.target.expand_tilde_path()?.absolute()?;
 
-            for action in self.config.get_effective_actions() {
-                match action {
-                    Action::ShellCommand {
-                        name,
-                        if_cond,
-                        if_not_cond,
-                        ..
-                    } => {
-                        if !condition_is_met(if_cond, if_not_cond, &os, hostname.as_deref()) {
-                            print!("{}", "[CONDITION NOT MET]".yellow().bold());
-                        } else {
-                            print!("{}", "[READY TO RUN]".green().bold());
-                        }
-
-                        print!(" ");
-                        println!("Action: {}", name);
+            let mode = self.mode_of(link);
+            let is_symlink = self.fs.kind(&target) == Some(EntryKind::Symlink);
+            let status = if !link.is_met(&self.context) {
+                LinkStatus::ConditionNotMet
+            } else if !self.fs.exists(&source) {
+                LinkStatus::SourceMissing
+            } else if !self.fs.exists(&target) && !is_symlink {
+                LinkStatus::NotLinked
+            } else if !mode.is_link() && !is_symlink {
+                self.generated_status(link, mode, &source, &target)
+            } else if is_symlink {
+                match self.fs.read_link(&target) {
+                    Ok(actual_source) if actual_source == source && mode.is_link() => {
+                        LinkStatus::Ok
                     }
+                    Ok(actual_source) => LinkStatus::WrongTarget(actual_source),
+                    Err(_) => LinkStatus::SymlinkError,
                 }
-            }
+            } else {
+                LinkStatus::NotSymlink
+            };
+            let status = if status == LinkStatus::Ok && !self.has_permissions(link, &target) {
+                LinkStatus::WrongPermissions
+            } else {
+                status
+            };
+
+            reports.push(LinkReport {
+                link,
+                source,
+                target,
+                status,
+            });
         }
+        self.save_checksums();
+        Ok(reports)
+    }
+}
 
-        Ok(())
+/// Command that shows how a locally modified target differs from its source.
+fn diff_hint(mode: LinkMode, source: &std::path::Path, target: &std::path::Path) -> String {
+    match mode {
+        LinkMode::Secret => format!(
+            "dotman secret decrypt {} | diff -u - {}",
+            source.display(),
+            target.display()
+        ),
+        _ => format!("diff -u {} {}", source.display(), target.display()),
     }
 }
 
@@ -278,14 +1616,10 @@ mod tests {
     use tempfile::TempDir;
 
     fn create_test_config(links: Vec<Link>, actions: Vec<Action>) -> DotmanConfig {
-        DotmanConfig {
-            links,
-            actions,
-            overwrite: false,
-            ask: false,
-            config_path: String::new(),
-            selected_profile: None,
-        }
+        DotmanConfig::builder()
+            .links(links)
+            .actions(actions)
+            .build()
     }
 
     #[test]
@@ -296,13 +1630,7 @@ mod tests {
 
         fs::write(&source_file, "test content").unwrap();
 
-        let link = Link {
-            source: source_file.to_string_lossy().to_string(),
-            target: target_file.to_string_lossy().to_string(),
-            if_cond: None,
-            if_not_cond: None,
-            profiles: vec![],
-        };
+        let link = Link::new(source_file.to_string_lossy(), target_file.to_string_lossy());
 
         let config = create_test_config(vec![link], vec![]);
         let dotman = Dotman::new(config);
@@ -321,18 +1649,12 @@ mod tests {
 
         fs::write(&source_file, "test content").unwrap();
 
-        let link = Link {
-            source: source_file.to_string_lossy().to_string(),
-            target: target_file.to_string_lossy().to_string(),
-            if_cond: Some(Condition {
-                os: vec![],
-                hostname: None,
+        let link = Link::new(source_file.to_string_lossy(), target_file.to_string_lossy()).with_if(
+            Condition {
                 run: Some(RunCommand::Simple("true".to_string())),
                 ..Default::default()
-            }),
-            if_not_cond: None,
-            profiles: vec![],
-        };
+            },
+        );
 
         let config = create_test_config(vec![link], vec![]);
         let dotman = Dotman::new(config);
@@ -350,18 +1672,12 @@ mod tests {
 
         fs::write(&source_file, "test content").unwrap();
 
-        let link = Link {
-            source: source_file.to_string_lossy().to_string(),
-            target: target_file.to_string_lossy().to_string(),
-            if_cond: Some(Condition {
-                os: vec![],
-                hostname: None,
+        let link = Link::new(source_file.to_string_lossy(), target_file.to_string_lossy()).with_if(
+            Condition {
                 run: Some(RunCommand::Simple("false".to_string())),
                 ..Default::default()
-            }),
-            if_not_cond: None,
-            profiles: vec![],
-        };
+            },
+        );
 
         let config = create_test_config(vec![link], vec![]);
         let dotman = Dotman::new(config);
@@ -378,13 +1694,7 @@ mod tests {
 
         fs::write(&target_file, "test content").unwrap();
 
-        let link = Link {
-            source: "source.txt".to_string(),
-            target: target_file.to_string_lossy().to_string(),
-            if_cond: None,
-            if_not_cond: None,
-            profiles: vec![],
-        };
+        let link = Link::new("source.txt".to_string(), target_file.to_string_lossy());
 
         let config = create_test_config(vec![link], vec![]);
         let dotman = Dotman::new(config);
@@ -393,4 +1703,590 @@ mod tests {
 
         assert!(!target_file.exists());
     }
+
+    #[test]
+    fn test_dotman_sync_links_only_missing_targets() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("source.txt");
+        let linked_target = temp_dir.path().join("linked.txt");
+        let drifted_target = temp_dir.path().join("drifted.txt");
+
+        fs::write(&source_file, "test content").unwrap();
+        fs::write(&drifted_target, "local edit").unwrap();
+
+        let links = [&linked_target, &drifted_target]
+            .iter()
+            .map(|target| Link::new(source_file.to_string_lossy(), target.to_string_lossy()))
+            .collect();
+
+        let config = create_test_config(links, vec![]);
+        let dotman = Dotman::new(config);
+
+        dotman.sync(&InstallOptions::default(), false).unwrap();
+
+        let statuses: Vec<LinkStatus> = dotman
+            .link_statuses()
+            .unwrap()
+            .into_iter()
+            .map(|report| report.status)
+            .collect();
+        assert_eq!(statuses, vec![LinkStatus::Ok, LinkStatus::NotSymlink]);
+        assert_eq!(fs::read_to_string(&drifted_target).unwrap(), "local edit");
+    }
+
+    #[test]
+    fn test_dotman_install_template_link() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("gitconfig");
+        let target_file = temp_dir.path().join(".gitconfig");
+
+        fs::write(&source_file, "email = {{ email }}\n").unwrap();
+
+        let link = Link::new(source_file.to_string_lossy(), target_file.to_string_lossy())
+            .with_mode(LinkMode::Template);
+
+        let mut config = create_test_config(vec![link], vec![]);
+        config
+            .variables
+            .insert("email".to_string(), "me@example.com".to_string());
+        let dotman = Dotman::new(config);
+
+        dotman.install().unwrap();
+
+        assert!(!target_file.is_symlink());
+        assert_eq!(
+            fs::read_to_string(&target_file).unwrap(),
+            "email = me@example.com\n"
+        );
+        assert_eq!(dotman.link_statuses().unwrap()[0].status, LinkStatus::Ok);
+
+        fs::write(&target_file, "email = other@example.com\n").unwrap();
+        assert_eq!(
+            dotman.link_statuses().unwrap()[0].status,
+            LinkStatus::Modified
+        );
+    }
+
+    #[test]
+    fn test_dotman_install_copy_link() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("nvim");
+        let target_dir = temp_dir.path().join(".config/nvim");
+
+        fs::create_dir(&source_dir).unwrap();
+        fs::write(source_dir.join("init.lua"), "-- version 1\n").unwrap();
+
+        let link = Link::new(source_dir.to_string_lossy(), target_dir.to_string_lossy())
+            .with_mode(LinkMode::Copy);
+        let dotman = Dotman::new(create_test_config(vec![link], vec![]));
+
+        dotman.install().unwrap();
+
+        assert!(!target_dir.is_symlink());
+        assert_eq!(
+            fs::read_to_string(target_dir.join("init.lua")).unwrap(),
+            "-- version 1\n"
+        );
+        assert_eq!(dotman.link_statuses().unwrap()[0].status, LinkStatus::Ok);
+
+        // An unmodified copy follows source changes.
+        fs::write(source_dir.join("init.lua"), "-- version 2\n").unwrap();
+        assert_eq!(
+            dotman.link_statuses().unwrap()[0].status,
+            LinkStatus::Modified
+        );
+        dotman.install().unwrap();
+        assert_eq!(
+            fs::read_to_string(target_dir.join("init.lua")).unwrap(),
+            "-- version 2\n"
+        );
+    }
+
+    #[test]
+    fn test_dotman_install_protects_modified_targets() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("gitconfig");
+        let target_file = temp_dir.path().join(".gitconfig");
+
+        fs::write(&source_file, "version 1\n").unwrap();
+
+        let link = Link::new(source_file.to_string_lossy(), target_file.to_string_lossy())
+            .with_mode(LinkMode::Template);
+        let config = create_test_config(vec![link], vec![]);
+        let dotman = Dotman::new(config.clone());
+
+        dotman.install().unwrap();
+
+        // Unmodified targets follow source changes without --overwrite.
+        fs::write(&source_file, "version 2\n").unwrap();
+        dotman.install().unwrap();
+        assert_eq!(fs::read_to_string(&target_file).unwrap(), "version 2\n");
+
+        // Local edits are kept...
+        fs::write(&target_file, "local edit\n").unwrap();
+        dotman.install().unwrap();
+        assert_eq!(fs::read_to_string(&target_file).unwrap(), "local edit\n");
+
+        // ...unless forced.
+        let state = dotman.state.lock().unwrap().clone();
+        let forced = Dotman::new(config).with_state(state);
+        forced
+            .install_with(&InstallOptions {
+                force: true,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(fs::read_to_string(&target_file).unwrap(), "version 2\n");
+    }
+
+    #[test]
+    fn test_dotman_verify_reports_mismatches() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("source.txt");
+        let template_file = temp_dir.path().join("template.txt");
+        let linked = temp_dir.path().join("linked.txt");
+        let rendered = temp_dir.path().join("rendered.txt");
+        let never_installed = temp_dir.path().join("never.txt");
+
+        fs::write(&source_file, "test content").unwrap();
+        fs::write(&template_file, "hello").unwrap();
+
+        let link = |source: &std::path::Path, target: &std::path::Path, mode| {
+            Link::new(source.to_string_lossy(), target.to_string_lossy()).with_mode(mode)
+        };
+        let config = create_test_config(
+            vec![
+                link(&source_file, &linked, LinkMode::Symlink),
+                link(&template_file, &rendered, LinkMode::Template),
+            ],
+            vec![],
+        );
+        let dotman = Dotman::new(config);
+        dotman.install().unwrap();
+        assert!(dotman.verify().unwrap().is_empty());
+
+        fs::remove_file(&linked).unwrap();
+        fs::write(&linked, "replaced").unwrap();
+        fs::write(&template_file, "changed").unwrap();
+        let mut config = dotman.config.clone();
+        config
+            .links
+            .push(link(&source_file, &never_installed, LinkMode::Symlink));
+        let state = dotman.state.lock().unwrap().clone();
+        let dotman = Dotman::new(config).with_state(state);
+
+        assert_eq!(
+            dotman.verify().unwrap(),
+            vec![
+                (linked, Mismatch::NotSymlink),
+                (rendered, Mismatch::Outdated)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dotman_install_and_remove_in_memory() {
+        let fs = std::sync::Arc::new(crate::fs::MemoryFilesystem::new());
+        fs.write(
+            std::path::Path::new("/dotfiles/zshrc"),
+            b"export EDITOR=vim",
+            false,
+        )
+        .unwrap();
+        let config = create_test_config(
+            vec![Link::new(
+                "/dotfiles/zshrc".to_string(),
+                "/home/me/.zshrc".to_string(),
+            )],
+            vec![],
+        );
+        let dotman = Dotman::new(config).with_filesystem(fs.clone());
+
+        dotman.install().unwrap();
+        assert_eq!(
+            fs.read_link(std::path::Path::new("/home/me/.zshrc"))
+                .unwrap(),
+            std::path::Path::new("/dotfiles/zshrc")
+        );
+        assert_eq!(dotman.link_statuses().unwrap()[0].status, LinkStatus::Ok);
+
+        dotman.remove().unwrap();
+        assert!(!fs.exists(std::path::Path::new("/home/me/.zshrc")));
+    }
+
+    #[test]
+    fn test_dotman_install_junction_link() {
+        let fs = std::sync::Arc::new(crate::fs::MemoryFilesystem::new());
+        fs.create_dir_all(std::path::Path::new("/dotfiles/nvim"))
+            .unwrap();
+        let link =
+            Link::new("/dotfiles/nvim", "/home/me/.config/nvim").with_mode(LinkMode::Junction);
+        let dotman =
+            Dotman::new(create_test_config(vec![link], vec![])).with_filesystem(fs.clone());
+
+        dotman.install().unwrap();
+        assert_eq!(
+            fs.read_link(std::path::Path::new("/home/me/.config/nvim"))
+                .unwrap(),
+            std::path::Path::new("/dotfiles/nvim")
+        );
+        assert_eq!(dotman.link_statuses().unwrap()[0].status, LinkStatus::Ok);
+        assert!(dotman.verify().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_dotman_logs_action_output() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = create_test_config(
+            vec![],
+            vec![Action::shell(
+                "Install (brew)",
+                "echo installing; echo broken >&2; exit 1",
+            )],
+        );
+        config.logging = Some(crate::config::Logging {
+            actions_dir: Some(temp_dir.path().join("logs").to_string_lossy().to_string()),
+        });
+
+        assert!(Dotman::new(config).install().is_err());
+
+        let logs: Vec<_> = fs::read_dir(temp_dir.path().join("logs"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(logs.len(), 1);
+        assert!(logs[0].to_string_lossy().ends_with("-install--brew.log"));
+        let log = fs::read_to_string(&logs[0]).unwrap();
+        assert!(log.contains("installing"));
+        assert!(log.contains("broken"));
+    }
+
+    #[test]
+    fn test_dotman_install_with_dry_run_and_tags() {
+        let fs = std::sync::Arc::new(crate::fs::MemoryFilesystem::new());
+        for name in ["zshrc", "vimrc"] {
+            fs.write(
+                &std::path::Path::new("/dotfiles").join(name),
+                b"# config",
+                false,
+            )
+            .unwrap();
+        }
+        let config = create_test_config(
+            vec![
+                Link {
+                    tags: vec!["shell".to_string()],
+                    ..Link::new("/dotfiles/zshrc", "/home/me/.zshrc")
+                },
+                Link::new("/dotfiles/vimrc", "/home/me/.vimrc"),
+            ],
+            vec![Action::shell("Fail", "exit 1")],
+        );
+        let dotman = Dotman::new(config).with_filesystem(fs.clone());
+
+        dotman
+            .install_with(&InstallOptions {
+                dry_run: true,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(!fs.exists(std::path::Path::new("/home/me/.zshrc")));
+
+        dotman
+            .install_with(&InstallOptions {
+                tags: vec!["shell".to_string()],
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(fs.exists(std::path::Path::new("/home/me/.zshrc")));
+        assert!(!fs.exists(std::path::Path::new("/home/me/.vimrc")));
+    }
+
+    #[test]
+    fn test_dotman_install_reports_events() {
+        let fs = std::sync::Arc::new(crate::fs::MemoryFilesystem::new());
+        fs.write(std::path::Path::new("/dotfiles/zshrc"), b"# zsh", false)
+            .unwrap();
+        let config = create_test_config(
+            vec![
+                Link::new("/dotfiles/zshrc", "/home/me/.zshrc"),
+                Link::new("/dotfiles/missing", "/home/me/.missing"),
+            ],
+            vec![Action::shell("Greet", "true")],
+        );
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let dotman = Dotman::new(config)
+            .with_filesystem(fs)
+            .with_events(move |event| recorded.lock().unwrap().push(event.clone()));
+
+        dotman.install().unwrap();
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                Event::LinkCreated {
+                    source: "/dotfiles/zshrc".into(),
+                    target: "/home/me/.zshrc".into(),
+                    mode: LinkMode::Symlink,
+                },
+                Event::LinkSkipped {
+                    target: "/home/me/.missing".into(),
+                    reason: SkipReason::SourceMissing,
+                },
+                Event::ActionStarted {
+                    name: "Greet".to_string(),
+                },
+                Event::ActionFinished {
+                    name: "Greet".to_string(),
+                    success: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dotman_install_stops_when_cancelled() {
+        let fs = std::sync::Arc::new(crate::fs::MemoryFilesystem::new());
+        fs.write(std::path::Path::new("/dotfiles/zshrc"), b"# zsh", false)
+            .unwrap();
+        let config = create_test_config(
+            vec![Link::new("/dotfiles/zshrc", "/home/me/.zshrc")],
+            vec![],
+        );
+        let dotman = Dotman::new(config).with_filesystem(fs.clone());
+
+        let options = InstallOptions {
+            cancel: Some(std::sync::Arc::new(std::sync::atomic::AtomicBool::new(
+                true,
+            ))),
+            ..Default::default()
+        };
+        assert!(matches!(
+            dotman.install_with(&options),
+            Err(DotmanError::Cancelled)
+        ));
+        assert!(!fs.exists(std::path::Path::new("/home/me/.zshrc")));
+    }
+
+    #[test]
+    fn test_dotman_install_errors_name_the_link() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("gitconfig");
+        let target_file = temp_dir.path().join(".gitconfig");
+        fs::write(&source_file, "email = {{ email").unwrap();
+
+        let link = Link::new(source_file.to_string_lossy(), target_file.to_string_lossy())
+            .with_mode(LinkMode::Template);
+        let dotman = Dotman::new(create_test_config(vec![link], vec![]));
+
+        let err = dotman.install().unwrap_err();
+        assert!(matches!(
+            &err,
+            DotmanError::Link { error, .. } if matches!(**error, DotmanError::TemplateError(_))
+        ));
+        assert!(err.to_string().contains(&*target_file.to_string_lossy()));
+    }
+
+    #[test]
+    fn test_dotman_install_keeps_going_and_reports_every_failure() {
+        let config = create_test_config(
+            vec![],
+            vec![
+                Action::shell("First", "exit 1"),
+                Action::shell("Second", "true"),
+                Action::shell("Third", "exit 2"),
+            ],
+        );
+        let dotman = Dotman::new(config).with_filesystem(crate::fs::MemoryFilesystem::new());
+
+        let err = dotman
+            .install_with(&InstallOptions {
+                fail_fast: false,
+                ..Default::default()
+            })
+            .unwrap_err();
+        let failed: Vec<String> = err
+            .errors()
+            .iter()
+            .map(|err| match err {
+                DotmanError::CommandError { command, .. } => command.clone(),
+                err => panic!("unexpected error: {err}"),
+            })
+            .collect();
+        assert_eq!(failed, ["First", "Third"]);
+
+        let err = dotman.install().unwrap_err();
+        assert_eq!(err.errors().len(), 1);
+    }
+
+    #[test]
+    fn test_dotman_undo_restores_backups() {
+        let fs = std::sync::Arc::new(crate::fs::MemoryFilesystem::new());
+        let target = std::path::Path::new("/home/me/.zshrc");
+        fs.write(std::path::Path::new("/dotfiles/zshrc"), b"# new", false)
+            .unwrap();
+        fs.write(target, b"# mine", false).unwrap();
+        let config = create_test_config(
+            vec![Link::new("/dotfiles/zshrc", "/home/me/.zshrc")],
+            vec![],
+        );
+        let recorder = crate::journal::Recorder::start("dotman install --overwrite");
+        let dotman = Dotman::new(config)
+            .with_filesystem(fs.clone())
+            .with_backup_dir("/backups")
+            .with_events(recorder.handler());
+
+        dotman
+            .install_with(&InstallOptions {
+                overwrite: true,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(fs.kind(target), Some(EntryKind::Symlink));
+        assert_eq!(
+            fs.read(std::path::Path::new("/backups/home/me/.zshrc"))
+                .unwrap(),
+            b"# mine"
+        );
+
+        dotman.undo(&recorder.finish(None)).unwrap();
+        assert_eq!(fs.read(target).unwrap(), b"# mine");
+        assert!(!fs.exists(std::path::Path::new("/backups/home/me/.zshrc")));
+    }
+
+    #[test]
+    fn test_dotman_undo_restores_rerendered_templates() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("gitconfig");
+        let target_file = temp_dir.path().join(".gitconfig");
+        fs::write(&source_file, "email = {{ email }}\n").unwrap();
+        let link = Link::new(source_file.to_string_lossy(), target_file.to_string_lossy())
+            .with_mode(LinkMode::Template);
+        let config = |email: &str| {
+            let mut config = create_test_config(vec![link.clone()], vec![]);
+            config
+                .variables
+                .insert("email".to_string(), email.to_string());
+            config
+        };
+        let dotman = Dotman::new(config("me@example.com"));
+        dotman.install().unwrap();
+
+        // Each run keeps what it replaced apart, so undoing the newer run
+        // does not disturb what the older one needs.
+        let mut runs = Vec::new();
+        for (run, email) in ["work@example.com", "home@example.com"].iter().enumerate() {
+            let recorder = crate::journal::Recorder::start("dotman install");
+            let state = dotman.state.lock().unwrap().clone();
+            let rerendered = Dotman::new(config(email))
+                .with_state(state)
+                .with_replaced_dir(temp_dir.path().join("replaced").join(run.to_string()))
+                .with_events(recorder.handler());
+            rerendered.install().unwrap();
+            *dotman.state.lock().unwrap() = rerendered.state.lock().unwrap().clone();
+            runs.push(recorder.finish(None));
+        }
+        assert_eq!(
+            fs::read_to_string(&target_file).unwrap(),
+            "email = home@example.com\n"
+        );
+
+        dotman.undo(&runs[1]).unwrap();
+        assert_eq!(
+            fs::read_to_string(&target_file).unwrap(),
+            "email = work@example.com\n"
+        );
+        dotman.undo(&runs[0]).unwrap();
+        assert_eq!(
+            fs::read_to_string(&target_file).unwrap(),
+            "email = me@example.com\n"
+        );
+        assert_eq!(dotman.link_statuses().unwrap()[0].status, LinkStatus::Ok);
+    }
+
+    #[test]
+    fn test_dotman_update_applies_only_changes() {
+        let fs = std::sync::Arc::new(crate::fs::MemoryFilesystem::new());
+        fs.write(std::path::Path::new("/dotfiles/zshrc"), b"# zsh", false)
+            .unwrap();
+        let config = create_test_config(
+            vec![Link::new("/dotfiles/zshrc", "/home/me/.zshrc")],
+            vec![Action::shell("Greet", "true")],
+        );
+        let dotman = Dotman::new(config.clone()).with_filesystem(fs.clone());
+        let options = InstallOptions::default();
+
+        assert_eq!(dotman.update(&options).unwrap(), 2);
+        assert!(fs.exists(std::path::Path::new("/home/me/.zshrc")));
+        assert_eq!(dotman.update(&options).unwrap(), 0);
+
+        let mut changed = config;
+        changed.actions = vec![Action::shell("Greet", "echo hi")];
+        let state = dotman.state.lock().unwrap().clone();
+        let dotman = Dotman::new(changed).with_filesystem(fs).with_state(state);
+        assert_eq!(dotman.update(&options).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_dotman_update_ignores_skipped_actions() {
+        let skipped = Action::ShellCommand {
+            name: "Skipped".to_string(),
+            run: RunCommand::Simple("exit 1".to_string()),
+            undo: None,
+            if_cond: Some(Condition {
+                run: Some(RunCommand::Simple("false".to_string())),
+                ..Default::default()
+            }),
+            if_not_cond: None,
+            profiles: vec![],
+            elevate: false,
+        };
+        let config = create_test_config(vec![], vec![skipped, Action::shell("Greet", "true")]);
+        let dotman = Dotman::new(config)
+            .with_filesystem(std::sync::Arc::new(crate::fs::MemoryFilesystem::new()));
+        let options = InstallOptions::default();
+
+        let dry_run = InstallOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+        assert_eq!(dotman.update(&dry_run).unwrap(), 1);
+        assert_eq!(dotman.update(&options).unwrap(), 1);
+        assert_eq!(dotman.update(&options).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_dotman_fix_repairs_drift_without_actions() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("source.txt");
+        let missing_target = temp_dir.path().join("missing.txt");
+        let wrong_target = temp_dir.path().join("wrong.txt");
+        let marker = temp_dir.path().join("ran");
+        fs::write(&source_file, "test content").unwrap();
+        crate::utils::symlink(&temp_dir.path().join("old.txt"), &wrong_target, false).unwrap();
+
+        let links = [&missing_target, &wrong_target]
+            .iter()
+            .map(|target| Link::new(source_file.to_string_lossy(), target.to_string_lossy()))
+            .collect();
+        let actions = vec![Action::shell(
+            "Touch",
+            format!("touch '{}'", marker.display()),
+        )];
+        let dotman = Dotman::new(create_test_config(links, actions));
+
+        assert_eq!(dotman.status().unwrap(), 2);
+        assert_eq!(dotman.fix(&InstallOptions::default()).unwrap(), 2);
+        assert_eq!(dotman.status().unwrap(), 0);
+
+        let statuses: Vec<LinkStatus> = dotman
+            .link_statuses()
+            .unwrap()
+            .into_iter()
+            .map(|report| report.status)
+            .collect();
+        assert_eq!(statuses, vec![LinkStatus::Ok, LinkStatus::Ok]);
+        assert!(!marker.exists());
+        assert_eq!(dotman.fix(&InstallOptions::default()).unwrap(), 0);
+    }
 }
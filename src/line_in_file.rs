@@ -0,0 +1,180 @@
+//! Editing files dotman does not own, for `type = "line-in-file"` actions.
+
+use colored::Colorize;
+
+use crate::{Dotman, error::DotmanError};
+
+/// What a `line-in-file` action keeps in its file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Managed<'a> {
+    Line(&'a str),
+    /// Lines between the markers `<comment> BEGIN dotman: <name>` and
+    /// `<comment> END dotman: <name>`.
+    Block {
+        name: &'a str,
+        block: &'a str,
+        comment: &'a str,
+    },
+}
+
+impl<'a> Managed<'a> {
+    /// Picks the line or block of an action, which must declare exactly one
+    /// of them.
+    pub fn new(
+        name: &'a str,
+        line: Option<&'a str>,
+        block: Option<&'a str>,
+        comment: Option<&'a str>,
+    ) -> Result<Managed<'a>, DotmanError> {
+        match (line, block) {
+            (Some(line), None) => Ok(Managed::Line(line)),
+            (None, Some(block)) => Ok(Managed::Block {
+                name,
+                block,
+                comment: comment.unwrap_or("#"),
+            }),
+            _ => Err(DotmanError::InvalidConfig(format!(
+                "Action '{name}' must set either `line` or `block`"
+            ))),
+        }
+    }
+
+    /// Returns `contents` with the line or block in place, or `None` if it
+    /// already is.
+    pub fn apply(&self, contents: &str) -> Option<String> {
+        match *self {
+            Managed::Line(line) => {
+                if contents
+                    .lines()
+                    .any(|existing| existing.trim() == line.trim())
+                {
+                    return None;
+                }
+                Some(format!("{}{line}\n", with_final_newline(contents)))
+            }
+            Managed::Block {
+                name,
+                block,
+                comment,
+            } => {
+                let begin = format!("{comment} BEGIN dotman: {name}");
+                let end = format!("{comment} END dotman: {name}");
+                let managed = format!("{begin}\n{}\n{end}\n", block.trim_end_matches('\n'));
+
+                let lines: Vec<&str> = contents.split_inclusive('\n').collect();
+                let start = lines.iter().position(|line| line.trim_end() == begin);
+                let stop = start.and_then(|start| {
+                    lines[start..]
+                        .iter()
+                        .position(|line| line.trim_end() == end)
+                        .map(|offset| start + offset)
+                });
+                let updated = match (start, stop) {
+                    (Some(start), Some(stop)) => format!(
+                        "{}{managed}{}",
+                        lines[..start].concat(),
+                        lines[stop + 1..].concat()
+                    ),
+                    _ => format!("{}{managed}", with_final_newline(contents)),
+                };
+                (updated != contents).then_some(updated)
+            }
+        }
+    }
+}
+
+fn with_final_newline(contents: &str) -> String {
+    if contents.is_empty() || contents.ends_with('\n') {
+        contents.to_string()
+    } else {
+        format!("{contents}\n")
+    }
+}
+
+impl Dotman {
+    /// Whether the file at `path` already has the line or block.
+    pub(crate) fn has_line(&self, path: &str, managed: Managed) -> bool {
+        self.config
+            .resolve_path(path)
+            .ok()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .is_some_and(|contents| managed.apply(&contents).is_none())
+    }
+
+    /// Adds the line or block to the file at `path`, or updates the block.
+    pub(crate) fn ensure_line(&self, path: &str, managed: Managed) -> Result<(), DotmanError> {
+        let path = self.config.resolve_path(path)?;
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        if let Some(updated) = managed.apply(&contents) {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, updated)?;
+            println!("{} {}", "Updated:".green().bold(), path.display());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line() {
+        let managed = Managed::Line("source ~/.config/bash/rc");
+
+        assert_eq!(
+            managed.apply("export EDITOR=vim").unwrap(),
+            "export EDITOR=vim\nsource ~/.config/bash/rc\n"
+        );
+        assert_eq!(managed.apply("source ~/.config/bash/rc  \n"), None);
+        assert_eq!(managed.apply("  source ~/.config/bash/rc\n"), None);
+        assert_eq!(
+            Managed::Line("source ~/.config/bash/rc ").apply("source ~/.config/bash/rc\n"),
+            None
+        );
+        assert_eq!(managed.apply("").unwrap(), "source ~/.config/bash/rc\n");
+    }
+
+    #[test]
+    fn test_block() {
+        let managed = |block| Managed::Block {
+            name: "Path",
+            block,
+            comment: "#",
+        };
+        let contents = "# system defaults\n";
+
+        let added = managed("PATH=~/bin:$PATH").apply(contents).unwrap();
+        assert_eq!(
+            added,
+            "# system defaults\n# BEGIN dotman: Path\nPATH=~/bin:$PATH\n# END dotman: Path\n"
+        );
+        assert_eq!(managed("PATH=~/bin:$PATH\n").apply(&added), None);
+
+        let changed = managed("PATH=~/.local/bin:$PATH")
+            .apply(&format!("{added}alias ll='ls -l'\n"))
+            .unwrap();
+        assert_eq!(
+            changed,
+            "# system defaults\n# BEGIN dotman: Path\nPATH=~/.local/bin:$PATH\n# END dotman: Path\nalias ll='ls -l'\n"
+        );
+    }
+
+    #[test]
+    fn test_line_or_block_required() {
+        assert!(matches!(
+            Managed::new("Both", Some("a"), Some("b"), None),
+            Err(DotmanError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            Managed::new("Neither", None, None, None),
+            Err(DotmanError::InvalidConfig(_))
+        ));
+    }
+}
@@ -0,0 +1,334 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    DotmanConfig,
+    config::Action,
+    download,
+    error::DotmanError,
+    git,
+    hash::{self, ChecksumCache},
+};
+
+/// `dotman.lock`: the sources, repository commits and downloads a
+/// configuration resolved to, so another machine can check it installs
+/// exactly the same.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<LockedLink>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub repos: Vec<LockedRepo>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<LockedAction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedLink {
+    /// The source as written in the configuration.
+    pub source: String,
+    pub path: std::path::PathBuf,
+    /// Digest of the source file or directory. Unset if it is missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedRepo {
+    /// Name in `[repos]`, unset for the main repository.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub commit: String,
+}
+
+/// What an action that is not pinned in the configuration resolved to: the
+/// commit of a `git` action following a branch, or the digest of an
+/// `extract` archive without a `sha256`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedAction {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl Lockfile {
+    /// Where the lockfile of `config` lives: next to the configuration file.
+    pub fn path_for(config: &DotmanConfig) -> std::path::PathBuf {
+        std::path::Path::new(&config.config_path).with_file_name("dotman.lock")
+    }
+
+    /// Resolves every link source of `config`, the commits its repositories
+    /// are at, and what its unpinned `git` and `extract` actions fetch.
+    pub fn resolve(config: &DotmanConfig) -> Result<Lockfile, DotmanError> {
+        let integrity = config.integrity();
+        let mut checksums = ChecksumCache::default();
+        let mut links = Vec::new();
+        for link in config.get_effective_links() {
+            let path = config.resolve_source(&link.source)?;
+            links.push(LockedLink {
+                source: link.source.clone(),
+                checksum: checksums.checksum(&integrity, &path).ok(),
+                path,
+            });
+        }
+
+        let mut dirs = vec![(None, config.repo_dir()?)];
+        for name in config.repos.keys() {
+            if let Some(dir) = config.named_repo_dir(name)? {
+                dirs.push((Some(name.clone()), dir));
+            }
+        }
+        let repos = dirs
+            .into_iter()
+            .filter(|(_, dir)| git::is_repo(dir))
+            .map(|(name, dir)| {
+                Ok(LockedRepo {
+                    name,
+                    commit: git::head(&dir)?,
+                })
+            })
+            .collect::<Result<_, DotmanError>>()?;
+
+        let actions = config
+            .get_effective_actions()
+            .into_iter()
+            .filter_map(|action| Self::resolve_action(config, action).transpose())
+            .collect::<Result<_, DotmanError>>()?;
+
+        Ok(Lockfile {
+            links,
+            repos,
+            actions,
+        })
+    }
+
+    /// The commit a `git` action without a `rev` is at, or would clone, and
+    /// the digest of an `extract` archive without a `sha256`.
+    fn resolve_action(
+        config: &DotmanConfig,
+        action: &Action,
+    ) -> Result<Option<LockedAction>, DotmanError> {
+        match action {
+            Action::Git {
+                name,
+                url,
+                dest,
+                branch,
+                rev: None,
+                ..
+            } => {
+                let dir = config.resolve_path(dest)?;
+                let commit = if git::is_repo(&dir) {
+                    git::head(&dir)?
+                } else {
+                    git::remote_head(url, branch.as_deref())?
+                };
+                Ok(Some(LockedAction {
+                    name: name.clone(),
+                    commit: Some(commit),
+                    sha256: None,
+                }))
+            }
+            Action::Extract {
+                name,
+                source,
+                sha256: None,
+                ..
+            } => {
+                let contents = if crate::remote::is_url(std::path::Path::new(source)) {
+                    download::fetch(source)?
+                } else {
+                    std::fs::read(config.resolve_path(source)?)?
+                };
+                Ok(Some(LockedAction {
+                    name: name.clone(),
+                    commit: None,
+                    sha256: Some(hash::sha256(&contents)),
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Lockfile, DotmanError> {
+        let contents = std::fs::read_to_string(path)?;
+        toml::from_str(&contents).map_err(|err| {
+            DotmanError::Lockfile(format!("Invalid lockfile {}: {}", path.display(), err))
+        })
+    }
+
+    pub fn save(&self, path: &std::path::Path) -> Result<(), DotmanError> {
+        let contents =
+            toml::to_string_pretty(self).map_err(|err| DotmanError::Lockfile(err.to_string()))?;
+        std::fs::write(
+            path,
+            format!("# Written by `dotman lock`. Do not edit.\n\n{contents}"),
+        )?;
+        Ok(())
+    }
+
+    /// Describes every way `current` differs from this lockfile.
+    pub fn drift(&self, current: &Lockfile) -> Vec<String> {
+        let mut drift = Vec::new();
+        for locked in &self.links {
+            match current
+                .links
+                .iter()
+                .find(|link| link.source == locked.source)
+            {
+                None => drift.push(format!("{} is no longer linked", locked.source)),
+                Some(link) if link.path != locked.path => drift.push(format!(
+                    "{} resolves to {} instead of {}",
+                    locked.source,
+                    link.path.display(),
+                    locked.path.display()
+                )),
+                Some(link) if link.checksum != locked.checksum => {
+                    drift.push(format!("{} has changed", locked.source))
+                }
+                Some(_) => {}
+            }
+        }
+        for link in &current.links {
+            if !self.links.iter().any(|locked| locked.source == link.source) {
+                drift.push(format!("{} is not in the lockfile", link.source));
+            }
+        }
+        for locked in &self.repos {
+            let name = locked.name.as_deref().unwrap_or("the dotfiles repository");
+            match current.repos.iter().find(|repo| repo.name == locked.name) {
+                None => drift.push(format!("{name} is missing")),
+                Some(repo) if repo.commit != locked.commit => drift.push(format!(
+                    "{name} is at {} instead of {}",
+                    repo.commit, locked.commit
+                )),
+                Some(_) => {}
+            }
+        }
+        for locked in &self.actions {
+            match current
+                .actions
+                .iter()
+                .find(|action| action.name == locked.name)
+            {
+                None => drift.push(format!("{} is no longer locked", locked.name)),
+                Some(action) if action.commit != locked.commit => drift.push(format!(
+                    "{} is at {} instead of {}",
+                    locked.name,
+                    action.commit.as_deref().unwrap_or("no commit"),
+                    locked.commit.as_deref().unwrap_or("no commit")
+                )),
+                Some(action) if action.sha256 != locked.sha256 => {
+                    drift.push(format!("{} unpacks a different archive", locked.name))
+                }
+                Some(_) => {}
+            }
+        }
+        for action in &current.actions {
+            if !self.actions.iter().any(|locked| locked.name == action.name) {
+                drift.push(format!("{} is not in the lockfile", action.name));
+            }
+        }
+        drift
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Link;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_lockfile_detects_drift() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("zshrc"), "# zsh").unwrap();
+        let config = DotmanConfig::builder()
+            .config_path(temp_dir.path().join("dotman.toml").to_string_lossy())
+            .link(Link::new("zshrc", "~/.zshrc"))
+            .build();
+
+        let lockfile = Lockfile::resolve(&config).unwrap();
+        let path = Lockfile::path_for(&config);
+        lockfile.save(&path).unwrap();
+        assert_eq!(Lockfile::load(&path).unwrap(), lockfile);
+        assert!(
+            lockfile
+                .drift(&Lockfile::resolve(&config).unwrap())
+                .is_empty()
+        );
+
+        std::fs::write(temp_dir.path().join("zshrc"), "# changed").unwrap();
+        assert_eq!(
+            lockfile.drift(&Lockfile::resolve(&config).unwrap()),
+            ["zshrc has changed"]
+        );
+    }
+
+    fn git(dir: &std::path::Path, args: &[&str]) {
+        let status = std::process::Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success());
+    }
+
+    #[test]
+    fn test_lockfile_pins_unpinned_actions() {
+        let temp_dir = TempDir::new().unwrap();
+        let origin = temp_dir.path().join("origin");
+        std::fs::create_dir_all(&origin).unwrap();
+        git(&origin, &["init", "--quiet"]);
+        git(
+            &origin,
+            &["commit", "--quiet", "--allow-empty", "-m", "first"],
+        );
+        std::fs::write(temp_dir.path().join("theme.tar.gz"), "archive").unwrap();
+
+        let mut config: DotmanConfig = toml::from_str(&format!(
+            r#"
+            [[actions]]
+            type = "git"
+            name = "plugins"
+            url = "{}"
+            dest = "plugins"
+
+            [[actions]]
+            type = "extract"
+            name = "theme"
+            source = "theme.tar.gz"
+            dest = "theme"
+            "#,
+            origin.display()
+        ))
+        .unwrap();
+        config.config_path = temp_dir
+            .path()
+            .join("dotman.toml")
+            .to_string_lossy()
+            .to_string();
+
+        let lockfile = Lockfile::resolve(&config).unwrap();
+        assert_eq!(lockfile.actions.len(), 2);
+        assert!(
+            lockfile
+                .drift(&Lockfile::resolve(&config).unwrap())
+                .is_empty()
+        );
+
+        git(
+            &origin,
+            &["commit", "--quiet", "--allow-empty", "-m", "second"],
+        );
+        std::fs::write(temp_dir.path().join("theme.tar.gz"), "changed").unwrap();
+        let drift = lockfile.drift(&Lockfile::resolve(&config).unwrap());
+        assert_eq!(drift.len(), 2);
+        assert!(drift[0].starts_with("plugins is at "));
+        assert_eq!(drift[1], "theme unpacks a different archive");
+    }
+}
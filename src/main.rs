@@ -1,12 +1,13 @@
 mod cli;
+mod exit_code;
 
-use crate::cli::Cli;
+use crate::{cli::Cli, exit_code::ExitCode};
 use clap::Parser;
 
 fn main() {
     let cli = Cli::parse();
 
-    if cli.run().is_err() {
-        std::process::exit(1);
+    if let Err(err) = cli.run() {
+        std::process::exit(ExitCode::of(&err) as i32);
     }
 }
@@ -0,0 +1,510 @@
+//! Deep-merging snippets into JSON, YAML and TOML files, for
+//! `type = "merge"` actions.
+
+use colored::Colorize;
+use toml_edit::{DocumentMut, Item, TableLike};
+
+use crate::{Dotman, config::ConfigFormat, error::DotmanError};
+
+/// Merges `snippet` into `value`: mappings are merged key by key, anything
+/// else in `snippet` replaces what `value` has.
+fn merge_value(value: &mut serde_yaml::Value, snippet: &serde_yaml::Value) {
+    match (value, snippet) {
+        (serde_yaml::Value::Mapping(mapping), serde_yaml::Value::Mapping(snippet)) => {
+            for (key, snippet) in snippet {
+                match mapping.get_mut(key) {
+                    Some(value) => merge_value(value, snippet),
+                    None => {
+                        mapping.insert(key.clone(), snippet.clone());
+                    }
+                }
+            }
+        }
+        (value, snippet) => *value = snippet.clone(),
+    }
+}
+
+/// Merges `snippet` into `table` like [`merge_value`], keeping the comments
+/// and layout of what it does not replace.
+fn merge_table(table: &mut dyn TableLike, snippet: &dyn TableLike) {
+    for (key, item) in snippet.iter() {
+        if let (Some(existing), Some(nested)) = (
+            table.get_mut(key).and_then(Item::as_table_like_mut),
+            item.as_table_like(),
+        ) {
+            merge_table(existing, nested);
+        } else {
+            table.insert(key, item.clone());
+        }
+    }
+}
+
+/// Parses a JSON or YAML document into a value that keeps the order of its
+/// keys. JSON may have comments and trailing commas. An empty document is an
+/// empty mapping.
+fn parse(format: ConfigFormat, contents: &str) -> Result<serde_yaml::Value, String> {
+    if contents.trim().is_empty() {
+        return Ok(serde_yaml::Value::Mapping(Default::default()));
+    }
+    match format {
+        ConfigFormat::Json => {
+            serde_json::from_str(&strip_jsonc(contents)).map_err(|err| err.to_string())
+        }
+        _ => serde_yaml::from_str(contents).map_err(|err| err.to_string()),
+    }
+}
+
+/// Blanks out the comments and trailing commas of JSONC, like VS Code's
+/// `settings.json`, leaving plain JSON at the same offsets as the original.
+fn strip_jsonc(contents: &str) -> String {
+    let bytes = contents.as_bytes();
+    let mut stripped = bytes.to_vec();
+    let mut comma = None;
+    let mut index = 0;
+    while index < bytes.len() {
+        match (bytes[index], bytes.get(index + 1)) {
+            (b'"', _) => {
+                comma = None;
+                index += 1;
+                while index < bytes.len() && bytes[index] != b'"' {
+                    index += if bytes[index] == b'\\' { 2 } else { 1 };
+                }
+            }
+            (b'/', Some(b'/')) => {
+                while index < bytes.len() && bytes[index] != b'\n' {
+                    stripped[index] = b' ';
+                    index += 1;
+                }
+                continue;
+            }
+            (b'/', Some(b'*')) => {
+                let end = contents[index + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |end| index + 2 + end + 2);
+                for byte in &mut stripped[index..end] {
+                    if *byte != b'\n' {
+                        *byte = b' ';
+                    }
+                }
+                index = end;
+                continue;
+            }
+            (b',', _) => comma = Some(index),
+            (b'}' | b']', _) => {
+                if let Some(comma) = comma.take() {
+                    stripped[comma] = b' ';
+                }
+            }
+            (byte, _) if byte.is_ascii_whitespace() => {}
+            _ => comma = None,
+        }
+        index += 1;
+    }
+    // Only whole characters are blanked, so this is still valid UTF-8.
+    String::from_utf8_lossy(&stripped).into_owned()
+}
+
+/// A value in a JSON document and where it is, so the document can be
+/// edited without touching the comments and layout around what changes.
+struct Node {
+    span: std::ops::Range<usize>,
+    /// The members of an object with where their keys start, or `None` for
+    /// any other value.
+    members: Option<Vec<(String, usize, Node)>>,
+}
+
+/// Finds the values in a JSON document already known to be valid.
+struct Parser<'a> {
+    text: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&mut self) -> Option<u8> {
+        while self
+            .text
+            .get(self.position)
+            .is_some_and(u8::is_ascii_whitespace)
+        {
+            self.position += 1;
+        }
+        self.text.get(self.position).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.peek() != Some(byte) {
+            return Err(format!(
+                "expected `{}` at byte {}",
+                byte as char, self.position
+            ));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let start = self.position;
+        self.expect(b'"')?;
+        while let Some(&byte) = self.text.get(self.position) {
+            self.position += if byte == b'\\' { 2 } else { 1 };
+            if byte == b'"' {
+                return serde_json::from_slice(&self.text[start..self.position])
+                    .map_err(|err| err.to_string());
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+
+    fn value(&mut self) -> Result<Node, String> {
+        let next = self.peek();
+        let start = self.position;
+        let members = match next {
+            Some(b'{') => {
+                self.position += 1;
+                let mut members = Vec::new();
+                if self.peek() == Some(b'}') {
+                    self.position += 1;
+                } else {
+                    loop {
+                        self.peek();
+                        let key_start = self.position;
+                        let key = self.string()?;
+                        self.expect(b':')?;
+                        members.push((key, key_start, self.value()?));
+                        if self.peek() == Some(b'}') {
+                            self.position += 1;
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                Some(members)
+            }
+            Some(b'[') => {
+                self.position += 1;
+                if self.peek() == Some(b']') {
+                    self.position += 1;
+                } else {
+                    loop {
+                        self.value()?;
+                        if self.peek() == Some(b']') {
+                            self.position += 1;
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                None
+            }
+            Some(b'"') => {
+                self.string()?;
+                None
+            }
+            Some(_) => {
+                while self.text.get(self.position).is_some_and(|byte| {
+                    !matches!(byte, b',' | b'}' | b']') && !byte.is_ascii_whitespace()
+                }) {
+                    self.position += 1;
+                }
+                None
+            }
+            None => return Err("unexpected end of document".to_string()),
+        };
+        Ok(Node {
+            span: start..self.position,
+            members,
+        })
+    }
+}
+
+/// The indentation of the line `position` is on, if nothing but that
+/// indentation comes before it.
+fn indent_at(text: &str, position: usize) -> Option<&str> {
+    let line_start = text[..position]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    let indent = &text[line_start..position];
+    indent.trim().is_empty().then_some(indent)
+}
+
+/// `value` as JSON pretty-printed with `unit` per level, continuing at
+/// `indent`.
+fn render(value: &serde_yaml::Value, indent: &str, unit: &str) -> Result<String, String> {
+    use serde::Serialize;
+
+    let mut json = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(unit.as_bytes());
+    value
+        .serialize(&mut serde_json::Serializer::with_formatter(
+            &mut json, formatter,
+        ))
+        .map_err(|err| err.to_string())?;
+    Ok(String::from_utf8_lossy(&json).replace('\n', &format!("\n{indent}")))
+}
+
+/// Collects the edits that merge `snippet` into `node`, a value in the
+/// stripped document `text` on a line indented by `indent`, where `unit`
+/// indents each level.
+fn json_edits(
+    text: &str,
+    node: &Node,
+    snippet: &serde_yaml::Value,
+    (indent, unit): (&str, &str),
+    edits: &mut Vec<(std::ops::Range<usize>, String)>,
+) -> Result<(), String> {
+    let (Some(members), serde_yaml::Value::Mapping(mapping), Some((_, first, _))) =
+        (&node.members, snippet, node.members.iter().flatten().next())
+    else {
+        let current: serde_yaml::Value =
+            serde_json::from_str(&text[node.span.clone()]).map_err(|err| err.to_string())?;
+        let mut merged = current.clone();
+        merge_value(&mut merged, snippet);
+        if merged != current {
+            edits.push((node.span.clone(), render(&merged, indent, unit)?));
+        }
+        return Ok(());
+    };
+    let member_indent = indent_at(text, *first);
+    let mut added = String::new();
+    for (key, value) in mapping {
+        let key = key.as_str().ok_or("JSON keys must be strings")?;
+        match members.iter().find(|(name, _, _)| name == key) {
+            Some((_, start, member)) => {
+                let indent = indent_at(text, *start).unwrap_or(indent);
+                json_edits(text, member, value, (indent, unit), edits)?;
+            }
+            None => {
+                let key = serde_json::to_string(key).map_err(|err| err.to_string())?;
+                match member_indent {
+                    Some(indent) => {
+                        added += &format!(",\n{indent}{key}: {}", render(value, indent, unit)?);
+                    }
+                    None => {
+                        let value = serde_json::to_string(value).map_err(|err| err.to_string())?;
+                        added += &format!(", {key}: {value}");
+                    }
+                }
+            }
+        }
+    }
+    if let Some((_, _, last)) = members.last()
+        && !added.is_empty()
+    {
+        edits.push((last.span.end..last.span.end, added));
+    }
+    Ok(())
+}
+
+/// Merges `snippet` into the JSON document `contents`, which may have
+/// comments and trailing commas, changing only the values that differ so
+/// the rest of the document is kept as it was.
+fn merge_json(contents: &str, snippet: &str) -> Result<Option<String>, String> {
+    let snippet = parse(ConfigFormat::Json, snippet)?;
+    if contents.trim().is_empty() {
+        return render(&snippet, "", "  ").map(|json| Some(json + "\n"));
+    }
+    let stripped = strip_jsonc(contents);
+    parse(ConfigFormat::Json, contents)?;
+    let root = Parser {
+        text: stripped.as_bytes(),
+        position: 0,
+    }
+    .value()?;
+
+    // Added values are indented like the top-level keys.
+    let unit = root
+        .members
+        .iter()
+        .flatten()
+        .next()
+        .and_then(|(_, start, _)| indent_at(&stripped, *start))
+        .filter(|indent| !indent.is_empty())
+        .unwrap_or("  ");
+    let mut edits = Vec::new();
+    json_edits(&stripped, &root, &snippet, ("", unit), &mut edits)?;
+    if edits.is_empty() {
+        return Ok(None);
+    }
+    edits.sort_by_key(|(span, _)| std::cmp::Reverse(span.start));
+    let mut merged = contents.to_string();
+    for (span, replacement) in edits {
+        merged.replace_range(span, &replacement);
+    }
+    Ok(Some(merged))
+}
+
+/// Returns `contents` with `snippet` merged into it, or `None` if it already
+/// has every value the snippet sets.
+pub fn merge(
+    format: ConfigFormat,
+    contents: &str,
+    snippet: &str,
+) -> Result<Option<String>, String> {
+    match format {
+        ConfigFormat::Toml => {
+            let mut document: DocumentMut = contents.parse().map_err(|err| format!("{err}"))?;
+            let snippet: DocumentMut = snippet.parse().map_err(|err| format!("{err}"))?;
+            merge_table(document.as_table_mut(), snippet.as_table());
+            let merged = document.to_string();
+            Ok((merged != contents).then_some(merged))
+        }
+        ConfigFormat::Json => merge_json(contents, snippet),
+        ConfigFormat::Yaml => {
+            let original = parse(format, contents)?;
+            let snippet = parse(format, snippet)?;
+            let mut merged = original.clone();
+            merge_value(&mut merged, &snippet);
+            if merged == original {
+                return Ok(None);
+            }
+            serde_yaml::to_string(&merged)
+                .map(Some)
+                .map_err(|err| err.to_string())
+        }
+    }
+}
+
+impl Dotman {
+    /// Reads the snippet at `source` and the file at `target`, and returns
+    /// the target's path with its merged contents, if they differ.
+    fn merged(
+        &self,
+        source: &str,
+        target: &str,
+    ) -> Result<(std::path::PathBuf, Option<String>), DotmanError> {
+        let source = self.config.resolve_path(source)?;
+        let target = self.config.resolve_path(target)?;
+        let snippet = std::fs::read_to_string(&source)?;
+        let contents = match std::fs::read_to_string(&target) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let merged =
+            merge(ConfigFormat::from_path(&target), &contents, &snippet).map_err(|message| {
+                DotmanError::Merge {
+                    target: target.clone(),
+                    message,
+                }
+            })?;
+        Ok((target, merged))
+    }
+
+    /// Whether the file at `target` already has every value in `source`.
+    pub(crate) fn is_merged(&self, source: &str, target: &str) -> bool {
+        self.merged(source, target)
+            .is_ok_and(|(_, merged)| merged.is_none())
+    }
+
+    /// Merges the snippet at `source` into the file at `target`, creating it
+    /// if missing.
+    pub(crate) fn merge_into(&self, source: &str, target: &str) -> Result<(), DotmanError> {
+        let (target, merged) = self.merged(source, target)?;
+        if let Some(merged) = merged {
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&target, merged)?;
+            println!(
+                "{} {} -> {}",
+                "Merged:".green().bold(),
+                source,
+                target.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_json() {
+        let settings = "{\n    \"editor.fontSize\": 12,\n    \"editor.minimap\": {\"enabled\": true, \"side\": \"left\"},\n    \"window.zoomLevel\": 1\n}\n";
+        let snippet = r#"{"editor.fontSize": 14, "editor.minimap": {"enabled": false}}"#;
+
+        let merged = merge(ConfigFormat::Json, settings, snippet)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            merged,
+            "{\n    \"editor.fontSize\": 14,\n    \"editor.minimap\": {\"enabled\": false, \"side\": \"left\"},\n    \"window.zoomLevel\": 1\n}\n"
+        );
+        assert_eq!(merge(ConfigFormat::Json, &merged, snippet).unwrap(), None);
+
+        let merged = merge(ConfigFormat::Json, "", snippet).unwrap().unwrap();
+        assert_eq!(
+            merged,
+            "{\n  \"editor.fontSize\": 14,\n  \"editor.minimap\": {\n    \"enabled\": false\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_merge_commented_json() {
+        let settings = r#"{
+    // Font, see https://example.com/fonts
+    "editor.fontSize": 12,
+    /* Panels */
+    "workbench.sideBar.location": "right",
+    "files.exclude": {
+        "**/.git": true, // hide git
+    },
+}
+"#;
+        let snippet = r#"{
+    "editor.fontSize": 14, // larger
+    "files.exclude": {"**/node_modules": true},
+    "editor.rulers": [80, 100],
+}"#;
+
+        let merged = merge(ConfigFormat::Json, settings, snippet)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            merged,
+            r#"{
+    // Font, see https://example.com/fonts
+    "editor.fontSize": 14,
+    /* Panels */
+    "workbench.sideBar.location": "right",
+    "files.exclude": {
+        "**/.git": true,
+        "**/node_modules": true, // hide git
+    },
+    "editor.rulers": [
+        80,
+        100
+    ],
+}
+"#
+        );
+        assert_eq!(merge(ConfigFormat::Json, &merged, snippet).unwrap(), None);
+        assert!(merge(ConfigFormat::Json, "{\"a\": }", snippet).is_err());
+    }
+
+    #[test]
+    fn test_merge_yaml() {
+        let merged = merge(ConfigFormat::Yaml, "", "font:\n  size: 11\nkeys: [a, b]\n")
+            .unwrap()
+            .unwrap();
+        let merged = merge(ConfigFormat::Yaml, &merged, "keys: [c]\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(merged, "font:\n  size: 11\nkeys:\n- c\n");
+    }
+
+    #[test]
+    fn test_merge_toml_keeps_comments() {
+        let config = "# Managed by hand\n[font]\nsize = 11 # points\nfamily = \"Iosevka\"\n";
+        let snippet = "[font]\nsize = 13\n\n[window]\nopacity = 0.9\n";
+
+        let merged = merge(ConfigFormat::Toml, config, snippet).unwrap().unwrap();
+        assert!(merged.starts_with("# Managed by hand\n[font]\n"));
+        assert!(merged.contains("size = 13"));
+        assert!(merged.contains("family = \"Iosevka\""));
+        assert!(merged.contains("[window]\nopacity = 0.9"));
+        assert_eq!(merge(ConfigFormat::Toml, &merged, snippet).unwrap(), None);
+    }
+}
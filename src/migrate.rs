@@ -0,0 +1,128 @@
+use toml_edit::{DocumentMut, Item};
+
+/// The configuration format version this build of dotman understands.
+pub const CURRENT_VERSION: u32 = 2;
+
+/// Configs without a `version` key predate versioning.
+const UNVERSIONED: u32 = 1;
+
+struct Migration {
+    /// Version the migration upgrades from.
+    from: u32,
+    description: &'static str,
+    apply: fn(&mut DocumentMut),
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 1,
+    description: "rename [[link]] and [[action]] tables to [[links]] and [[actions]]",
+    apply: rename_singular_tables,
+}];
+
+/// The result of migrating a configuration file.
+#[derive(Debug)]
+pub struct Migrated {
+    pub from: u32,
+    pub output: String,
+    /// Descriptions of the migrations that were applied, in order.
+    pub applied: Vec<&'static str>,
+}
+
+/// Returns the version declared in a configuration file.
+pub fn config_version(doc: &DocumentMut) -> u32 {
+    doc.get("version")
+        .and_then(Item::as_integer)
+        .and_then(|version| u32::try_from(version).ok())
+        .unwrap_or(UNVERSIONED)
+}
+
+/// Upgrades a configuration file to [`CURRENT_VERSION`], keeping comments and
+/// formatting of untouched entries.
+pub fn migrate(input: &str) -> anyhow::Result<Migrated> {
+    let mut doc: DocumentMut = input.parse()?;
+    let from = config_version(&doc);
+
+    if from > CURRENT_VERSION {
+        anyhow::bail!(
+            "Configuration version {} is newer than the supported version {}",
+            from,
+            CURRENT_VERSION
+        );
+    }
+
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.from >= from) {
+        (migration.apply)(&mut doc);
+        applied.push(migration.description);
+    }
+
+    if from == CURRENT_VERSION {
+        return Ok(Migrated {
+            from,
+            output: input.to_string(),
+            applied,
+        });
+    }
+
+    doc["version"] = toml_edit::value(i64::from(CURRENT_VERSION));
+    doc.as_table_mut()
+        .sort_values_by(|a, _, b, _| (a.get() != "version").cmp(&(b.get() != "version")));
+
+    Ok(Migrated {
+        from,
+        output: doc.to_string(),
+        applied,
+    })
+}
+
+/// Version 1 accepted the singular `[[link]]`/`[[action]]` spelling.
+fn rename_singular_tables(doc: &mut DocumentMut) {
+    for (old, new) in [("link", "links"), ("action", "actions")] {
+        let Some(Item::ArrayOfTables(tables)) = doc.remove(old) else {
+            continue;
+        };
+
+        match doc.get_mut(new) {
+            Some(Item::ArrayOfTables(existing)) => {
+                tables.into_iter().for_each(|table| existing.push(table));
+            }
+            _ => {
+                doc.insert(new, Item::ArrayOfTables(tables));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_unversioned_config() {
+        let input = "# My links\n[[link]]\nsource = \"foo.txt\"\ntarget = \"bar.txt\"\n";
+
+        let migrated = migrate(input).unwrap();
+
+        assert_eq!(migrated.from, 1);
+        assert_eq!(migrated.applied.len(), 1);
+        assert_eq!(
+            migrated.output,
+            "version = 2\n# My links\n[[links]]\nsource = \"foo.txt\"\ntarget = \"bar.txt\"\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_current_config_is_unchanged() {
+        let input = "version = 2\n\n[[links]]\nsource = \"a\"\ntarget = \"b\"\n";
+
+        let migrated = migrate(input).unwrap();
+
+        assert!(migrated.applied.is_empty());
+        assert_eq!(migrated.output, input);
+    }
+
+    #[test]
+    fn test_migrate_rejects_newer_version() {
+        assert!(migrate("version = 99\n").is_err());
+    }
+}
@@ -0,0 +1,143 @@
+use crate::{
+    config::OperatingSystem,
+    error::DotmanError,
+    journal::{Change, Run},
+};
+
+/// A desktop notification, shown with `osascript` on macOS and `notify-send`
+/// elsewhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+}
+
+impl Notification {
+    /// Summarizes a journaled run, e.g. "dotman install finished" with
+    /// "42 linked, 1 failed".
+    pub fn for_run(run: &Run) -> Notification {
+        let linked = run
+            .changes
+            .iter()
+            .filter(|change| matches!(change, Change::Link { .. }))
+            .count();
+        let removed = run
+            .changes
+            .iter()
+            .filter(|change| matches!(change, Change::Unlink { .. }))
+            .count();
+        let actions = run
+            .changes
+            .iter()
+            .filter(|change| matches!(change, Change::Action { success: true, .. }))
+            .count();
+
+        let mut parts = vec![format!("{linked} linked")];
+        if removed > 0 {
+            parts.push(format!("{removed} removed"));
+        }
+        if actions > 0 {
+            parts.push(format!("{actions} actions run"));
+        }
+        if !run.failures.is_empty() {
+            parts.push(format!("{} failed", run.failures.len()));
+        }
+
+        let outcome = if run.failures.is_empty() {
+            "finished"
+        } else {
+            "failed"
+        };
+        Notification {
+            title: format!("{} {}", run.command, outcome),
+            body: parts.join(", "),
+        }
+    }
+
+    pub fn send(&self, os: &OperatingSystem) -> Result<(), DotmanError> {
+        let mut command = match os {
+            OperatingSystem::MacOS => {
+                let mut command = std::process::Command::new("osascript");
+                command.arg("-e").arg(format!(
+                    "display notification {} with title {}",
+                    applescript_string(&self.body),
+                    applescript_string(&self.title)
+                ));
+                command
+            }
+            OperatingSystem::Windows => {
+                return Err(DotmanError::IoError(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "Notifications are not supported on Windows",
+                )));
+            }
+            _ => {
+                let mut command = std::process::Command::new("notify-send");
+                command.args(["--app-name", "dotman", &self.title, &self.body]);
+                command
+            }
+        };
+
+        let program = command.get_program().to_string_lossy().into_owned();
+        let output = command.output().map_err(|err| DotmanError::CommandError {
+            command: program.clone(),
+            message: err.to_string(),
+        })?;
+        if !output.status.success() {
+            return Err(DotmanError::CommandError {
+                command: program,
+                message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Quotes `value` as an AppleScript string literal.
+fn applescript_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notification_for_run() {
+        let mut run = Run {
+            started: 0,
+            finished: 1,
+            command: "dotman install".to_string(),
+            changes: vec![
+                Change::Link {
+                    source: "zshrc".into(),
+                    target: "/home/me/.zshrc".into(),
+                    mode: Default::default(),
+                },
+                Change::Action {
+                    name: "zap".to_string(),
+                    success: true,
+                },
+            ],
+            failures: vec![],
+            undoes: None,
+        };
+        assert_eq!(
+            Notification::for_run(&run),
+            Notification {
+                title: "dotman install finished".to_string(),
+                body: "1 linked, 1 actions run".to_string(),
+            }
+        );
+
+        run.failures.push("Action failed".to_string());
+        let notification = Notification::for_run(&run);
+        assert_eq!(notification.title, "dotman install failed");
+        assert_eq!(notification.body, "1 linked, 1 actions run, 1 failed");
+    }
+
+    #[test]
+    fn test_applescript_string() {
+        assert_eq!(applescript_string(r#"a "b" \c"#), r#""a \"b\" \\c""#);
+    }
+}
@@ -0,0 +1,159 @@
+use crate::config::{Action, Directory, GSetting, Link, Package, Preference};
+
+/// How [`crate::Dotman::install_with`] and [`crate::Dotman::sync`] apply the
+/// configuration.
+#[derive(Debug, Clone)]
+pub struct InstallOptions {
+    /// Replace existing targets, as if `overwrite = true` were configured.
+    pub overwrite: bool,
+    /// Replace targets even if they were modified since dotman wrote them.
+    pub force: bool,
+    /// Ask before creating each link.
+    pub ask: bool,
+    /// Report what would change without touching the filesystem, the state
+    /// file or running actions.
+    pub dry_run: bool,
+    /// Only install links whose source or target is one of these, and
+    /// actions with one of these names. Empty means all.
+    pub names: Vec<String>,
+    /// Only install links with one of these tags. Empty means all.
+    pub tags: Vec<String>,
+    /// Stop at the first link that fails instead of installing the rest.
+    pub fail_fast: bool,
+    /// Number of links installed at once. Asking forces one at a time.
+    pub parallelism: usize,
+    /// Set this to stop the install after the links and actions that are
+    /// already running. The state still records everything installed.
+    pub cancel: Option<std::sync::Arc<std::sync::atomic::AtomicBool>>,
+}
+
+impl Default for InstallOptions {
+    fn default() -> Self {
+        InstallOptions {
+            overwrite: false,
+            force: false,
+            ask: false,
+            dry_run: false,
+            names: vec![],
+            tags: vec![],
+            fail_fast: true,
+            parallelism: 1,
+            cancel: None,
+        }
+    }
+}
+
+impl InstallOptions {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(std::sync::atomic::Ordering::Relaxed))
+    }
+
+    fn is_filtered(&self) -> bool {
+        !self.names.is_empty() || !self.tags.is_empty()
+    }
+
+    /// Whether `link` passes the name and tag filters.
+    pub fn selects_link(&self, link: &Link) -> bool {
+        let named = self.names.is_empty()
+            || self.names.iter().any(|name| {
+                *name == link.source
+                    || *name == link.target
+                    || std::path::Path::new(&link.target)
+                        .file_name()
+                        .is_some_and(|file_name| file_name == name.as_str())
+            });
+        let tagged = self.tags.is_empty() || link.tags.iter().any(|tag| self.tags.contains(tag));
+        named && tagged
+    }
+
+    /// Whether `action` runs. Filtering by tag selects no actions, since
+    /// actions have no tags.
+    pub fn selects_action(&self, action: &Action) -> bool {
+        !self.is_filtered() || self.names.iter().any(|name| name == action.name())
+    }
+
+    /// Whether `dir` is created: when nothing is filtered, or when it is
+    /// named by its path.
+    pub fn selects_dir(&self, dir: &Directory) -> bool {
+        !self.is_filtered() || self.names.contains(&dir.path)
+    }
+
+    /// Whether `preference` is set: when nothing is filtered, or when it is
+    /// named by its key.
+    pub fn selects_preference(&self, preference: &Preference) -> bool {
+        !self.is_filtered() || self.names.contains(&preference.key)
+    }
+
+    /// Whether `setting` is set: when nothing is filtered, or when it is
+    /// named by its key.
+    pub fn selects_gsetting(&self, setting: &GSetting) -> bool {
+        !self.is_filtered() || self.names.contains(&setting.key)
+    }
+
+    /// Whether the includes in `file` are written: when nothing is
+    /// filtered, or when it is named by its path.
+    pub fn selects_gitconfig(&self, file: &str) -> bool {
+        !self.is_filtered() || self.names.iter().any(|name| name == file)
+    }
+
+    /// Whether the shell environment files are written. They are generated
+    /// as a whole, so only when nothing is filtered.
+    pub fn selects_shell_env(&self) -> bool {
+        !self.is_filtered()
+    }
+
+    /// Whether `package` is installed: when nothing is filtered, or when it
+    /// is named by any of its names or its Brewfile. Like actions, packages
+    /// have no tags.
+    pub fn selects_package(&self, package: &Package) -> bool {
+        !self.is_filtered()
+            || package
+                .names()
+                .any(|(_, name)| self.names.iter().any(|n| n == name))
+            || package
+                .brewfile
+                .as_ref()
+                .is_some_and(|brewfile| self.names.contains(brewfile))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filters() {
+        let link = Link {
+            tags: vec!["shell".to_string()],
+            ..Link::new("zsh/zshrc", "~/.zshrc")
+        };
+        let action = Action::shell("Install zap", "true");
+
+        let all = InstallOptions::default();
+        assert!(all.selects_link(&link) && all.selects_action(&action));
+
+        let by_name = InstallOptions {
+            names: vec![".zshrc".to_string()],
+            ..Default::default()
+        };
+        assert!(by_name.selects_link(&link) && !by_name.selects_action(&action));
+
+        let package = Package {
+            brew: Some("ripgrep".to_string()),
+            ..Default::default()
+        };
+        let by_package = InstallOptions {
+            names: vec!["ripgrep".to_string()],
+            ..Default::default()
+        };
+        assert!(by_package.selects_package(&package) && !by_name.selects_package(&package));
+
+        let by_tag = InstallOptions {
+            tags: vec!["editor".to_string()],
+            ..Default::default()
+        };
+        assert!(!by_tag.selects_link(&link) && !by_tag.selects_action(&action));
+    }
+}
@@ -0,0 +1,199 @@
+//! Targets dotman installed for links that are no longer configured.
+
+use colored::Colorize;
+
+use crate::{
+    Dotman,
+    config::{Action, Link},
+    error::DotmanError,
+    events::Event,
+    fs::EntryKind,
+    options::InstallOptions,
+    state::TargetState,
+    systemd,
+    utils::Absolute,
+    utils::ExpandTilde,
+};
+
+impl Dotman {
+    /// Targets recorded in the state that no link in the configuration
+    /// points at any more, in any profile, with what was installed there.
+    /// Unit files that systemd-unit actions link count as configured.
+    pub fn orphans(&self) -> Vec<(std::path::PathBuf, TargetState)> {
+        let unit_links: Vec<Link> = self
+            .config
+            .actions
+            .iter()
+            .filter_map(|action| match action {
+                Action::SystemdUnit {
+                    unit,
+                    source: Some(source),
+                    ..
+                } => systemd::unit_link(unit, source).ok(),
+                _ => None,
+            })
+            .collect();
+        let configured: std::collections::HashSet<std::path::PathBuf> = self
+            .config
+            .all_links()
+            .chain(&unit_links)
+            .filter_map(|link| link.target.expand_tilde_path().ok()?.absolute().ok())
+            .collect();
+        self.state
+            .lock()
+            .unwrap()
+            .targets
+            .iter()
+            .filter(|(target, _)| !configured.contains(*target))
+            .map(|(target, state)| (target.clone(), state.clone()))
+            .collect()
+    }
+
+    /// Removes orphaned targets that are still as dotman left them: symlinks
+    /// to their recorded source and unmodified files it wrote. Those already
+    /// gone are forgotten, and those changed since are kept. Returns how many
+    /// were removed or forgotten.
+    pub fn prune_orphans(&self, options: &InstallOptions) -> Result<usize, DotmanError> {
+        let mut pruned = 0;
+        for (target, recorded) in self.orphans() {
+            let unchanged = match self.fs.kind(&target) {
+                None => {
+                    if !options.dry_run {
+                        self.state.lock().unwrap().forget(&target);
+                    }
+                    pruned += 1;
+                    continue;
+                }
+                Some(EntryKind::Symlink) => self
+                    .fs
+                    .read_link(&target)
+                    .is_ok_and(|source| source == recorded.source),
+                Some(_) => recorded
+                    .checksum
+                    .as_ref()
+                    .is_some_and(|checksum| self.matches_recorded(&target, checksum)),
+            };
+            if !unchanged {
+                println!(
+                    "{} {} changed since dotman installed it, keeping it.",
+                    "Warning:".yellow().bold(),
+                    target.display()
+                );
+                continue;
+            }
+            if options.dry_run {
+                println!("{} {}", "Would remove:".cyan().bold(), target.display());
+                pruned += 1;
+                continue;
+            }
+            self.fs.remove(&target)?;
+            self.state.lock().unwrap().forget(&target);
+            self.emit(Event::LinkRemoved {
+                target: target.clone(),
+            });
+            println!(
+                "{} {} removed.",
+                "Removed:".green().bold(),
+                target.display()
+            );
+            pruned += 1;
+        }
+        if !options.dry_run {
+            self.state.lock().unwrap().save()?;
+        }
+        Ok(pruned)
+    }
+
+    /// Prints the orphaned targets for `dotman status`.
+    pub(crate) fn print_orphans(&self, orphans: &[(std::path::PathBuf, TargetState)]) {
+        for (target, recorded) in orphans {
+            println!(
+                "{} {} -> {}",
+                "[ORPHANED]".yellow().bold(),
+                recorded.source.display(),
+                target.display()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Action, Link};
+    use crate::fs::{Filesystem, MemoryFilesystem};
+    use crate::{Dotman, DotmanConfig, options::InstallOptions};
+    use std::path::Path;
+
+    #[test]
+    fn test_prune_orphans() {
+        let fs = std::sync::Arc::new(MemoryFilesystem::new());
+        for source in ["/dotfiles/zshrc", "/dotfiles/vimrc", "/dotfiles/tmux.conf"] {
+            fs.write(Path::new(source), b"", false).unwrap();
+        }
+        let links = vec![
+            Link::new("/dotfiles/zshrc", "/home/me/.zshrc"),
+            Link::new("/dotfiles/vimrc", "/home/me/.vimrc"),
+            Link::new("/dotfiles/tmux.conf", "/home/me/.tmux.conf"),
+        ];
+        let dotman = Dotman::new(DotmanConfig::builder().links(links.clone()).build())
+            .with_filesystem(fs.clone());
+        dotman.install().unwrap();
+        assert!(dotman.orphans().is_empty());
+
+        let state = dotman.state.lock().unwrap().clone();
+        let dotman = Dotman::new(DotmanConfig::builder().link(links[0].clone()).build())
+            .with_filesystem(fs.clone())
+            .with_state(state);
+        fs.remove(Path::new("/home/me/.tmux.conf")).unwrap();
+        fs.symlink(
+            Path::new("/elsewhere/tmux.conf"),
+            Path::new("/home/me/.tmux.conf"),
+        )
+        .unwrap();
+        let orphans: Vec<_> = dotman
+            .orphans()
+            .into_iter()
+            .map(|(target, _)| target)
+            .collect();
+        assert_eq!(
+            orphans,
+            vec![
+                Path::new("/home/me/.tmux.conf"),
+                Path::new("/home/me/.vimrc")
+            ]
+        );
+
+        assert_eq!(dotman.prune_orphans(&InstallOptions::default()).unwrap(), 1);
+        assert!(!fs.exists(Path::new("/home/me/.vimrc")));
+        assert!(fs.kind(Path::new("/home/me/.tmux.conf")).is_some());
+        assert!(fs.exists(Path::new("/home/me/.zshrc")));
+        assert_eq!(dotman.orphans().len(), 1);
+    }
+
+    #[test]
+    fn test_linked_unit_files_are_not_orphans() {
+        let fs = std::sync::Arc::new(MemoryFilesystem::new());
+        fs.write(Path::new("/dotfiles/syncthing.service"), b"", false)
+            .unwrap();
+        let action = Action::SystemdUnit {
+            name: "syncthing".to_string(),
+            unit: "syncthing.service".to_string(),
+            source: Some("/dotfiles/syncthing.service".to_string()),
+            if_cond: None,
+            if_not_cond: None,
+            profiles: vec![],
+        };
+        let dotman =
+            Dotman::new(DotmanConfig::builder().action(action).build()).with_filesystem(fs.clone());
+        let link =
+            crate::systemd::unit_link("syncthing.service", "/dotfiles/syncthing.service").unwrap();
+        dotman
+            .install_link(&link, &InstallOptions::default())
+            .unwrap();
+        assert_eq!(dotman.state.lock().unwrap().targets.len(), 1);
+
+        assert!(dotman.orphans().is_empty());
+        assert_eq!(dotman.prune_orphans(&InstallOptions::default()).unwrap(), 0);
+        assert!(fs.kind(Path::new(&link.target)).is_some());
+    }
+}
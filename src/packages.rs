@@ -0,0 +1,904 @@
+//! Installing the `[[packages]]` with the machine's package manager, and
+//! Brewfiles with `brew bundle`.
+
+use colored::Colorize;
+
+use crate::{
+    Dotman,
+    config::{AurHelper, Package},
+    error::DotmanError,
+    options::InstallOptions,
+};
+
+/// A package manager dotman can install packages with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageManager {
+    Brew,
+    Apt,
+    Pacman,
+    /// The Arch User Repository, through an AUR helper.
+    Aur(AurHelper),
+    Flatpak,
+    Snap,
+    /// Rust tools, built with `cargo install`.
+    Cargo,
+    /// Python tools, each in its own virtual environment.
+    Pipx,
+    /// Node.js tools, installed globally.
+    Npm,
+}
+
+impl PackageManager {
+    /// The key packages are named with for this manager, e.g. `brew`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PackageManager::Brew => "brew",
+            PackageManager::Apt => "apt",
+            PackageManager::Pacman => "pacman",
+            PackageManager::Aur(_) => "aur",
+            PackageManager::Flatpak => "flatpak",
+            PackageManager::Snap => "snap",
+            PackageManager::Cargo => "cargo",
+            PackageManager::Pipx => "pipx",
+            PackageManager::Npm => "npm",
+        }
+    }
+
+    fn program(&self) -> &'static str {
+        match self {
+            PackageManager::Brew => "brew",
+            PackageManager::Apt => "apt-get",
+            PackageManager::Pacman => "pacman",
+            PackageManager::Aur(helper) => helper.program(),
+            PackageManager::Flatpak => "flatpak",
+            PackageManager::Snap => "snap",
+            PackageManager::Cargo => "cargo",
+            PackageManager::Pipx => "pipx",
+            PackageManager::Npm => "npm",
+        }
+    }
+
+    /// The package manager of this machine: the distribution's own, or
+    /// Homebrew where there is none, such as on macOS.
+    pub fn detect() -> Option<PackageManager> {
+        [
+            PackageManager::Apt,
+            PackageManager::Pacman,
+            PackageManager::Brew,
+        ]
+        .into_iter()
+        .find(|manager| which::which(manager.program()).is_ok())
+    }
+
+    /// Whether installing packages needs root. Homebrew and AUR helpers
+    /// refuse to run as root; the helpers use sudo themselves. Language
+    /// tools are installed for the user.
+    pub fn needs_root(&self) -> bool {
+        matches!(
+            self,
+            PackageManager::Apt
+                | PackageManager::Pacman
+                | PackageManager::Flatpak
+                | PackageManager::Snap
+        )
+    }
+
+    /// Lists the installed packages among `names`. apt asks `dpkg -s` about
+    /// just those; the others list every installed package, which for the
+    /// AUR is everything pacman knows of.
+    pub fn installed(
+        &self,
+        names: &[&str],
+    ) -> Result<std::collections::BTreeSet<String>, DotmanError> {
+        let mut command = std::process::Command::new(self.program());
+        match self {
+            PackageManager::Brew => command.args(["list", "-1"]),
+            PackageManager::Apt => return dpkg_status(names),
+            PackageManager::Pacman => command.arg("-Qq"),
+            PackageManager::Aur(_) => return PackageManager::Pacman.installed(names),
+            PackageManager::Flatpak => command.args(["list", "--app", "--columns=application"]),
+            PackageManager::Snap => command.arg("list"),
+            PackageManager::Cargo => command.args(["install", "--list"]),
+            PackageManager::Pipx => command.args(["list", "--short"]),
+            PackageManager::Npm => command.args(["ls", "--global", "--depth=0", "--json"]),
+        };
+        Ok(self.parse_installed(&query(command)?))
+    }
+
+    /// Reads the package names in the output of [`PackageManager::installed`]'s
+    /// query. `snap list` prints a table with a header. Crates are listed by
+    /// name and as `name@version`.
+    fn parse_installed(&self, output: &str) -> std::collections::BTreeSet<String> {
+        match self {
+            PackageManager::Cargo => return parse_cargo_list(output),
+            PackageManager::Npm => return parse_npm_list(output),
+            _ => {}
+        }
+        let skip = usize::from(*self == PackageManager::Snap);
+        output
+            .lines()
+            .skip(skip)
+            .filter_map(|line| line.split_whitespace().next())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Whether `package` is among the `installed` packages, at the version
+    /// asked for if it is a crate with one.
+    fn has(&self, package: &Package, installed: &std::collections::BTreeSet<String>) -> bool {
+        let Some(name) = package.name_for(*self) else {
+            return false;
+        };
+        match (self, &package.cargo_version) {
+            (PackageManager::Cargo, Some(version)) => {
+                installed.contains(&format!("{name}@{version}"))
+            }
+            _ => missing([name], installed).is_empty(),
+        }
+    }
+
+    /// Arguments that `package` is installed with besides its name: the
+    /// remote of a Flatpak, `--classic` for snaps that need it, and the
+    /// version of a crate and whether it is `--locked`.
+    fn install_args<'a>(&self, package: &'a Package) -> Vec<&'a str> {
+        match self {
+            PackageManager::Flatpak => {
+                vec![package.flatpak_remote.as_deref().unwrap_or("flathub")]
+            }
+            PackageManager::Snap if package.snap_classic => vec!["--classic"],
+            PackageManager::Cargo => {
+                let mut args = Vec::new();
+                if let Some(version) = &package.cargo_version {
+                    args.extend(["--version", version.as_str()]);
+                }
+                if package.cargo_locked {
+                    args.push("--locked");
+                }
+                args
+            }
+            _ => vec![],
+        }
+    }
+
+    /// The command that installs `names`, all at once. apt runs without
+    /// asking questions, through `env` since sudo would drop the variable
+    /// that says so. Flatpaks are installed from the remote given first.
+    pub fn install_command(&self, names: &[&str]) -> std::process::Command {
+        let mut command = match self {
+            PackageManager::Apt => {
+                let mut command = std::process::Command::new("env");
+                command.args(["DEBIAN_FRONTEND=noninteractive", self.program()]);
+                command
+            }
+            _ => std::process::Command::new(self.program()),
+        };
+        match self {
+            PackageManager::Brew => command.arg("install"),
+            PackageManager::Apt => command.args(["install", "-y"]),
+            PackageManager::Pacman | PackageManager::Aur(_) => {
+                command.args(["-S", "--needed", "--noconfirm"])
+            }
+            PackageManager::Flatpak => command.args(["install", "-y", "--noninteractive"]),
+            PackageManager::Snap | PackageManager::Cargo | PackageManager::Pipx => {
+                command.arg("install")
+            }
+            PackageManager::Npm => command.args(["install", "--global"]),
+        };
+        command.args(names);
+        command
+    }
+}
+
+impl std::fmt::Display for PackageManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Reads the crates in `cargo install --list` output, whose lines are like
+/// `ripgrep v14.1.0:` followed by the binaries, indented.
+fn parse_cargo_list(output: &str) -> std::collections::BTreeSet<String> {
+    let mut installed = std::collections::BTreeSet::new();
+    for line in output
+        .lines()
+        .filter(|line| !line.starts_with(char::is_whitespace))
+    {
+        let mut words = line.trim_end_matches(':').split_whitespace();
+        let (Some(name), Some(version)) = (words.next(), words.next()) else {
+            continue;
+        };
+        let version = version.trim_start_matches('v');
+        installed.insert(format!("{name}@{version}"));
+        installed.insert(name.to_string());
+    }
+    installed
+}
+
+/// Reads the globally installed packages in `npm ls --global --json`
+/// output.
+fn parse_npm_list(output: &str) -> std::collections::BTreeSet<String> {
+    serde_json::from_str::<serde_json::Value>(output)
+        .ok()
+        .and_then(|list| {
+            Some(
+                list.get("dependencies")?
+                    .as_object()?
+                    .keys()
+                    .cloned()
+                    .collect(),
+            )
+        })
+        .unwrap_or_default()
+}
+
+/// The package manager `package` is installed with: the first of
+/// `managers` it has a name for.
+fn pick<'a>(
+    package: &'a Package,
+    managers: &[PackageManager],
+) -> Option<(PackageManager, &'a str)> {
+    managers
+        .iter()
+        .find_map(|manager| Some((*manager, package.name_for(*manager)?)))
+}
+
+/// Groups `packages` by the package manager each is installed with, in
+/// the order of `managers`.
+fn plan<'a>(
+    packages: &[&'a Package],
+    managers: &[PackageManager],
+) -> Vec<(PackageManager, Vec<&'a Package>)> {
+    managers
+        .iter()
+        .map(|manager| {
+            let packages = packages
+                .iter()
+                .copied()
+                .filter(|package| {
+                    pick(package, managers).is_some_and(|(picked, _)| picked == *manager)
+                })
+                .collect();
+            (*manager, packages)
+        })
+        .filter(|(_, packages): &(_, Vec<&Package>)| !packages.is_empty())
+        .collect()
+}
+
+/// The names of `packages` for `manager`.
+fn names_for<'a>(packages: &[&'a Package], manager: PackageManager) -> Vec<&'a str> {
+    packages
+        .iter()
+        .filter_map(|package| package.name_for(manager))
+        .collect()
+}
+
+/// Asks `dpkg -s` which of `names` are installed. It fails when any of them
+/// is not, but still describes the others.
+fn dpkg_status(names: &[&str]) -> Result<std::collections::BTreeSet<String>, DotmanError> {
+    if names.is_empty() {
+        return Ok(Default::default());
+    }
+    let output = std::process::Command::new("dpkg")
+        .arg("-s")
+        .args(names)
+        .output()
+        .map_err(|err| DotmanError::CommandError {
+            command: "dpkg".to_string(),
+            message: err.to_string(),
+        })?;
+    Ok(parse_dpkg_status(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// The installed packages in `dpkg -s` output, by name and by name with
+/// their architecture, like `libc6:i386`. Packages removed with their
+/// configuration kept are not installed.
+fn parse_dpkg_status(output: &str) -> std::collections::BTreeSet<String> {
+    let mut installed = std::collections::BTreeSet::new();
+    for stanza in output.split("\n\n") {
+        let field = |key: &str| {
+            stanza
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix(':'))
+                .map(str::trim)
+        };
+        let Some(name) = field("Package") else {
+            continue;
+        };
+        if field("Status").and_then(|status| status.split_whitespace().last()) != Some("installed")
+        {
+            continue;
+        }
+        if let Some(arch) = field("Architecture") {
+            installed.insert(format!("{name}:{arch}"));
+        }
+        installed.insert(name.to_string());
+    }
+    installed
+}
+
+/// The names in `wanted` that are not `installed`, each once. Homebrew
+/// lists formulae from taps, like `user/tap/name`, by their last part.
+pub fn missing<'a>(
+    wanted: impl IntoIterator<Item = &'a str>,
+    installed: &std::collections::BTreeSet<String>,
+) -> Vec<&'a str> {
+    let mut missing: Vec<&str> = Vec::new();
+    for name in wanted {
+        if !installed.contains(name)
+            && !installed.contains(short_name(name))
+            && !missing.contains(&name)
+        {
+            missing.push(name);
+        }
+    }
+    missing
+}
+
+/// Runs a query like `brew list` and returns what it printed.
+fn query(mut command: std::process::Command) -> Result<String, DotmanError> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command.output().map_err(|err| DotmanError::CommandError {
+        command: program.clone(),
+        message: err.to_string(),
+    })?;
+    if !output.status.success() {
+        return Err(DotmanError::CommandError {
+            command: program,
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The formulae and casks of a Homebrew Brewfile, or of what Homebrew has
+/// installed. Taps, Mac App Store apps and other entries are not tracked.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Brewfile {
+    pub formulae: std::collections::BTreeSet<String>,
+    pub casks: std::collections::BTreeSet<String>,
+}
+
+impl Brewfile {
+    /// Reads the `brew "name"` and `cask "name"` lines of a Brewfile.
+    pub fn parse(contents: &str) -> Brewfile {
+        let mut brewfile = Brewfile::default();
+        for line in contents.lines() {
+            let Some((kind, rest)) = line.trim().split_once(char::is_whitespace) else {
+                continue;
+            };
+            let rest = rest.trim_start();
+            let Some(quote) = rest.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+                continue;
+            };
+            let Some((name, _)) = rest[1..].split_once(quote) else {
+                continue;
+            };
+            match kind {
+                "brew" => brewfile.formulae.insert(name.to_string()),
+                "cask" => brewfile.casks.insert(name.to_string()),
+                _ => continue,
+            };
+        }
+        brewfile
+    }
+
+    /// What Homebrew has installed. With `requested`, only the formulae
+    /// installed on request are listed, leaving out dependencies.
+    pub fn installed(requested: bool) -> Result<Brewfile, DotmanError> {
+        let mut formulae = std::process::Command::new("brew");
+        if requested {
+            formulae.args(["leaves", "--installed-on-request"]);
+        } else {
+            formulae.args(["list", "--formula", "-1"]);
+        }
+        let mut casks = std::process::Command::new("brew");
+        casks.args(["list", "--cask", "-1"]);
+
+        let names = |output: String| {
+            output
+                .lines()
+                .map(|line| line.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .collect()
+        };
+        Ok(Brewfile {
+            formulae: names(query(formulae)?),
+            casks: names(query(casks)?),
+        })
+    }
+
+    /// The formulae and casks of `self` that `other` does not have. Names
+    /// from taps, like `user/tap/name`, match by their last part.
+    pub fn without(&self, other: &Brewfile) -> Brewfile {
+        let without = |ours: &std::collections::BTreeSet<String>,
+                       theirs: &std::collections::BTreeSet<String>| {
+            let theirs: std::collections::BTreeSet<&str> =
+                theirs.iter().map(|name| short_name(name)).collect();
+            ours.iter()
+                .filter(|name| !theirs.contains(short_name(name)))
+                .cloned()
+                .collect()
+        };
+        Brewfile {
+            formulae: without(&self.formulae, &other.formulae),
+            casks: without(&self.casks, &other.casks),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.formulae.is_empty() && self.casks.is_empty()
+    }
+}
+
+fn short_name(name: &str) -> &str {
+    name.rsplit('/').next().unwrap_or(name)
+}
+
+impl Dotman {
+    /// The packages `options` selects whose conditions are met.
+    fn selected_packages(&self, options: &InstallOptions) -> Vec<&Package> {
+        self.config
+            .get_effective_packages()
+            .into_iter()
+            .filter(|package| options.selects_package(package) && package.is_met(&self.context))
+            .collect()
+    }
+
+    /// The package managers of this machine, in the order packages are
+    /// looked up in: the system's own, the AUR on Arch when an AUR helper is
+    /// installed, then Flatpak, Snap, Cargo, pipx and npm where they are
+    /// installed.
+    fn package_managers(&self) -> Vec<PackageManager> {
+        let mut managers: Vec<PackageManager> = PackageManager::detect().into_iter().collect();
+        if managers.contains(&PackageManager::Pacman) {
+            let helper = self.config.aur_helper.or_else(|| {
+                [AurHelper::Paru, AurHelper::Yay]
+                    .into_iter()
+                    .find(|helper| which::which(helper.program()).is_ok())
+            });
+            managers.extend(helper.map(PackageManager::Aur));
+        }
+        managers.extend(
+            [
+                PackageManager::Flatpak,
+                PackageManager::Snap,
+                PackageManager::Cargo,
+                PackageManager::Pipx,
+                PackageManager::Npm,
+            ]
+            .into_iter()
+            .filter(|manager| which::which(manager.program()).is_ok()),
+        );
+        managers
+    }
+
+    /// Prints whether each package is installed, and how Homebrew differs
+    /// from each Brewfile, for `dotman status`.
+    pub(crate) fn print_package_status(&self) -> Result<(), DotmanError> {
+        let packages = self.config.get_effective_packages();
+        if packages
+            .iter()
+            .any(|package| package.names().next().is_some())
+        {
+            let managers = self.package_managers();
+            if managers.is_empty() {
+                println!("No supported package manager found.");
+            } else {
+                self.print_named_status(&packages, &managers)?;
+            }
+        }
+        for package in packages {
+            if let Some(brewfile) = &package.brewfile {
+                self.print_brewfile_status(package, brewfile)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn print_named_status(
+        &self,
+        packages: &[&Package],
+        managers: &[PackageManager],
+    ) -> Result<(), DotmanError> {
+        let installed = plan(packages, managers)
+            .into_iter()
+            .map(|(manager, packages)| {
+                Ok((manager, manager.installed(&names_for(&packages, manager))?))
+            })
+            .collect::<Result<Vec<_>, DotmanError>>()?;
+        for package in packages {
+            let Some((key, name)) = package.names().next() else {
+                continue;
+            };
+            let (key, name, label) = match pick(package, managers) {
+                _ if !package.is_met(&self.context) => {
+                    (key, name, "[CONDITION NOT MET]".yellow().bold())
+                }
+                None => (key, name, "[NOT AVAILABLE]".yellow().bold()),
+                Some((manager, name)) => {
+                    let is_installed = installed.iter().any(|(installed_by, installed)| {
+                        *installed_by == manager && manager.has(package, installed)
+                    });
+                    let label = if is_installed {
+                        "[INSTALLED]".green().bold()
+                    } else {
+                        "[MISSING]".red().bold()
+                    };
+                    (manager.as_str(), name, label)
+                }
+            };
+            println!("{label} {key}: {name}");
+        }
+        Ok(())
+    }
+
+    fn print_brewfile_status(&self, package: &Package, brewfile: &str) -> Result<(), DotmanError> {
+        if !package.is_met(&self.context) {
+            println!("{} {}", "[CONDITION NOT MET]".yellow().bold(), brewfile);
+            return Ok(());
+        }
+        if which::which("brew").is_err() {
+            println!("{} {}", "[BREW NOT INSTALLED]".yellow().bold(), brewfile);
+            return Ok(());
+        }
+        let path = self.config.resolve_source(brewfile)?;
+        let declared = Brewfile::parse(&std::fs::read_to_string(&path)?);
+        let missing = declared.without(&Brewfile::installed(false)?);
+        let extraneous = Brewfile::installed(true)?.without(&declared);
+
+        if missing.is_empty() && extraneous.is_empty() {
+            println!("{} {}", "[OK]".green().bold(), brewfile);
+        }
+        for (label, entries) in [
+            ("[MISSING]".red().bold(), missing),
+            ("[EXTRANEOUS]".yellow().bold(), extraneous),
+        ] {
+            for formula in &entries.formulae {
+                println!("{label} {brewfile}: brew {formula}");
+            }
+            for cask in &entries.casks {
+                println!("{label} {brewfile}: cask {cask}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Installs the selected packages that are missing, each with the first
+    /// of the machine's package managers it is named for, then the
+    /// Brewfiles with `brew bundle`. Packages named for none of them are left
+    /// out.
+    pub fn install_packages(&self, options: &InstallOptions) -> Result<(), DotmanError> {
+        let packages = self.selected_packages(options);
+        if packages
+            .iter()
+            .any(|package| package.names().next().is_some())
+        {
+            let managers = self.package_managers();
+            if managers.is_empty() {
+                println!(
+                    "{} No supported package manager found, skipping packages.",
+                    "Warning:".yellow().bold()
+                );
+            }
+            for (manager, packages) in plan(&packages, &managers) {
+                self.install_named(manager, &packages, options)?;
+            }
+        }
+        for brewfile in packages
+            .iter()
+            .filter_map(|package| package.brewfile.as_ref())
+        {
+            self.bundle(brewfile, options)?;
+        }
+        Ok(())
+    }
+
+    /// Installs those of `packages` that `manager` has not installed yet,
+    /// together unless they need different arguments.
+    fn install_named(
+        &self,
+        manager: PackageManager,
+        packages: &[&Package],
+        options: &InstallOptions,
+    ) -> Result<(), DotmanError> {
+        let installed = manager.installed(&names_for(packages, manager))?;
+        let mut batches: Vec<(Vec<&str>, Vec<&str>)> = Vec::new();
+        for package in packages {
+            let Some(name) = package.name_for(manager) else {
+                continue;
+            };
+            if manager.has(package, &installed) {
+                continue;
+            }
+            let args = manager.install_args(package);
+            match batches
+                .iter_mut()
+                .find(|(batch_args, _)| *batch_args == args)
+            {
+                Some((_, names)) if !names.contains(&name) => names.push(name),
+                Some(_) => {}
+                None => batches.push((args, vec![name])),
+            }
+        }
+        if options.dry_run {
+            if !batches.is_empty() {
+                let names: Vec<&str> = batches
+                    .iter()
+                    .flat_map(|(_, names)| names.iter().copied())
+                    .collect();
+                println!("{} {}", "Would install:".cyan().bold(), names.join(", "));
+            }
+            return Ok(());
+        }
+        for (args, names) in batches {
+            self.install_batch(manager, &[args, names].concat())?;
+        }
+        Ok(())
+    }
+
+    /// Runs `manager`'s install command with `args`, as root if it needs
+    /// it.
+    fn install_batch(&self, manager: PackageManager, args: &[&str]) -> Result<(), DotmanError> {
+        let name = format!("{manager} install");
+        println!(
+            "{} Installing {}",
+            "Packages:".blue().bold(),
+            args.join(" ")
+        );
+        self.emit(crate::events::Event::ActionStarted { name: name.clone() });
+        let command = manager.install_command(args);
+        let mut command = match crate::privilege::program() {
+            Some(program) if manager.needs_root() && !self.context.root => {
+                crate::privilege::elevate(&command, program)
+            }
+            _ => command,
+        };
+        self.finish_action(&name, command.output())
+            .map_err(|err| err.in_action(&name))
+    }
+
+    /// Runs `brew bundle` on `brewfile`, unless everything in it is
+    /// installed already.
+    fn bundle(&self, brewfile: &str, options: &InstallOptions) -> Result<(), DotmanError> {
+        if which::which("brew").is_err() {
+            println!(
+                "{} Homebrew is not installed, skipping {}.",
+                "Warning:".yellow().bold(),
+                brewfile
+            );
+            return Ok(());
+        }
+        let path = self.config.resolve_source(brewfile)?;
+        let satisfied = std::process::Command::new("brew")
+            .args(["bundle", "check", "--no-upgrade", "--file"])
+            .arg(&path)
+            .output()
+            .is_ok_and(|output| output.status.success());
+        if satisfied {
+            return Ok(());
+        }
+        if options.dry_run {
+            println!("{} brew bundle {}", "Would run:".cyan().bold(), brewfile);
+            return Ok(());
+        }
+
+        let name = format!("brew bundle {brewfile}");
+        println!("{} Running {}", "Packages:".blue().bold(), name);
+        self.emit(crate::events::Event::ActionStarted { name: name.clone() });
+        let output = std::process::Command::new("brew")
+            .args(["bundle", "install", "--no-upgrade", "--file"])
+            .arg(&path)
+            .output();
+        self.finish_action(&name, output)
+            .map_err(|err| err.in_action(&name))
+    }
+
+    /// Writes what Homebrew has installed to the first Brewfile in the
+    /// configuration, with `brew bundle dump`, and returns its path.
+    pub fn dump_brewfile(&self) -> Result<std::path::PathBuf, DotmanError> {
+        let brewfile = self
+            .config
+            .get_effective_packages()
+            .into_iter()
+            .find_map(|package| package.brewfile.as_ref())
+            .ok_or_else(|| {
+                DotmanError::InvalidConfig("No [[packages]] entry has a brewfile".to_string())
+            })?;
+        let path = self.config.resolve_source(brewfile)?;
+        let mut command = std::process::Command::new("brew");
+        command
+            .args(["bundle", "dump", "--force", "--file"])
+            .arg(&path);
+        query(command)?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing() {
+        let installed = parse_dpkg_status(
+            "Package: ripgrep\nStatus: install ok installed\nArchitecture: amd64\n\nPackage: fd-find\nStatus: deinstall ok config-files\n\nPackage: git\nStatus: install ok installed\n",
+        );
+        assert_eq!(
+            installed.iter().collect::<Vec<_>>(),
+            ["git", "ripgrep", "ripgrep:amd64"]
+        );
+        assert_eq!(
+            missing(["ripgrep:amd64", "fd-find", "git"], &installed),
+            ["fd-find"]
+        );
+
+        let installed = ["ripgrep", "neovim"].map(String::from).into();
+        assert_eq!(
+            missing(["ripgrep", "fd", "homebrew/core/neovim", "fd"], &installed),
+            ["fd"]
+        );
+    }
+
+    #[test]
+    fn test_brewfile() {
+        let declared = Brewfile::parse(
+            "tap \"homebrew/cask-fonts\"\nbrew \"ripgrep\"\nbrew 'neovim', args: [\"HEAD\"]\n# brew \"old\"\ncask \"homebrew/cask-fonts/font-fira-code\"\nmas \"Xcode\", id: 497799835\n",
+        );
+        assert_eq!(
+            declared.formulae.iter().collect::<Vec<_>>(),
+            ["neovim", "ripgrep"]
+        );
+        assert_eq!(
+            declared.casks.iter().collect::<Vec<_>>(),
+            ["homebrew/cask-fonts/font-fira-code"]
+        );
+
+        let installed = Brewfile {
+            formulae: ["ripgrep", "jq"].map(String::from).into(),
+            casks: ["font-fira-code", "firefox"].map(String::from).into(),
+        };
+        assert_eq!(
+            declared.without(&installed),
+            Brewfile {
+                formulae: ["neovim".to_string()].into(),
+                casks: Default::default(),
+            }
+        );
+        assert_eq!(
+            installed.without(&declared),
+            Brewfile {
+                formulae: ["jq".to_string()].into(),
+                casks: ["firefox".to_string()].into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_plan() {
+        let ripgrep = Package {
+            apt: Some("ripgrep".to_string()),
+            pacman: Some("ripgrep".to_string()),
+            ..Default::default()
+        };
+        let paru = Package {
+            aur: Some("paru-bin".to_string()),
+            ..Default::default()
+        };
+        let fd = Package {
+            apt: Some("fd-find".to_string()),
+            ..Default::default()
+        };
+        let packages = [&ripgrep, &paru, &fd];
+
+        let aur = PackageManager::Aur(AurHelper::Paru);
+        let planned = plan(&packages, &[PackageManager::Pacman, aur]);
+        assert_eq!(
+            planned
+                .iter()
+                .map(|(manager, packages)| (*manager, names_for(packages, *manager)))
+                .collect::<Vec<_>>(),
+            [
+                (PackageManager::Pacman, vec!["ripgrep"]),
+                (aur, vec!["paru-bin"]),
+            ]
+        );
+        let planned = plan(&packages, &[PackageManager::Apt]);
+        assert_eq!(
+            names_for(&planned[0].1, PackageManager::Apt),
+            ["ripgrep", "fd-find"]
+        );
+        assert_eq!(aur.install_command(&["paru-bin"]).get_program(), "paru");
+    }
+
+    #[test]
+    fn test_install_command() {
+        let command = PackageManager::Pacman.install_command(&["ripgrep", "fd"]);
+        assert_eq!(command.get_program(), "pacman");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            ["-S", "--needed", "--noconfirm", "ripgrep", "fd"]
+        );
+
+        let code = Package {
+            snap: Some("code".to_string()),
+            snap_classic: true,
+            flatpak: Some("com.visualstudio.code".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(PackageManager::Snap.install_args(&code), ["--classic"]);
+        assert_eq!(PackageManager::Flatpak.install_args(&code), ["flathub"]);
+        assert_eq!(
+            PackageManager::Flatpak
+                .install_command(&["flathub", "com.visualstudio.code"])
+                .get_args()
+                .collect::<Vec<_>>(),
+            [
+                "install",
+                "-y",
+                "--noninteractive",
+                "flathub",
+                "com.visualstudio.code"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_installed() {
+        let installed = PackageManager::Snap.parse_installed(
+            "Name    Version  Rev    Tracking       Publisher  Notes\ncode    1.85     150    latest/stable  vscode     classic\ncore22  2024     1122   latest/stable  canonical  base\n",
+        );
+        assert_eq!(installed.iter().collect::<Vec<_>>(), ["code", "core22"]);
+
+        let installed = PackageManager::Flatpak.parse_installed("org.mozilla.firefox\n\n");
+        assert_eq!(
+            installed.iter().collect::<Vec<_>>(),
+            ["org.mozilla.firefox"]
+        );
+    }
+
+    #[test]
+    fn test_cargo_versions() {
+        let installed = PackageManager::Cargo.parse_installed(
+            "cargo-watch v8.5.2:\n    cargo-watch\nripgrep v14.1.0 (https://github.com/BurntSushi/ripgrep#4649aa97):\n    rg\n",
+        );
+        assert_eq!(
+            installed.iter().collect::<Vec<_>>(),
+            [
+                "cargo-watch",
+                "cargo-watch@8.5.2",
+                "ripgrep",
+                "ripgrep@14.1.0"
+            ]
+        );
+
+        let mut watch = Package {
+            cargo: Some("cargo-watch".to_string()),
+            ..Default::default()
+        };
+        assert!(PackageManager::Cargo.has(&watch, &installed));
+        watch.cargo_version = Some("8.5.2".to_string());
+        assert!(PackageManager::Cargo.has(&watch, &installed));
+        watch.cargo_version = Some("8.4.0".to_string());
+        watch.cargo_locked = true;
+        assert!(!PackageManager::Cargo.has(&watch, &installed));
+        assert_eq!(
+            PackageManager::Cargo.install_args(&watch),
+            ["--version", "8.4.0", "--locked"]
+        );
+    }
+
+    #[test]
+    fn test_parse_language_tools() {
+        let installed = PackageManager::Pipx.parse_installed("poetry 1.8.2\nruff 0.4.1\n");
+        assert_eq!(installed.iter().collect::<Vec<_>>(), ["poetry", "ruff"]);
+
+        let installed = PackageManager::Npm.parse_installed(
+            r#"{"name": "lib", "dependencies": {"typescript": {"version": "5.4.5"}, "@biomejs/biome": {"version": "1.7.0"}}}"#,
+        );
+        assert_eq!(
+            installed.iter().collect::<Vec<_>>(),
+            ["@biomejs/biome", "typescript"]
+        );
+        assert!(PackageManager::Npm.parse_installed("").is_empty());
+    }
+}
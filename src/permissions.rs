@@ -0,0 +1,200 @@
+//! Enforcing the `chmod`, `owner` and `group` of link targets.
+
+use colored::Colorize;
+
+use crate::{
+    Dotman,
+    config::Link,
+    error::DotmanError,
+    utils::{self, Absolute, ExpandTilde},
+};
+
+/// Permissions and ownership a target must have. Unset fields are left as
+/// they are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Permissions {
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+impl Permissions {
+    /// Resolves the `chmod`, `owner` and `group` of `link`.
+    pub fn of(link: &Link) -> Result<Permissions, DotmanError> {
+        let mode = link.chmod.as_deref().map(utils::parse_mode).transpose()?;
+        let uid = link
+            .owner
+            .as_deref()
+            .map(|owner| {
+                user_id(owner)
+                    .ok_or_else(|| DotmanError::InvalidConfig(format!("Unknown user '{owner}'")))
+            })
+            .transpose()?;
+        let gid = link
+            .group
+            .as_deref()
+            .map(|group| {
+                group_id(group)
+                    .ok_or_else(|| DotmanError::InvalidConfig(format!("Unknown group '{group}'")))
+            })
+            .transpose()?;
+        Ok(Permissions { mode, uid, gid })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Permissions::default()
+    }
+}
+
+/// Looks up the ID of the user `name`. Numeric names are taken as IDs.
+pub fn user_id(name: &str) -> Option<u32> {
+    if let Ok(id) = name.parse() {
+        return Some(id);
+    }
+    #[cfg(unix)]
+    {
+        let name = std::ffi::CString::new(name).ok()?;
+        // SAFETY: `passwd` is plain data that getpwnam_r fills in.
+        let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut buffer = vec![0; 16384];
+        let mut result = std::ptr::null_mut();
+        // SAFETY: every pointer is valid for the call, and `buffer`, which
+        // the strings in `passwd` point into, outlives it.
+        let code = unsafe {
+            libc::getpwnam_r(
+                name.as_ptr(),
+                &mut passwd,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        };
+        (code == 0 && !result.is_null()).then_some(passwd.pw_uid)
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+/// Looks up the ID of the group `name`. Numeric names are taken as IDs.
+pub fn group_id(name: &str) -> Option<u32> {
+    if let Ok(id) = name.parse() {
+        return Some(id);
+    }
+    #[cfg(unix)]
+    {
+        let name = std::ffi::CString::new(name).ok()?;
+        // SAFETY: `group` is plain data that getgrnam_r fills in.
+        let mut group: libc::group = unsafe { std::mem::zeroed() };
+        let mut buffer = vec![0; 16384];
+        let mut result = std::ptr::null_mut();
+        // SAFETY: every pointer is valid for the call, and `buffer`, which
+        // the strings in `group` point into, outlives it.
+        let code = unsafe {
+            libc::getgrnam_r(
+                name.as_ptr(),
+                &mut group,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        };
+        (code == 0 && !result.is_null()).then_some(group.gr_gid)
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+impl Dotman {
+    /// Whether `link`'s target has the permissions and owner it declares.
+    pub(crate) fn has_permissions(&self, link: &Link, target: &std::path::Path) -> bool {
+        let Ok(wanted) = Permissions::of(link) else {
+            return false;
+        };
+        let fs = self.fs_for(link);
+        let mode_ok = match (wanted.mode, fs.mode(target)) {
+            (Some(wanted), Some(actual)) => wanted == actual,
+            _ => true,
+        };
+        let owner_ok = match fs.owner(target) {
+            Some((uid, gid)) => {
+                wanted.uid.is_none_or(|wanted| wanted == uid)
+                    && wanted.gid.is_none_or(|wanted| wanted == gid)
+            }
+            None => true,
+        };
+        mode_ok && owner_ok
+    }
+
+    /// Gives `link`'s target the permissions and owner it declares, if it
+    /// exists and differs.
+    pub(crate) fn apply_permissions(&self, link: &Link) -> Result<(), DotmanError> {
+        let wanted = Permissions::of(link)?;
+        let target = link.target.expand_tilde_path()?.absolute()?;
+        if wanted.is_empty() || !self.fs.exists(&target) || self.has_permissions(link, &target) {
+            return Ok(());
+        }
+        let fs = self.fs_for(link);
+        if let Some(mode) = wanted.mode {
+            fs.set_mode(&target, mode)?;
+        }
+        if wanted.uid.is_some() || wanted.gid.is_some() {
+            fs.set_owner(&target, wanted.uid, wanted.gid)?;
+        }
+        let declared: Vec<&str> = [&link.chmod, &link.owner, &link.group]
+            .into_iter()
+            .filter_map(|value| value.as_deref())
+            .collect();
+        println!(
+            "{} {} is now {}",
+            "Permissions:".green().bold(),
+            target.display(),
+            declared.join(" ")
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{Filesystem, MemoryFilesystem};
+
+    #[test]
+    fn test_lookup_ids() {
+        assert_eq!(user_id("0"), Some(0));
+        assert_eq!(group_id("1000"), Some(1000));
+        assert_eq!(user_id("no-such-user-dotman"), None);
+        #[cfg(unix)]
+        assert_eq!(user_id("root"), Some(0));
+    }
+
+    #[test]
+    fn test_apply_permissions() {
+        let fs = std::sync::Arc::new(MemoryFilesystem::new());
+        fs.write(std::path::Path::new("/dotfiles/ssh_config"), b"", false)
+            .unwrap();
+        let link = Link {
+            chmod: Some("600".to_string()),
+            group: Some("100".to_string()),
+            ..Link::new("/dotfiles/ssh_config", "/home/me/.ssh/config")
+        };
+        let config = crate::DotmanConfig::builder().link(link).build();
+        let dotman = Dotman::new(config).with_filesystem(fs.clone());
+
+        dotman.install().unwrap();
+
+        let source = std::path::Path::new("/dotfiles/ssh_config");
+        assert_eq!(fs.mode(source), Some(0o600));
+        assert_eq!(fs.owner(source), Some((1000, 100)));
+        let reports = dotman.link_statuses().unwrap();
+        assert_eq!(reports[0].status, crate::LinkStatus::Ok);
+
+        fs.set_mode(source, 0o644).unwrap();
+        let reports = dotman.link_statuses().unwrap();
+        assert_eq!(reports[0].status, crate::LinkStatus::WrongPermissions);
+    }
+}
@@ -0,0 +1,256 @@
+//! Checks that an install can go through before it changes anything.
+
+use crate::{
+    Dotman,
+    config::{Action, Link},
+    error::DotmanError,
+    fs::EntryKind,
+    options::InstallOptions,
+    utils::{Absolute, ExpandTilde},
+};
+
+/// Something that would make an install fail part way through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    /// What the problem is about, such as a target or an action.
+    pub subject: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.subject, self.message)
+    }
+}
+
+impl Dotman {
+    /// Checks the prerequisites of installing what `options` selects on this
+    /// machine: that the targets' directories and those `[[dirs]]` are
+    /// created in are writable (or that sudo or doas is there for those with
+    /// `become`), that the shells or interpreters actions run in are
+    /// installed, that the hosts of pending downloads can be reached and
+    /// that the modes, users and groups links declare are valid.
+    /// Returns every problem found.
+    pub fn preflight(&self, options: &InstallOptions) -> Vec<Problem> {
+        let links: Vec<&Link> = self
+            .config
+            .get_effective_links()
+            .into_iter()
+            .filter(|link| options.selects_link(link) && link.is_met(&self.context))
+            .collect();
+
+        let mut problems: Vec<Problem> = links
+            .iter()
+            .filter(|link| !link.elevate || self.context.root)
+            .filter_map(|link| self.check_target(link, options).err())
+            .collect();
+
+        for link in &links {
+            if let Err(err) = crate::permissions::Permissions::of(link) {
+                problems.push(Problem {
+                    subject: link.target.clone(),
+                    message: err.to_string(),
+                });
+            }
+        }
+
+        for dir in self.config.get_effective_dirs() {
+            if options.selects_dir(dir)
+                && dir.is_met(&self.context)
+                && let Err(message) = self.check_dir(dir)
+            {
+                problems.push(Problem {
+                    subject: dir.path.clone(),
+                    message,
+                });
+            }
+        }
+
+        if self.needs_root(options) && crate::privilege::program().is_none() {
+            problems.push(Problem {
+                subject: "become".to_string(),
+                message: "needs sudo or doas, and neither is installed".to_string(),
+            });
+        }
+
+        for action in self.config.get_effective_actions() {
+            if !options.selects_action(action) || !action.is_met(&self.context) {
+                continue;
+            }
+            if let Some(run) = action.run()
+                && which::which(run.interpreter()).is_err()
+            {
+                let interpreter = run.interpreter();
+                problems.push(Problem {
+                    subject: format!("action '{}'", action.name()),
+                    message: format!("{interpreter} is not installed"),
+                });
+            }
+            if matches!(action, Action::Git { .. }) && which::which("git").is_err() {
+                problems.push(Problem {
+                    subject: format!("action '{}'", action.name()),
+                    message: "git is not installed".to_string(),
+                });
+            }
+            if matches!(action, Action::SystemdUnit { .. }) && which::which("systemctl").is_err() {
+                problems.push(Problem {
+                    subject: format!("action '{}'", action.name()),
+                    message: "systemctl is not installed".to_string(),
+                });
+            }
+            if matches!(action, Action::Registry { .. }) && which::which("reg").is_err() {
+                problems.push(Problem {
+                    subject: format!("action '{}'", action.name()),
+                    message: "reg is not available, registry actions only run on Windows"
+                        .to_string(),
+                });
+            }
+            if let Some(url) = self.pending_url(action)
+                && let Err(message) = crate::download::check_reachable(url)
+            {
+                problems.push(Problem {
+                    subject: format!("action '{}'", action.name()),
+                    message,
+                });
+            }
+        }
+        problems
+    }
+
+    /// Fails with every problem [`Dotman::preflight`] finds. Otherwise, if
+    /// anything selected has `become`, asks for the root password now so
+    /// the install does not stop for it later.
+    pub(crate) fn check_preflight(&self, options: &InstallOptions) -> Result<(), DotmanError> {
+        let problems = self.preflight(options);
+        if !problems.is_empty() {
+            return Err(DotmanError::Preflight(problems));
+        }
+        match crate::privilege::program() {
+            Some(program) if !options.dry_run && self.needs_root(options) => {
+                crate::privilege::authenticate(program)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether a selected link or action has `become` and dotman does not
+    /// run as root already.
+    fn needs_root(&self, options: &InstallOptions) -> bool {
+        if self.context.root {
+            return false;
+        }
+        let links =
+            self.config.get_effective_links().into_iter().any(|link| {
+                link.elevate && options.selects_link(link) && link.is_met(&self.context)
+            });
+        links
+            || self
+                .config
+                .get_effective_actions()
+                .into_iter()
+                .any(|action| {
+                    action.elevate()
+                        && options.selects_action(action)
+                        && action.is_met(&self.context)
+                })
+    }
+
+    /// The URL an action still has to fetch from, if any.
+    fn pending_url<'a>(&self, action: &'a Action) -> Option<&'a str> {
+        let url = match action {
+            Action::Download { url, .. } => url,
+            Action::Extract { source, .. } if crate::remote::is_url(source.as_ref()) => source,
+            _ => return None,
+        };
+        (!self.is_done(action)).then_some(url.as_str())
+    }
+
+    /// Checks that `link`'s target can be created in the closest directory
+    /// that exists. Targets that are already linked, or that exist and would
+    /// be left alone, need nothing.
+    fn check_target(&self, link: &Link, options: &InstallOptions) -> Result<(), Problem> {
+        let problem = |message: String| Problem {
+            subject: link.target.clone(),
+            message,
+        };
+        let source = self
+            .config
+            .resolve_source(&link.source)
+            .map_err(|err| problem(err.to_string()))?;
+        let target = link
+            .target
+            .expand_tilde_path()
+            .and_then(|target| target.absolute())
+            .map_err(|err| problem(err.to_string()))?;
+
+        if self.fs.kind(&target).is_some() {
+            let linked = self
+                .fs
+                .read_link(&target)
+                .is_ok_and(|destination| destination == source);
+            if linked || !(options.overwrite || self.config.overwrite) {
+                return Ok(());
+            }
+        }
+
+        let Some(dir) = target
+            .ancestors()
+            .skip(1)
+            .find(|ancestor| self.fs.kind(ancestor).is_some())
+        else {
+            return Ok(());
+        };
+        if !self.fs.exists(dir) || self.fs.kind(dir) == Some(EntryKind::File) {
+            return Err(problem(format!("{} is not a directory", dir.display())));
+        }
+        if !self.fs.is_writable(dir) {
+            return Err(problem(format!("{} is not writable", dir.display())));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{Filesystem, MemoryFilesystem};
+
+    #[test]
+    fn test_preflight_reports_every_unwritable_target() {
+        let fs = std::sync::Arc::new(MemoryFilesystem::new());
+        fs.write(std::path::Path::new("/dotfiles/hosts"), b"", false)
+            .unwrap();
+        fs.write(std::path::Path::new("/dotfiles/motd"), b"", false)
+            .unwrap();
+        fs.create_dir_all(std::path::Path::new("/etc")).unwrap();
+        fs.create_dir_all(std::path::Path::new("/home/me")).unwrap();
+        fs.set_read_only(std::path::Path::new("/etc"));
+
+        let config = crate::DotmanConfig::builder()
+            .link(Link::new("/dotfiles/hosts", "/etc/hosts"))
+            .link(Link::new("/dotfiles/motd", "/etc/motd.d/dotman"))
+            .link(Link::new("/dotfiles/hosts", "/home/me/.hosts"))
+            .action(Action::shell("Greet", "true"))
+            .build();
+        let dotman = Dotman::new(config).with_filesystem(fs.clone());
+
+        let problems = dotman.preflight(&InstallOptions::default());
+        assert_eq!(
+            problems,
+            [
+                Problem {
+                    subject: "/etc/hosts".to_string(),
+                    message: "/etc is not writable".to_string(),
+                },
+                Problem {
+                    subject: "/etc/motd.d/dotman".to_string(),
+                    message: "/etc is not writable".to_string(),
+                },
+            ]
+        );
+
+        let err = dotman.install().unwrap_err();
+        assert!(matches!(err, DotmanError::Preflight(ref problems) if problems.len() == 2));
+        assert_eq!(fs.kind(std::path::Path::new("/home/me/.hosts")), None);
+    }
+}
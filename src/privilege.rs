@@ -0,0 +1,209 @@
+//! Running actions and changing targets as root, for entries with
+//! `become = true`.
+
+use crate::{
+    error::DotmanError,
+    fs::{EntryKind, Filesystem, RealFilesystem},
+};
+
+/// The program used to become root: `sudo`, or `doas` where sudo is not
+/// installed.
+pub fn program() -> Option<&'static str> {
+    ["sudo", "doas"]
+        .into_iter()
+        .find(|program| which::which(program).is_ok())
+}
+
+/// Asks for the password once, before anything runs, so the rest of the run
+/// reuses the cached credential instead of prompting in the middle of it.
+pub fn authenticate(program: &str) -> Result<(), DotmanError> {
+    let args: &[&str] = match program {
+        "sudo" => &["-v"],
+        _ => &["true"],
+    };
+    let status = std::process::Command::new(program).args(args).status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(DotmanError::CommandError {
+            command: program.to_string(),
+            message: "could not become root".to_string(),
+        })
+    }
+}
+
+/// Wraps `command` to run through `program`.
+pub fn elevate(command: &std::process::Command, program: &str) -> std::process::Command {
+    let mut elevated = std::process::Command::new(program);
+    elevated.arg(command.get_program()).args(command.get_args());
+    elevated
+}
+
+/// The machine's filesystem, changed through `sudo` or `doas` so that
+/// targets in system directories can be written. Reading needs no
+/// privileges, so it happens directly.
+#[derive(Debug, Default)]
+pub struct ElevatedFilesystem;
+
+impl ElevatedFilesystem {
+    fn run(&self, args: &[&std::ffi::OsStr], stdin: Option<&[u8]>) -> std::io::Result<()> {
+        use std::io::Write;
+
+        let program = program().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "`become` needs sudo or doas, and neither is installed",
+            )
+        })?;
+        let mut command = std::process::Command::new(program);
+        command
+            .args(args)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped());
+        if stdin.is_some() {
+            command.stdin(std::process::Stdio::piped());
+        }
+
+        let mut child = command.spawn()?;
+        if let (Some(contents), Some(mut pipe)) = (stdin, child.stdin.take()) {
+            pipe.write_all(contents)?;
+        }
+        let output = child.wait_with_output()?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(std::io::Error::other(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ))
+        }
+    }
+
+    fn create_parent(&self, path: &std::path::Path) -> std::io::Result<()> {
+        match path.parent() {
+            Some(parent) => self.create_dir_all(parent),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Filesystem for ElevatedFilesystem {
+    fn kind(&self, path: &std::path::Path) -> Option<EntryKind> {
+        RealFilesystem.kind(path)
+    }
+
+    fn exists(&self, path: &std::path::Path) -> bool {
+        RealFilesystem.exists(path)
+    }
+
+    fn read_link(&self, path: &std::path::Path) -> std::io::Result<std::path::PathBuf> {
+        RealFilesystem.read_link(path)
+    }
+
+    fn symlink(&self, source: &std::path::Path, target: &std::path::Path) -> std::io::Result<()> {
+        self.run(
+            &[
+                "ln".as_ref(),
+                "-s".as_ref(),
+                source.as_ref(),
+                target.as_ref(),
+            ],
+            None,
+        )
+    }
+
+    fn junction(&self, source: &std::path::Path, target: &std::path::Path) -> std::io::Result<()> {
+        self.symlink(source, target)
+    }
+
+    fn write(&self, path: &std::path::Path, contents: &[u8], private: bool) -> std::io::Result<()> {
+        let mode = if private { "600" } else { "644" };
+        self.run(
+            &[
+                "install".as_ref(),
+                "-m".as_ref(),
+                mode.as_ref(),
+                "/dev/stdin".as_ref(),
+                path.as_ref(),
+            ],
+            Some(contents),
+        )
+    }
+
+    fn copy(&self, source: &std::path::Path, target: &std::path::Path) -> std::io::Result<()> {
+        self.run(
+            &[
+                "cp".as_ref(),
+                "-RP".as_ref(),
+                source.as_ref(),
+                target.as_ref(),
+            ],
+            None,
+        )
+    }
+
+    fn create_dir_all(&self, path: &std::path::Path) -> std::io::Result<()> {
+        if path.is_dir() {
+            return Ok(());
+        }
+        self.run(&["mkdir".as_ref(), "-p".as_ref(), path.as_ref()], None)
+    }
+
+    fn remove(&self, path: &std::path::Path) -> std::io::Result<()> {
+        self.run(&["rm".as_ref(), "-rf".as_ref(), path.as_ref()], None)
+    }
+
+    fn rename(&self, from: &std::path::Path, to: &std::path::Path) -> std::io::Result<()> {
+        self.create_parent(to)?;
+        self.run(&["mv".as_ref(), from.as_ref(), to.as_ref()], None)
+    }
+
+    fn is_writable(&self, _dir: &std::path::Path) -> bool {
+        true
+    }
+
+    fn mode(&self, path: &std::path::Path) -> Option<u32> {
+        RealFilesystem.mode(path)
+    }
+
+    fn set_mode(&self, path: &std::path::Path, mode: u32) -> std::io::Result<()> {
+        let mode = format!("{mode:o}");
+        self.run(&["chmod".as_ref(), mode.as_ref(), path.as_ref()], None)
+    }
+
+    fn owner(&self, path: &std::path::Path) -> Option<(u32, u32)> {
+        RealFilesystem.owner(path)
+    }
+
+    fn set_owner(
+        &self,
+        path: &std::path::Path,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    ) -> std::io::Result<()> {
+        let owner = match (uid, gid) {
+            (Some(uid), Some(gid)) => format!("{uid}:{gid}"),
+            (Some(uid), None) => uid.to_string(),
+            (None, Some(gid)) => format!(":{gid}"),
+            (None, None) => return Ok(()),
+        };
+        self.run(&["chown".as_ref(), owner.as_ref(), path.as_ref()], None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elevate() {
+        let mut command = std::process::Command::new("sh");
+        command.arg("-c").arg("id -u");
+
+        let elevated = elevate(&command, "doas");
+        assert_eq!(elevated.get_program(), "doas");
+        assert_eq!(
+            elevated.get_args().collect::<Vec<_>>(),
+            ["sh", "-c", "id -u"]
+        );
+    }
+}
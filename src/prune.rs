@@ -0,0 +1,127 @@
+//! Finding symlinks left dangling into the dotfiles after sources were
+//! renamed or deleted, for `dotman prune`.
+
+use colored::Colorize;
+
+use crate::{Dotman, error::DotmanError, events::Event, utils::Absolute};
+
+impl Dotman {
+    /// Directories that link sources live in: the one holding the
+    /// configuration, the `[repo]` and each of `[repos]`.
+    pub fn source_dirs(&self) -> Result<Vec<std::path::PathBuf>, DotmanError> {
+        let mut dirs = vec![self.config.resolve_path(".")?, self.config.repo_dir()?];
+        for name in self.config.repos.keys() {
+            dirs.extend(self.config.named_repo_dir(name)?);
+        }
+        dirs.sort();
+        dirs.dedup();
+        Ok(dirs)
+    }
+
+    /// Symlinks under `dirs` that point to something missing in one of the
+    /// [`Dotman::source_dirs`], with where they point. The source directories
+    /// themselves are not searched.
+    pub fn dangling_links(
+        &self,
+        dirs: &[std::path::PathBuf],
+    ) -> Result<Vec<(std::path::PathBuf, std::path::PathBuf)>, DotmanError> {
+        let source_dirs = self.source_dirs()?;
+        let mut dangling = Vec::new();
+        for dir in dirs {
+            let skipped = source_dirs.clone();
+            let walker = ignore::WalkBuilder::new(dir)
+                .standard_filters(false)
+                .follow_links(false)
+                .filter_entry(move |entry| !skipped.iter().any(|dir| entry.path() == dir))
+                .build();
+            // Directories that cannot be read hold nothing dotman linked.
+            for entry in walker.filter_map(Result::ok) {
+                if !entry.path_is_symlink() {
+                    continue;
+                }
+                let path = entry.path();
+                let Ok(points_to) = std::fs::read_link(path) else {
+                    continue;
+                };
+                let points_to = match path.parent() {
+                    Some(parent) if points_to.is_relative() => parent.join(points_to),
+                    _ => points_to,
+                };
+                if !points_to.exists() && source_dirs.iter().any(|dir| points_to.starts_with(dir)) {
+                    dangling.push((path.to_path_buf(), points_to));
+                }
+            }
+        }
+        Ok(dangling)
+    }
+
+    /// Removes the symlinks [`Dotman::dangling_links`] finds under `dirs`,
+    /// or only lists them with `dry_run`. Returns how many there were.
+    pub fn prune(&self, dirs: &[std::path::PathBuf], dry_run: bool) -> Result<usize, DotmanError> {
+        let dirs = dirs
+            .iter()
+            .map(|dir| dir.absolute())
+            .collect::<Result<Vec<_>, _>>()?;
+        let dangling = self.dangling_links(&dirs)?;
+        for (link, points_to) in &dangling {
+            if dry_run {
+                println!(
+                    "{} {} -> {}",
+                    "Would remove:".cyan().bold(),
+                    link.display(),
+                    points_to.display()
+                );
+                continue;
+            }
+            self.fs.remove(link)?;
+            self.state.lock().unwrap().forget(link);
+            self.emit(Event::LinkRemoved {
+                target: link.clone(),
+            });
+            println!(
+                "{} {} -> {}",
+                "Removed:".green().bold(),
+                link.display(),
+                points_to.display()
+            );
+        }
+        if !dry_run {
+            self.state.lock().unwrap().save()?;
+        }
+        Ok(dangling.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Dotman, DotmanConfig, utils};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_prune() {
+        let temp_dir = TempDir::new().unwrap();
+        let dotfiles = temp_dir.path().join("dotfiles");
+        let home = temp_dir.path().join("home");
+        std::fs::create_dir_all(dotfiles.join("zsh")).unwrap();
+        std::fs::create_dir_all(home.join(".config")).unwrap();
+        std::fs::write(dotfiles.join("zsh/zshrc"), "").unwrap();
+
+        let renamed = home.join(".config/nvim");
+        utils::symlink(&dotfiles.join("vim"), &renamed, false).unwrap();
+        utils::symlink(&dotfiles.join("zsh/zshrc"), &home.join(".zshrc"), false).unwrap();
+        utils::symlink(&temp_dir.path().join("gone"), &home.join(".other"), false).unwrap();
+
+        let config = DotmanConfig::builder()
+            .config_path(dotfiles.join("dotman.toml").to_string_lossy())
+            .build();
+        let dotman = Dotman::new(config);
+        let dirs = vec![home.clone()];
+
+        assert_eq!(dotman.prune(&dirs, true).unwrap(), 1);
+        assert!(renamed.symlink_metadata().is_ok());
+        assert_eq!(dotman.prune(&dirs, false).unwrap(), 1);
+        assert!(renamed.symlink_metadata().is_err());
+        assert!(home.join(".zshrc").exists());
+        assert!(home.join(".other").symlink_metadata().is_ok());
+    }
+}
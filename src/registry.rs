@@ -0,0 +1,275 @@
+//! Setting Windows registry values for `type = "registry"` actions.
+
+use colored::Colorize;
+
+use crate::{
+    Dotman,
+    config::{Action, RegistryData, RegistryType},
+    error::DotmanError,
+};
+
+/// A registry value an action sets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegistryValue<'a> {
+    pub hive: &'a str,
+    pub key: &'a str,
+    /// Name of the value, or `None` for the key's default value.
+    pub name: Option<&'a str>,
+    pub kind: RegistryType,
+    pub data: &'a RegistryData,
+}
+
+impl<'a> RegistryValue<'a> {
+    /// Describes a value, taking its type from `data` unless `kind` is
+    /// given.
+    pub fn new(
+        hive: &'a str,
+        key: &'a str,
+        name: Option<&'a str>,
+        kind: Option<RegistryType>,
+        data: &'a RegistryData,
+    ) -> Self {
+        let kind = kind.unwrap_or(match data {
+            RegistryData::Number(_) => RegistryType::Dword,
+            RegistryData::String(_) => RegistryType::Sz,
+        });
+        RegistryValue {
+            hive,
+            key,
+            name,
+            kind,
+            data,
+        }
+    }
+
+    /// The value a `registry` action sets.
+    pub fn of(action: &'a Action) -> Option<Self> {
+        match action {
+            Action::Registry {
+                hive,
+                key,
+                value,
+                value_type,
+                data,
+                ..
+            } => Some(RegistryValue::new(
+                hive,
+                key,
+                value.as_deref(),
+                *value_type,
+                data,
+            )),
+            _ => None,
+        }
+    }
+
+    /// Short name of the hive, like `HKCU`.
+    pub fn hive(&self) -> Result<&'static str, DotmanError> {
+        match self.hive.to_ascii_uppercase().as_str() {
+            "HKCU" | "HKEY_CURRENT_USER" => Ok("HKCU"),
+            "HKLM" | "HKEY_LOCAL_MACHINE" => Ok("HKLM"),
+            "HKCR" | "HKEY_CLASSES_ROOT" => Ok("HKCR"),
+            "HKU" | "HKEY_USERS" => Ok("HKU"),
+            "HKCC" | "HKEY_CURRENT_CONFIG" => Ok("HKCC"),
+            _ => Err(DotmanError::InvalidConfig(format!(
+                "Unknown registry hive '{}'",
+                self.hive
+            ))),
+        }
+    }
+
+    /// Path of the key under its hive, with backslashes.
+    pub fn subkey(&self) -> String {
+        self.key.trim_matches(['\\', '/']).replace('/', "\\")
+    }
+
+    /// Full path of the key, like `HKCU\Console`.
+    pub fn path(&self) -> Result<String, DotmanError> {
+        Ok(format!("{}\\{}", self.hive()?, self.subkey()))
+    }
+
+    /// The data converted to the declared type. Numbers must fit it, and
+    /// strings given for a number must be one.
+    pub fn typed(&self) -> Result<TypedData, DotmanError> {
+        let number = || match self.data {
+            RegistryData::Number(number) => Some(*number),
+            RegistryData::String(string) => string.trim().parse().ok(),
+        };
+        let invalid = |kind: &str| {
+            DotmanError::InvalidConfig(format!(
+                "{} of {} is not a {kind}",
+                self.data,
+                self.path().unwrap_or_else(|_| self.key.to_string())
+            ))
+        };
+        Ok(match self.kind {
+            RegistryType::Sz => TypedData::Sz(self.data.to_string()),
+            RegistryType::ExpandSz => TypedData::ExpandSz(self.data.to_string()),
+            RegistryType::Dword => TypedData::Dword(
+                number()
+                    .and_then(|number| u32::try_from(number).ok())
+                    .ok_or_else(|| invalid("dword"))?,
+            ),
+            RegistryType::Qword => TypedData::Qword(number().ok_or_else(|| invalid("qword"))?),
+        })
+    }
+}
+
+/// Data of a registry value with its type settled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypedData {
+    Sz(String),
+    ExpandSz(String),
+    Dword(u32),
+    Qword(u64),
+}
+
+#[cfg(windows)]
+mod windows {
+    use winreg::{
+        RegKey, RegValue,
+        enums::*,
+        types::{FromRegValue, ToRegValue},
+    };
+
+    use super::{RegistryValue, TypedData};
+    use crate::error::DotmanError;
+
+    fn root(value: &RegistryValue) -> Result<RegKey, DotmanError> {
+        Ok(RegKey::predef(match value.hive()? {
+            "HKCU" => HKEY_CURRENT_USER,
+            "HKLM" => HKEY_LOCAL_MACHINE,
+            "HKCR" => HKEY_CLASSES_ROOT,
+            "HKU" => HKEY_USERS,
+            _ => HKEY_CURRENT_CONFIG,
+        }))
+    }
+
+    /// Reads the value, or `None` if it is missing or of another type.
+    pub(super) fn read(value: &RegistryValue) -> Result<Option<TypedData>, DotmanError> {
+        let key = match root(value)?.open_subkey(value.subkey()) {
+            Ok(key) => key,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let raw = match key.get_raw_value(value.name.unwrap_or_default()) {
+            Ok(raw) => raw,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(match raw.vtype {
+            REG_SZ => String::from_reg_value(&raw).ok().map(TypedData::Sz),
+            REG_EXPAND_SZ => String::from_reg_value(&raw).ok().map(TypedData::ExpandSz),
+            REG_DWORD => u32::from_reg_value(&raw).ok().map(TypedData::Dword),
+            REG_QWORD => u64::from_reg_value(&raw).ok().map(TypedData::Qword),
+            _ => None,
+        })
+    }
+
+    /// Writes the value, creating its key if missing.
+    pub(super) fn write(value: &RegistryValue, data: &TypedData) -> Result<(), DotmanError> {
+        let (key, _) = root(value)?.create_subkey(value.subkey())?;
+        let raw = match data {
+            TypedData::Sz(string) => string.to_reg_value(),
+            TypedData::ExpandSz(string) => RegValue {
+                vtype: REG_EXPAND_SZ,
+                ..string.to_reg_value()
+            },
+            TypedData::Dword(number) => number.to_reg_value(),
+            TypedData::Qword(number) => number.to_reg_value(),
+        };
+        key.set_raw_value(value.name.unwrap_or_default(), &raw)?;
+        Ok(())
+    }
+}
+
+#[cfg(not(windows))]
+mod windows {
+    use super::{RegistryValue, TypedData};
+    use crate::error::DotmanError;
+
+    pub(super) fn read(_value: &RegistryValue) -> Result<Option<TypedData>, DotmanError> {
+        Ok(None)
+    }
+
+    pub(super) fn write(_value: &RegistryValue, _data: &TypedData) -> Result<(), DotmanError> {
+        Err(DotmanError::Unsupported(
+            "The registry only exists on Windows".to_string(),
+        ))
+    }
+}
+
+impl Dotman {
+    /// Whether the registry already holds `value`, with its declared type.
+    pub(crate) fn has_registry_value(&self, value: &RegistryValue) -> bool {
+        let Ok(data) = value.typed() else {
+            return false;
+        };
+        windows::read(value).is_ok_and(|actual| actual == Some(data))
+    }
+
+    /// Sets `value` in the registry, creating its key if missing.
+    pub(crate) fn set_registry_value(&self, value: &RegistryValue) -> Result<(), DotmanError> {
+        windows::write(value, &value.typed()?)?;
+        println!(
+            "{} {}\\{} to {}",
+            "Set:".green().bold(),
+            value.path()?,
+            value.name.unwrap_or("(Default)"),
+            value.data
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path() {
+        let data = RegistryData::Number(1);
+        let quick_edit = RegistryValue::new(
+            "HKEY_CURRENT_USER",
+            "Console",
+            Some("QuickEdit"),
+            None,
+            &data,
+        );
+        assert_eq!(quick_edit.path().unwrap(), "HKCU\\Console");
+
+        let data = RegistryData::String("%USERPROFILE%\\bin".to_string());
+        let default = RegistryValue::new("hkcu", "Software/Tool/", None, None, &data);
+        assert_eq!(default.path().unwrap(), "HKCU\\Software\\Tool");
+
+        assert!(
+            RegistryValue::new("HKXX", "Key", None, None, &data)
+                .path()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_typed() {
+        let data = RegistryData::Number(1);
+        let value = RegistryValue::new("HKCU", "Console", Some("QuickEdit"), None, &data);
+        assert_eq!(value.typed().unwrap(), TypedData::Dword(1));
+        let value = RegistryValue::new("HKCU", "Console", None, Some(RegistryType::Sz), &data);
+        assert_eq!(value.typed().unwrap(), TypedData::Sz("1".to_string()));
+
+        let data = RegistryData::Number(u64::from(u32::MAX) + 1);
+        let value = RegistryValue::new("HKCU", "Console", None, None, &data);
+        assert!(value.typed().is_err());
+        let value = RegistryValue::new("HKCU", "Console", None, Some(RegistryType::Qword), &data);
+        assert_eq!(value.typed().unwrap(), TypedData::Qword(1 << 32));
+
+        let data = RegistryData::String("%USERPROFILE%\\bin".to_string());
+        let value = RegistryValue::new("HKCU", "Env", None, Some(RegistryType::ExpandSz), &data);
+        assert_eq!(
+            value.typed().unwrap(),
+            TypedData::ExpandSz("%USERPROFILE%\\bin".to_string())
+        );
+        let value = RegistryValue::new("HKCU", "Env", None, Some(RegistryType::Dword), &data);
+        assert!(value.typed().is_err());
+    }
+}
@@ -0,0 +1,124 @@
+use crate::error::{DotmanError, PathError};
+
+/// A configuration file fetched from a URL.
+#[derive(Debug)]
+pub struct RemoteConfig {
+    /// The cached copy of the configuration.
+    pub path: std::path::PathBuf,
+    /// Set when the download failed and a previously cached copy is used.
+    pub stale: Option<String>,
+}
+
+/// Returns true if the configuration path is a URL rather than a file.
+pub fn is_url(path: &std::path::Path) -> bool {
+    path.to_str()
+        .is_some_and(|path| path.starts_with("https://") || path.starts_with("http://"))
+}
+
+/// Downloads the configuration at `url` into the cache directory. If the
+/// download fails, the last cached copy is used instead.
+pub fn fetch_config(url: &str) -> Result<RemoteConfig, DotmanError> {
+    let cache_dir = dirs::cache_dir()
+        .ok_or(PathError::CacheDirNotFound)?
+        .join("dotman")
+        .join("remote");
+    fetch_config_into(url, &cache_dir)
+}
+
+fn fetch_config_into(url: &str, cache_dir: &std::path::Path) -> Result<RemoteConfig, DotmanError> {
+    let path = cache_dir.join(cache_file_name(url));
+
+    match download(url) {
+        Ok(contents) => {
+            std::fs::create_dir_all(cache_dir)?;
+            std::fs::write(&path, contents)?;
+            Ok(RemoteConfig { path, stale: None })
+        }
+        Err(message) if path.exists() => Ok(RemoteConfig {
+            path,
+            stale: Some(message),
+        }),
+        Err(message) => Err(DotmanError::FetchError {
+            url: url.to_string(),
+            message,
+        }),
+    }
+}
+
+fn download(url: &str) -> Result<String, String> {
+    if !url.starts_with("https://") {
+        return Err("only https URLs are supported".to_string());
+    }
+
+    ureq::get(url)
+        .call()
+        .map_err(|err| err.to_string())?
+        .body_mut()
+        .read_to_string()
+        .map_err(|err| err.to_string())
+}
+
+/// Builds a file name that is unique per URL and keeps the extension of the
+/// remote file, so the configuration format can still be detected.
+fn cache_file_name(url: &str) -> String {
+    let url = url.split(['?', '#']).next().unwrap_or(url);
+    let extension = std::path::Path::new(url)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("toml");
+    let name: String = url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("{name}.{extension}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_is_url() {
+        assert!(is_url(std::path::Path::new(
+            "https://example.com/dotman.toml"
+        )));
+        assert!(!is_url(std::path::Path::new("dotman.toml")));
+    }
+
+    #[test]
+    fn test_cache_file_name_keeps_extension() {
+        assert_eq!(
+            cache_file_name("https://example.com/me/dotman.yaml?token=1"),
+            "example_com_me_dotman_yaml.yaml"
+        );
+        assert_eq!(
+            cache_file_name("https://example.com/config"),
+            "example_com_config.toml"
+        );
+    }
+
+    #[test]
+    fn test_fetch_falls_back_to_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = "http://example.com/dotman.toml";
+        let cached = temp_dir.path().join(cache_file_name(url));
+        std::fs::write(&cached, "version = 2\n").unwrap();
+
+        let remote = fetch_config_into(url, temp_dir.path()).unwrap();
+
+        assert_eq!(remote.path, cached);
+        assert!(remote.stale.is_some());
+    }
+
+    #[test]
+    fn test_fetch_without_cache_fails() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let result = fetch_config_into("http://example.com/dotman.toml", temp_dir.path());
+
+        assert!(matches!(result, Err(DotmanError::FetchError { .. })));
+    }
+}
@@ -0,0 +1,138 @@
+//! Re-pointing symlinks that point somewhere other than their source, such
+//! as the old location of a repository that was moved, for `dotman repair`.
+
+use colored::Colorize;
+
+use crate::{
+    Dotman, LinkStatus, config::Link, config::LinkMode, error::DotmanError, events::Event,
+    state::TargetState,
+};
+
+impl Dotman {
+    /// Replaces the symlink at `target` with one to `source`. The new link
+    /// is created next to it and renamed over it, so the target is never
+    /// missing in between.
+    pub(crate) fn repoint(
+        &self,
+        link: &Link,
+        source: &std::path::Path,
+        target: &std::path::Path,
+    ) -> Result<(), DotmanError> {
+        let fs = self.fs_for(link);
+        let mode = self.mode_of(link);
+        let name = target.file_name().unwrap_or_default().to_string_lossy();
+        let temporary = target.with_file_name(format!(".{name}.dotman-repair"));
+        if fs.kind(&temporary).is_some() {
+            fs.remove(&temporary)?;
+        }
+        if mode == LinkMode::Junction {
+            fs.junction(source, &temporary)?;
+        } else {
+            fs.symlink(source, &temporary)?;
+        }
+        if let Err(err) = fs.rename(&temporary, target) {
+            let _ = fs.remove(&temporary);
+            return Err(err.into());
+        }
+
+        self.emit(Event::LinkCreated {
+            source: source.to_path_buf(),
+            target: target.to_path_buf(),
+            mode,
+        });
+        self.state.lock().unwrap().record(
+            target,
+            TargetState {
+                source: source.to_path_buf(),
+                checksum: None,
+            },
+        );
+        Ok(())
+    }
+
+    /// Re-points every symlinked target that points somewhere other than
+    /// its resolved source, or only lists them with `dry_run`. Returns how
+    /// many there were.
+    pub fn repair(&self, dry_run: bool) -> Result<usize, DotmanError> {
+        let mut repaired = 0;
+        for report in self.link_statuses()? {
+            let LinkStatus::WrongTarget(actual) = &report.status else {
+                continue;
+            };
+            if !self.mode_of(report.link).is_link() {
+                continue;
+            }
+            repaired += 1;
+            if dry_run {
+                println!(
+                    "{} {} -> {} (points to {})",
+                    "Would repair:".cyan().bold(),
+                    report.target.display(),
+                    report.source.display(),
+                    actual.display()
+                );
+                continue;
+            }
+            self.repoint(report.link, &report.source, &report.target)
+                .map_err(|err| err.in_link(report.link))?;
+            println!(
+                "{} {} -> {} (pointed to {})",
+                "Repaired:".green().bold(),
+                report.target.display(),
+                report.source.display(),
+                actual.display()
+            );
+        }
+        if !dry_run {
+            self.state.lock().unwrap().save()?;
+        }
+        Ok(repaired)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Link;
+    use crate::fs::{Filesystem, MemoryFilesystem};
+    use crate::{Dotman, DotmanConfig, LinkStatus};
+    use std::path::Path;
+
+    #[test]
+    fn test_repair() {
+        let fs = std::sync::Arc::new(MemoryFilesystem::new());
+        fs.write(Path::new("/home/me/dotfiles/zshrc"), b"# zsh", false)
+            .unwrap();
+        fs.write(Path::new("/home/me/dotfiles/vimrc"), b"", false)
+            .unwrap();
+        fs.symlink(
+            Path::new("/home/me/.dotfiles/zshrc"),
+            Path::new("/home/me/.zshrc"),
+        )
+        .unwrap();
+        let links = vec![
+            Link::new("/home/me/dotfiles/zshrc", "/home/me/.zshrc"),
+            Link::new("/home/me/dotfiles/vimrc", "/home/me/.vimrc"),
+        ];
+        let dotman =
+            Dotman::new(DotmanConfig::builder().links(links).build()).with_filesystem(fs.clone());
+
+        assert_eq!(dotman.repair(true).unwrap(), 1);
+        assert_eq!(dotman.repair(false).unwrap(), 1);
+
+        assert_eq!(
+            fs.read_link(Path::new("/home/me/.zshrc")).unwrap(),
+            Path::new("/home/me/dotfiles/zshrc")
+        );
+        assert!(
+            fs.kind(Path::new("/home/me/..zshrc.dotman-repair"))
+                .is_none()
+        );
+        let statuses: Vec<LinkStatus> = dotman
+            .link_statuses()
+            .unwrap()
+            .into_iter()
+            .map(|report| report.status)
+            .collect();
+        assert_eq!(statuses, vec![LinkStatus::Ok, LinkStatus::NotLinked]);
+    }
+}
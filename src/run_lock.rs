@@ -0,0 +1,58 @@
+use crate::error::{DotmanError, PathError};
+
+/// Advisory lock held while a run changes the machine, so two runs never
+/// race on the same targets and state file. Released when dropped.
+#[derive(Debug)]
+pub struct RunLock {
+    _file: std::fs::File,
+}
+
+impl RunLock {
+    /// Takes the lock in the state directory.
+    pub fn acquire_default(wait: bool) -> Result<RunLock, DotmanError> {
+        let dir = crate::state::state_dir().ok_or(PathError::StateDirNotFound)?;
+        RunLock::acquire(&dir.join("run.lock"), wait)
+    }
+
+    /// Takes the lock on `path`. If another run holds it, waits for it to
+    /// finish when `wait` is set, and fails otherwise.
+    pub fn acquire(path: &std::path::Path, wait: bool) -> Result<RunLock, DotmanError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+        if wait {
+            file.lock()?;
+        } else {
+            file.try_lock().map_err(|err| match err {
+                std::fs::TryLockError::WouldBlock => DotmanError::Locked(path.to_path_buf()),
+                std::fs::TryLockError::Error(err) => err.into(),
+            })?;
+        }
+        Ok(RunLock { _file: file })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_run_lock_excludes_other_runs() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("dotman/run.lock");
+
+        let lock = RunLock::acquire(&path, false).unwrap();
+        assert!(matches!(
+            RunLock::acquire(&path, false),
+            Err(DotmanError::Locked(_))
+        ));
+        drop(lock);
+        assert!(RunLock::acquire(&path, false).is_ok());
+    }
+}
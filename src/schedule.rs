@@ -0,0 +1,235 @@
+use crate::{config::OperatingSystem, error::DotmanError};
+
+/// Name of the systemd units and launchd agent.
+const NAME: &str = "dotman-sync";
+const LAUNCHD_LABEL: &str = "com.github.omfj.dotman-sync";
+
+/// Parses an interval like `30m`, `6h` or `1d`.
+pub fn parse_interval(interval: &str) -> Result<std::time::Duration, String> {
+    let interval = interval.trim();
+    let split = interval
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(interval.len());
+    let (amount, unit) = interval.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("Invalid interval '{interval}', expected e.g. 30m, 6h or 1d"))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" | "" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "Unknown interval unit '{unit}', expected s, m, h or d"
+            ));
+        }
+    };
+    if amount == 0 {
+        return Err("The interval must be longer than zero".to_string());
+    }
+    amount
+        .checked_mul(seconds)
+        .map(std::time::Duration::from_secs)
+        .ok_or_else(|| format!("Invalid interval '{interval}', it is too long"))
+}
+
+/// A file to write to schedule the sync.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitFile {
+    pub path: std::path::PathBuf,
+    pub contents: String,
+}
+
+/// The systemd user service and timer running `dotman sync` every `every`.
+pub fn systemd_units(
+    exe: &std::path::Path,
+    config: &std::path::Path,
+    every: std::time::Duration,
+    dir: &std::path::Path,
+) -> Vec<UnitFile> {
+    let service = format!(
+        "[Unit]\n\
+         Description=Apply dotfiles with dotman\n\
+         \n\
+         [Service]\n\
+         Type=oneshot\n\
+         ExecStart=\"{}\" --config \"{}\" --wait sync\n",
+        exe.display(),
+        config.display()
+    );
+    let timer = format!(
+        "[Unit]\n\
+         Description=Apply dotfiles with dotman every {seconds}s\n\
+         \n\
+         [Timer]\n\
+         OnBootSec=5min\n\
+         OnUnitActiveSec={seconds}s\n\
+         Persistent=true\n\
+         \n\
+         [Install]\n\
+         WantedBy=timers.target\n",
+        seconds = every.as_secs()
+    );
+    vec![
+        UnitFile {
+            path: dir.join(format!("{NAME}.service")),
+            contents: service,
+        },
+        UnitFile {
+            path: dir.join(format!("{NAME}.timer")),
+            contents: timer,
+        },
+    ]
+}
+
+/// The launchd agent running `dotman sync` every `every`.
+pub fn launchd_plist(
+    exe: &std::path::Path,
+    config: &std::path::Path,
+    every: std::time::Duration,
+    dir: &std::path::Path,
+) -> UnitFile {
+    let escape = |path: &std::path::Path| {
+        path.display()
+            .to_string()
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    };
+    let contents = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{LAUNCHD_LABEL}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+        <string>--config</string>
+        <string>{}</string>
+        <string>--wait</string>
+        <string>sync</string>
+    </array>
+    <key>StartInterval</key>
+    <integer>{}</integer>
+    <key>RunAtLoad</key>
+    <true/>
+</dict>
+</plist>
+"#,
+        escape(exe),
+        escape(config),
+        every.as_secs()
+    );
+    UnitFile {
+        path: dir.join(format!("{LAUNCHD_LABEL}.plist")),
+        contents,
+    }
+}
+
+/// A command that activates the written unit files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Activation {
+    pub args: Vec<String>,
+    /// Set for cleanup steps that fail when nothing was scheduled before.
+    pub may_fail: bool,
+}
+
+impl Activation {
+    fn new(args: &[&str], may_fail: bool) -> Activation {
+        Activation {
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            may_fail,
+        }
+    }
+}
+
+/// The files that schedule `dotman sync` on `os`, with the commands that
+/// activate them.
+pub fn unit_files(
+    os: &OperatingSystem,
+    exe: &std::path::Path,
+    config: &std::path::Path,
+    every: std::time::Duration,
+) -> Result<(Vec<UnitFile>, Vec<Activation>), DotmanError> {
+    let home = dirs::home_dir().ok_or(crate::error::PathError::HomeDirNotFound)?;
+    match os {
+        OperatingSystem::Linux => {
+            let dir = dirs::config_dir()
+                .unwrap_or_else(|| home.join(".config"))
+                .join("systemd")
+                .join("user");
+            Ok((
+                systemd_units(exe, config, every, &dir),
+                vec![
+                    Activation::new(&["systemctl", "--user", "daemon-reload"], false),
+                    Activation::new(
+                        &[
+                            "systemctl",
+                            "--user",
+                            "enable",
+                            "--now",
+                            &format!("{NAME}.timer"),
+                        ],
+                        false,
+                    ),
+                ],
+            ))
+        }
+        OperatingSystem::MacOS => {
+            let plist = launchd_plist(exe, config, every, &home.join("Library/LaunchAgents"));
+            let path = plist.path.display().to_string();
+            Ok((
+                vec![plist],
+                vec![
+                    Activation::new(&["launchctl", "unload", &path], true),
+                    Activation::new(&["launchctl", "load", "-w", &path], false),
+                ],
+            ))
+        }
+        os => Err(DotmanError::Unsupported(format!(
+            "Scheduling is only supported with systemd on Linux and launchd on macOS, not {os:?}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(
+            parse_interval("6h"),
+            Ok(std::time::Duration::from_secs(21600))
+        );
+        assert_eq!(
+            parse_interval("90"),
+            Ok(std::time::Duration::from_secs(5400))
+        );
+        assert!(parse_interval("0h").is_err());
+        assert!(parse_interval("6w").is_err());
+        assert!(parse_interval("h").is_err());
+        assert!(parse_interval("18446744073709551615d").is_err());
+    }
+
+    #[test]
+    fn test_systemd_units() {
+        let units = systemd_units(
+            std::path::Path::new("/usr/bin/dotman"),
+            std::path::Path::new("/home/me/dotfiles/dotman.toml"),
+            std::time::Duration::from_secs(21600),
+            std::path::Path::new("/home/me/.config/systemd/user"),
+        );
+        assert_eq!(
+            units[0].path,
+            std::path::Path::new("/home/me/.config/systemd/user/dotman-sync.service")
+        );
+        assert!(units[0].contents.contains(
+            "ExecStart=\"/usr/bin/dotman\" --config \"/home/me/dotfiles/dotman.toml\" --wait sync"
+        ));
+        assert!(units[1].contents.contains("OnUnitActiveSec=21600s"));
+    }
+}
@@ -0,0 +1,326 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+
+use crate::{
+    config::{DotmanConfig, SecretBackend, Secrets},
+    error::DotmanError,
+};
+
+/// Marks a `[variables]` value as encrypted: `enc:` followed by the base64
+/// encoded ciphertext.
+pub const ENCRYPTED_PREFIX: &str = "enc:";
+
+/// Decrypts a secret source into memory, so the plaintext only ever reaches
+/// the target.
+pub fn decrypt(config: &DotmanConfig, source: &std::path::Path) -> Result<Vec<u8>, DotmanError> {
+    let mut command = decrypt_command(config)?;
+    command.arg(source);
+    run(command, None)
+}
+
+/// Encrypts `plaintext` for the configured recipient.
+pub fn encrypt(config: &DotmanConfig, plaintext: &[u8]) -> Result<Vec<u8>, DotmanError> {
+    run(encrypt_command(config)?, Some(plaintext))
+}
+
+/// Encrypts a small value into an `enc:` string for `[variables]`.
+pub fn encrypt_value(config: &DotmanConfig, plaintext: &str) -> Result<String, DotmanError> {
+    let ciphertext = encrypt(config, plaintext.as_bytes())?;
+    Ok(format!("{ENCRYPTED_PREFIX}{}", STANDARD.encode(ciphertext)))
+}
+
+/// Decrypts an `enc:` string. Values without the prefix are returned as is.
+pub fn decrypt_value(config: &DotmanConfig, value: &str) -> Result<String, DotmanError> {
+    let Some(encoded) = value.strip_prefix(ENCRYPTED_PREFIX) else {
+        return Ok(value.to_string());
+    };
+    let ciphertext = STANDARD
+        .decode(encoded.trim())
+        .map_err(|err| DotmanError::SecretError(format!("invalid encrypted value: {err}")))?;
+    let plaintext = run(decrypt_command(config)?, Some(&ciphertext))?;
+    String::from_utf8(plaintext)
+        .map_err(|_| DotmanError::SecretError("decrypted value is not valid UTF-8".to_string()))
+}
+
+fn decrypt_command(config: &DotmanConfig) -> Result<std::process::Command, DotmanError> {
+    let secrets = config.secrets.clone().unwrap_or_default();
+    match secrets.backend {
+        SecretBackend::Age => {
+            let mut command = std::process::Command::new("age");
+            command
+                .arg("--decrypt")
+                .arg("--identity")
+                .arg(identity(config, &secrets)?);
+            Ok(command)
+        }
+        SecretBackend::Gpg => {
+            let mut command = std::process::Command::new("gpg");
+            command.args(["--quiet", "--batch", "--decrypt"]);
+            Ok(command)
+        }
+    }
+}
+
+fn encrypt_command(config: &DotmanConfig) -> Result<std::process::Command, DotmanError> {
+    let secrets = config.secrets.clone().unwrap_or_default();
+    match secrets.backend {
+        SecretBackend::Age => {
+            let mut command = std::process::Command::new("age");
+            command.arg("--encrypt");
+            match &secrets.recipient {
+                Some(recipient) => command.arg("--recipient").arg(recipient),
+                // age encrypts to the identity's own recipient.
+                None => command.arg("--identity").arg(identity(config, &secrets)?),
+            };
+            Ok(command)
+        }
+        SecretBackend::Gpg => {
+            let recipient = secrets.recipient.ok_or_else(|| {
+                DotmanError::SecretError("No gpg recipient configured in [secrets]".to_string())
+            })?;
+            let mut command = std::process::Command::new("gpg");
+            command
+                .args(["--quiet", "--batch", "--yes", "--encrypt", "--recipient"])
+                .arg(recipient);
+            Ok(command)
+        }
+    }
+}
+
+fn identity(config: &DotmanConfig, secrets: &Secrets) -> Result<std::path::PathBuf, DotmanError> {
+    let identity = secrets.identity.as_deref().ok_or_else(|| {
+        DotmanError::SecretError("No age identity configured in [secrets]".to_string())
+    })?;
+    Ok(config.resolve_source(identity)?)
+}
+
+/// Runs an age or gpg command, feeding it `input` on stdin, and returns its
+/// stdout. The input is written from another thread while the output is
+/// read, so neither side blocks on a full pipe.
+fn run(mut command: std::process::Command, input: Option<&[u8]>) -> Result<Vec<u8>, DotmanError> {
+    use std::io::Write;
+
+    let program = command.get_program().to_string_lossy().to_string();
+    let mut child = command
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|err| DotmanError::CommandError {
+            command: program.clone(),
+            message: err.to_string(),
+        })?;
+
+    let stdin = child.stdin.take();
+    let input = input.unwrap_or_default();
+    let (written, output) = std::thread::scope(|scope| {
+        // Dropping stdin once written closes it, so the command sees the end
+        // of its input.
+        let writer = scope.spawn(move || match stdin {
+            Some(mut stdin) => stdin.write_all(input),
+            None => Ok(()),
+        });
+        let output = child.wait_with_output();
+        (writer.join(), output)
+    });
+    let output = output?;
+
+    // A command that fails may stop reading early, so its own error is the
+    // one worth reporting.
+    if !output.status.success() {
+        return Err(DotmanError::CommandError {
+            command: program,
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    written.map_err(|_| DotmanError::CommandError {
+        command: program.clone(),
+        message: "writing the input panicked".to_string(),
+    })??;
+    Ok(output.stdout)
+}
+
+/// Decrypts `file` to a private temporary file, opens it in `editor` and
+/// re-encrypts it if it changed. Returns whether the file changed.
+pub fn edit(
+    config: &DotmanConfig,
+    file: &std::path::Path,
+    editor: &str,
+) -> Result<bool, DotmanError> {
+    let plaintext = decrypt(config, file)?;
+
+    let name = file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // Created exclusively and readable only by the owner, and removed when
+    // dropped, whichever way editing ends.
+    let mut temp = tempfile::Builder::new()
+        .prefix("dotman-")
+        .suffix(&format!("-{name}"))
+        .tempfile()?;
+    {
+        use std::io::Write;
+        temp.write_all(&plaintext)?;
+        temp.flush()?;
+    }
+
+    run_editor(editor, temp.path())?;
+    let edited = std::fs::read(temp.path())?;
+    if edited == plaintext {
+        return Ok(false);
+    }
+    std::fs::write(file, encrypt(config, &edited)?)?;
+    Ok(true)
+}
+
+fn run_editor(editor: &str, path: &std::path::Path) -> Result<(), DotmanError> {
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$1\""))
+        .arg("--")
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        return Err(DotmanError::CommandError {
+            command: editor.to_string(),
+            message: format!("editor exited with {status}"),
+        });
+    }
+    Ok(())
+}
+
+/// Writes decrypted contents to `target`, readable only by the owner.
+pub fn write_plaintext(target: &std::path::Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(target)?.write_all(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn config(secrets: Secrets) -> DotmanConfig {
+        DotmanConfig {
+            config_path: "/home/me/dotfiles/dotman.toml".to_string(),
+            secrets: Some(secrets),
+            ..Default::default()
+        }
+    }
+
+    fn args(command: &std::process::Command) -> Vec<String> {
+        std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_decrypt_requires_identity() {
+        let config = DotmanConfig::default();
+
+        let result = decrypt(&config, std::path::Path::new("netrc.age"));
+
+        assert!(matches!(result, Err(DotmanError::SecretError(_))));
+    }
+
+    #[test]
+    fn test_decrypt_value_passes_plain_values_through() {
+        let config = DotmanConfig::default();
+
+        assert_eq!(
+            decrypt_value(&config, "me@example.com").unwrap(),
+            "me@example.com"
+        );
+        assert!(matches!(
+            decrypt_value(&config, "enc:not base64!"),
+            Err(DotmanError::SecretError(_))
+        ));
+    }
+
+    #[test]
+    fn test_age_commands() {
+        let config = config(Secrets {
+            identity: Some("key.txt".to_string()),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            args(&decrypt_command(&config).unwrap()),
+            [
+                "age",
+                "--decrypt",
+                "--identity",
+                "/home/me/dotfiles/key.txt"
+            ]
+        );
+        assert_eq!(
+            args(&encrypt_command(&config).unwrap()),
+            [
+                "age",
+                "--encrypt",
+                "--identity",
+                "/home/me/dotfiles/key.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gpg_commands() {
+        let config = config(Secrets {
+            backend: SecretBackend::Gpg,
+            recipient: Some("me@example.com".to_string()),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            args(&decrypt_command(&config).unwrap()),
+            ["gpg", "--quiet", "--batch", "--decrypt"]
+        );
+        assert_eq!(
+            args(&encrypt_command(&config).unwrap()),
+            [
+                "gpg",
+                "--quiet",
+                "--batch",
+                "--yes",
+                "--encrypt",
+                "--recipient",
+                "me@example.com"
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_plaintext_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("netrc");
+
+        write_plaintext(&target, b"machine example.com").unwrap();
+
+        let mode = std::fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(std::fs::read(&target).unwrap(), b"machine example.com");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_streams_input_larger_than_a_pipe() {
+        let input = vec![b'x'; 1 << 20];
+
+        let output = run(std::process::Command::new("cat"), Some(&input)).unwrap();
+
+        assert_eq!(output.len(), input.len());
+    }
+}
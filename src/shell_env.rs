@@ -0,0 +1,531 @@
+//! Rendering `[env]`, `path` and `[aliases]` into files that each shell
+//! sources from its startup file.
+
+use colored::Colorize;
+
+use crate::{Dotman, DotmanConfig, error::DotmanError, error::PathError, line_in_file::Managed};
+use crate::{options::InstallOptions, utils::Absolute};
+
+/// Directory, under the home directory, the environment files are written
+/// to.
+const DIR: &str = ".config/dotman";
+
+const HEADER: &str = "Generated by dotman. Do not edit: changes are overwritten.";
+
+/// A shell dialect that gets its own environment file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    /// bash, zsh and other POSIX shells.
+    Posix,
+    Fish,
+    Nu,
+}
+
+impl Shell {
+    pub const ALL: [Shell; 3] = [Shell::Posix, Shell::Fish, Shell::Nu];
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Shell::Posix => "env.sh",
+            Shell::Fish => "env.fish",
+            Shell::Nu => "env.nu",
+        }
+    }
+
+    pub fn aliases_file_name(self) -> &'static str {
+        match self {
+            Shell::Posix => "aliases.sh",
+            Shell::Fish => "aliases.fish",
+            Shell::Nu => "aliases.nu",
+        }
+    }
+
+    /// Startup files, relative to the home directory, that source the
+    /// environment file when they exist.
+    pub fn rc_files(self) -> &'static [&'static str] {
+        match self {
+            Shell::Posix => &[".bashrc", ".zshrc"],
+            Shell::Fish => &[".config/fish/config.fish"],
+            Shell::Nu => &[".config/nushell/config.nu"],
+        }
+    }
+
+    /// `value` as a string literal that the shell takes as is.
+    fn quote(self, value: &str) -> String {
+        match self {
+            Shell::Posix => format!("'{}'", value.replace('\'', r"'\''")),
+            Shell::Fish => format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'")),
+            Shell::Nu => format!("\"{}\"", value.replace('\\', r"\\").replace('"', "\\\"")),
+        }
+    }
+
+    fn export(self, name: &str, value: &str) -> String {
+        let value = self.quote(value);
+        match self {
+            Shell::Posix => format!("export {name}={value}"),
+            Shell::Fish => format!("set -gx {name} {value}"),
+            Shell::Nu => format!("$env.{name} = {value}"),
+        }
+    }
+
+    /// Defines `name` to run `command`. Nushell aliases take a command rather
+    /// than a string, so it is written as is.
+    fn alias(self, name: &str, command: &str) -> String {
+        match self {
+            Shell::Posix => format!("alias {name}={}", self.quote(command)),
+            Shell::Fish => format!("alias {name} {}", self.quote(command)),
+            Shell::Nu => format!("alias {name} = {command}"),
+        }
+    }
+
+    /// The line in a startup file that loads the environment file at `path`.
+    pub fn source_line(self, path: &std::path::Path) -> String {
+        self.source(&self.quote(&path.to_string_lossy()))
+    }
+
+    /// Like [`Shell::source_line`], but for the environment file in the home
+    /// directory of whoever starts the shell, for startup files that are
+    /// shared between machines.
+    pub fn portable_source_line(self) -> String {
+        self.source(&format!("~/{DIR}/{}", self.file_name()))
+    }
+
+    fn source(self, path: &str) -> String {
+        match self {
+            Shell::Posix => format!("[ -f {path} ] && . {path}"),
+            Shell::Fish => format!("test -f {path}; and source {path}"),
+            Shell::Nu => format!("source {path}"),
+        }
+    }
+
+    /// Prepends `entries` to `PATH`, so the first one takes priority.
+    fn prepend_path(self, entries: &[String]) -> String {
+        let entries: Vec<String> = entries.iter().map(|entry| self.quote(entry)).collect();
+        match self {
+            Shell::Posix => format!("export PATH={}:\"$PATH\"", entries.join(":")),
+            Shell::Fish => format!("set -gx PATH {} $PATH", entries.join(" ")),
+            Shell::Nu => format!(
+                "$env.PATH = ($env.PATH | split row (char esep) | prepend [{}])",
+                entries.join(", ")
+            ),
+        }
+    }
+
+    /// The environment file exporting `[env]` and prepending `path` to
+    /// `PATH`. With `[aliases]`, it also loads the aliases file under `home`.
+    pub fn render(
+        self,
+        config: &DotmanConfig,
+        home: &std::path::Path,
+    ) -> Result<String, DotmanError> {
+        let mut contents = format!("# {HEADER}\n");
+        for (name, value) in &config.env {
+            if !is_variable_name(name) {
+                return Err(DotmanError::InvalidConfig(format!(
+                    "'{name}' in [env] is not a valid environment variable name"
+                )));
+            }
+            contents.push_str(&self.export(name, &expand_home(value, home)));
+            contents.push('\n');
+        }
+        if !config.path.is_empty() {
+            let entries: Vec<String> = config
+                .path
+                .iter()
+                .map(|entry| expand_home(entry, home))
+                .collect();
+            contents.push_str(&self.prepend_path(&entries));
+            contents.push('\n');
+        }
+        if !config.aliases.is_empty() {
+            let aliases = home.join(DIR).join(self.aliases_file_name());
+            contents.push_str(&self.source_line(&aliases));
+            contents.push('\n');
+        }
+        Ok(contents)
+    }
+
+    /// The aliases file defining `[aliases]`.
+    pub fn render_aliases(self, config: &DotmanConfig) -> Result<String, DotmanError> {
+        let mut contents = format!("# {HEADER}\n");
+        for (name, command) in &config.aliases {
+            if !is_alias_name(name) {
+                return Err(DotmanError::InvalidConfig(format!(
+                    "'{name}' in [aliases] is not a valid alias name"
+                )));
+            }
+            contents.push_str(&self.alias(name, command));
+            contents.push('\n');
+        }
+        Ok(contents)
+    }
+
+    /// The files to write under `home` with their contents: the environment
+    /// file, and the aliases file if there are aliases.
+    fn files(
+        self,
+        config: &DotmanConfig,
+        home: &std::path::Path,
+    ) -> Result<Vec<(std::path::PathBuf, String)>, DotmanError> {
+        let mut files = vec![(
+            home.join(DIR).join(self.file_name()),
+            self.render(config, home)?,
+        )];
+        if !config.aliases.is_empty() {
+            files.push((
+                home.join(DIR).join(self.aliases_file_name()),
+                self.render_aliases(config)?,
+            ));
+        }
+        Ok(files)
+    }
+}
+
+/// `value` with a leading `~/` expanded against `home`, since quoting keeps
+/// the shell from doing it.
+fn expand_home(value: &str, home: &std::path::Path) -> String {
+    match value.strip_prefix("~/") {
+        Some(rest) => home.join(rest).to_string_lossy().into_owned(),
+        None if value == "~" => home.to_string_lossy().into_owned(),
+        None => value.to_string(),
+    }
+}
+
+/// Whether there is anything to write to the environment files.
+pub(crate) fn exports_anything(config: &DotmanConfig) -> bool {
+    !config.env.is_empty() || !config.path.is_empty() || !config.aliases.is_empty()
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_alias_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+fn is_symlink(path: &std::path::Path) -> bool {
+    path.symlink_metadata()
+        .is_ok_and(|metadata| metadata.is_symlink())
+}
+
+fn home_dir() -> Result<std::path::PathBuf, DotmanError> {
+    Ok(dirs::home_dir()
+        .ok_or(PathError::HomeDirNotFound)?
+        .absolute()?)
+}
+
+impl Dotman {
+    /// The environment and aliases files under `home` that are missing or
+    /// differ from what the configuration renders, and the startup files
+    /// that do not source them.
+    fn outdated_shell_env(
+        &self,
+        home: &std::path::Path,
+    ) -> Result<Vec<std::path::PathBuf>, DotmanError> {
+        let mut outdated = Vec::new();
+        for shell in Shell::ALL {
+            for (file, contents) in shell.files(&self.config, home)? {
+                if std::fs::read_to_string(&file).ok().as_deref() != Some(contents.as_str()) {
+                    outdated.push(file);
+                }
+            }
+            let file = home.join(DIR).join(shell.file_name());
+            let line = shell.source_line(&file);
+            for rc in shell.rc_files() {
+                let rc = home.join(rc);
+                if rc.exists()
+                    && !is_symlink(&rc)
+                    && !self.has_line(&rc.to_string_lossy(), Managed::Line(&line))
+                {
+                    outdated.push(rc);
+                }
+            }
+        }
+        Ok(outdated)
+    }
+
+    /// The startup files under `home` that are symlinks, like those dotman
+    /// links from the dotfiles, and do not load the environment file, with
+    /// the line to add to them. dotman does not write through them, as that
+    /// would put a line for this machine into the repository.
+    fn unsourced_links(&self, home: &std::path::Path) -> Vec<(std::path::PathBuf, String)> {
+        let mut unsourced = Vec::new();
+        for shell in Shell::ALL {
+            let file = format!("{DIR}/{}", shell.file_name());
+            for rc in shell.rc_files() {
+                let rc = home.join(rc);
+                let sourced =
+                    std::fs::read_to_string(&rc).is_ok_and(|contents| contents.contains(&file));
+                if is_symlink(&rc) && rc.exists() && !sourced {
+                    unsourced.push((rc, shell.portable_source_line()));
+                }
+            }
+        }
+        unsourced
+    }
+
+    /// The entries of `path` that are missing from the `PATH` in `live`.
+    fn missing_from_path(
+        &self,
+        home: &std::path::Path,
+        live: &std::ffi::OsStr,
+    ) -> Vec<std::path::PathBuf> {
+        let live: Vec<std::path::PathBuf> = std::env::split_paths(live).collect();
+        self.config
+            .path
+            .iter()
+            .map(|entry| std::path::PathBuf::from(expand_home(entry, home)))
+            .filter(|entry| !live.contains(entry))
+            .collect()
+    }
+
+    /// Writes the environment files for `[env]`, `path` and `[aliases]` and
+    /// sources them from the startup files of the installed shells.
+    pub fn write_shell_env(&self, options: &InstallOptions) -> Result<(), DotmanError> {
+        if !exports_anything(&self.config) || !options.selects_shell_env() {
+            return Ok(());
+        }
+        self.write_shell_env_in(&home_dir()?, options)
+    }
+
+    fn write_shell_env_in(
+        &self,
+        home: &std::path::Path,
+        options: &InstallOptions,
+    ) -> Result<(), DotmanError> {
+        if options.dry_run {
+            for path in self.outdated_shell_env(home)? {
+                println!("{} {}", "Would update:".cyan().bold(), path.display());
+            }
+            return Ok(());
+        }
+        for shell in Shell::ALL {
+            for (file, contents) in shell.files(&self.config, home)? {
+                if std::fs::read_to_string(&file).ok().as_deref() != Some(contents.as_str()) {
+                    std::fs::create_dir_all(home.join(DIR))?;
+                    std::fs::write(&file, contents)?;
+                    println!("{} {}", "Updated:".green().bold(), file.display());
+                }
+            }
+            let file = home.join(DIR).join(shell.file_name());
+            let line = shell.source_line(&file);
+            for rc in shell.rc_files() {
+                let rc = home.join(rc);
+                if rc.exists() && !is_symlink(&rc) {
+                    self.ensure_line(&rc.to_string_lossy(), Managed::Line(&line))?;
+                }
+            }
+        }
+        for (rc, line) in self.unsourced_links(home) {
+            println!(
+                "{} {} is a symlink, so dotman leaves it alone. Add `{}` to it to load the environment.",
+                "Warning:".yellow().bold(),
+                rc.display(),
+                line
+            );
+        }
+        Ok(())
+    }
+
+    /// Prints whether the environment files are up to date, and which
+    /// `path` entries the current `PATH` lacks, for `dotman status`.
+    pub(crate) fn print_shell_env_status(&self) -> Result<(), DotmanError> {
+        let home = home_dir()?;
+        let outdated = self.outdated_shell_env(&home)?;
+        let unsourced = self.unsourced_links(&home);
+        let missing = self.missing_from_path(&home, &std::env::var_os("PATH").unwrap_or_default());
+        if outdated.is_empty() && unsourced.is_empty() && missing.is_empty() {
+            println!(
+                "{} {} variables, {} PATH entries, {} aliases",
+                "[OK]".green().bold(),
+                self.config.env.len(),
+                self.config.path.len(),
+                self.config.aliases.len()
+            );
+        }
+        for path in outdated {
+            println!("{} {}", "[OUTDATED]".yellow().bold(), path.display());
+        }
+        for (rc, line) in unsourced {
+            println!(
+                "{} {} (add `{}`)",
+                "[NOT SOURCED]".yellow().bold(),
+                rc.display(),
+                line
+            );
+        }
+        for entry in missing {
+            println!("{} {}", "[NOT IN PATH]".yellow().bold(), entry.display());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn config() -> DotmanConfig {
+        DotmanConfig::builder()
+            .env("EDITOR", "nvim")
+            .env("NOTES", "~/notes")
+            .env("PS4", "it's $0")
+            .build()
+    }
+
+    #[test]
+    fn test_render() {
+        let home = std::path::Path::new("/home/me");
+
+        assert_eq!(
+            Shell::Posix.render(&config(), home).unwrap(),
+            format!(
+                "# {HEADER}\nexport EDITOR='nvim'\nexport NOTES='/home/me/notes'\nexport PS4='it'\\''s $0'\n"
+            )
+        );
+        assert_eq!(
+            Shell::Fish.render(&config(), home).unwrap(),
+            format!(
+                "# {HEADER}\nset -gx EDITOR 'nvim'\nset -gx NOTES '/home/me/notes'\nset -gx PS4 'it\\'s $0'\n"
+            )
+        );
+        assert_eq!(
+            Shell::Nu.render(&config(), home).unwrap(),
+            format!(
+                "# {HEADER}\n$env.EDITOR = \"nvim\"\n$env.NOTES = \"/home/me/notes\"\n$env.PS4 = \"it's $0\"\n"
+            )
+        );
+
+        let invalid = DotmanConfig::builder().env("MY-VAR", "1").build();
+        assert!(Shell::Posix.render(&invalid, home).is_err());
+    }
+
+    #[test]
+    fn test_render_path() {
+        let home = std::path::Path::new("/home/me");
+        let config = DotmanConfig::builder()
+            .path("~/.local/bin")
+            .path("/opt/tools/bin")
+            .build();
+
+        assert_eq!(
+            Shell::Posix.render(&config, home).unwrap(),
+            format!("# {HEADER}\nexport PATH='/home/me/.local/bin':'/opt/tools/bin':\"$PATH\"\n")
+        );
+        assert_eq!(
+            Shell::Fish.render(&config, home).unwrap(),
+            format!("# {HEADER}\nset -gx PATH '/home/me/.local/bin' '/opt/tools/bin' $PATH\n")
+        );
+        assert_eq!(
+            Shell::Nu.render(&config, home).unwrap(),
+            format!(
+                "# {HEADER}\n$env.PATH = ($env.PATH | split row (char esep) | prepend [\"/home/me/.local/bin\", \"/opt/tools/bin\"])\n"
+            )
+        );
+
+        let dotman = Dotman::new(config);
+        let live = std::env::join_paths(["/home/me/.local/bin", "/usr/bin"]).unwrap();
+        assert_eq!(
+            dotman.missing_from_path(home, &live),
+            vec![std::path::PathBuf::from("/opt/tools/bin")]
+        );
+    }
+
+    #[test]
+    fn test_render_aliases() {
+        let home = std::path::Path::new("/home/me");
+        let config = DotmanConfig::builder()
+            .alias("gs", "git status")
+            .alias("ll", "ls -l")
+            .build();
+
+        assert_eq!(
+            Shell::Posix.render_aliases(&config).unwrap(),
+            format!("# {HEADER}\nalias gs='git status'\nalias ll='ls -l'\n")
+        );
+        assert_eq!(
+            Shell::Fish.render_aliases(&config).unwrap(),
+            format!("# {HEADER}\nalias gs 'git status'\nalias ll 'ls -l'\n")
+        );
+        assert_eq!(
+            Shell::Nu.render_aliases(&config).unwrap(),
+            format!("# {HEADER}\nalias gs = git status\nalias ll = ls -l\n")
+        );
+        assert_eq!(
+            Shell::Posix.render(&config, home).unwrap(),
+            format!(
+                "# {HEADER}\n[ -f '/home/me/.config/dotman/aliases.sh' ] && . '/home/me/.config/dotman/aliases.sh'\n"
+            )
+        );
+
+        let invalid = DotmanConfig::builder().alias("l l", "ls -l").build();
+        assert!(Shell::Posix.render_aliases(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_write_shell_env() {
+        let temp_dir = TempDir::new().unwrap();
+        let home = temp_dir.path();
+        std::fs::write(home.join(".bashrc"), "alias ll='ls -l'\n").unwrap();
+        let dotman = Dotman::new(config());
+
+        assert_eq!(dotman.outdated_shell_env(home).unwrap().len(), 4);
+        dotman
+            .write_shell_env_in(home, &InstallOptions::default())
+            .unwrap();
+
+        let env_sh = home.join(DIR).join("env.sh");
+        assert!(home.join(DIR).join("env.fish").exists());
+        assert!(!home.join(".zshrc").exists());
+        assert_eq!(
+            std::fs::read_to_string(home.join(".bashrc")).unwrap(),
+            format!(
+                "alias ll='ls -l'\n[ -f '{0}' ] && . '{0}'\n",
+                env_sh.display()
+            )
+        );
+        assert!(dotman.outdated_shell_env(home).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_write_shell_env_leaves_linked_rc_files_alone() {
+        let temp_dir = TempDir::new().unwrap();
+        let home = temp_dir.path().join("home");
+        let zshrc = temp_dir.path().join("dotfiles/zshrc");
+        std::fs::create_dir_all(zshrc.parent().unwrap()).unwrap();
+        std::fs::create_dir_all(&home).unwrap();
+        std::fs::write(&zshrc, "setopt autocd\n").unwrap();
+        crate::utils::symlink(&zshrc, &home.join(".zshrc"), false).unwrap();
+        let dotman = Dotman::new(config());
+
+        dotman
+            .write_shell_env_in(&home, &InstallOptions::default())
+            .unwrap();
+
+        assert_eq!(std::fs::read_to_string(&zshrc).unwrap(), "setopt autocd\n");
+        assert!(dotman.outdated_shell_env(&home).unwrap().is_empty());
+        assert_eq!(
+            dotman.unsourced_links(&home),
+            vec![(
+                home.join(".zshrc"),
+                "[ -f ~/.config/dotman/env.sh ] && . ~/.config/dotman/env.sh".to_string()
+            )]
+        );
+
+        std::fs::write(
+            &zshrc,
+            "setopt autocd\n[ -f ~/.config/dotman/env.sh ] && . ~/.config/dotman/env.sh\n",
+        )
+        .unwrap();
+        assert!(dotman.unsourced_links(&home).is_empty());
+    }
+}
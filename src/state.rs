@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{DotmanError, PathError};
+
+/// What dotman last installed at a target.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetState {
+    pub source: std::path::PathBuf,
+    /// Checksum of the file dotman wrote. Unset for symlinks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+}
+
+/// Record of installed targets, kept between runs in the state directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct State {
+    #[serde(default)]
+    pub targets: std::collections::BTreeMap<std::path::PathBuf, TargetState>,
+    /// Hash of the command each action last ran successfully.
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub actions: std::collections::BTreeMap<String, String>,
+    /// Where the state is saved. In-memory state is never written.
+    #[serde(skip)]
+    path: Option<std::path::PathBuf>,
+}
+
+/// Returns dotman's state directory.
+pub fn state_dir() -> Option<std::path::PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join("dotman"))
+}
+
+impl State {
+    /// Loads the state file from the state directory.
+    pub fn load_default() -> Result<State, DotmanError> {
+        let dir = state_dir().ok_or(PathError::StateDirNotFound)?;
+        State::load(&dir.join("state.json"))
+    }
+
+    /// Loads the state file at `path`; a missing file is an empty state.
+    pub fn load(path: &std::path::Path) -> Result<State, DotmanError> {
+        let mut state: State = match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|err| {
+                DotmanError::State(format!("Invalid state file {}: {}", path.display(), err))
+            })?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => State::default(),
+            Err(err) => return Err(err.into()),
+        };
+        state.path = Some(path.to_path_buf());
+        Ok(state)
+    }
+
+    /// Writes the state back to the file it was loaded from.
+    pub fn save(&self) -> Result<(), DotmanError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|err| DotmanError::State(format!("Cannot write the state: {err}")))?;
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    pub fn get(&self, target: &std::path::Path) -> Option<&TargetState> {
+        self.targets.get(target)
+    }
+
+    pub fn record(&mut self, target: &std::path::Path, state: TargetState) {
+        self.targets.insert(target.to_path_buf(), state);
+    }
+
+    pub fn forget(&mut self, target: &std::path::Path) {
+        self.targets.remove(target);
+    }
+
+    pub fn action_hash(&self, name: &str) -> Option<&str> {
+        self.actions.get(name).map(String::as_str)
+    }
+
+    pub fn record_action(&mut self, name: &str, hash: String) {
+        self.actions.insert(name.to_string(), hash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_state_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("nested/state.json");
+
+        let mut state = State::load(&path).unwrap();
+        assert!(state.targets.is_empty());
+        state.record(
+            std::path::Path::new("/home/me/.gitconfig"),
+            TargetState {
+                source: "/home/me/dotfiles/gitconfig".into(),
+                checksum: Some("abc".to_string()),
+            },
+        );
+        state.save().unwrap();
+
+        let loaded = State::load(&path).unwrap();
+        assert_eq!(loaded.targets, state.targets);
+    }
+
+    #[test]
+    fn test_in_memory_state_is_not_saved() {
+        let state = State::default();
+        assert!(state.save().is_ok());
+    }
+}
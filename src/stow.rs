@@ -0,0 +1,322 @@
+use crate::{
+    config::{DotmanConfig, Link},
+    error::{DotmanError, PathError},
+    utils::{self, Absolute, ExpandTilde},
+};
+
+/// A link that was written into the stow tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportedLink {
+    pub package: String,
+    pub source: std::path::PathBuf,
+    /// Location of the copy inside the stow directory.
+    pub destination: std::path::PathBuf,
+    /// The link had conditions, which stow cannot express.
+    pub dropped_condition: bool,
+}
+
+/// A link that could not be exported, with the reason.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedLink {
+    pub source: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ExportReport {
+    pub exported: Vec<ExportedLink>,
+    pub skipped: Vec<SkippedLink>,
+}
+
+/// Copies every effective link source into a GNU stow layout under `out`,
+/// one package per link, so that `stow -d <out> -t ~ <package>` recreates
+/// the link. Only targets inside the home directory can be exported.
+pub fn export(config: &DotmanConfig, out: &std::path::Path) -> Result<ExportReport, DotmanError> {
+    let home = dirs::home_dir().ok_or(PathError::HomeDirNotFound)?;
+    export_to(config, out, &home)
+}
+
+fn export_to(
+    config: &DotmanConfig,
+    out: &std::path::Path,
+    home: &std::path::Path,
+) -> Result<ExportReport, DotmanError> {
+    let mut report = ExportReport::default();
+
+    for link in config.get_effective_links() {
+        if !link.mode.is_link() {
+            report.skipped.push(SkippedLink {
+                source: link.source.clone(),
+                reason: "only symlinked links can be stowed".to_string(),
+            });
+            continue;
+        }
+        let source = config.resolve_source(&link.source)?;
+        let target = link.target.expand_tilde_path()?.absolute()?;
+
+        let Ok(relative) = target.strip_prefix(home) else {
+            report.skipped.push(SkippedLink {
+                source: link.source.clone(),
+                reason: format!("target {} is outside the home directory", target.display()),
+            });
+            continue;
+        };
+        if !source.exists() {
+            report.skipped.push(SkippedLink {
+                source: link.source.clone(),
+                reason: format!("source {} does not exist", source.display()),
+            });
+            continue;
+        }
+
+        let package = unique_package_name(relative, &report.exported);
+        let destination = out.join(&package).join(relative);
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        utils::copy_recursive(&source, &destination)?;
+
+        report.exported.push(ExportedLink {
+            package,
+            source,
+            destination,
+            dropped_condition: link.if_cond.is_some() || link.if_not_cond.is_some(),
+        });
+    }
+
+    Ok(report)
+}
+
+/// Names a package after the target's file name without its leading dot,
+/// e.g. `.config/nvim` becomes `nvim`, adding a numeric suffix on clashes.
+fn unique_package_name(relative_target: &std::path::Path, taken: &[ExportedLink]) -> String {
+    let base = relative_target
+        .file_name()
+        .map(|name| name.to_string_lossy().trim_start_matches('.').to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "dotfiles".to_string());
+
+    let is_taken = |name: &str| taken.iter().any(|link| link.package == name);
+    if !is_taken(&base) {
+        return base;
+    }
+    (2..)
+        .map(|n| format!("{base}-{n}"))
+        .find(|name| !is_taken(name))
+        .unwrap_or(base)
+}
+
+/// Files stow ignores at the top of a package by default.
+const STOW_IGNORED: &[&str] = &[
+    ".stow-local-ignore",
+    ".git",
+    ".gitignore",
+    ".gitmodules",
+    "README",
+    "README.md",
+    "LICENSE",
+    "COPYING",
+];
+
+/// Infers links from a stow directory: every top-level directory is a
+/// package mirroring `home`. Directories that already exist as real
+/// directories in `home`, or that several packages contribute to, are
+/// descended into; everything else becomes a single link, like stow's tree
+/// folding. Sources are relative to `stow_dir`.
+pub fn import(
+    stow_dir: &std::path::Path,
+    home: &std::path::Path,
+) -> Result<Vec<Link>, DotmanError> {
+    let mut packages: Vec<std::path::PathBuf> = std::fs::read_dir(stow_dir)?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_dir())
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .map(|entry| entry.path())
+        .collect();
+    packages.sort();
+
+    let mut links = Vec::new();
+    for package in &packages {
+        import_dir(
+            stow_dir,
+            package,
+            std::path::Path::new(""),
+            &packages,
+            home,
+            &mut links,
+        )?;
+    }
+    Ok(links)
+}
+
+fn import_dir(
+    stow_dir: &std::path::Path,
+    package: &std::path::Path,
+    relative: &std::path::Path,
+    packages: &[std::path::PathBuf],
+    home: &std::path::Path,
+    links: &mut Vec<Link>,
+) -> Result<(), DotmanError> {
+    let mut entries: Vec<std::fs::DirEntry> = std::fs::read_dir(package.join(relative))?
+        .filter_map(Result::ok)
+        .collect();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = entry.file_name();
+        if relative.as_os_str().is_empty() && STOW_IGNORED.contains(&&*name.to_string_lossy()) {
+            continue;
+        }
+
+        let relative = relative.join(&name);
+        let target = home.join(&relative);
+        let shared = packages
+            .iter()
+            .filter(|other| other.join(&relative).is_dir())
+            .count()
+            > 1;
+        let existing_dir = target.is_dir() && !target.is_symlink();
+
+        if entry.path().is_dir() && (shared || existing_dir) {
+            import_dir(stow_dir, package, &relative, packages, home, links)?;
+            continue;
+        }
+
+        let source = entry
+            .path()
+            .strip_prefix(stow_dir)
+            .map(std::path::Path::to_path_buf)
+            .unwrap_or_else(|_| entry.path());
+        links.push(Link {
+            source: source.to_string_lossy().to_string(),
+            target: format!("~/{}", relative.display()),
+            if_cond: None,
+            if_not_cond: None,
+            profiles: vec![],
+            ..Default::default()
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Condition, Link};
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn link(source: &std::path::Path, target: &std::path::Path) -> Link {
+        Link {
+            source: source.to_string_lossy().to_string(),
+            target: target.to_string_lossy().to_string(),
+            if_cond: None,
+            if_not_cond: None,
+            profiles: vec![],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_export_creates_stow_packages() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path().join("repo");
+        let home = temp_dir.path().join("home");
+        let out = temp_dir.path().join("stow");
+        fs::create_dir_all(repo.join("nvim")).unwrap();
+        fs::write(repo.join("nvim/init.lua"), "-- nvim").unwrap();
+        fs::write(repo.join("zshrc"), "# zsh").unwrap();
+
+        let mut zshrc = link(&repo.join("zshrc"), &home.join(".zshrc"));
+        zshrc.if_cond = Some(Condition::default());
+        let config = DotmanConfig {
+            links: vec![
+                link(&repo.join("nvim"), &home.join(".config/nvim")),
+                zshrc,
+                link(&repo.join("zshrc"), std::path::Path::new("/etc/zshrc")),
+            ],
+            ..Default::default()
+        };
+
+        let report = export_to(&config, &out, &home).unwrap();
+
+        assert_eq!(report.exported.len(), 2);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(
+            fs::read_to_string(out.join("nvim/.config/nvim/init.lua")).unwrap(),
+            "-- nvim"
+        );
+        assert_eq!(
+            fs::read_to_string(out.join("zshrc/.zshrc")).unwrap(),
+            "# zsh"
+        );
+        assert!(!report.exported[0].dropped_condition);
+        assert!(report.exported[1].dropped_condition);
+    }
+
+    #[test]
+    fn test_import_folds_unshared_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let stow = temp_dir.path().join("dotfiles");
+        let home = temp_dir.path().join("home");
+        fs::create_dir_all(stow.join("nvim/.config/nvim")).unwrap();
+        fs::write(stow.join("nvim/.config/nvim/init.lua"), "").unwrap();
+        fs::create_dir_all(stow.join("git/.config/git")).unwrap();
+        fs::write(stow.join("git/.config/git/config"), "").unwrap();
+        fs::write(stow.join("git/README.md"), "").unwrap();
+        fs::create_dir_all(stow.join("zsh")).unwrap();
+        fs::write(stow.join("zsh/.zshrc"), "").unwrap();
+        fs::create_dir_all(stow.join(".git")).unwrap();
+        fs::create_dir_all(&home).unwrap();
+
+        let links = import(&stow, &home).unwrap();
+        let pairs: Vec<(&str, &str)> = links
+            .iter()
+            .map(|link| (link.source.as_str(), link.target.as_str()))
+            .collect();
+
+        assert_eq!(
+            pairs,
+            vec![
+                ("git/.config/git", "~/.config/git"),
+                ("nvim/.config/nvim", "~/.config/nvim"),
+                ("zsh/.zshrc", "~/.zshrc"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_descends_into_existing_home_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let stow = temp_dir.path().join("dotfiles");
+        let home = temp_dir.path().join("home");
+        fs::create_dir_all(stow.join("bin/.local/bin")).unwrap();
+        fs::write(stow.join("bin/.local/bin/tool"), "").unwrap();
+        fs::create_dir_all(home.join(".local")).unwrap();
+
+        let links = import(&stow, &home).unwrap();
+
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].source, "bin/.local/bin");
+        assert_eq!(links[0].target, "~/.local/bin");
+    }
+
+    #[test]
+    fn test_unique_package_name() {
+        let taken = vec![ExportedLink {
+            package: "git".to_string(),
+            source: "a".into(),
+            destination: "b".into(),
+            dropped_condition: false,
+        }];
+
+        assert_eq!(
+            unique_package_name(std::path::Path::new(".gitconfig"), &taken),
+            "gitconfig"
+        );
+        assert_eq!(
+            unique_package_name(std::path::Path::new(".config/git"), &taken),
+            "git-2"
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! Enabling systemd user units for `type = "systemd-unit"` actions.
+
+use colored::Colorize;
+
+use crate::{
+    Dotman,
+    config::Link,
+    error::{DotmanError, PathError},
+    options::InstallOptions,
+};
+
+/// Whether a unit is enabled and running.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UnitState {
+    pub enabled: bool,
+    pub active: bool,
+}
+
+impl UnitState {
+    /// Asks `systemctl --user` about `unit`. Both are false where systemd
+    /// is not running.
+    pub fn of(unit: &str) -> UnitState {
+        let check = |command: &str| {
+            std::process::Command::new("systemctl")
+                .args(["--user", "--quiet", command, unit])
+                .status()
+                .is_ok_and(|status| status.success())
+        };
+        UnitState {
+            enabled: check("is-enabled"),
+            active: check("is-active"),
+        }
+    }
+}
+
+impl std::fmt::Display for UnitState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let enabled = if self.enabled { "enabled" } else { "disabled" };
+        let active = if self.active { "active" } else { "inactive" };
+        write!(f, "{enabled}, {active}")
+    }
+}
+
+/// The directory systemd reads user unit files from.
+pub fn unit_dir() -> Result<std::path::PathBuf, DotmanError> {
+    dirs::config_dir()
+        .map(|dir| dir.join("systemd").join("user"))
+        .ok_or_else(|| PathError::ConfigDirNotFound.into())
+}
+
+/// The link that puts the unit file `source` in place for `unit`.
+pub(crate) fn unit_link(unit: &str, source: &str) -> Result<Link, DotmanError> {
+    let target = unit_dir()?.join(unit);
+    Ok(Link::new(source, target.to_string_lossy()))
+}
+
+fn systemctl(args: &[&str]) -> Result<(), DotmanError> {
+    let command = format!("systemctl --user {}", args.join(" "));
+    let output = std::process::Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .output()
+        .map_err(|err| DotmanError::CommandError {
+            command: command.clone(),
+            message: err.to_string(),
+        })?;
+    if !output.status.success() {
+        return Err(DotmanError::CommandError {
+            command,
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(())
+}
+
+impl Dotman {
+    /// Whether `unit` is enabled and running, with its unit file, if any,
+    /// linked from `source`.
+    pub(crate) fn is_unit_enabled(&self, unit: &str, source: Option<&str>) -> bool {
+        let linked = source.is_none_or(|source| {
+            unit_link(unit, source).is_ok_and(|link| {
+                let source = self.config.resolve_source(&link.source);
+                let target = std::path::Path::new(&link.target);
+                source.is_ok_and(|source| {
+                    self.fs
+                        .read_link(target)
+                        .is_ok_and(|actual| actual == source)
+                })
+            })
+        });
+        let state = UnitState::of(unit);
+        linked && state.enabled && state.active
+    }
+
+    /// Links the unit file of `unit` from `source`, if given, and enables
+    /// and starts it.
+    pub(crate) fn enable_unit(&self, unit: &str, source: Option<&str>) -> Result<(), DotmanError> {
+        if let Some(source) = source {
+            let link = unit_link(unit, source)?;
+            self.fs.create_dir_all(&unit_dir()?)?;
+            self.install_link(&link, &InstallOptions::default())?;
+            self.state.lock().unwrap().save()?;
+            systemctl(&["daemon-reload"])?;
+        }
+        systemctl(&["enable", "--now", unit])?;
+        println!("{} {}", "Enabled:".green().bold(), unit);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_link() {
+        let link = unit_link("syncthing.service", "systemd/syncthing.service").unwrap();
+        assert_eq!(link.source, "systemd/syncthing.service");
+        assert!(link.target.ends_with("systemd/user/syncthing.service"));
+        assert_eq!(
+            UnitState {
+                enabled: true,
+                active: false
+            }
+            .to_string(),
+            "enabled, inactive"
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use minijinja::{Environment, ErrorKind, UndefinedBehavior};
+
+use crate::{config::DotmanConfig, error::DotmanError};
+
+/// Renders a template source with the configuration's `[variables]`.
+///
+/// Besides variables, templates can call `onepassword(item, field)` and
+/// `bitwarden(item, field)` to fetch values from the `op` and `bw` CLIs at
+/// render time. `field` defaults to `"password"`.
+pub fn render(config: &DotmanConfig, source: &std::path::Path) -> Result<String, DotmanError> {
+    let template = std::fs::read_to_string(source)?;
+    render_str(config, &source.display().to_string(), &template)
+}
+
+fn render_str(config: &DotmanConfig, name: &str, template: &str) -> Result<String, DotmanError> {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.set_keep_trailing_newline(true);
+    env.add_function("onepassword", |item: String, field: Option<String>| {
+        provider(onepassword_command(&item, field.as_deref()))
+    });
+    env.add_function("bitwarden", |item: String, field: Option<String>| {
+        provider(bitwarden_command(&item, field.as_deref()))
+    });
+
+    env.render_named_str(name, template, config.resolved_variables()?)
+        .map_err(|err| DotmanError::TemplateError(format!("{:#}", err)))
+}
+
+/// The variables a template source reads, or `None` if it cannot be parsed.
+pub fn referenced_variables(template: &str) -> Option<std::collections::HashSet<String>> {
+    let mut env = Environment::new();
+    env.add_template("template", template).ok()?;
+    Some(
+        env.get_template("template")
+            .ok()?
+            .undeclared_variables(false),
+    )
+}
+
+/// Reads a 1Password field. `item` may also be a full `op://` reference.
+fn onepassword_command(item: &str, field: Option<&str>) -> std::process::Command {
+    let mut command = std::process::Command::new("op");
+    if item.starts_with("op://") {
+        command.args(["read", item]);
+    } else {
+        let field = format!("label={}", field.unwrap_or("password"));
+        command.args(["item", "get", item, "--fields", &field, "--reveal"]);
+    }
+    command
+}
+
+/// Reads a Bitwarden field (`password`, `username`, `totp`, `notes`, ...).
+fn bitwarden_command(item: &str, field: Option<&str>) -> std::process::Command {
+    let mut command = std::process::Command::new("bw");
+    command.args(["get", field.unwrap_or("password"), item]);
+    command
+}
+
+fn provider(mut command: std::process::Command) -> Result<String, minijinja::Error> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let output = command.output().map_err(|err| {
+        minijinja::Error::new(
+            ErrorKind::InvalidOperation,
+            format!("failed to run {program}: {err}"),
+        )
+    })?;
+
+    if !output.status.success() {
+        return Err(minijinja::Error::new(
+            ErrorKind::InvalidOperation,
+            format!(
+                "{program} failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .trim_end_matches(['\r', '\n'])
+        .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: &std::process::Command) -> Vec<String> {
+        std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_render_variables() {
+        let config = DotmanConfig {
+            variables: [("email".to_string(), "me@example.com".to_string())].into(),
+            ..Default::default()
+        };
+
+        let rendered = render_str(&config, "gitconfig", "email = {{ email }}\n").unwrap();
+
+        assert_eq!(rendered, "email = me@example.com\n");
+    }
+
+    #[test]
+    fn test_cloned_config_renders_its_own_variables() {
+        let config = DotmanConfig::builder()
+            .variable("email", "me@example.com")
+            .build();
+        render_str(&config, "gitconfig", "{{ email }}").unwrap();
+
+        let mut cloned = config.clone();
+        cloned
+            .variables
+            .insert("email".to_string(), "work@example.com".to_string());
+
+        assert_eq!(
+            render_str(&cloned, "gitconfig", "{{ email }}").unwrap(),
+            "work@example.com"
+        );
+    }
+
+    #[test]
+    fn test_render_undefined_variable_fails() {
+        let config = DotmanConfig::default();
+
+        let result = render_str(&config, "gitconfig", "email = {{ email }}");
+
+        assert!(matches!(result, Err(DotmanError::TemplateError(_))));
+    }
+
+    #[test]
+    fn test_provider_commands() {
+        assert_eq!(
+            args(&onepassword_command("github-token", None)),
+            [
+                "op",
+                "item",
+                "get",
+                "github-token",
+                "--fields",
+                "label=password",
+                "--reveal"
+            ]
+        );
+        assert_eq!(
+            args(&onepassword_command("op://Personal/GitHub/token", None)),
+            ["op", "read", "op://Personal/GitHub/token"]
+        );
+        assert_eq!(
+            args(&bitwarden_command("github", Some("username"))),
+            ["bw", "get", "username", "github"]
+        );
+    }
+}
@@ -1,71 +1,82 @@
-use colored::Colorize;
-
-use crate::config::OperatingSystem;
+use crate::{config::OperatingSystem, error::PathError};
 
 pub trait ExpandTilde {
     /// Expands a path starting with `~` to the user's home directory.
     /// If the path does not start with `~`, it is returned as is.
-    fn expand_tilde_path(&self) -> Result<std::path::PathBuf, String>;
+    fn expand_tilde_path(&self) -> Result<std::path::PathBuf, PathError>;
 }
 
 impl<P: AsRef<std::path::Path>> ExpandTilde for P {
-    fn expand_tilde_path(&self) -> Result<std::path::PathBuf, String> {
-        let path_str = self.as_ref().to_string_lossy().to_string();
-        if path_str.starts_with("~") {
-            if let Some(home_dir) = dirs::home_dir() {
-                let relative_path = path_str.strip_prefix("~").unwrap_or(&path_str);
-                Ok(home_dir.join(relative_path.trim_start_matches('/')))
-            } else {
-                Err("Home directory not found".to_string())
-            }
-        } else {
-            Ok(self.as_ref().to_path_buf())
+    fn expand_tilde_path(&self) -> Result<std::path::PathBuf, PathError> {
+        let path = self.as_ref();
+        if !path.as_os_str().as_encoded_bytes().starts_with(b"~") {
+            return Ok(path.to_path_buf());
         }
+        let path_str = path
+            .to_str()
+            .ok_or_else(|| PathError::InvalidUtf8(path.to_path_buf()))?;
+        let home_dir = dirs::home_dir().ok_or(PathError::HomeDirNotFound)?;
+        let relative_path = path_str.strip_prefix("~").unwrap_or(path_str);
+        Ok(home_dir.join(relative_path.trim_start_matches('/')))
     }
 }
 
 pub trait Absolute {
     /// Converts a relative path to an absolute path based on the current working directory.
-    fn absolute(&self) -> Result<std::path::PathBuf, String>;
+    fn absolute(&self) -> Result<std::path::PathBuf, PathError>;
 }
 
 impl<P: AsRef<std::path::Path>> Absolute for P {
-    fn absolute(&self) -> Result<std::path::PathBuf, String> {
+    fn absolute(&self) -> Result<std::path::PathBuf, PathError> {
         let path = self.as_ref();
         if path.is_absolute() {
             Ok(path.to_path_buf())
         } else {
             std::env::current_dir()
-                .map_err(|e| e.to_string())
+                .map_err(PathError::CurrentDirUnavailable)
                 .map(|current_dir| current_dir.join(path))
         }
     }
 }
 
 /// Detects the current operating system and returns an `OperatingSystem` enum.
-/// Panics if the operating system is unsupported / not found.
 pub fn get_current_os() -> OperatingSystem {
-    match std::env::consts::OS {
-        "linux" => OperatingSystem::Linux,
-        "macos" => OperatingSystem::MacOS,
-        "windows" => OperatingSystem::Windows,
-        os => panic!(
-            "{} Unsupported operating system '{}' for Dotman.",
-            "Error:".red().bold(),
-            os
-        ),
-    }
+    let Ok(os) = std::env::consts::OS.parse();
+    os
 }
 
 /// Wrapper for creating symbolic links that works across different operating systems.
-pub fn symlink<P: AsRef<std::path::Path>>(source: P, target: P) -> std::io::Result<()> {
+/// With `junction`, directories are linked with an NTFS junction on Windows,
+/// which unlike a symlink needs no special rights.
+pub fn symlink<P: AsRef<std::path::Path>>(
+    source: P,
+    target: P,
+    junction: bool,
+) -> std::io::Result<()> {
     #[cfg(unix)]
     {
+        let _ = junction;
         std::os::unix::fs::symlink(source, target)
     }
     #[cfg(windows)]
     {
-        if source.as_ref().is_dir() {
+        if junction {
+            // Junctions have no std API, but cmd's mklink creates them.
+            let output = std::process::Command::new("cmd")
+                .arg("/C")
+                .arg("mklink")
+                .arg("/J")
+                .arg(target.as_ref())
+                .arg(source.as_ref())
+                .output()?;
+            if !output.status.success() {
+                let message = [output.stderr, output.stdout].concat();
+                return Err(std::io::Error::other(
+                    String::from_utf8_lossy(&message).trim().to_string(),
+                ));
+            }
+            Ok(())
+        } else if source.as_ref().is_dir() {
             std::os::windows::fs::symlink_dir(source, target)
         } else {
             std::os::windows::fs::symlink_file(source, target)
@@ -73,27 +84,216 @@ pub fn symlink<P: AsRef<std::path::Path>>(source: P, target: P) -> std::io::Resu
     }
 }
 
-pub fn get_hostname() -> Option<String> {
-    // Try to get hostname from the hostname command
-    let from_command = std::process::Command::new("hostname")
-        .output()
+/// Whether this process may create symlinks. On Windows that takes
+/// Developer Mode or administrator rights, so it is tried out once in the
+/// temporary directory.
+pub fn can_create_symlinks() -> bool {
+    #[cfg(windows)]
+    {
+        static ALLOWED: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+        *ALLOWED.get_or_init(|| {
+            let dir = std::env::temp_dir();
+            let link = dir.join(format!("dotman-symlink-check-{}", std::process::id()));
+            let created = std::os::windows::fs::symlink_file(dir.join("dotman"), &link).is_ok();
+            let _ = std::fs::remove_file(&link);
+            created
+        })
+    }
+    #[cfg(not(windows))]
+    {
+        true
+    }
+}
+
+/// Parses octal permissions such as `"755"` or `"0644"`.
+pub fn parse_mode(mode: &str) -> Result<u32, PathError> {
+    u32::from_str_radix(mode.trim_start_matches("0o"), 8)
         .ok()
-        .and_then(|output| {
-            let hostname = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            if hostname.is_empty() {
-                None
-            } else {
-                Some(hostname)
+        .filter(|mode| *mode <= 0o7777)
+        .ok_or_else(|| PathError::InvalidMode(mode.to_string()))
+}
+
+/// Sets the permissions of `path` to `mode`. Windows has no such
+/// permissions, so nothing changes there.
+pub fn set_mode(path: &std::path::Path, mode: u32) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (path, mode);
+        Ok(())
+    }
+}
+
+/// Returns the machine's hostname, asking the OS directly and falling back
+/// to `/etc/hostname`. `DOTMAN_HOSTNAME`, looked up with `var`, overrides it.
+pub fn get_hostname(var: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let non_empty = |hostname: String| {
+        let hostname = hostname.trim();
+        (!hostname.is_empty()).then(|| hostname.to_string())
+    };
+
+    var("DOTMAN_HOSTNAME")
+        .and_then(non_empty)
+        .or_else(|| non_empty(gethostname::gethostname().to_string_lossy().into_owned()))
+        .or_else(|| {
+            std::fs::read_to_string("/etc/hostname")
+                .ok()
+                .and_then(non_empty)
+        })
+}
+
+/// Returns the name of the user running dotman, looking environment
+/// variables up with `var`.
+pub fn get_username(var: impl Fn(&str) -> Option<String>) -> Option<String> {
+    ["USER", "USERNAME", "LOGNAME"]
+        .iter()
+        .find_map(|name| var(name))
+        .filter(|user| !user.is_empty())
+}
+
+/// Whether dotman runs with root privileges. Always false outside Unix.
+pub fn is_root() -> bool {
+    #[cfg(unix)]
+    {
+        // SAFETY: geteuid has no preconditions and cannot fail.
+        unsafe { libc::geteuid() == 0 }
+    }
+    #[cfg(not(unix))]
+    {
+        false
+    }
+}
+
+/// Returns the running desktop environments from `XDG_CURRENT_DESKTOP` and
+/// `XDG_SESSION_DESKTOP`, lowercased, followed by `wayland` or `x11` for the
+/// display server in use. Environment variables are looked up with `var`.
+pub fn get_desktops(var: impl Fn(&str) -> Option<String>) -> Vec<String> {
+    let mut desktops: Vec<String> = Vec::new();
+    for name in ["XDG_CURRENT_DESKTOP", "XDG_SESSION_DESKTOP"] {
+        for desktop in var(name).unwrap_or_default().split(':') {
+            let desktop = desktop.trim().to_lowercase();
+            if !desktop.is_empty() && !desktops.contains(&desktop) {
+                desktops.push(desktop);
             }
-        });
+        }
+    }
 
-    if from_command.is_some() {
-        return from_command;
+    let set = |name| var(name).is_some_and(|value| !value.is_empty());
+    if set("WAYLAND_DISPLAY") {
+        desktops.push("wayland".to_string());
+    } else if set("DISPLAY") {
+        desktops.push("x11".to_string());
     }
+    desktops
+}
 
-    // Fallback: try to read from /etc/hostname
-    std::fs::read_to_string("/etc/hostname")
-        .ok()
-        .map(|content| content.trim().to_string())
-        .filter(|hostname| !hostname.is_empty())
+/// Returns the Linux distribution's `ID` followed by its `ID_LIKE` entries
+/// from os-release, e.g. `["ubuntu", "debian"]`. Empty elsewhere.
+pub fn get_distro_ids() -> Vec<String> {
+    ["/etc/os-release", "/usr/lib/os-release"]
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .map(|contents| parse_os_release(&contents))
+        .unwrap_or_default()
+}
+
+fn parse_os_release(contents: &str) -> Vec<String> {
+    let value = |key: &str| {
+        contents.lines().find_map(|line| {
+            let value = line.trim().strip_prefix(key)?.strip_prefix('=')?;
+            Some(value.trim_matches(['"', '\'']).to_lowercase())
+        })
+    };
+
+    let mut ids: Vec<String> = value("ID").into_iter().collect();
+    if let Some(like) = value("ID_LIKE") {
+        ids.extend(like.split_whitespace().map(str::to_string));
+    }
+    ids
+}
+
+/// Recursively copies a file or directory. Symlinks inside directories are
+/// recreated rather than followed.
+pub fn copy_recursive<P: AsRef<std::path::Path>>(source: P, target: P) -> std::io::Result<()> {
+    let (source, target) = (source.as_ref(), target.as_ref());
+    let metadata = std::fs::symlink_metadata(source)?;
+
+    if metadata.is_symlink() {
+        let destination = std::fs::read_link(source)?;
+        symlink(destination.as_path(), target, false)
+    } else if metadata.is_dir() {
+        std::fs::create_dir_all(target)?;
+        for entry in std::fs::read_dir(source)? {
+            let entry = entry?;
+            copy_recursive(entry.path(), target.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        std::fs::copy(source, target).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_expand_tilde_path_rejects_invalid_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = std::path::Path::new(std::ffi::OsStr::from_bytes(b"~/\xff"));
+        assert!(matches!(
+            path.expand_tilde_path(),
+            Err(PathError::InvalidUtf8(_))
+        ));
+
+        let path = std::path::Path::new(std::ffi::OsStr::from_bytes(b"/tmp/\xff"));
+        assert_eq!(path.expand_tilde_path().unwrap(), path);
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("755").unwrap(), 0o755);
+        assert_eq!(parse_mode("0644").unwrap(), 0o644);
+        assert!(parse_mode("rwx").is_err());
+        assert!(parse_mode("17777").is_err());
+    }
+
+    #[test]
+    fn test_hostname_override() {
+        let env = |name: &str| (name == "DOTMAN_HOSTNAME").then(|| "work-laptop".to_string());
+
+        assert_eq!(get_hostname(env).as_deref(), Some("work-laptop"));
+        assert_eq!(
+            get_hostname(|_| Some(" ".to_string())),
+            get_hostname(|_| None)
+        );
+    }
+
+    #[test]
+    fn test_parse_os_release() {
+        let contents =
+            "NAME=\"Pop!_OS\"\nID=pop\nID_LIKE=\"ubuntu debian\"\nVERSION_ID=\"22.04\"\n";
+
+        assert_eq!(parse_os_release(contents), ["pop", "ubuntu", "debian"]);
+        assert!(parse_os_release("").is_empty());
+    }
+
+    #[test]
+    fn test_desktops_from_environment() {
+        let env = |name: &str| match name {
+            "XDG_CURRENT_DESKTOP" => Some("ubuntu:GNOME".to_string()),
+            "XDG_SESSION_DESKTOP" => Some("ubuntu".to_string()),
+            "WAYLAND_DISPLAY" => Some("wayland-0".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(get_desktops(env), ["ubuntu", "gnome", "wayland"]);
+        assert!(get_desktops(|_| None).is_empty());
+    }
 }
@@ -0,0 +1,796 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{Action, Condition, DotmanConfig, Hostname, Link, LinkMode, OperatingSystem},
+    download,
+    migrate::CURRENT_VERSION,
+    template,
+    utils::{Absolute, ExpandTilde},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// How a lint is reported, configurable per lint in the `[lints]` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// Every check validation can report.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Lint {
+    UnknownKey,
+    OutdatedVersion,
+    DuplicateTarget,
+    PossibleTargetConflict,
+    MissingSource,
+    ImpossibleCondition,
+    EmptyCondition,
+    UnknownProfile,
+    UnusedProfile,
+    UndeclaredProfile,
+    EmptyCommand,
+    UnusedVariable,
+    UnknownOs,
+    InvalidDigest,
+}
+
+impl Lint {
+    pub fn default_level(&self) -> LintLevel {
+        match self {
+            Lint::UnknownKey
+            | Lint::DuplicateTarget
+            | Lint::UnknownProfile
+            | Lint::InvalidDigest => LintLevel::Deny,
+            Lint::OutdatedVersion
+            | Lint::PossibleTargetConflict
+            | Lint::MissingSource
+            | Lint::ImpossibleCondition
+            | Lint::EmptyCondition
+            | Lint::UnusedProfile
+            | Lint::UndeclaredProfile
+            | Lint::EmptyCommand
+            | Lint::UnusedVariable
+            | Lint::UnknownOs => LintLevel::Warn,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Lint::UnknownKey => "unknown-key",
+            Lint::OutdatedVersion => "outdated-version",
+            Lint::DuplicateTarget => "duplicate-target",
+            Lint::PossibleTargetConflict => "possible-target-conflict",
+            Lint::MissingSource => "missing-source",
+            Lint::ImpossibleCondition => "impossible-condition",
+            Lint::EmptyCondition => "empty-condition",
+            Lint::UnknownProfile => "unknown-profile",
+            Lint::UnusedProfile => "unused-profile",
+            Lint::UndeclaredProfile => "undeclared-profile",
+            Lint::EmptyCommand => "empty-command",
+            Lint::UnusedVariable => "unused-variable",
+            Lint::UnknownOs => "unknown-os",
+            Lint::InvalidDigest => "invalid-digest",
+        }
+    }
+}
+
+/// A single problem found while validating a configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub lint: Lint,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic with the lint's default severity.
+    pub fn new(lint: Lint, message: impl Into<String>) -> Self {
+        let severity = match lint.default_level() {
+            LintLevel::Deny => Severity::Error,
+            _ => Severity::Warning,
+        };
+        Diagnostic {
+            lint,
+            severity,
+            message: message.into(),
+        }
+    }
+}
+
+/// Checks the configuration for mistakes that parsing alone does not catch.
+///
+/// Severities follow the levels configured in the `[lints]` table; lints set
+/// to `allow` are dropped.
+pub fn validate(config: &DotmanConfig) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    check_unknown_keys(config, &mut diagnostics);
+    check_version(config, &mut diagnostics);
+    check_target_conflicts(config, &mut diagnostics);
+    check_sources_exist(config, &mut diagnostics);
+    check_conditions(config, &mut diagnostics);
+    check_selected_profile(config, &mut diagnostics);
+    check_digests(config, &mut diagnostics);
+    apply_lint_levels(config, diagnostics)
+}
+
+/// Like [`validate`], but every warning is promoted to an error.
+pub fn validate_strict(config: &DotmanConfig) -> Vec<Diagnostic> {
+    let mut diagnostics = validate(config);
+    deny_warnings(&mut diagnostics);
+    diagnostics
+}
+
+/// Promotes every warning to an error, as `--strict` does.
+pub fn deny_warnings(diagnostics: &mut [Diagnostic]) {
+    for diagnostic in diagnostics {
+        diagnostic.severity = Severity::Error;
+    }
+}
+
+/// Reports dead and unused entries: declared profiles nothing uses, profiles
+/// used but never declared, conditions that can never match, actions
+/// without a command and variables no template reads.
+pub fn lint(config: &DotmanConfig) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    check_conditions(config, &mut diagnostics);
+    check_declared_profiles(config, &mut diagnostics);
+    check_empty_commands(config, &mut diagnostics);
+    check_unused_variables(config, &mut diagnostics);
+    apply_lint_levels(config, diagnostics)
+}
+
+pub(crate) fn apply_lint_levels(
+    config: &DotmanConfig,
+    diagnostics: Vec<Diagnostic>,
+) -> Vec<Diagnostic> {
+    diagnostics
+        .into_iter()
+        .filter_map(|mut diagnostic| {
+            match config.lints.get(&diagnostic.lint) {
+                Some(LintLevel::Allow) => return None,
+                Some(LintLevel::Warn) => diagnostic.severity = Severity::Warning,
+                Some(LintLevel::Deny) => diagnostic.severity = Severity::Error,
+                None => {}
+            }
+            Some(diagnostic)
+        })
+        .collect()
+}
+
+fn check_unknown_keys(config: &DotmanConfig, diagnostics: &mut Vec<Diagnostic>) {
+    diagnostics.extend(
+        config
+            .unknown_keys
+            .iter()
+            .map(|key| Diagnostic::new(Lint::UnknownKey, key.to_string())),
+    );
+}
+
+fn check_version(config: &DotmanConfig, diagnostics: &mut Vec<Diagnostic>) {
+    if config
+        .version
+        .is_none_or(|version| version < CURRENT_VERSION)
+    {
+        diagnostics.push(Diagnostic::new(
+            Lint::OutdatedVersion,
+            format!(
+                "Configuration is not at version {CURRENT_VERSION}. Run `dotman migrate` to upgrade it"
+            ),
+        ));
+    }
+}
+
+/// Reports links that write to the same target, or into a directory another
+/// link already owns. Links whose profiles never overlap cannot clash; links
+/// guarded by conditions might be mutually exclusive, so they only warn.
+fn check_target_conflicts(config: &DotmanConfig, diagnostics: &mut Vec<Diagnostic>) {
+    let targets: Vec<(&Link, std::path::PathBuf)> = config
+        .all_links()
+        .filter_map(|link| {
+            let target = link.target.expand_tilde_path().ok()?.absolute().ok()?;
+            Some((link, target))
+        })
+        .collect();
+
+    for (i, (a, a_target)) in targets.iter().enumerate() {
+        for (b, b_target) in &targets[i + 1..] {
+            if !profiles_overlap(&a.profiles, &b.profiles) {
+                continue;
+            }
+
+            let message = if a_target == b_target {
+                format!(
+                    "Links '{}' and '{}' both target {}",
+                    a.source,
+                    b.source,
+                    a_target.display()
+                )
+            } else if b_target.starts_with(a_target) || a_target.starts_with(b_target) {
+                let (outer, inner) = if b_target.starts_with(a_target) {
+                    (a, b)
+                } else {
+                    (b, a)
+                };
+                format!(
+                    "Link target {} is nested inside the target of '{}' ({})",
+                    inner.target, outer.source, outer.target
+                )
+            } else {
+                continue;
+            };
+
+            if is_conditional(a) || is_conditional(b) {
+                diagnostics.push(Diagnostic::new(
+                    Lint::PossibleTargetConflict,
+                    format!("{message} (conflicts if both conditions are met)"),
+                ));
+            } else {
+                diagnostics.push(Diagnostic::new(Lint::DuplicateTarget, message));
+            }
+        }
+    }
+}
+
+fn check_sources_exist(config: &DotmanConfig, diagnostics: &mut Vec<Diagnostic>) {
+    for link in config.all_links() {
+        match config.resolve_source(&link.source) {
+            Ok(source) if !source.exists() => diagnostics.push(Diagnostic::new(
+                Lint::MissingSource,
+                format!(
+                    "Source '{}' does not exist ({})",
+                    link.source,
+                    source.display()
+                ),
+            )),
+            Ok(_) => {}
+            Err(err) => diagnostics.push(Diagnostic::new(
+                Lint::MissingSource,
+                format!("Source '{}' could not be resolved: {}", link.source, err),
+            )),
+        }
+    }
+}
+
+fn check_conditions(config: &DotmanConfig, diagnostics: &mut Vec<Diagnostic>) {
+    let links = config.links.iter().map(|link| {
+        (
+            format!("Link '{}'", link.source),
+            &link.if_cond,
+            &link.if_not_cond,
+        )
+    });
+    let actions = config.actions.iter().map(|action| {
+        let (if_cond, if_not_cond) = action.conditions();
+        (format!("Action '{}'", action.name()), if_cond, if_not_cond)
+    });
+
+    for (entry, if_cond, if_not_cond) in links.chain(actions) {
+        if let Some(reason) = impossible_condition(if_cond, if_not_cond) {
+            diagnostics.push(Diagnostic::new(
+                Lint::ImpossibleCondition,
+                format!("{entry} will never apply: {reason}"),
+            ));
+        }
+        if if_cond.as_ref().is_some_and(Condition::is_empty) {
+            diagnostics.push(Diagnostic::new(
+                Lint::EmptyCondition,
+                format!("{entry} has an empty if condition, which always matches"),
+            ));
+        }
+        let systems = if_cond.iter().chain(if_not_cond).flat_map(|cond| &cond.os);
+        for os in systems.filter(|os| !os.is_known()) {
+            let OperatingSystem::Other(name) = os else {
+                continue;
+            };
+            let mut message = format!("{entry} names an unknown operating system '{name}'");
+            if let Some(suggestion) = os.suggestion() {
+                message.push_str(&format!(" (did you mean '{suggestion}'?)"));
+            }
+            diagnostics.push(Diagnostic::new(Lint::UnknownOs, message));
+        }
+    }
+}
+
+/// Returns why a pair of `if`/`if-not` conditions can never pass, if it is
+/// detectable without evaluating anything.
+fn impossible_condition(
+    if_cond: &Option<Condition>,
+    if_not_cond: &Option<Condition>,
+) -> Option<&'static str> {
+    if let Some(Condition {
+        hostname: Some(Hostname::Multiple(hosts)),
+        ..
+    }) = if_cond
+        && hosts.is_empty()
+    {
+        return Some("its hostname list is empty");
+    }
+
+    let if_not = if_not_cond.as_ref()?;
+    if if_not.is_empty() {
+        return Some("its if-not condition is empty and always matches");
+    }
+
+    let if_not_only_os = Condition {
+        os: vec![],
+        ..if_not.clone()
+    }
+    .is_empty();
+    if let Some(if_cond) = if_cond
+        && !if_cond.os.is_empty()
+        && if_not_only_os
+        && if_cond.os.iter().all(|os| if_not.os.contains(os))
+    {
+        return Some("every operating system in if is excluded by if-not");
+    }
+
+    None
+}
+
+/// Reports `sha256` digests of downloads and archives that are not 64 hex
+/// digits, which could never match.
+fn check_digests(config: &DotmanConfig, diagnostics: &mut Vec<Diagnostic>) {
+    for action in &config.actions {
+        let sha256 = match action {
+            Action::Download { sha256, .. } => sha256,
+            Action::Extract {
+                sha256: Some(sha256),
+                ..
+            } => sha256,
+            _ => continue,
+        };
+        if !download::is_sha256(sha256) {
+            diagnostics.push(Diagnostic::new(
+                Lint::InvalidDigest,
+                format!(
+                    "Action '{}' has sha256 '{sha256}', which is not 64 hex digits",
+                    action.name()
+                ),
+            ));
+        }
+    }
+}
+
+fn check_selected_profile(config: &DotmanConfig, diagnostics: &mut Vec<Diagnostic>) {
+    let Some(profile) = &config.selected_profile else {
+        return;
+    };
+
+    let used = config
+        .links
+        .iter()
+        .map(|link| link.profiles.as_slice())
+        .chain(config.actions.iter().map(|action| action.profiles()))
+        .any(|profiles| profiles.contains(profile));
+
+    if !used {
+        diagnostics.push(Diagnostic::new(
+            Lint::UnknownProfile,
+            format!("Profile '{profile}' is not used by any link or action"),
+        ));
+    }
+}
+
+/// Compares profile usage against the top-level `profiles` declaration. Does
+/// nothing when no profiles are declared.
+fn check_declared_profiles(config: &DotmanConfig, diagnostics: &mut Vec<Diagnostic>) {
+    if config.profiles.is_empty() {
+        return;
+    }
+
+    let used: std::collections::BTreeSet<&String> = config
+        .links
+        .iter()
+        .flat_map(|link| &link.profiles)
+        .chain(config.actions.iter().flat_map(|action| action.profiles()))
+        .collect();
+
+    for profile in &config.profiles {
+        if !used.contains(profile) {
+            diagnostics.push(Diagnostic::new(
+                Lint::UnusedProfile,
+                format!("Profile '{profile}' is declared but not used by any link or action"),
+            ));
+        }
+    }
+
+    for profile in used {
+        if !config.profiles.contains(profile) {
+            diagnostics.push(Diagnostic::new(
+                Lint::UndeclaredProfile,
+                format!("Profile '{profile}' is used but not declared in `profiles`"),
+            ));
+        }
+    }
+}
+
+fn check_empty_commands(config: &DotmanConfig, diagnostics: &mut Vec<Diagnostic>) {
+    for action in &config.actions {
+        if action
+            .run()
+            .is_some_and(|run| run.command().trim().is_empty())
+        {
+            diagnostics.push(Diagnostic::new(
+                Lint::EmptyCommand,
+                format!("Action '{}' has an empty command", action.name()),
+            ));
+        }
+    }
+}
+
+/// Reports `[variables]` that no template link's source reads. Nothing is
+/// reported when a template cannot be read or parsed, as it might read any.
+fn check_unused_variables(config: &DotmanConfig, diagnostics: &mut Vec<Diagnostic>) {
+    if config.variables.is_empty() {
+        return;
+    }
+    let mut referenced = std::collections::HashSet::new();
+    for link in config.all_links() {
+        if link.mode != LinkMode::Template {
+            continue;
+        }
+        let Some(variables) = config
+            .resolve_source(&link.source)
+            .ok()
+            .and_then(|source| std::fs::read_to_string(source).ok())
+            .and_then(|template| template::referenced_variables(&template))
+        else {
+            return;
+        };
+        referenced.extend(variables);
+    }
+    for name in config.variables.keys() {
+        if !referenced.contains(name) {
+            diagnostics.push(Diagnostic::new(
+                Lint::UnusedVariable,
+                format!("Variable '{name}' is not used by any template"),
+            ));
+        }
+    }
+}
+
+fn profiles_overlap(a: &[String], b: &[String]) -> bool {
+    a.is_empty() || b.is_empty() || a.iter().any(|profile| b.contains(profile))
+}
+
+fn is_conditional(link: &Link) -> bool {
+    link.if_cond.is_some() || link.if_not_cond.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RunCommand;
+
+    fn link(source: &str, target: &str) -> Link {
+        Link {
+            source: source.to_string(),
+            target: target.to_string(),
+            if_cond: None,
+            if_not_cond: None,
+            profiles: vec![],
+            ..Default::default()
+        }
+    }
+
+    fn config(links: Vec<Link>) -> DotmanConfig {
+        DotmanConfig {
+            links,
+            version: Some(CURRENT_VERSION),
+            ..Default::default()
+        }
+    }
+
+    fn conflicts(config: DotmanConfig) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        check_target_conflicts(&config, &mut diagnostics);
+        diagnostics
+    }
+
+    #[test]
+    fn test_missing_source_is_warning() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("present"), "").unwrap();
+        let mut config = config(vec![
+            link("present", "/home/user/.present"),
+            link("missing", "/home/user/.missing"),
+        ]);
+        config.config_path = temp_dir.path().join("dotman.toml").display().to_string();
+
+        let diagnostics = validate(&config);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(diagnostics[0].message.contains("'missing' does not exist"));
+    }
+
+    #[test]
+    fn test_impossible_conditions_are_warnings() {
+        let empty_hosts = Some(Condition {
+            hostname: Some(Hostname::Multiple(vec![])),
+            ..Default::default()
+        });
+        assert!(impossible_condition(&empty_hosts, &None).is_some());
+
+        let empty_if_not = Some(Condition::default());
+        assert!(impossible_condition(&None, &empty_if_not).is_some());
+
+        let mac = Some(Condition {
+            os: vec![OperatingSystem::MacOS],
+            ..Default::default()
+        });
+        assert!(impossible_condition(&mac, &mac).is_some());
+
+        let linux = Some(Condition {
+            os: vec![OperatingSystem::Linux],
+            ..Default::default()
+        });
+        assert!(impossible_condition(&mac, &linux).is_none());
+        assert!(impossible_condition(&mac, &None).is_none());
+    }
+
+    #[test]
+    fn test_unknown_operating_systems_are_warnings() {
+        let mut config = DotmanConfig::default();
+        let mut link = Link::new("zshrc", "~/.zshrc");
+        link.if_cond = Some(Condition {
+            os: vec![
+                OperatingSystem::Linux,
+                OperatingSystem::Other("illumos".to_string()),
+                OperatingSystem::Other("darwin".to_string()),
+            ],
+            ..Default::default()
+        });
+        link.if_not_cond = Some(Condition {
+            os: vec![OperatingSystem::Other("Windws".to_string())],
+            ..Default::default()
+        });
+        config.links.push(link);
+
+        let diagnostics: Vec<_> = lint(&config)
+            .into_iter()
+            .filter(|diagnostic| diagnostic.lint == Lint::UnknownOs)
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+        assert_eq!(
+            diagnostics,
+            [
+                "Link 'zshrc' names an unknown operating system 'darwin' (did you mean 'macos'?)",
+                "Link 'zshrc' names an unknown operating system 'Windws' (did you mean 'windows'?)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_if_condition_is_warning() {
+        let mut link = link("/dotfiles/vimrc", "/home/user/.vimrc");
+        link.if_cond = Some(Condition::default());
+        let mut diagnostics = Vec::new();
+        check_conditions(&config(vec![link]), &mut diagnostics);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].lint, Lint::EmptyCondition);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_lint_levels_override_defaults() {
+        let mut config = config(vec![link("missing", "/home/user/.missing")]);
+        config.config_path = "/nonexistent/dotman.toml".to_string();
+
+        let diagnostics = validate(&config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+
+        config.lints.insert(Lint::MissingSource, LintLevel::Deny);
+        assert_eq!(validate(&config)[0].severity, Severity::Error);
+
+        config.lints.insert(Lint::MissingSource, LintLevel::Allow);
+        assert!(validate(&config).is_empty());
+    }
+
+    #[test]
+    fn test_strict_promotes_warnings() {
+        let mut config = config(vec![link("missing", "/home/user/.missing")]);
+        config.config_path = "/nonexistent/dotman.toml".to_string();
+
+        let diagnostics = validate_strict(&config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_lint_reports_declared_profile_usage() {
+        let mut work = link("/work/gitconfig", "/home/user/.gitconfig");
+        work.profiles = vec!["wrok".to_string()];
+        let mut config = config(vec![work]);
+        config.profiles = vec!["work".to_string(), "personal".to_string()];
+
+        let lints: Vec<Lint> = lint(&config).iter().map(|d| d.lint).collect();
+
+        assert_eq!(
+            lints,
+            vec![
+                Lint::UnusedProfile,
+                Lint::UnusedProfile,
+                Lint::UndeclaredProfile
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_ignores_profiles_when_none_declared() {
+        let mut work = link("/work/gitconfig", "/home/user/.gitconfig");
+        work.profiles = vec!["work".to_string()];
+
+        assert!(lint(&config(vec![work])).is_empty());
+    }
+
+    #[test]
+    fn test_lint_reports_empty_commands() {
+        let mut config = config(vec![]);
+        config.actions = vec![Action::ShellCommand {
+            name: "noop".to_string(),
+            run: RunCommand::Simple("  ".to_string()),
+            undo: None,
+            if_cond: None,
+            if_not_cond: None,
+            profiles: vec![],
+            elevate: false,
+        }];
+
+        let diagnostics = lint(&config);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].lint, Lint::EmptyCommand);
+    }
+
+    #[test]
+    fn test_lint_reports_unused_variables() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let source = temp_dir.path().join("gitconfig");
+        std::fs::write(
+            &source,
+            "[user]\n  email = {{ email }}\n{% if work %}signingkey = x{% endif %}\n",
+        )
+        .unwrap();
+        let mut template = link(source.to_str().unwrap(), "/home/user/.gitconfig");
+        template.mode = LinkMode::Template;
+        let mut config = config(vec![template]);
+        for name in ["email", "work", "editor"] {
+            config.variables.insert(name.to_string(), String::new());
+        }
+
+        let diagnostics = lint(&config);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].lint, Lint::UnusedVariable);
+        assert!(diagnostics[0].message.contains("'editor'"));
+
+        std::fs::write(&source, "{{ email ").unwrap();
+        assert!(lint(&config).is_empty());
+    }
+
+    #[test]
+    fn test_unused_selected_profile_is_error() {
+        let mut work = link("/work/gitconfig", "/home/user/.gitconfig");
+        work.profiles = vec!["work".to_string()];
+        let config = config(vec![work]);
+
+        let used = config.clone().with_profile(Some("work".to_string()));
+        assert!(
+            validate(&used)
+                .iter()
+                .all(|d| !d.message.contains("Profile"))
+        );
+
+        let unused = config.with_profile(Some("hoem".to_string()));
+        assert!(
+            validate(&unused)
+                .iter()
+                .any(|d| d.severity == Severity::Error && d.message.contains("'hoem'"))
+        );
+    }
+
+    #[test]
+    fn test_invalid_digests_are_errors() {
+        let config: DotmanConfig = toml::from_str(&format!(
+            r#"
+            [[actions]]
+            type = "download"
+            name = "Tool"
+            url = "https://example.com/tool"
+            dest = "~/.local/bin/tool"
+            sha256 = "../../.bashrc"
+
+            [[actions]]
+            type = "extract"
+            name = "Theme"
+            source = "https://example.com/theme.tar.gz"
+            dest = "~/.themes"
+            sha256 = "{}"
+            "#,
+            crate::hash::sha256(b"theme")
+        ))
+        .unwrap();
+
+        let diagnostics: Vec<_> = validate(&config)
+            .into_iter()
+            .filter(|diagnostic| diagnostic.lint == Lint::InvalidDigest)
+            .collect();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert!(diagnostics[0].message.starts_with("Action 'Tool'"));
+    }
+
+    #[test]
+    fn test_duplicate_targets_are_errors() {
+        let diagnostics = conflicts(config(vec![
+            link("a/zshrc", "/home/user/.zshrc"),
+            link("b/zshrc", "/home/user/.zshrc"),
+        ]));
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert!(diagnostics[0].message.contains("both target"));
+    }
+
+    #[test]
+    fn test_nested_targets_are_errors() {
+        let diagnostics = conflicts(config(vec![
+            link("nvim/init.lua", "/home/user/.config/nvim/init.lua"),
+            link("nvim", "/home/user/.config/nvim"),
+        ]));
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert!(diagnostics[0].message.contains("nested inside"));
+    }
+
+    #[test]
+    fn test_sibling_targets_do_not_conflict() {
+        let diagnostics = conflicts(config(vec![
+            link("nvim", "/home/user/.config/nvim"),
+            link("nvim-extra", "/home/user/.config/nvim-extra"),
+        ]));
+
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_disjoint_profiles_do_not_conflict() {
+        let mut work = link("work/gitconfig", "/home/user/.gitconfig");
+        work.profiles = vec!["work".to_string()];
+        let mut personal = link("personal/gitconfig", "/home/user/.gitconfig");
+        personal.profiles = vec!["personal".to_string()];
+
+        assert!(conflicts(config(vec![work, personal])).is_empty());
+    }
+
+    #[test]
+    fn test_conditional_duplicates_are_warnings() {
+        let mut mac = link("mac/zshrc", "/home/user/.zshrc");
+        mac.if_cond = Some(Condition {
+            os: vec![OperatingSystem::MacOS],
+            ..Default::default()
+        });
+        let linux = link("linux/zshrc", "/home/user/.zshrc");
+
+        let diagnostics = conflicts(config(vec![mac, linux]));
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+    }
+}
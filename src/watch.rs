@@ -0,0 +1,117 @@
+use crate::{DotmanConfig, error::DotmanError, utils::Absolute};
+
+/// The configuration file and every link source that exists.
+pub fn watched_paths(config: &DotmanConfig) -> Vec<std::path::PathBuf> {
+    let config_path = std::path::Path::new(&config.config_path);
+    let mut paths = vec![config_path.absolute().unwrap_or(config_path.to_path_buf())];
+    paths.extend(
+        config
+            .get_effective_links()
+            .into_iter()
+            .filter_map(|link| config.resolve_source(&link.source).ok())
+            .filter(|source| source.exists()),
+    );
+    paths
+}
+
+/// Reports changes to a set of files and directories.
+pub struct Watcher {
+    _watcher: notify::RecommendedWatcher,
+    events: std::sync::mpsc::Receiver<notify::Result<notify::Event>>,
+    paths: Vec<std::path::PathBuf>,
+}
+
+impl Watcher {
+    /// Watches `paths`. Files are watched through their directory, so they
+    /// are still seen after editors replace them instead of writing in place.
+    pub fn new(paths: Vec<std::path::PathBuf>) -> Result<Watcher, DotmanError> {
+        use notify::Watcher as _;
+
+        let (sender, events) = std::sync::mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(watch_error)?;
+        let mut watched = std::collections::BTreeSet::new();
+        for path in &paths {
+            let (dir, mode) = if path.is_dir() {
+                (path.as_path(), notify::RecursiveMode::Recursive)
+            } else {
+                match path.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => {
+                        (parent, notify::RecursiveMode::NonRecursive)
+                    }
+                    _ => (
+                        std::path::Path::new("."),
+                        notify::RecursiveMode::NonRecursive,
+                    ),
+                }
+            };
+            if watched.insert((dir.to_path_buf(), mode == notify::RecursiveMode::Recursive)) {
+                watcher.watch(dir, mode).map_err(watch_error)?;
+            }
+        }
+        Ok(Watcher {
+            _watcher: watcher,
+            events,
+            paths,
+        })
+    }
+
+    /// Blocks until a watched path changes and then stays quiet for `settle`,
+    /// so a burst of writes is reported once. Returns the changed paths.
+    pub fn wait(
+        &self,
+        settle: std::time::Duration,
+    ) -> Result<Vec<std::path::PathBuf>, DotmanError> {
+        let mut changed = std::collections::BTreeSet::new();
+        while changed.is_empty() {
+            let event = self
+                .events
+                .recv()
+                .map_err(|err| DotmanError::IoError(std::io::Error::other(err)))?;
+            changed.extend(self.relevant(event.map_err(watch_error)?));
+        }
+        while let Ok(event) = self.events.recv_timeout(settle) {
+            changed.extend(self.relevant(event.map_err(watch_error)?));
+        }
+        Ok(changed.into_iter().collect())
+    }
+
+    /// The watched paths `event` touched, ignoring mere reads.
+    fn relevant(&self, event: notify::Event) -> Vec<std::path::PathBuf> {
+        if event.kind.is_access() {
+            return vec![];
+        }
+        event
+            .paths
+            .into_iter()
+            .filter(|changed| self.paths.iter().any(|path| changed.starts_with(path)))
+            .collect()
+    }
+}
+
+fn watch_error(err: notify::Error) -> DotmanError {
+    DotmanError::IoError(std::io::Error::other(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Link;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_watched_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("zshrc"), "# zsh").unwrap();
+        let config_path = temp_dir.path().join("dotman.toml");
+        let config = DotmanConfig::builder()
+            .config_path(config_path.to_string_lossy())
+            .link(Link::new("zshrc", "~/.zshrc"))
+            .link(Link::new("missing", "~/.missing"))
+            .build();
+
+        assert_eq!(
+            watched_paths(&config),
+            [config_path, temp_dir.path().join("zshrc")]
+        );
+    }
+}